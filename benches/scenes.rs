@@ -0,0 +1,210 @@
+//! Frame time benchmarks of Firefly, rendering generated stress scenes to an offscreen image.
+//!
+//! Run with `cargo bench`, or `cargo bench -- <scene>` to only run the scenes whose name contains `<scene>`.
+//!
+//! Each frame is split into:
+//! - **main**: the main world's schedules, including visibility and occluder bounds.
+//! - **extract**: extraction of lights, occluders and sprites into the render world.
+//! - **prepare**: the render world's prepare systems, including light binning and buffer uploads.
+//! - **render**: the rest of the render world's schedule, recording and submitting the GPU passes.
+//! - **gpu**: time spent waiting for the GPU to finish the submitted passes.
+
+use std::time::{Duration, Instant};
+
+use bevy::{
+    app::{AppLabel, PluginsState},
+    camera::RenderTarget,
+    prelude::*,
+    render::{
+        Render, RenderApp, RenderSystems,
+        render_resource::{PollType, TextureFormat},
+        renderer::RenderDevice,
+    },
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use bevy_firefly::{prelude::*, stress::StressScene};
+
+const WARMUP_FRAMES: usize = 30;
+const MEASURED_FRAMES: usize = 200;
+
+const SCENES: &[(&str, StressScene)] = &[
+    ("lights", StressScene::new(500, 200, 0)),
+    ("occluders", StressScene::new(50, 5000, 0)),
+    ("sprites", StressScene::new(50, 200, 5000)),
+    ("mixed", StressScene::new(200, 2000, 1000)),
+];
+
+fn main() {
+    let filters = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect::<Vec<_>>();
+
+    println!(
+        "{:<12} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "scene", "main", "extract", "prepare", "render", "gpu", "total"
+    );
+
+    for (name, scene) in SCENES {
+        if !filters.is_empty() && !filters.iter().any(|filter| name.contains(filter.as_str())) {
+            continue;
+        }
+
+        let mut app = scene_app(*scene);
+
+        for _ in 0..WARMUP_FRAMES {
+            frame(&mut app);
+        }
+
+        let mut frames = (0..MEASURED_FRAMES)
+            .map(|_| frame(&mut app))
+            .collect::<Vec<_>>();
+
+        // medians are less affected by the occasional hitch than means
+        let median = |f: fn(&FrameTimes) -> Duration, frames: &mut Vec<FrameTimes>| {
+            frames.sort_by_key(f);
+            f(&frames[frames.len() / 2])
+        };
+
+        let times = [
+            median(|t| t.main, &mut frames),
+            median(|t| t.extract, &mut frames),
+            median(|t| t.prepare, &mut frames),
+            median(|t| t.render, &mut frames),
+            median(|t| t.gpu, &mut frames),
+            median(|t| t.total(), &mut frames),
+        ];
+
+        print!("{name:<12}");
+        for time in times {
+            print!(" {:>8.3}ms", time.as_secs_f64() * 1000.);
+        }
+        println!();
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct FrameTimes {
+    main: Duration,
+    extract: Duration,
+    prepare: Duration,
+    render: Duration,
+    gpu: Duration,
+}
+
+impl FrameTimes {
+    fn total(&self) -> Duration {
+        self.main + self.extract + self.prepare + self.render + self.gpu
+    }
+}
+
+/// Start and duration of the render world's prepare systems during the last frame.
+#[derive(Resource, Default)]
+struct PrepareTime {
+    start: Option<Instant>,
+    duration: Duration,
+}
+
+fn scene_app(scene: StressScene) -> App {
+    let mut app = App::new();
+
+    app.add_plugins((
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                close_when_requested: false,
+                ..default()
+            })
+            .disable::<WinitPlugin>(),
+        FireflyPlugin,
+    ));
+
+    app.add_systems(
+        Startup,
+        move |mut commands: Commands, mut images: ResMut<Assets<Image>>| {
+            let image = images.add(Image::new_target_texture(
+                1280,
+                720,
+                TextureFormat::Rgba8UnormSrgb,
+                None,
+            ));
+
+            commands.spawn((
+                Camera2d,
+                RenderTarget::Image(image.into()),
+                FireflyConfig::default(),
+            ));
+
+            scene.spawn(&mut commands);
+        },
+    );
+
+    let render_app = app.sub_app_mut(RenderApp);
+    render_app.init_resource::<PrepareTime>();
+    render_app.add_systems(
+        Render,
+        (
+            (|mut time: ResMut<PrepareTime>| time.start = Some(Instant::now()))
+                .after(RenderSystems::PhaseSort)
+                .before(RenderSystems::Prepare),
+            (|mut time: ResMut<PrepareTime>| {
+                time.duration = time
+                    .start
+                    .take()
+                    .map_or(Duration::ZERO, |start| start.elapsed())
+            })
+            .after(RenderSystems::Prepare)
+            .before(RenderSystems::Render),
+        ),
+    );
+
+    // same as the default runner, the render plugin creates the GPU device asynchronously
+    while app.plugins_state() == PluginsState::Adding {
+        bevy::tasks::tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+
+    app
+}
+
+fn frame(app: &mut App) -> FrameTimes {
+    let sub_apps = app.sub_apps_mut();
+    let render_app = sub_apps
+        .sub_apps
+        .get_mut(&RenderApp.intern())
+        .expect("the render app should exist");
+
+    let start = Instant::now();
+    sub_apps.main.run_default_schedule();
+    let main = start.elapsed();
+
+    let start = Instant::now();
+    render_app.extract(sub_apps.main.world_mut());
+    let extract = start.elapsed();
+
+    let start = Instant::now();
+    render_app.update();
+    let update = start.elapsed();
+
+    let start = Instant::now();
+    let _ = render_app
+        .world()
+        .resource::<RenderDevice>()
+        .poll(PollType::wait_indefinitely());
+    let gpu = start.elapsed();
+
+    sub_apps.main.world_mut().clear_trackers();
+
+    let prepare = render_app.world().resource::<PrepareTime>().duration;
+
+    FrameTimes {
+        main,
+        extract,
+        prepare,
+        render: update.saturating_sub(prepare),
+        gpu,
+    }
+}
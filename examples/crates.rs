@@ -12,7 +12,8 @@ fn main() {
     app.init_resource::<Dragged>();
 
     app.add_systems(Startup, setup);
-    app.add_systems(Update, (z_sorting, drag_objects));
+    app.add_plugins(YSortPlugin);
+    app.add_systems(Update, drag_objects);
 
     app.run();
 }
@@ -41,6 +42,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         // component added to simulate height for the normal maps. Could be useful if the object is floating above the ground.
         // this can safely not be added, and it defaults to 0.
         SpriteHeight(0.),
+        YSort::default(),
     ));
 
     commands.spawn((
@@ -49,6 +51,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         NormalMap::from_file("crate_normal.png", &asset_server),
         Transform::from_translation(vec3(-20., 20., 0.)),
         Occluder2d::rectangle(12., 5.1),
+        YSort::default(),
     ));
 
     commands.spawn((
@@ -57,6 +60,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         NormalMap::from_file("vase_normal.png", &asset_server),
         Transform::from_translation(vec3(0., 20., 0.)),
         Occluder2d::round_rectangle(5.4, 0.5, 3.),
+        YSort::default(),
     ));
 
     commands.spawn((
@@ -65,6 +69,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         NormalMap::from_file("vase_normal.png", &asset_server),
         Transform::from_translation(vec3(10., -20., 0.)),
         Occluder2d::round_rectangle(5.4, 0.5, 3.),
+        YSort::default(),
     ));
 
     commands.spawn((
@@ -81,7 +86,8 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         },
         // component added to simulate height for the normal maps.
         // you can see the lamp lighting up the top of the sprites because it has a greater height than the bonfire.
-        LightHeight(3.),
+        LightHeight::Fixed(3.),
+        YSort::default(),
     ));
 
     commands.spawn((
@@ -98,17 +104,11 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
             color: Color::srgb(0.8, 0.8, 1.0),
             ..default()
         },
-        LightHeight(22.),
+        LightHeight::Fixed(22.),
+        YSort::default(),
     ));
 }
 
-// setting the sprite's z in relation to their y, so that Bevy's sprite renderer and firefly sort them properly.
-fn z_sorting(mut sprites: Query<&mut Transform, With<FireflySprite>>) {
-    for mut transform in &mut sprites {
-        transform.translation.z = -transform.translation.y;
-    }
-}
-
 #[derive(Resource, Default)]
 struct Dragged(pub Option<Entity>);
 
@@ -41,6 +41,7 @@ fn setup(mut commands: Commands) {
         angle: LightAngle {
             inner: 45.0,
             outer: 90.0,
+            ..default()
         },
         ..default()
     },));
@@ -12,7 +12,8 @@ fn main() {
     app.init_resource::<Dragged>();
 
     app.add_systems(Startup, setup);
-    app.add_systems(Update, (z_sorting, drag_objects));
+    app.add_plugins(YSortPlugin);
+    app.add_systems(Update, drag_objects);
 
     app.run();
 }
@@ -44,30 +45,19 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     ));
 
     commands.spawn((
-        FireflySprite {
-            image: asset_server.load("composite_sheet.png"),
-            texture_atlas: Some(TextureAtlas {
-                layout,
-                ..Default::default()
-            }),
-            image_mode: FireflySpriteImageMode::Instances(vec![
-                SpriteInstance {
-                    index: 0,
-                    offset: Vec2::new(10., 0.),
-                    ..default()
-                },
-                SpriteInstance {
-                    index: 1,
-                    offset: Vec2::new(-10., -10.),
-                    ..default()
-                },
-            ]),
-            ..Default::default()
-        },
+        FireflySprite::from_atlas_image(
+            asset_server.load("composite_sheet.png"),
+            TextureAtlas::from(layout),
+        ),
+        CompositeSprite(vec![
+            SpriteInstance::new(0, Vec2::new(10., 0.)),
+            SpriteInstance::new(1, Vec2::new(-10., -10.)),
+        ]),
         Anchor(vec2(0.0, -0.5 + 3.0 / 18.0)),
         NormalMap::from_file("composite_sheet_normal.png", &asset_server),
         Transform::from_translation(vec3(-20., 20., 0.)),
         Occluder2d::rectangle(12., 5.1),
+        YSort::default(),
     ));
 
     commands.spawn((
@@ -79,6 +69,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         NormalMap::from_file("statue_normal.png", &asset_server),
         Transform::from_translation(vec3(-20., -20., 0.)),
         Occluder2d::rectangle(12., 5.1),
+        YSort::default(),
     ));
 
     commands.spawn((
@@ -93,17 +84,11 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         },
         // component added to simulate height for the normal maps.
         // you can see the lamp lighting up the top of the sprites because it has a greater height than the bonfire.
-        LightHeight(3.),
+        LightHeight::Fixed(3.),
+        YSort::default(),
     ));
 }
 
-// setting the sprite's z in relation to their y, so that Bevy's sprite renderer and firefly sort them properly.
-fn z_sorting(mut sprites: Query<&mut Transform, With<FireflySprite>>) {
-    for mut transform in &mut sprites {
-        transform.translation.z = -transform.translation.y;
-    }
-}
-
 #[derive(Resource, Default)]
 struct Dragged(pub Option<Entity>);
 
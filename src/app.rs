@@ -3,27 +3,50 @@
 use std::f32::consts::{FRAC_PI_2, PI};
 
 use bevy::{
-    color::palettes::css::{GREY, PINK, WHITE},
+    color::palettes::css::{AQUA, GREY, ORANGE, PINK, WHITE, YELLOW},
     core_pipeline::core_2d::graph::{Core2d, Node2d},
     prelude::*,
     render::{
         RenderApp,
         render_graph::{RenderGraphExt, ViewNodeRunner},
     },
+    window::PrimaryWindow,
 };
 
 use crate::{
+    bake::BakePlugin,
     buffers::BuffersPlugin,
+    caustics::CausticsPlugin,
     change::ChangePlugin,
+    cues::CuePlugin,
+    directional::DirectionalLightPlugin,
+    exposure::ExposurePlugin,
     extract::ExtractPlugin,
+    flare::FlarePlugin,
+    flicker::FlickerPlugin,
+    gradient::GradientPlugin,
+    illumination::IlluminationPlugin,
     lights::LightPlugin,
-    nodes::{ApplyLightmapNode, CreateLightmapNode, SpriteNode},
+    line::LineLightPlugin,
+    memory::MemoryStatsPlugin,
+    nodes::{ApplyLightmapNode, CreateLightmapNode},
     occluders::{Occluder2dShape, OccluderPlugin, translate_vertices},
+    outline::OutlinePlugin,
+    parallax::ParallaxLightPlugin,
     pipelines::PipelinePlugin,
-    sprites::SpritesPlugin,
+    profile::ProfilePlugin,
+    quality::QualityPlugin,
+    sensor::SensorPlugin,
+    stencil::StencilPlugin,
     visibility::VisibilityPlugin,
+    weather::WeatherPlugin,
     *,
 };
+#[cfg(feature = "sprites")]
+use crate::{
+    drop_shadow::DropShadowPlugin, nodes::SpriteNode, sprites::SpritesPlugin,
+    tilemap::TilemapPlugin,
+};
 use crate::{prelude::*, prepare::PreparePlugin};
 
 /// Plugin necessary to use Firefly.
@@ -41,7 +64,42 @@ impl Plugin for FireflyPlugin {
             VisibilityPlugin,
             ChangePlugin,
         ));
-        app.add_plugins((LightPlugin, OccluderPlugin, SpritesPlugin));
+        app.add_plugins((
+            LightPlugin,
+            OccluderPlugin,
+            GradientPlugin,
+            SensorPlugin,
+            QualityPlugin,
+            MemoryStatsPlugin,
+            CausticsPlugin,
+            WeatherPlugin,
+            ProfilePlugin,
+            FlickerPlugin,
+            StencilPlugin,
+            OutlinePlugin,
+            CuePlugin,
+            IlluminationPlugin,
+        ));
+        app.add_plugins((
+            ExposurePlugin,
+            FlarePlugin,
+            BakePlugin,
+            DirectionalLightPlugin,
+            ParallaxLightPlugin,
+            LineLightPlugin,
+        ));
+
+        #[cfg(feature = "sprites")]
+        app.add_plugins((SpritesPlugin, TilemapPlugin, DropShadowPlugin));
+
+        #[cfg(feature = "preview")]
+        app.add_plugins(crate::preview::LightmapPreviewPlugin);
+
+        #[cfg(feature = "svg")]
+        app.add_plugins(crate::svg::SvgOccluderPlugin);
+
+        #[cfg(all(feature = "aseprite", feature = "sprites"))]
+        app.add_plugins(crate::aseprite::AsepritePlugin);
 
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
@@ -52,18 +110,37 @@ impl Plugin for FireflyPlugin {
                 Core2d,
                 CreateLightmapLabel,
             )
-            .add_render_graph_node::<ViewNodeRunner<ApplyLightmapNode>>(Core2d, ApplyLightmapLabel)
-            .add_render_graph_node::<ViewNodeRunner<SpriteNode>>(Core2d, SpriteLabel);
+            .add_render_graph_node::<ViewNodeRunner<ApplyLightmapNode<false>>>(
+                Core2d,
+                ApplyLightmapLabel,
+            )
+            .add_render_graph_node::<ViewNodeRunner<ApplyLightmapNode<true>>>(
+                Core2d,
+                ApplyLightmapAfterTonemappingLabel,
+            );
         // render_app.add_render_graph_edges(Core2d, (, CreateLightmapLabel));
 
+        #[cfg(feature = "sprites")]
+        render_app
+            .add_render_graph_node::<ViewNodeRunner<SpriteNode>>(Core2d, SpriteLabel)
+            .add_render_graph_edges(
+                Core2d,
+                (
+                    Node2d::StartMainPassPostProcessing,
+                    SpriteLabel,
+                    CreateLightmapLabel,
+                ),
+            );
+
         render_app.add_render_graph_edges(
             Core2d,
             (
                 Node2d::StartMainPassPostProcessing,
-                SpriteLabel,
                 CreateLightmapLabel,
                 ApplyLightmapLabel,
                 Node2d::Tonemapping,
+                ApplyLightmapAfterTonemappingLabel,
+                Node2d::EndMainPassPostProcessing,
             ),
         );
     }
@@ -72,12 +149,361 @@ impl Plugin for FireflyPlugin {
 /// Plugin that shows gizmos for firefly occluders.
 ///
 /// Useful for debugging. Insert the [`FireflyGizmoStyle`] resource to configure.
+///
+/// Also adds the [`FireflyLightRecorder`], which can be enabled to place lights by clicking, and the
+/// [`FireflyLightHandles`], which can be enabled to tune the range of lights by dragging.
 pub struct FireflyGizmosPlugin;
 
 impl Plugin for FireflyGizmosPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<FireflyGizmoStyle>();
-        app.add_systems(Update, draw_gizmos);
+        app.init_resource::<FireflyLightRecorder>();
+        app.init_resource::<FireflyLightHandles>();
+        app.add_systems(
+            Update,
+            (
+                draw_gizmos,
+                record_lights.run_if(|recorder: Res<FireflyLightRecorder>| recorder.enabled),
+                edit_light_ranges,
+            ),
+        );
+    }
+}
+
+/// Resource controlling the light recorder of the [`FireflyGizmosPlugin`].
+///
+/// When [enabled](FireflyLightRecorder::enabled), clicking places a copy of the [preset](FireflyLightRecorder::preset) light
+/// at the cursor, and pressing the [export key](FireflyLightRecorder::export_key) serializes all placed lights into a
+/// RON snippet, which is logged and stored in [`last_export`](FireflyLightRecorder::last_export). This is meant for
+/// quickly lighting levels by hand while the game is running, before having a proper editor.
+///
+/// Lights placed by the recorder are marked with [`RecordedLight`], so they can be moved, tweaked or despawned
+/// before exporting. Exporting requires the `ron` feature.
+#[derive(Resource)]
+pub struct FireflyLightRecorder {
+    /// Whether clicking places lights.
+    ///
+    /// **Default:** false.
+    pub enabled: bool,
+    /// The light that is placed when clicking.
+    ///
+    /// **Default:** [`PointLight2d::default`].
+    pub preset: PointLight2d,
+    /// The mouse button that places lights.
+    ///
+    /// **Default:** [`MouseButton::Left`].
+    pub place_button: MouseButton,
+    /// The key that exports the placed lights.
+    ///
+    /// **Default:** [`KeyCode::F9`].
+    pub export_key: KeyCode,
+    /// The result of the last export.
+    pub last_export: Option<String>,
+}
+
+impl Default for FireflyLightRecorder {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            preset: default(),
+            place_button: MouseButton::Left,
+            export_key: KeyCode::F9,
+            last_export: None,
+        }
+    }
+}
+
+/// Marker component added to lights placed by the [`FireflyLightRecorder`].
+#[derive(Component, Default)]
+pub struct RecordedLight;
+
+/// A light exported by the [`FireflyLightRecorder`].
+#[cfg(feature = "ron")]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct RecordedLightData {
+    pub translation: Vec3,
+    pub light: PointLight2d,
+}
+
+impl FireflyLightRecorder {
+    /// Serializes the given lights into a RON list of [`RecordedLightData`].
+    #[cfg(feature = "ron")]
+    pub fn export<'a>(
+        lights: impl IntoIterator<Item = (&'a Transform, &'a PointLight2d)>,
+    ) -> Result<String, ron::Error> {
+        let lights = lights
+            .into_iter()
+            .map(|(transform, light)| RecordedLightData {
+                translation: transform.translation,
+                light: light.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        ron::ser::to_string_pretty(&lights, ron::ser::PrettyConfig::default())
+    }
+}
+
+fn record_lights(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    #[cfg_attr(not(feature = "ron"), allow(unused_mut))] mut recorder: ResMut<FireflyLightRecorder>,
+    style: Res<FireflyGizmoStyle>,
+    // missing in apps without an `InputPlugin` or a primary window, such as headless ones
+    mouse: Option<Res<ButtonInput<MouseButton>>>,
+    keyboard: Option<Res<ButtonInput<KeyCode>>>,
+    window: Option<Single<&Window, With<PrimaryWindow>>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<FireflyConfig>>,
+    #[cfg(feature = "ron")] recorded: Query<(&Transform, &PointLight2d), With<RecordedLight>>,
+) {
+    if keyboard.is_some_and(|keyboard| keyboard.just_pressed(recorder.export_key)) {
+        #[cfg(feature = "ron")]
+        match FireflyLightRecorder::export(recorded) {
+            Ok(export) => {
+                info!("Recorded lights:\n{export}");
+                recorder.last_export = Some(export);
+            }
+            Err(error) => warn!("Failed to export recorded lights: {error}"),
+        }
+
+        #[cfg(not(feature = "ron"))]
+        warn!("Exporting recorded lights requires the `ron` feature");
+    }
+
+    let Some(cursor_position) = window.and_then(|window| window.cursor_position()) else {
+        return;
+    };
+
+    let cursor = cameras.iter().find_map(|(camera, transform)| {
+        camera.viewport_to_world_2d(transform, cursor_position).ok()
+    });
+
+    let Some(cursor) = cursor else {
+        return;
+    };
+
+    let isometry = Isometry2d::from_translation(cursor);
+    gizmos.circle_2d(
+        isometry,
+        recorder.preset.core.radius,
+        style.light_inner_color,
+    );
+    gizmos.circle_2d(isometry, recorder.preset.radius, style.light_outer_color);
+
+    if mouse.is_some_and(|mouse| mouse.just_pressed(recorder.place_button)) {
+        commands.spawn((
+            recorder.preset.clone(),
+            Transform::from_translation(cursor.extend(0.)),
+            RecordedLight,
+        ));
+    }
+}
+
+/// Resource controlling the light range handles of the [`FireflyGizmosPlugin`].
+///
+/// When [enabled](FireflyLightHandles::enabled), clicking near a light selects it. The selected light's
+/// [range](PointLight2d::radius) and [core radius](LightCore::radius) rings can then be dragged to resize them,
+/// with their values optionally drawn next to the rings.
+///
+/// Clicks are ignored while the [`FireflyLightRecorder`] is enabled.
+#[derive(Resource)]
+pub struct FireflyLightHandles {
+    /// Whether lights can be selected and resized.
+    ///
+    /// **Default:** false.
+    pub enabled: bool,
+    /// Whether the values of the selected light's rings are drawn next to them.
+    ///
+    /// **Default:** true.
+    pub show_values: bool,
+    /// The mouse button that selects lights and drags the rings.
+    ///
+    /// **Default:** [`MouseButton::Left`].
+    pub drag_button: MouseButton,
+    /// How close the cursor needs to be to a ring or light in order to grab it, in world units.
+    ///
+    /// **Default:** 4.
+    pub grab_distance: f32,
+    /// Height of the drawn values, in world units.
+    ///
+    /// **Default:** 6.
+    pub value_size: f32,
+    /// The currently selected light.
+    pub selected: Option<Entity>,
+    dragging: Option<LightHandle>,
+}
+
+impl Default for FireflyLightHandles {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            show_values: true,
+            drag_button: MouseButton::Left,
+            grab_distance: 4.,
+            value_size: 6.,
+            selected: None,
+            dragging: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum LightHandle {
+    Range,
+    Core,
+}
+
+fn edit_light_ranges(
+    mut gizmos: Gizmos,
+    mut handles: ResMut<FireflyLightHandles>,
+    recorder: Res<FireflyLightRecorder>,
+    style: Res<FireflyGizmoStyle>,
+    // missing in apps without an `InputPlugin` or a primary window, such as headless ones
+    mouse: Option<Res<ButtonInput<MouseButton>>>,
+    window: Option<Single<&Window, With<PrimaryWindow>>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<FireflyConfig>>,
+    mut lights: Query<(Entity, &GlobalTransform, &mut PointLight2d)>,
+) {
+    if !handles.enabled {
+        return;
+    }
+
+    let cursor = window
+        .and_then(|window| window.cursor_position())
+        .and_then(|cursor_position| {
+            cameras.iter().find_map(|(camera, transform)| {
+                camera.viewport_to_world_2d(transform, cursor_position).ok()
+            })
+        });
+
+    if let Some(cursor) = cursor
+        && let Some(mouse) = mouse
+        && !recorder.enabled
+    {
+        if mouse.just_pressed(handles.drag_button) {
+            let grab = handles.grab_distance;
+
+            // grabbing a ring of the selected light takes priority over selecting another light
+            handles.dragging = handles
+                .selected
+                .and_then(|selected| lights.get(selected).ok())
+                .and_then(|(_, transform, light)| {
+                    let distance = transform.translation().xy().distance(cursor);
+                    if (distance - light.radius).abs() <= grab {
+                        Some(LightHandle::Range)
+                    } else if (distance - light.core.radius).abs() <= grab {
+                        Some(LightHandle::Core)
+                    } else {
+                        None
+                    }
+                });
+
+            if handles.dragging.is_none() {
+                handles.selected = lights
+                    .iter()
+                    .map(|(entity, transform, light)| {
+                        let distance = transform.translation().xy().distance(cursor);
+                        (entity, distance, distance <= grab.max(light.core.radius))
+                    })
+                    .filter(|(_, _, grabbed)| *grabbed)
+                    .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
+                    .map(|(entity, _, _)| entity);
+            }
+        }
+
+        if !mouse.pressed(handles.drag_button) {
+            handles.dragging = None;
+        }
+
+        if let Some(dragging) = handles.dragging
+            && let Some(selected) = handles.selected
+            && let Ok((_, transform, mut light)) = lights.get_mut(selected)
+        {
+            let distance = transform.translation().xy().distance(cursor);
+            match dragging {
+                LightHandle::Range => light.radius = distance.max(light.core.radius),
+                LightHandle::Core => light.core.radius = distance.min(light.radius),
+            }
+        }
+    }
+
+    let Some((_, transform, light)) = handles
+        .selected
+        .and_then(|selected| lights.get(selected).ok())
+    else {
+        handles.selected = None;
+        return;
+    };
+
+    let center = transform.translation().xy();
+    for (radius, color) in [
+        (light.radius, style.light_outer_color),
+        (light.core.radius, style.light_inner_color),
+    ] {
+        // rings are drawn twice, slightly apart, to stand out from the regular light gizmos
+        let isometry = Isometry2d::from_translation(center);
+        gizmos.circle_2d(isometry, radius, color);
+        gizmos.circle_2d(isometry, radius + handles.grab_distance * 0.25, color);
+
+        let handle = center + Vec2::X * radius;
+        gizmos.rect_2d(
+            Isometry2d::from_translation(handle),
+            Vec2::splat(handles.grab_distance),
+            color,
+        );
+
+        if handles.show_values {
+            draw_number(
+                &mut gizmos,
+                handle + vec2(handles.grab_distance, handles.grab_distance * 0.5),
+                radius,
+                handles.value_size,
+                color,
+            );
+        }
+    }
+}
+
+/// Draws a number with one decimal using seven-segment digits, starting with the bottom left corner at `pos`.
+fn draw_number(gizmos: &mut Gizmos, pos: Vec2, value: f32, height: f32, color: Color) {
+    // segments: top, top right, bottom right, bottom, bottom left, top left, middle
+    const DIGITS: [u8; 10] = [
+        0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110, 0b1101101, 0b1111101, 0b0000111,
+        0b1111111, 0b1101111,
+    ];
+
+    let width = height * 0.5;
+    let half = height * 0.5;
+    let mut x = pos.x;
+
+    for char in format!("{value:.1}").chars() {
+        let origin = vec2(x, pos.y);
+        let segments = match char {
+            '.' => {
+                gizmos.line_2d(origin, origin + vec2(0., height * 0.1), color);
+                x += width * 0.5;
+                continue;
+            }
+            '-' => 0b1000000,
+            char => char.to_digit(10).map_or(0, |digit| DIGITS[digit as usize]),
+        };
+
+        let lines = [
+            (vec2(0., height), vec2(width, height)),
+            (vec2(width, height), vec2(width, half)),
+            (vec2(width, half), vec2(width, 0.)),
+            (vec2(0., 0.), vec2(width, 0.)),
+            (vec2(0., half), vec2(0., 0.)),
+            (vec2(0., height), vec2(0., half)),
+            (vec2(0., half), vec2(width, half)),
+        ];
+
+        for (i, (from, to)) in lines.into_iter().enumerate() {
+            if segments & (1 << i) != 0 {
+                gizmos.line_2d(origin + from, origin + to, color);
+            }
+        }
+
+        x += width * 1.5;
     }
 }
 
@@ -86,6 +512,12 @@ impl Plugin for FireflyGizmosPlugin {
 pub struct FireflyGizmoStyle {
     pub light_outer_color: Color,
     pub light_inner_color: Color,
+    /// Color of the direction arrows of angled lights.
+    pub light_direction_color: Color,
+    /// Color of the lines from entities to their lights' [offsets](PointLight2d::offset).
+    pub light_offset_color: Color,
+    /// Color of the polygons of lights with a [`ComputedLightVisibility`].
+    pub light_visibility_color: Color,
     pub occluder_color: Color,
 }
 
@@ -94,6 +526,9 @@ impl Default for FireflyGizmoStyle {
         Self {
             light_outer_color: Color::Srgba(GREY),
             light_inner_color: Color::Srgba(WHITE),
+            light_direction_color: Color::Srgba(YELLOW),
+            light_offset_color: Color::Srgba(AQUA),
+            light_visibility_color: Color::Srgba(ORANGE),
             occluder_color: Color::Srgba(PINK),
         }
     }
@@ -103,13 +538,74 @@ fn draw_gizmos(
     mut gizmos: Gizmos,
     style: Res<FireflyGizmoStyle>,
     occluders: Query<(&GlobalTransform, &Occluder2d)>,
-    lights: Query<(&GlobalTransform, &PointLight2d)>,
+    lights: Query<(
+        &GlobalTransform,
+        &PointLight2d,
+        Option<&ComputedLightVisibility>,
+    )>,
 ) {
-    for (transform, light) in lights {
-        let isometry = Isometry2d::from_translation(transform.translation().xy());
+    for (transform, light, visibility) in lights {
+        let origin = transform.translation().xy();
+        let center = origin + light.offset.xy();
+        let isometry = Isometry2d::from_translation(center);
+
+        // the offset is drawn as a line from the entity to the light
+        if light.offset.xy() != Vec2::ZERO {
+            gizmos.line_2d(origin, center, style.light_offset_color);
+            gizmos.cross_2d(
+                Isometry2d::from_translation(origin),
+                light.core.radius.max(1.) * 0.5,
+                style.light_offset_color,
+            );
+        }
 
         gizmos.circle_2d(isometry, light.core.radius, style.light_inner_color);
-        gizmos.circle_2d(isometry, light.radius, style.light_outer_color);
+
+        // reuses the polygon computed this frame instead of sweeping the occluders again
+        if let Some(visibility) = visibility {
+            let mut polygon = visibility.polygon();
+            if let Some(first) = polygon.first().copied() {
+                polygon.push(first);
+            }
+            gizmos.linestrip_2d(polygon, style.light_visibility_color);
+        }
+
+        if light.angle.outer >= 360. {
+            gizmos.circle_2d(isometry, light.radius, style.light_outer_color);
+            continue;
+        }
+
+        // same direction as the one extracted for the shader
+        let dir = (transform.rotation() * Vec3::Y).xy().normalize_or(Vec2::Y);
+        // arcs start at the isometry's +Y and sweep counter-clockwise, so they're started half a cone before `dir`
+        let rotation = Rot2::radians(Vec2::Y.angle_to(dir) - light.angle.outer.to_radians() * 0.5);
+
+        gizmos.arc_2d(
+            Isometry2d::new(center, rotation),
+            light.angle.outer.to_radians(),
+            light.radius,
+            style.light_outer_color,
+        );
+
+        for (angle, color) in [
+            (light.angle.outer, style.light_outer_color),
+            (light.angle.inner, style.light_inner_color),
+        ] {
+            let half = (angle * 0.5).to_radians();
+            for side in [-half, half] {
+                gizmos.line_2d(
+                    center,
+                    center + Rot2::radians(side) * dir * light.radius,
+                    color,
+                );
+            }
+        }
+
+        gizmos.arrow_2d(
+            center,
+            center + dir * (light.radius * 0.25).max(light.core.radius),
+            style.light_direction_color,
+        );
     }
 
     for (transform, occluder) in &occluders {
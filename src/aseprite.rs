@@ -0,0 +1,381 @@
+//! Module containing [`AsepriteSheet`], for wiring the metadata of Aseprite exports into occluders and normal maps.
+
+use bevy::{
+    asset::{AssetEventSystems, AssetLoader, LoadContext, io::Reader},
+    image::ImageLoaderSettings,
+    platform::collections::HashSet,
+    prelude::*,
+    sprite::Anchor,
+};
+
+use crate::{
+    prelude::{FireflySprite, Occluder2d},
+    sprites::NormalMap,
+};
+
+/// Asset loaded from the JSON data that Aseprite exports alongside a sprite sheet.
+///
+/// With the `aseprite` feature, it's loaded from `.aseprite.json` files. In Aseprite's `Export Sprite Sheet`
+/// dialog, the JSON data needs to use the `Array` format, with `Layers` and `Slices` enabled. Frames shouldn't be trimmed.
+///
+/// ## Companion Layers
+/// If the sprite has a layer named `Normal` or `Emissive` (in any case), the loader expects it to be exported as a sheet
+/// with the same layout next to the main one, suffixed with the layer's name in lowercase. E.g. for `crate.png`, the normal
+/// map is loaded from `crate_normal.png` (without gamma correction) and the emissive map from `crate_emissive.png`.
+///
+/// ## Occluder Slices
+/// A slice named `occluder` becomes a rectangular [`Occluder2d`] covering the slice's bounds in the first frame,
+/// and a slice named `occluder_round` becomes a round occluder fitting them.
+///
+/// Add the [`AsepriteLighting`] component to a sprite to have these attached automatically.
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct AsepriteSheet {
+    /// The sprite sheet image.
+    pub image: Handle<Image>,
+
+    /// The sheet of the `Normal` layer, if the sprite has one.
+    pub normal_map: Option<Handle<Image>>,
+
+    /// The sheet of the `Emissive` layer, if the sprite has one.
+    pub emissive: Option<Handle<Image>>,
+
+    /// Layout of the frames in the sheets, in the order they were exported.
+    pub atlas_layout: Handle<TextureAtlasLayout>,
+
+    /// Size of each frame, in pixels.
+    pub frame_size: UVec2,
+
+    /// Names of the sprite's layers.
+    pub layers: Vec<String>,
+
+    /// The sprite's slices.
+    pub slices: Vec<AsepriteSlice>,
+}
+
+/// A slice of an [`AsepriteSheet`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsepriteSlice {
+    /// Name of the slice.
+    pub name: String,
+
+    /// Bounds of the slice in its first key, in pixels relative to the top-left corner of the frame.
+    pub bounds: URect,
+
+    /// Pivot of the slice, relative to the top-left corner of its bounds.
+    pub pivot: Option<UVec2>,
+}
+
+impl AsepriteSheet {
+    /// Returns a [`FireflySprite`] showing the given frame of the sheet.
+    pub fn sprite(&self, frame: usize) -> FireflySprite {
+        FireflySprite {
+            image: self.image.clone(),
+            texture_atlas: Some(TextureAtlas {
+                layout: self.atlas_layout.clone(),
+                index: frame,
+            }),
+            ..default()
+        }
+    }
+
+    /// Returns the occluder described by the sheet's `occluder` or `occluder_round` slice, positioned for
+    /// a sprite with the given anchor.
+    pub fn occluder(&self, anchor: Vec2) -> Option<Occluder2d> {
+        let slice = self.slices.iter().find(|slice| {
+            slice.name.eq_ignore_ascii_case("occluder")
+                || slice.name.eq_ignore_ascii_case("occluder_round")
+        })?;
+
+        let size = slice.bounds.size().as_vec2();
+        let occluder = match slice.name.eq_ignore_ascii_case("occluder_round") {
+            false => Occluder2d::rectangle(size.x, size.y),
+            true => {
+                let radius = size.min_element() * 0.5;
+                Occluder2d::round_rectangle(size.x - 2. * radius, size.y - 2. * radius, radius)
+            }
+        };
+
+        // slices are relative to the top-left corner of the frame, with the y axis pointing down
+        let frame_size = self.frame_size.as_vec2();
+        let center = (slice.bounds.min + slice.bounds.max).as_vec2() * 0.5 - frame_size * 0.5;
+        let offset = vec2(center.x, -center.y) - anchor * frame_size;
+
+        Some(occluder.with_offset(offset.extend(0.)))
+    }
+}
+
+/// Component that attaches the [`NormalMap`] and [`Occluder2d`] described by an [`AsepriteSheet`] to the entity.
+///
+/// They're inserted once the sheet is loaded, and replaced whenever it's modified. The occluder is positioned using
+/// the entity's [`Anchor`], assuming the sprite is drawn at its original size.
+///
+/// # Example
+///
+/// ```
+/// let sheet: Handle<AsepriteSheet> = asset_server.load("crate.aseprite.json");
+///
+/// commands.spawn((
+///     FireflySprite::from_image(asset_server.load("crate.png")),
+///     Anchor(vec2(0.0, -0.5 + 3.0 / 18.0)),
+///     AsepriteLighting(sheet),
+/// ));
+/// ```
+#[derive(Component, Clone, Default, Debug)]
+pub struct AsepriteLighting(pub Handle<AsepriteSheet>);
+
+/// [`AssetLoader`] for [`AsepriteSheet`]s stored as `.aseprite.json` files.
+#[derive(Default, TypePath)]
+pub struct AsepriteSheetLoader;
+
+/// Error that can occur while loading an [`AsepriteSheet`].
+#[derive(Debug)]
+pub enum AsepriteSheetLoaderError {
+    /// The file couldn't be read.
+    Io(std::io::Error),
+    /// The file isn't valid Aseprite JSON data.
+    Json(serde_json::Error),
+    /// The sheet's image path couldn't be resolved.
+    Path(bevy::asset::ParseAssetPathError),
+}
+
+impl std::fmt::Display for AsepriteSheetLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "could not read aseprite sheet: {err}"),
+            Self::Json(err) => write!(f, "could not parse aseprite sheet: {err}"),
+            Self::Path(err) => write!(f, "could not resolve aseprite sheet image: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for AsepriteSheetLoaderError {}
+
+impl From<std::io::Error> for AsepriteSheetLoaderError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for AsepriteSheetLoaderError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<bevy::asset::ParseAssetPathError> for AsepriteSheetLoaderError {
+    fn from(err: bevy::asset::ParseAssetPathError) -> Self {
+        Self::Path(err)
+    }
+}
+
+/// The parts of Aseprite's JSON data that are read.
+#[derive(serde::Deserialize)]
+struct AsepriteData {
+    frames: Vec<AsepriteFrame>,
+    meta: AsepriteMeta,
+}
+
+#[derive(serde::Deserialize)]
+struct AsepriteFrame {
+    frame: AsepriteRect,
+}
+
+#[derive(serde::Deserialize)]
+struct AsepriteMeta {
+    image: String,
+    size: AsepriteSize,
+    #[serde(default)]
+    layers: Vec<AsepriteLayer>,
+    #[serde(default)]
+    slices: Vec<AsepriteSliceData>,
+}
+
+#[derive(serde::Deserialize)]
+struct AsepriteLayer {
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct AsepriteSliceData {
+    name: String,
+    keys: Vec<AsepriteSliceKey>,
+}
+
+#[derive(serde::Deserialize)]
+struct AsepriteSliceKey {
+    bounds: AsepriteRect,
+    pivot: Option<AsepritePoint>,
+}
+
+#[derive(serde::Deserialize)]
+struct AsepriteRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+impl AsepriteRect {
+    fn to_urect(&self) -> URect {
+        URect::new(self.x, self.y, self.x + self.w, self.y + self.h)
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct AsepriteSize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(serde::Deserialize)]
+struct AsepritePoint {
+    x: u32,
+    y: u32,
+}
+
+impl AssetLoader for AsepriteSheetLoader {
+    type Asset = AsepriteSheet;
+    type Settings = ();
+    type Error = AsepriteSheetLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let data: AsepriteData = serde_json::from_slice(&bytes)?;
+
+        let image_path = load_context.path().resolve_embed(&data.meta.image)?;
+        let image = load_context.load(image_path.clone());
+
+        // companion layers are exported next to the sheet, e.g. `crate_normal.png` for `crate.png`
+        let companion = |layer: &str| {
+            data.meta
+                .layers
+                .iter()
+                .any(|l| l.name.eq_ignore_ascii_case(layer))
+                .then(|| {
+                    let image = &data.meta.image;
+                    match image.rsplit_once('.') {
+                        Some((stem, extension)) => format!("{stem}_{layer}.{extension}"),
+                        None => format!("{image}_{layer}"),
+                    }
+                })
+        };
+
+        let base_path = load_context.path().clone_owned();
+        let normal_path = companion("normal")
+            .map(|path| base_path.resolve_embed(&path))
+            .transpose()?;
+        let emissive_path = companion("emissive")
+            .map(|path| base_path.resolve_embed(&path))
+            .transpose()?;
+
+        let normal_map = normal_path.map(|path| {
+            load_context
+                .loader()
+                .with_settings(|settings: &mut ImageLoaderSettings| settings.is_srgb = false)
+                .load(path)
+        });
+        let emissive = emissive_path.map(|path| load_context.load(path));
+
+        let mut layout = TextureAtlasLayout::new_empty(uvec2(data.meta.size.w, data.meta.size.h));
+        for frame in &data.frames {
+            layout.add_texture(frame.frame.to_urect());
+        }
+        let frame_size = data
+            .frames
+            .first()
+            .map_or(UVec2::ZERO, |frame| uvec2(frame.frame.w, frame.frame.h));
+        let atlas_layout = load_context.add_labeled_asset("layout".to_string(), layout);
+
+        let slices = data
+            .meta
+            .slices
+            .into_iter()
+            .filter_map(|slice| {
+                let key = slice.keys.first()?;
+                Some(AsepriteSlice {
+                    bounds: key.bounds.to_urect(),
+                    pivot: key.pivot.as_ref().map(|pivot| uvec2(pivot.x, pivot.y)),
+                    name: slice.name,
+                })
+            })
+            .collect();
+
+        Ok(AsepriteSheet {
+            image,
+            normal_map,
+            emissive,
+            atlas_layout,
+            frame_size,
+            layers: data
+                .meta
+                .layers
+                .into_iter()
+                .map(|layer| layer.name)
+                .collect(),
+            slices,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["aseprite.json"]
+    }
+}
+
+/// Plugin that loads [`AsepriteSheet`]s and wires them into [`AsepriteLighting`] entities.
+/// Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin) with the `aseprite` feature.
+pub struct AsepritePlugin;
+
+impl Plugin for AsepritePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<AsepriteSheet>();
+        app.init_asset_loader::<AsepriteSheetLoader>();
+        app.add_systems(
+            PostUpdate,
+            update_aseprite_lighting.after(AssetEventSystems),
+        );
+    }
+}
+
+fn update_aseprite_lighting(
+    mut commands: Commands,
+    mut events: MessageReader<AssetEvent<AsepriteSheet>>,
+    sheets: Res<Assets<AsepriteSheet>>,
+    entities: Query<(Entity, Ref<AsepriteLighting>, Option<&Anchor>)>,
+) {
+    let changed: HashSet<_> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id }
+            | AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, lighting, anchor) in &entities {
+        if !lighting.is_changed() && !changed.contains(&lighting.0.id()) {
+            continue;
+        }
+
+        // not loaded yet, the components are inserted once it is
+        let Some(sheet) = sheets.get(&lighting.0) else {
+            continue;
+        };
+
+        let mut entity = commands.entity(entity);
+
+        if let Some(normal_map) = &sheet.normal_map {
+            entity.insert(NormalMap::from_handle(normal_map.clone()));
+        }
+
+        if let Some(occluder) = sheet.occluder(anchor.map_or(Vec2::ZERO, Anchor::as_vec)) {
+            entity.insert(occluder);
+        }
+    }
+}
@@ -0,0 +1,461 @@
+//! Module containing [`BakedShadow`], for baking the shadows of static scenes into sprites.
+//!
+//! On very low-end targets, the shadows of lights and occluders that never move can be baked once into images,
+//! and drawn as regular sprites. The lights can then have [`cast_shadows`](crate::prelude::PointLight2d::cast_shadows)
+//! disabled, skipping the runtime shadow pass entirely.
+//!
+//! If a [`Static`] light or occluder does end up moving (e.g. a door opening), the baked shadows of the affected lights
+//! cross-fade into their dynamic shadows, instead of popping.
+
+use std::{f32::consts::PI, time::Duration};
+
+use bevy::{
+    asset::RenderAssetUsages,
+    ecs::system::SystemParam,
+    math::bounding::{Aabb2d, IntersectsVolume},
+    platform::collections::HashSet,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    transform::TransformSystems,
+};
+
+use crate::{
+    data::{FireflyConfig, FireflyGlobalOverrides, FireflyTestClock},
+    lights::PointLight2d,
+    occluders::Occluder2d,
+    visibility::{OccluderAabb, segment_hit, shape_aabb},
+};
+
+/// The shadow of a single [`Occluder2d`], as cast by a single [`PointLight2d`], baked into an image.
+///
+/// The shadow is computed like the lightmap pass does, with the camera's [soft shadows](FireflyConfig::soft_shadows),
+/// [softness](FireflyConfig::softness), [shadow skew](FireflyConfig::shadow_skew) and [shadow strength](FireflyConfig::shadow_strength),
+/// and the occluder's [opacity](Occluder2d::opacity), [color](Occluder2d::color), [shadow falloff](Occluder2d::shadow_falloff)
+/// and [distance opacity](Occluder2d::distance_opacity). Each pixel is the color the shadow multiplies the light by,
+/// stored in its alpha and color so that alpha blending the image over the lit scene applies it.
+///
+/// # Example
+///
+/// ```
+/// fn bake(
+///     mut commands: Commands,
+///     baker: ShadowBaker,
+///     light: Single<Entity, With<PointLight2d>>,
+///     camera: Single<Entity, With<FireflyConfig>>,
+///     mut images: ResMut<Assets<Image>>,
+/// ) {
+///     for (occluder, shadow) in baker.bake_light(*light, *camera, 1.0) {
+///         let (sprite, transform) = shadow.into_sprite(&mut images, 1.0);
+///         commands.spawn((sprite, transform, BakedShadowOf::new(*light, occluder)));
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BakedShadow {
+    /// The baked image.
+    pub image: Image,
+    /// World-space rectangle covered by the image.
+    pub rect: Rect,
+}
+
+impl BakedShadow {
+    /// Bakes the shadow cast by an occluder, given the position of the light and the config of the camera it's drawn for.
+    ///
+    /// The image has `pixels_per_unit` pixels for each world unit, and is cropped to the shadow.
+    /// Returns None if the occluder doesn't cast any shadow within the light's range.
+    #[allow(clippy::too_many_arguments)]
+    pub fn bake(
+        light_pos: Vec2,
+        light_dir: Vec2,
+        light: &PointLight2d,
+        occluder: &Occluder2d,
+        occluder_transform: &GlobalTransform,
+        config: &FireflyConfig,
+        overrides: &FireflyGlobalOverrides,
+        pixels_per_unit: f32,
+    ) -> Option<Self> {
+        if pixels_per_unit <= 0. || light.radius <= 0. {
+            return None;
+        }
+
+        let isometry = Isometry2d {
+            rotation: Rot2::radians(occluder_transform.rotation().to_euler(EulerRot::XYZ).2),
+            translation: occluder_transform.translation().truncate() + occluder.offset.truncate(),
+        };
+
+        if let Some(dir) = occluder.one_way
+            && (isometry.translation - light_pos).dot(isometry.rotation * *dir) <= 0.
+        {
+            return None;
+        }
+
+        let min = light_pos - light.radius;
+        let size = UVec2::splat(((2. * light.radius * pixels_per_unit).ceil() as u32).max(1));
+        let pixel_size = 1. / pixels_per_unit;
+        let half_angle = (light.angle.outer + light.angle.feather).to_radians() / 2.;
+
+        // the runtime pass measures the falloff and distance opacity from the occluder's closest point to the light
+        let caster_dist = shape_aabb(occluder.shape(), isometry)
+            .closest_point(light_pos)
+            .distance(light_pos);
+        let near = occluder
+            .distance_opacity
+            .map_or(1., |opacity| opacity.multiplier(caster_dist));
+
+        let core_radius = match config.soft_shadows && !overrides.disable_soft_shadows {
+            true => config.softness.core_radius(light.core.radius) * overrides.softness.max(0.),
+            false => 0.,
+        };
+        let samples = penumbra_samples(core_radius);
+
+        let color = occluder.color.to_linear().to_vec3();
+        let strength = light.shadow_strength * config.shadow_strength.clamp(0., 1.);
+
+        let mut pixels = vec![[0u8; 4]; (size.x * size.y) as usize];
+        let mut bounds: Option<(UVec2, UVec2)> = None;
+
+        for y in 0..size.y {
+            for x in 0..size.x {
+                // row 0 is the top of the image
+                let point = min + vec2(x as f32 + 0.5, (size.y - 1 - y) as f32 + 0.5) * pixel_size;
+
+                let dist = point.distance(light_pos);
+                if dist > light.radius {
+                    continue;
+                }
+
+                if half_angle < PI && (point - light_pos).angle_to(light_dir).abs() > half_angle {
+                    continue;
+                }
+
+                // shadows are cast from the light's core, shifted by the skew
+                let shadow_pos = light_pos - config.shadow_skew * dist;
+                let hits = samples
+                    .iter()
+                    .filter(|offset| {
+                        segment_hit(occluder.shape(), isometry, shadow_pos + **offset, point)
+                            .is_some()
+                    })
+                    .count();
+                if hits == 0 {
+                    continue;
+                }
+
+                let result = hits as f32 / samples.len() as f32;
+                let along =
+                    shadow_falloff(dist, caster_dist, light.radius, occluder.shadow_falloff);
+                let occlusion = occluder.opacity * result * along * near;
+
+                // matches `shadow_blend` and the shadow strength in the lightmap shader
+                let multiplier = (Vec3::ONE + color) * (1. - occlusion);
+                let multiplier = Vec3::ONE - (Vec3::ONE - multiplier.min(Vec3::ONE)) * strength;
+
+                let alpha = 1. - multiplier.min_element();
+                if alpha <= 0. {
+                    continue;
+                }
+
+                // alpha blending the pixel over white multiplies it by `multiplier`, and over any color for gray shadows
+                let rgb =
+                    ((multiplier - Vec3::splat(1. - alpha)) / alpha).clamp(Vec3::ZERO, Vec3::ONE);
+                let [r, g, b] = Srgba::from(LinearRgba::from_vec3(rgb)).to_u8_array_no_alpha();
+                pixels[(y * size.x + x) as usize] = [r, g, b, (alpha * 255.).round() as u8];
+
+                let pixel = uvec2(x, y);
+                bounds = Some(match bounds {
+                    None => (pixel, pixel),
+                    Some((min, max)) => (min.min(pixel), max.max(pixel)),
+                });
+            }
+        }
+
+        let (crop_min, crop_max) = bounds?;
+        let crop_size = crop_max - crop_min + UVec2::ONE;
+
+        let mut data = Vec::with_capacity((crop_size.x * crop_size.y * 4) as usize);
+        for y in crop_min.y..=crop_max.y {
+            for x in crop_min.x..=crop_max.x {
+                data.extend_from_slice(&pixels[(y * size.x + x) as usize]);
+            }
+        }
+
+        let image = Image::new(
+            Extent3d {
+                width: crop_size.x,
+                height: crop_size.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+
+        let rect_min = min + vec2(crop_min.x as f32, (size.y - 1 - crop_max.y) as f32) * pixel_size;
+        let rect = Rect::from_corners(rect_min, rect_min + crop_size.as_vec2() * pixel_size);
+
+        Some(Self { image, rect })
+    }
+
+    /// Adds the image to the assets and returns a [`Sprite`] and [`Transform`] that draw it in place, at the given z.
+    pub fn into_sprite(self, images: &mut Assets<Image>, z: f32) -> (Sprite, Transform) {
+        let size = self.rect.size();
+        let center = self.rect.center();
+
+        let sprite = Sprite {
+            custom_size: Some(size),
+            ..Sprite::from_image(images.add(self.image))
+        };
+
+        (sprite, Transform::from_translation(center.extend(z)))
+    }
+}
+
+/// [`SystemParam`] for [baking](BakedShadow) the shadows of static lights and occluders.
+#[derive(SystemParam)]
+pub struct ShadowBaker<'w, 's> {
+    lights: Query<'w, 's, (&'static GlobalTransform, &'static PointLight2d)>,
+    cameras: Query<'w, 's, &'static FireflyConfig>,
+    overrides: Res<'w, FireflyGlobalOverrides>,
+    occluders: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GlobalTransform,
+            &'static Occluder2d,
+            &'static OccluderAabb,
+        ),
+    >,
+}
+
+impl ShadowBaker<'_, '_> {
+    /// Bakes the shadows of every occluder within range of the given light, as rendered by the given camera,
+    /// returning them along with the occluder's entity.
+    ///
+    /// The light's [`cast_shadows`](PointLight2d::cast_shadows) is ignored, so it can already be disabled.
+    /// Returns nothing if the entity isn't a light, or the camera has no [`FireflyConfig`].
+    pub fn bake_light(
+        &self,
+        light: Entity,
+        camera: Entity,
+        pixels_per_unit: f32,
+    ) -> Vec<(Entity, BakedShadow)> {
+        let (Ok((transform, light)), Ok(config)) =
+            (self.lights.get(light), self.cameras.get(camera))
+        else {
+            return default();
+        };
+
+        let light_pos = transform.translation().truncate() + light.offset.truncate();
+        let light_dir = (transform.rotation() * Vec3::Y).truncate();
+        let light_aabb = Aabb2d::new(light_pos, Vec2::splat(light.radius));
+
+        self.occluders
+            .iter()
+            .filter(|(_, _, _, aabb)| aabb.0.intersects(&light_aabb))
+            .filter_map(|(entity, occluder_transform, occluder, _)| {
+                BakedShadow::bake(
+                    light_pos,
+                    light_dir,
+                    light,
+                    occluder,
+                    occluder_transform,
+                    config,
+                    &self.overrides,
+                    pixels_per_unit,
+                )
+                .map(|shadow| (entity, shadow))
+            })
+            .collect()
+    }
+}
+
+/// Marks a light or occluder whose shadows are [baked](BakedShadow).
+///
+/// Static entities aren't expected to move. If one does, every light with baked shadows involving it switches back to
+/// dynamic shadows: its [`cast_shadows`](PointLight2d::cast_shadows) is enabled, its
+/// [shadow strength](PointLight2d::shadow_strength) fades in while its baked shadows fade out, and the baked shadows
+/// are then despawned. Baked shadows are found through their [`BakedShadowOf`] component.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     PointLight2d {
+///         cast_shadows: false,
+///         ..default()
+///     },
+///     Static::default(),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Static {
+    /// Duration of the cross-fade between the baked and dynamic shadows, once the entity moves.
+    ///
+    /// **Default:** 0.3 seconds.
+    pub crossfade: Duration,
+}
+
+impl Default for Static {
+    fn default() -> Self {
+        Self {
+            crossfade: Duration::from_secs_f32(0.3),
+        }
+    }
+}
+
+impl Static {
+    /// Construct a new static marker with the given [cross-fade](Static::crossfade) duration.
+    pub fn new(crossfade: Duration) -> Self {
+        Self { crossfade }
+    }
+}
+
+/// Component added to the sprite of a [`BakedShadow`], linking it to the light and occluder it was baked from.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BakedShadowOf {
+    /// The light casting the shadow.
+    pub light: Entity,
+    /// The occluder blocking the light.
+    pub occluder: Entity,
+}
+
+impl BakedShadowOf {
+    /// Construct a new link between a baked shadow and its light and occluder.
+    pub fn new(light: Entity, occluder: Entity) -> Self {
+        Self { light, occluder }
+    }
+}
+
+/// Component added to lights whose baked shadows are cross-fading into dynamic ones.
+#[derive(Component)]
+struct BakeCrossfade {
+    timer: Timer,
+    /// The light's shadow strength before the cross-fade, faded in from 0.
+    shadow_strength: f32,
+}
+
+/// Plugin that cross-fades the shadows of [`Static`] entities that moved.
+/// Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct BakePlugin;
+
+impl Plugin for BakePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Static>();
+        app.add_systems(
+            PostUpdate,
+            (start_bake_crossfades, update_bake_crossfades)
+                .chain()
+                .after(TransformSystems::Propagate),
+        );
+    }
+}
+
+fn start_bake_crossfades(
+    statics: Query<(Entity, Ref<GlobalTransform>, &Static)>,
+    mut lights: Query<(Entity, &mut PointLight2d, Has<BakeCrossfade>)>,
+    baked: Query<&BakedShadowOf>,
+    mut commands: Commands,
+) {
+    let moved = statics
+        .iter()
+        .filter(|(_, transform, _)| transform.is_changed() && !transform.is_added())
+        .map(|(entity, _, marker)| (entity, marker.crossfade))
+        .collect::<Vec<_>>();
+
+    if moved.is_empty() {
+        return;
+    }
+
+    // lights whose baked shadows involve a moved entity, with the cross-fade duration of that entity
+    let mut affected = HashSet::<Entity>::default();
+    for (entity, crossfade) in moved {
+        let lights_of = baked
+            .iter()
+            .filter(|shadow| shadow.light == entity || shadow.occluder == entity)
+            .map(|shadow| shadow.light);
+
+        for light in lights_of {
+            if !affected.insert(light) {
+                continue;
+            }
+
+            let Ok((light, mut point_light, fading)) = lights.get_mut(light) else {
+                continue;
+            };
+
+            if fading {
+                continue;
+            }
+
+            commands.entity(light).insert(BakeCrossfade {
+                timer: Timer::new(crossfade, TimerMode::Once),
+                shadow_strength: point_light.shadow_strength,
+            });
+
+            point_light.cast_shadows = true;
+            point_light.shadow_strength = 0.;
+        }
+    }
+}
+
+fn update_bake_crossfades(
+    mut lights: Query<(Entity, &mut PointLight2d, &mut BakeCrossfade)>,
+    mut baked: Query<(Entity, &BakedShadowOf, &mut Sprite)>,
+    time: Res<Time>,
+    test_clock: Option<Res<FireflyTestClock>>,
+    mut commands: Commands,
+) {
+    let delta = test_clock.map_or(time.delta(), |clock| clock.delta);
+
+    for (entity, mut light, mut crossfade) in &mut lights {
+        crossfade.timer.tick(delta);
+        let t = crossfade.timer.fraction();
+
+        light.shadow_strength = crossfade.shadow_strength * t;
+
+        for (shadow, _, mut sprite) in baked.iter_mut().filter(|(_, of, _)| of.light == entity) {
+            if crossfade.timer.is_finished() {
+                commands.entity(shadow).despawn();
+            } else {
+                sprite.color.set_alpha(1. - t);
+            }
+        }
+
+        if crossfade.timer.is_finished() {
+            commands.entity(entity).remove::<BakeCrossfade>();
+        }
+    }
+}
+
+/// Number of points of the light's core a baked penumbra is sampled from.
+const PENUMBRA_SAMPLES: usize = 64;
+
+/// Points spread evenly over a disc of the given radius, for sampling the penumbra like the
+/// [stochastic](crate::prelude::PenumbraMode::Stochastic) penumbra does, but averaged.
+fn penumbra_samples(core_radius: f32) -> Vec<Vec2> {
+    if core_radius <= 0. {
+        return vec![Vec2::ZERO];
+    }
+
+    // golden angle spiral, with the same square root distribution as `penumbra_jitter` in the lightmap shader
+    let golden_angle = PI * (3. - 5f32.sqrt());
+    (0..PENUMBRA_SAMPLES)
+        .map(|i| {
+            let r = ((i as f32 + 0.5) / PENUMBRA_SAMPLES as f32).sqrt();
+            Vec2::from_angle(i as f32 * golden_angle) * r * core_radius
+        })
+        .collect()
+}
+
+/// Matches `shadow_falloff` in the lightmap shader.
+fn shadow_falloff(dist: f32, caster_dist: f32, radius: f32, falloff: f32) -> f32 {
+    if falloff <= 0. {
+        return 1.;
+    }
+
+    let t = ((dist - caster_dist) / (radius - caster_dist).max(0.0001)).clamp(0., 1.);
+    1. - falloff * t
+}
@@ -11,10 +11,13 @@ use std::{
     array,
     collections::{BinaryHeap, VecDeque},
     f32::consts::{PI, TAU},
+    ops::Range,
 };
 
 use bevy::{
-    platform::collections::HashMap,
+    camera::visibility::RenderLayers,
+    math::bounding::Aabb2d,
+    platform::collections::{HashMap, HashSet},
     prelude::*,
     render::{
         Render, RenderApp, RenderStartup, RenderSystems,
@@ -29,10 +32,13 @@ use bevy::{
 use bytemuck::{NoUninit, Pod, Zeroable};
 
 use crate::{
+    gradient::ColorGradientLuts,
     lights::{ExtractedPointLight, Falloff, LightIndex, UniformPointLight},
+    occluder_batch::{ExtractedOccluderBatch, OccluderBatchIndices},
+    occluder_group::{ExtractedOccluderGroup, OccluderGroupPose, OccluderGroupSlot},
     occluders::{
-        ExtractedOccluder, Occluder2dShape, PolyOccluderIndex, RoundOccluderIndex, UniformOccluder,
-        UniformRoundOccluder,
+        DistanceOpacity, ExtractedOccluder, Occluder2dShape, PolyOccluderIndex, RoundOccluderIndex,
+        UniformOccluder, UniformRoundOccluder,
     },
     visibility::NotVisible,
 };
@@ -66,12 +72,16 @@ impl Plugin for BuffersPlugin {
         render_app.init_resource::<BufferManager<UniformOccluder>>();
         render_app.init_resource::<BufferManager<UniformPointLight>>();
         render_app.init_resource::<VertexBuffer>();
+        render_app.init_resource::<ShadowReceiverBuffer>();
+        render_app.init_resource::<StaleBins>();
     }
 }
 
 fn spawn_observers(mut commands: Commands) {
     commands.spawn(Observer::new(on_occluder_removed));
     commands.spawn(Observer::new(on_light_removed));
+    commands.spawn(Observer::new(on_occluder_batch_removed));
+    commands.spawn(Observer::new(on_occluder_group_removed));
 }
 
 // handles buffer when the light gets despawned or the component is removed
@@ -102,26 +112,88 @@ fn on_occluder_removed(
     mut round_manager: ResMut<BufferManager<UniformRoundOccluder>>,
     mut poly_manager: ResMut<BufferManager<UniformOccluder>>,
     mut vertex_buffer: ResMut<VertexBuffer>,
+    mut stale: ResMut<StaleBins>,
 ) {
     if let Ok((occluder, mut round_index, mut poly_index)) = occluders.get_mut(trigger.entity) {
-        if matches!(occluder.shape, Occluder2dShape::RoundRectangle { .. }) {
-            if let Some(old_index) = round_index.0 {
-                round_manager.free_index(old_index);
-                round_index.0 = None;
-            }
-        } else {
-            if let Some(old_index) = poly_index.occluder {
-                poly_manager.free_index(old_index);
-                poly_index.occluder = None;
-            }
-            if let Some(old_index) = poly_index.vertices {
-                vertex_buffer.free_indices(occluder.shape.n_vertices(), old_index.generation);
-                poly_index.vertices = None;
-            }
+        free_occluder(
+            occluder.shape.n_vertices(),
+            &mut round_index,
+            &mut poly_index,
+            &mut round_manager,
+            &mut poly_manager,
+            &mut vertex_buffer,
+            &mut stale,
+        );
+    }
+}
+
+// handles buffer when the occluder group gets despawned or the component is removed
+fn on_occluder_group_removed(
+    trigger: On<Remove, ExtractedOccluderGroup>,
+    mut groups: Query<&mut OccluderGroupSlot>,
+    mut vertex_buffer: ResMut<VertexBuffer>,
+) {
+    if let Ok(mut slot) = groups.get_mut(trigger.entity)
+        && let Some(old_index) = slot.index
+    {
+        vertex_buffer.free_indices(1, old_index.generation);
+        slot.index = None;
+    }
+}
+
+// handles buffer when the occluder batch gets despawned or the component is removed
+fn on_occluder_batch_removed(
+    trigger: On<Remove, ExtractedOccluderBatch>,
+    mut batches: Query<&mut OccluderBatchIndices>,
+    mut round_manager: ResMut<BufferManager<UniformRoundOccluder>>,
+    mut poly_manager: ResMut<BufferManager<UniformOccluder>>,
+    mut vertex_buffer: ResMut<VertexBuffer>,
+    mut stale: ResMut<StaleBins>,
+) {
+    if let Ok(mut indices) = batches.get_mut(trigger.entity) {
+        for (mut round_index, mut poly_index, n_vertices) in indices.0.drain(..) {
+            free_occluder(
+                n_vertices,
+                &mut round_index,
+                &mut poly_index,
+                &mut round_manager,
+                &mut poly_manager,
+                &mut vertex_buffer,
+                &mut stale,
+            );
         }
     }
 }
 
+/// Frees the buffer slots of an occluder, if it has any.
+fn free_occluder(
+    n_vertices: u32,
+    round_index: &mut RoundOccluderIndex,
+    poly_index: &mut PolyOccluderIndex,
+    round_manager: &mut BufferManager<UniformRoundOccluder>,
+    poly_manager: &mut BufferManager<UniformOccluder>,
+    vertex_buffer: &mut VertexBuffer,
+    stale: &mut StaleBins,
+) {
+    // bins might still point to the freed slots
+    if round_index.0.is_some() || poly_index.occluder.is_some() {
+        stale.mark(round_index.0, poly_index.occluder);
+    }
+
+    if let Some(old_index) = round_index.0 {
+        round_manager.free_index(old_index);
+        round_index.0 = None;
+    }
+    if let Some(old_index) = poly_index.occluder {
+        poly_manager.free_index(old_index);
+        poly_index.occluder = None;
+    }
+    if let Some(old_index) = poly_index.vertices {
+        vertex_buffer.free_indices(n_vertices, old_index.generation);
+        poly_index.vertices = None;
+    }
+}
+
 // handles buffer when entity is not visible anymore
 fn handle_not_visible_entities(
     mut occluders: Query<
@@ -133,34 +205,36 @@ fn handle_not_visible_entities(
         ),
         With<NotVisible>,
     >,
+    batches: Query<Entity, (With<ExtractedOccluderBatch>, With<NotVisible>)>,
     mut lights: Query<(Entity, &mut LightIndex), With<NotVisible>>,
     mut round_manager: ResMut<BufferManager<UniformRoundOccluder>>,
     mut poly_manager: ResMut<BufferManager<UniformOccluder>>,
     mut vertex_buffer: ResMut<VertexBuffer>,
     mut light_manager: ResMut<BufferManager<UniformPointLight>>,
+    mut stale: ResMut<StaleBins>,
     mut commands: Commands,
 ) {
     for (id, occluder, mut round_index, mut poly_index) in &mut occluders {
-        if matches!(occluder.shape, Occluder2dShape::RoundRectangle { .. }) {
-            if let Some(old_index) = round_index.0 {
-                round_manager.free_index(old_index);
-                round_index.0 = None;
-            }
-        } else {
-            if let Some(old_index) = poly_index.occluder {
-                poly_manager.free_index(old_index);
-                poly_index.occluder = None;
-            }
-            if let Some(old_index) = poly_index.vertices {
-                vertex_buffer.free_indices(occluder.shape.n_vertices(), old_index.generation);
-                poly_index.vertices = None;
-            }
-        }
+        free_occluder(
+            occluder.shape.n_vertices(),
+            &mut round_index,
+            &mut poly_index,
+            &mut round_manager,
+            &mut poly_manager,
+            &mut vertex_buffer,
+            &mut stale,
+        );
 
         commands.entity(id).remove::<ExtractedOccluder>();
         commands.entity(id).remove::<NotVisible>();
     }
 
+    // the batch's slots are freed by its removal observer
+    for id in &batches {
+        commands.entity(id).remove::<ExtractedOccluderBatch>();
+        commands.entity(id).remove::<NotVisible>();
+    }
+
     for (id, mut index) in &mut lights {
         if let Some(old_index) = index.0 {
             light_manager.free_index(old_index);
@@ -173,14 +247,17 @@ fn handle_not_visible_entities(
 }
 
 // adds lights to buffer for use in prepare system
-fn prepare_lights(
+pub(crate) fn prepare_lights(
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
     mut lights: Query<(&ExtractedPointLight, &mut LightIndex)>,
     mut light_manager: ResMut<BufferManager<UniformPointLight>>,
+    gradient_luts: Res<ColorGradientLuts>,
 ) {
     for (light, mut index) in &mut lights {
-        let changed = light.changes.0;
+        // gradient rows might have been (re)assigned this frame
+        let changed =
+            light.changes.any() || (light.color_ramp.is_some() && gradient_luts.changed());
 
         let light = UniformPointLight {
             pos: light.pos,
@@ -206,6 +283,65 @@ fn prepare_lights(
             outer_angle: light.angle.outer / 180. * PI,
             dir: light.dir,
             height: light.height,
+            color_ramp: light.color_ramp.map_or(0, |id| gradient_luts.row(id)),
+            sprite_occlusion_steps: match (light.cast_shadows, light.sprite_occlusion) {
+                (true, Some(occlusion)) => occlusion.steps,
+                _ => 0,
+            },
+            sprite_occlusion_opacity: light
+                .sprite_occlusion
+                .map_or(0.0, |occlusion| occlusion.opacity),
+            affect_normals: match light.affect_normals {
+                true => 1,
+                false => 0,
+            },
+            pitch: light
+                .pitch
+                .map_or(-1.0, |pitch| pitch.clamp(0., 90.) / 180. * PI),
+            mask_period: light
+                .angular_mask
+                .map_or(0.0, |mask| mask.period / 180. * PI),
+            mask_duty: light
+                .angular_mask
+                .map_or(1.0, |mask| mask.duty_cycle.clamp(0., 1.)),
+            mask_phase: light
+                .angular_mask
+                .map_or(0.0, |mask| mask.phase / 180. * PI),
+            mask_softness: light
+                .angular_mask
+                .map_or(0.0, |mask| mask.softness.max(0.) / 180. * PI),
+            angle_feather: light.angle.feather.max(0.) / 180. * PI,
+            cast_shadows: match light.cast_shadows {
+                true => 1,
+                false => 0,
+            },
+            shadow_strength: light.shadow_strength.clamp(0., 1.),
+            streak: light.streak,
+            important: match light.important {
+                true => 1,
+                false => 0,
+            },
+            glow_radius: light
+                .glow
+                .map_or(0.0, |glow| glow.radius.clamp(0., light.radius)),
+            glow_intensity: light.glow.map_or(0.0, |glow| glow.intensity.max(0.)),
+            glow_falloff: light.glow.map_or(1.0, |glow| glow.falloff.max(0.)),
+            bin_start: light.bin_sector.x,
+            bin_span: light.bin_sector.y,
+            z_range: light.z_range.map_or(0., |range| range.max(0.)),
+            line: match light.line {
+                None => 0,
+                Some(true) => 1,
+                Some(false) => 2,
+            },
+            sprite_occlusion_heights: match light
+                .sprite_occlusion
+                .is_some_and(|occlusion| occlusion.compare_heights)
+            {
+                true => 1,
+                false => 0,
+            },
+            _pad1: 0,
         };
 
         let new_index =
@@ -224,84 +360,271 @@ fn prepare_occluders(
         &ExtractedOccluder,
         &mut RoundOccluderIndex,
         &mut PolyOccluderIndex,
+        &mut OccluderGroupPose,
     )>,
+    mut batches: Query<(&ExtractedOccluderBatch, &mut OccluderBatchIndices)>,
+    mut groups: Query<(&ExtractedOccluderGroup, &mut OccluderGroupSlot)>,
     mut round_manager: ResMut<BufferManager<UniformRoundOccluder>>,
     mut poly_manager: ResMut<BufferManager<UniformOccluder>>,
     mut vertex_buffer: ResMut<VertexBuffer>,
+    mut receiver_buffer: ResMut<ShadowReceiverBuffer>,
+    mut stale: ResMut<StaleBins>,
 ) {
-    for (occluder, mut round_index, mut poly_index) in &mut occluders {
-        let changed = occluder.changes.0;
-        if let Occluder2dShape::RoundRectangle {
-            half_width,
-            half_height,
-            radius,
-        } = occluder.shape
-        {
-            let value = UniformRoundOccluder {
-                pos: occluder.pos,
-                rot: occluder.rot,
-                half_width,
-                half_height,
-                radius,
-                // padding: default(),
-                z: occluder.z,
-                color: occluder.color.to_linear().to_vec4(),
-                opacity: occluder.opacity,
-                z_sorting: match occluder.z_sorting {
-                    true => 1,
-                    false => 0,
-                },
-                _pad1: [0, 0, 0],
-            };
+    receiver_buffer.clear();
+
+    // groups are written first, so their occluders point to their current slots
+    for (group, mut slot) in &mut groups {
+        let index = vertex_buffer.write_offset(
+            group.translation,
+            slot.index,
+            &render_device,
+            group.translation != slot.translation,
+        );
+        slot.index = Some(index);
+        slot.translation = group.translation;
+    }
 
-            // assert_eq!(std::mem::size_of::<UniformRoundOccluder>(), 64);
-            // assert_eq!(std::mem::align_of::<UniformRoundOccluder>(), 16);
+    for (occluder, mut round_index, mut poly_index, mut pose) in &mut occluders {
+        let group = occluder
+            .group
+            .and_then(|group| groups.get(group).ok())
+            .and_then(|(_, slot)| Some((slot.index?.index, slot.translation)));
+
+        let new_pose = group.map(|(slot, translation)| {
+            (
+                (occluder.pos - translation).extend(occluder.z),
+                occluder.rot,
+                slot,
+            )
+        });
 
-            let new_index = round_manager.set_value(
-                &value,
-                round_index.0,
-                changed,
-                &render_device,
-                &render_queue,
-            );
-            round_index.0 = Some(new_index);
-        } else {
-            let vertex_index = vertex_buffer.write_vertices(
-                occluder,
-                poly_index.vertices,
-                &render_device,
-                &render_queue,
-                changed,
-            );
-            poly_index.vertices = Some(vertex_index);
-
-            let value = UniformOccluder {
-                vertex_start: vertex_index.index as u32,
-                n_vertices: occluder.shape.n_vertices(),
-                z: occluder.z,
-                color: occluder.color.to_linear().to_vec4(),
-                opacity: occluder.opacity,
-                z_sorting: match occluder.z_sorting {
-                    true => 1,
-                    false => 0,
-                },
-                _pad1: [0, 0, 0],
-            };
+        // grouped occluders that only moved along with their group are translated on the GPU
+        let moved = match new_pose {
+            None => occluder.changes.moved || pose.0.is_some(),
+            Some(_) => !pose.matches(new_pose),
+        };
+        if moved {
+            pose.0 = new_pose;
+        }
+
+        let (group_index, offset) = match pose.0 {
+            Some((pos, _, slot)) => (slot as u32, occluder.pos - pos.truncate()),
+            None => (0, Vec2::ZERO),
+        };
+
+        write_occluder(
+            occluder,
+            group_index,
+            offset,
+            moved,
+            &mut round_index,
+            &mut poly_index,
+            &mut round_manager,
+            &mut poly_manager,
+            &mut vertex_buffer,
+            &mut receiver_buffer,
+            &mut stale,
+            &render_device,
+            &render_queue,
+        );
+    }
 
-            let new_index = poly_manager.set_value(
-                &value,
-                poly_index.occluder,
-                changed,
+    for (batch, mut indices) in &mut batches {
+        if batch.reallocate || indices.0.len() != batch.occluders.len() {
+            for (mut round_index, mut poly_index, n_vertices) in indices.0.drain(..) {
+                free_occluder(
+                    n_vertices,
+                    &mut round_index,
+                    &mut poly_index,
+                    &mut round_manager,
+                    &mut poly_manager,
+                    &mut vertex_buffer,
+                    &mut stale,
+                );
+            }
+
+            indices.0 = batch
+                .occluders
+                .iter()
+                .map(|occluder| (default(), default(), occluder.shape.n_vertices()))
+                .collect();
+        }
+
+        for (occluder, (round_index, poly_index, _)) in
+            batch.occluders.iter().zip(indices.0.iter_mut())
+        {
+            write_occluder(
+                occluder,
+                0,
+                Vec2::ZERO,
+                occluder.changes.moved,
+                round_index,
+                poly_index,
+                &mut round_manager,
+                &mut poly_manager,
+                &mut vertex_buffer,
+                &mut receiver_buffer,
+                &mut stale,
                 &render_device,
                 &render_queue,
             );
-            poly_index.occluder = Some(new_index);
         }
     }
 
+    let generations = (
+        round_manager.generation(),
+        poly_manager.generation(),
+        vertex_buffer.generation(),
+    );
+
+    receiver_buffer.write(&render_device, &render_queue);
     round_manager.flush(&render_device, &render_queue);
     poly_manager.flush(&render_device, &render_queue);
     vertex_buffer.pass(&render_device, &render_queue);
+
+    stale.refragmented |= generations
+        != (
+            round_manager.generation(),
+            poly_manager.generation(),
+            vertex_buffer.generation(),
+        );
+}
+
+/// Writes an occluder to the buffers, if it changed or its slots were lost to a refragmentation.
+///
+/// Occluders of a [group](crate::prelude::OccluderGroup) are written relative to the group's `offset`, stored at
+/// `group` in the vertex buffer, and are only considered `moved` if they moved relative to it.
+#[allow(clippy::too_many_arguments)]
+fn write_occluder(
+    occluder: &ExtractedOccluder,
+    group: u32,
+    offset: Vec2,
+    moved: bool,
+    round_index: &mut RoundOccluderIndex,
+    poly_index: &mut PolyOccluderIndex,
+    round_manager: &mut BufferManager<UniformRoundOccluder>,
+    poly_manager: &mut BufferManager<UniformOccluder>,
+    vertex_buffer: &mut VertexBuffer,
+    receiver_buffer: &mut ShadowReceiverBuffer,
+    stale: &mut StaleBins,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) {
+    let old_indices = (round_index.0, poly_index.occluder, poly_index.vertices);
+
+    // the receiver buffer is rebuilt every frame, so the occluders pointing into it are rewritten as well
+    let changed = moved
+        || occluder.changes.parameters
+        || occluder.changes.shape
+        || occluder.receivers.is_some();
+
+    let (receiver_mode, receiver_start, receiver_count) = match &occluder.receivers {
+        None => (0, 0, 0),
+        Some(receivers) => (
+            match receivers.only {
+                true => 1,
+                false => 2,
+            },
+            receiver_buffer.push(&receivers.ids),
+            receivers.ids.len() as u32,
+        ),
+    };
+
+    // a near opacity of 1 disables the effect
+    let distance_opacity = occluder
+        .distance_opacity
+        .unwrap_or(DistanceOpacity::default().with_near_opacity(1.));
+
+    if let Occluder2dShape::RoundRectangle {
+        half_width,
+        half_height,
+        radius,
+    } = occluder.shape
+    {
+        let value = UniformRoundOccluder {
+            pos: occluder.pos - offset,
+            rot: occluder.rot,
+            half_width,
+            half_height,
+            radius,
+            // padding: default(),
+            z: occluder.z,
+            color: occluder.color.to_linear().to_vec4(),
+            opacity: occluder.opacity,
+            z_sorting: match occluder.z_sorting {
+                true => 1,
+                false => 0,
+            },
+            shadow_falloff: occluder.shadow_falloff.clamp(0., 1.),
+            near_distance: distance_opacity.near,
+            far_distance: distance_opacity.far,
+            near_opacity: distance_opacity.near_opacity.clamp(0., 1.),
+            receiver_mode,
+            receiver_start,
+            receiver_count,
+            y: occluder.y - offset.y,
+            group,
+            _pad: default(),
+        };
+
+        // assert_eq!(std::mem::size_of::<UniformRoundOccluder>(), 64);
+        // assert_eq!(std::mem::align_of::<UniformRoundOccluder>(), 16);
+
+        let new_index =
+            round_manager.set_value(&value, round_index.0, changed, render_device, render_queue);
+        round_index.0 = Some(new_index);
+    } else {
+        let vertex_index = vertex_buffer.write_vertices(
+            occluder,
+            offset,
+            poly_index.vertices,
+            poly_index.n_vertices,
+            render_device,
+            // vertices are stored in world space (or relative to the group), so they don't depend on the
+            // occluder's parameters
+            moved || occluder.changes.shape,
+        );
+        poly_index.vertices = Some(vertex_index);
+        poly_index.n_vertices = occluder.shape.n_vertices();
+
+        let value = UniformOccluder {
+            vertex_start: vertex_index.index as u32,
+            n_vertices: occluder.shape.n_vertices(),
+            z: occluder.z,
+            color: occluder.color.to_linear().to_vec4(),
+            opacity: occluder.opacity,
+            z_sorting: match occluder.z_sorting {
+                true => 1,
+                false => 0,
+            },
+            shadow_falloff: occluder.shadow_falloff.clamp(0., 1.),
+            near_distance: distance_opacity.near,
+            far_distance: distance_opacity.far,
+            near_opacity: distance_opacity.near_opacity.clamp(0., 1.),
+            receiver_mode,
+            receiver_start,
+            receiver_count,
+            y: occluder.y - offset.y,
+            group,
+            _pad: default(),
+        };
+
+        let new_index = poly_manager.set_value(
+            &value,
+            poly_index.occluder,
+            changed,
+            render_device,
+            render_queue,
+        );
+        poly_index.occluder = Some(new_index);
+    }
+
+    // bins are built from the occluder's world-space shape, and point to its slots
+    if occluder.changes.needs_binning()
+        || old_indices != (round_index.0, poly_index.occluder, poly_index.vertices)
+    {
+        stale.mark(old_indices.0, old_indices.1);
+    }
 }
 
 /// The max number of elements that will be written in a single command by [`BufferManager`].
@@ -361,6 +684,16 @@ impl<T: ShaderType + WriteInto + Default + NoUninit> BufferManager<T> {
         self.buffer.binding().unwrap()
     }
 
+    /// Size of the GPU buffer, in bytes.
+    pub fn gpu_size(&self) -> u64 {
+        self.buffer.buffer().map_or(0, |buffer| buffer.size())
+    }
+
+    /// Generation of the buffer, increased whenever it is refragmented.
+    pub fn generation(&self) -> u32 {
+        self.current_generation
+    }
+
     /// Called by an entity to pass it's current index and value to the buffer.
     /// It returns back it's (possibly changed) index.  
     ///
@@ -483,6 +816,51 @@ pub const N_BINS_FLOAT: f32 = 256.0;
 #[derive(Component, Default)]
 pub struct BinBuffers(pub HashMap<RetainedViewEntity, BinBuffer>);
 
+/// Number of consecutive frames a light reused its outdated [`BinBuffers`] instead of rebuilding them,
+/// see [`FireflyBinningBudget`](crate::prelude::FireflyBinningBudget).
+#[derive(Component, Default)]
+pub struct SkippedBinning(pub u32);
+
+/// Occluder changes that outdate the [`BinBuffers`] of lights, collected until the lights are binned.
+#[derive(Resource, Default)]
+pub struct StaleBins {
+    /// An occluder moved, changed shape, or was given or lost a buffer slot.
+    pub any: bool,
+    /// Pointer [indices](OccluderPointer::index) of the occluders that moved, changed shape or lost their slot.
+    /// Bins that point to them are wrong, rather than just outdated.
+    pub pointers: HashSet<u32>,
+    /// A buffer was refragmented, which moves every occluder to a new slot.
+    pub refragmented: bool,
+}
+
+impl StaleBins {
+    /// Marks the bins of an occluder with the given slots as stale.
+    fn mark(&mut self, round_index: Option<BufferIndex>, poly_index: Option<BufferIndex>) {
+        self.any = true;
+        if let Some(index) = round_index {
+            self.pointers
+                .insert(OccluderPointer::encode_index(index.index as u32, false));
+        }
+        if let Some(index) = poly_index {
+            self.pointers
+                .insert(OccluderPointer::encode_index(index.index as u32, true));
+        }
+    }
+}
+
+/// The view of a camera that a [`BinBuffer`] was built for. The bins are rebuilt whenever it changes.
+#[derive(Clone, PartialEq)]
+pub struct BinnedView {
+    pub render_layers: RenderLayers,
+    pub soft_shadows: bool,
+    /// Radius of the light's core, scaled by the camera's softness.
+    pub core_radius: f32,
+    /// Minimum dilation of the occluders.
+    pub dilation: f32,
+    /// Area of the light that is visible to the camera, including its culling margin.
+    pub light_aabb: Aabb2d,
+}
+
 /// A struct containing sets of bins of occluders for faster iteration.
 /// This is the most important acceleration structure used by Firefly. It is used in a custom
 /// type of angular sweep with BVH-inspired elements.
@@ -493,9 +871,15 @@ pub struct BinBuffer {
     bin_indices: StorageBuffer<BinIndices>,
     /// Data stored on the CPU.
     occluders: [BinaryHeap<OccluderPointer>; N_BINS],
+    /// Start angle and span of the sector the bins are spread over, see [`ExtractedPointLight::bin_sector`].
+    sector: Vec2,
+    /// The view the bins were last built for.
+    pub view: Option<BinnedView>,
 }
 
 /// Wrapper for the bin indices, so it can impl Default.
+///
+/// Matches `BinIndices` in `firefly::types`. See the [`layout`](crate::layout) module for the stability guarantees.
 #[repr(C)]
 #[derive(Pod, Zeroable, Clone, Copy, ShaderType)]
 pub struct BinIndices {
@@ -516,6 +900,8 @@ impl Default for BinBuffer {
             buffer: RawBufferVec::<OccluderPointer>::new(BufferUsages::STORAGE),
             bin_indices: StorageBuffer::<BinIndices>::default(),
             occluders: array::from_fn(|_| default()),
+            sector: vec2(-PI, TAU),
+            view: None,
         }
     }
 }
@@ -531,6 +917,40 @@ impl BinBuffer {
         self.bin_indices.binding().unwrap()
     }
 
+    /// Number of occluders written to the bins, counted once for every bin they're in.
+    pub fn len(&self) -> usize {
+        self.buffer.len().saturating_sub(1)
+    }
+
+    /// Pointer [indices](OccluderPointer::index) of the occluders written to the bins, repeated for every bin they're in.
+    pub fn pointer_indices(&self) -> impl Iterator<Item = u32> + '_ {
+        // the first pointer is a placeholder
+        self.buffer
+            .values()
+            .iter()
+            .skip(1)
+            .map(|pointer| pointer.index)
+    }
+
+    /// Returns true if no occluders were written to the bins.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the bins contain any of the given pointer [indices](OccluderPointer::index).
+    pub fn contains_any(&self, pointers: &HashSet<u32>) -> bool {
+        !pointers.is_empty()
+            && self
+                .pointer_indices()
+                .any(|index| pointers.contains(&index))
+    }
+
+    /// Size of the GPU buffers, in bytes.
+    pub fn gpu_size(&self) -> u64 {
+        self.buffer.buffer().map_or(0, |buffer| buffer.size())
+            + self.bin_indices.buffer().map_or(0, |buffer| buffer.size())
+    }
+
     /// Write this buffer's data to the GPU. This function also sorts the
     /// occluders by distance enabling early-stopping in GPU checks.
     pub fn write(&mut self, device: &RenderDevice, queue: &RenderQueue) {
@@ -561,8 +981,9 @@ impl BinBuffer {
         self.bin_indices.write_buffer(device, queue);
     }
 
-    /// Clear the buffer and add one empty set of bins.
-    pub fn reset(&mut self) {
+    /// Clear the buffer and add one empty set of bins, spread over the given sector.
+    pub fn reset(&mut self, sector: Vec2) {
+        self.sector = sector;
         self.buffer.clear();
         self.buffer.push(OccluderPointer::default());
 
@@ -574,6 +995,14 @@ impl BinBuffer {
     // const SCALE: f32 = N_BINS_FLOAT / TAU;
     /// Add an occluder to this buffer. Or a set of edges, in case of a polygonal occluder.
     pub fn add_occluder(&mut self, data: &OccluderData) {
+        if self.sector.y < TAU {
+            // narrowed sectors don't wrap around, occluders are clipped to them instead
+            let start = (data.min_angle - self.sector.x).rem_euclid(TAU);
+            self.add_to_sector(start, start + data.angle, data.pointer);
+            self.add_to_sector(start - TAU, start + data.angle - TAU, data.pointer);
+            return;
+        }
+
         if data.angle.ceil() >= TAU {
             self.add_to_bins(0, N_BINS - 1, data.pointer);
             return;
@@ -601,6 +1030,20 @@ impl BinBuffer {
         }
     }
 
+    /// Add an occluder to the bins between the given angles, relative to the start of the sector.
+    fn add_to_sector(&mut self, min_angle: f32, max_angle: f32, pointer: OccluderPointer) {
+        let min_angle = min_angle.max(0.);
+        let max_angle = max_angle.min(self.sector.y);
+        if min_angle > max_angle {
+            return;
+        }
+
+        let scale = N_BINS_FLOAT / self.sector.y;
+        let min_bin = ((min_angle * scale).floor() as usize).min(N_BINS - 1);
+        let max_bin = ((max_angle * scale).ceil() as usize).min(N_BINS - 1);
+        self.add_to_bins(min_bin, max_bin, pointer);
+    }
+
     fn add_to_bins(&mut self, min_bin: usize, max_bin: usize, pointer: OccluderPointer) {
         // info!("writing buffers {min_bin} to {max_bin}");
         for index in min_bin..(max_bin + 1) {
@@ -618,6 +1061,8 @@ pub struct OccluderData {
 }
 
 /// Compact struct pointing to a round occluder, or a chain of vertices from a polygonal occluder.  
+///
+/// Matches `OccluderPointer` in `firefly::types`. See the [`layout`](crate::layout) module for the stability guarantees.
 #[repr(C)]
 #[derive(Default, Pod, Zeroable, Clone, Copy, ShaderType, Debug)]
 pub struct OccluderPointer {
@@ -664,6 +1109,16 @@ impl Ord for OccluderPointer {
     }
 }
 
+impl OccluderPointer {
+    /// Encodes the buffer slot of a round or polygonal occluder into a pointer [index](OccluderPointer::index).
+    pub fn encode_index(index: u32, poly: bool) -> u32 {
+        match poly {
+            true => (1 << 31) | index,
+            false => index,
+        }
+    }
+}
+
 /// A global buffer in which all visible vertices are stored.
 ///
 /// This is different from the [`BufferManager`] in order to use a specific allocation
@@ -671,12 +1126,20 @@ impl Ord for OccluderPointer {
 /// of their position for re-allocation. When an occluder disappears, it's number of vertices is simply
 /// subtracted from the total lenght of the buffer, and the buffer refragments itself when
 /// there is a significant amount of wasted space.  
+///
+/// Only the vertices of occluders that changed are uploaded. Their ranges are collected over the frame
+/// and written to the GPU in [`pass`](VertexBuffer::pass), merging ranges that are close to each other, so
+/// a single moving occluder among many static ones only uploads its own vertices.
 #[derive(Resource)]
 pub struct VertexBuffer {
     vertices: RawBufferVec<Vec2>,
     next_index: usize,
     empty_slots: u32,
     current_generation: u32,
+    /// Ranges of vertices that changed this frame and still need to be written to the GPU.
+    dirty: Vec<Range<usize>>,
+    /// Whether the GPU buffer was reallocated this frame, in which case it's written as a whole.
+    reallocated: bool,
 }
 
 impl FromWorld for VertexBuffer {
@@ -695,6 +1158,8 @@ impl VertexBuffer {
             next_index: 1,
             empty_slots: 0,
             current_generation: 0,
+            dirty: vec![],
+            reallocated: false,
         };
 
         res.vertices.set_label("vertex buffer".into());
@@ -712,14 +1177,62 @@ impl VertexBuffer {
         self.vertices.binding().unwrap()
     }
 
-    /// Insert all of an occluder's vertices to this buffer. This
-    /// function also automatically writes them to the GPU.  
+    /// Size of the GPU buffer, in bytes.
+    pub fn gpu_size(&self) -> u64 {
+        self.vertices.buffer().map_or(0, |buffer| buffer.size())
+    }
+
+    /// Generation of the buffer, increased whenever it is refragmented.
+    pub fn generation(&self) -> u32 {
+        self.current_generation
+    }
+
+    /// Insert all of an occluder's vertices to this buffer. The vertices are written
+    /// to the GPU at the end of the frame, in [`pass`](VertexBuffer::pass).
+    ///
+    /// `n_previous` is the number of vertices the occluder had when it was last written. If it
+    /// changed, the occluder is moved to a new slot so it doesn't overwrite its neighbours.
+    ///
+    /// The vertices are stored relative to `offset`, which is the translation of the occluder's
+    /// [group](crate::prelude::OccluderGroup), if it has one.
     pub fn write_vertices(
         &mut self,
         occluder: &ExtractedOccluder,
+        offset: Vec2,
         index: Option<BufferIndex>,
+        n_previous: u32,
+        device: &RenderDevice,
+        changed: bool,
+    ) -> BufferIndex {
+        self.write(
+            occluder.vertices_iter().map(|vertex| vertex - offset),
+            occluder.shape.n_vertices(),
+            index,
+            n_previous,
+            device,
+            changed,
+        )
+    }
+
+    /// Write the offset of an [occluder group](crate::prelude::OccluderGroup) to this buffer, as a single vertex
+    /// that the group's occluders are translated by.
+    pub fn write_offset(
+        &mut self,
+        offset: Vec2,
+        index: Option<BufferIndex>,
+        device: &RenderDevice,
+        changed: bool,
+    ) -> BufferIndex {
+        self.write([offset].into_iter(), 1, index, 1, device, changed)
+    }
+
+    fn write(
+        &mut self,
+        vertices: impl Iterator<Item = Vec2>,
+        n_vertices: u32,
+        index: Option<BufferIndex>,
+        n_previous: u32,
         device: &RenderDevice,
-        queue: &RenderQueue,
         changed: bool,
     ) -> BufferIndex {
         if !changed
@@ -733,7 +1246,12 @@ impl VertexBuffer {
             None => self.next_index,
             Some(BufferIndex { index, generation }) => {
                 if index < self.next_index && generation == self.current_generation {
-                    index
+                    if n_vertices == n_previous {
+                        index
+                    } else {
+                        self.empty_slots += n_previous;
+                        self.next_index
+                    }
                 } else {
                     self.next_index
                 }
@@ -743,24 +1261,12 @@ impl VertexBuffer {
         // change existent vertices
         if index < self.next_index {
             let mut last_index = index;
-            for vertex in occluder.vertices_iter() {
-                if last_index >= self.vertices.len() {
-                    self.vertices.push(vertex);
-                    warn!("hmm.. what?");
-                } else {
-                    self.vertices.set(last_index as u32, vertex);
-                }
-
+            for vertex in vertices {
+                self.vertices.set(last_index as u32, vertex);
                 last_index += 1;
             }
 
-            self.vertices
-                .write_buffer_range(queue, index..last_index)
-                .expect("couldn't write range");
-
-            // for vertex in self.vertices.values() {
-            //     info!("{vertex}");
-            // }
+            self.dirty.push(index..last_index);
 
             return BufferIndex {
                 index,
@@ -769,43 +1275,59 @@ impl VertexBuffer {
         }
 
         // add new vertices
-        for vertex in occluder.vertices_iter() {
+        for vertex in vertices {
             self.vertices.push(vertex);
             self.next_index += 1;
         }
 
-        // if self.next_index % 2 == 1 {
-        //     self.vertices.push(default());
-        //     self.next_index += 1;
-        // }
-
         if self.next_index >= self.vertices.capacity() {
-            self.vertices.reserve(
-                (self.next_index as f32 / 4096.0).ceil() as usize * 4096,
-                device,
-            );
-            self.vertices.write_buffer(device, queue);
-        } else {
             self.vertices
-                .write_buffer_range(queue, index..self.next_index)
-                .expect("couldn't write range");
+                .reserve((self.next_index / 4096 + 1) * 4096, device);
+            self.reallocated = true;
+        } else {
+            self.dirty.push(index..self.next_index);
         }
 
-        // info!(
-        //     "Vertex buffer capacity: {}, length: {}, empty slots: {}",
-        //     self.vertices.capacity(),
-        //     self.vertices.len(),
-        //     self.empty_slots
-        // );
-
         BufferIndex {
             index,
             generation: self.current_generation,
         }
     }
 
-    /// Called at the end of a frame. Potentially triggers refragmentation.
+    /// Called at the end of a frame. Writes the changed vertices to the GPU and potentially triggers refragmentation.
     pub fn pass(&mut self, device: &RenderDevice, queue: &RenderQueue) {
+        if self.reallocated {
+            self.vertices.write_buffer(device, queue);
+        } else {
+            self.dirty.sort_unstable_by_key(|range| range.start);
+
+            // close ranges are merged, trading a few redundant vertices for fewer writes
+            let mut pending: Option<Range<usize>> = None;
+            for range in self.dirty.drain(..) {
+                pending = match pending {
+                    Some(current) if range.start <= current.end + MAX_SINGLE_WRITE_LENGTH => {
+                        Some(current.start..current.end.max(range.end))
+                    }
+                    Some(current) => {
+                        self.vertices
+                            .write_buffer_range(queue, current)
+                            .expect("couldn't write range");
+                        Some(range)
+                    }
+                    None => Some(range),
+                };
+            }
+
+            if let Some(range) = pending {
+                self.vertices
+                    .write_buffer_range(queue, range)
+                    .expect("couldn't write range");
+            }
+        }
+
+        self.dirty.clear();
+        self.reallocated = false;
+
         if self.empty_slots > 500 && self.empty_slots > self.vertices.capacity() as u32 / 2 {
             let old_generation = self.current_generation;
             *self = Self::new(device, queue);
@@ -823,11 +1345,59 @@ impl VertexBuffer {
     }
 }
 
+/// Buffer with the stencil IDs of the [shadow receivers](crate::prelude::ShadowReceivers) of all occluders.
+///
+/// Occluders with receivers are rare, so this is simply rebuilt every frame.
+#[derive(Resource)]
+pub struct ShadowReceiverBuffer {
+    ids: RawBufferVec<u32>,
+}
+
+impl FromWorld for ShadowReceiverBuffer {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        let queue = world.resource::<RenderQueue>();
+
+        let mut ids = RawBufferVec::<u32>::new(BufferUsages::STORAGE);
+        ids.set_label("shadow receiver buffer".into());
+
+        // empty value is added so the buffer can be written to VRAM from the start
+        ids.push(0);
+        ids.write_buffer(device, queue);
+
+        Self { ids }
+    }
+}
+
+impl ShadowReceiverBuffer {
+    /// Get the binding of this buffer. It is guaranteed to exist.
+    pub fn binding(&self) -> BindingResource<'_> {
+        self.ids.binding().unwrap()
+    }
+
+    fn clear(&mut self) {
+        self.ids.truncate(1);
+    }
+
+    /// Adds the IDs to the buffer, returning the index of the first one.
+    fn push(&mut self, ids: &[u32]) -> u32 {
+        let start = self.ids.len() as u32;
+        for id in ids {
+            self.ids.push(*id);
+        }
+        start
+    }
+
+    fn write(&mut self, device: &RenderDevice, queue: &RenderQueue) {
+        self.ids.write_buffer(device, queue);
+    }
+}
+
 /// An index given and returned to the various buffer structures.
 ///
 /// This is used for storing an entity's slot in the buffer, and
 /// contains a generation to keep track of buffer refragmentations.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct BufferIndex {
     pub index: usize,
     pub generation: u32,
@@ -0,0 +1,394 @@
+//! Module containing [`Caustics2d`], animated light patterns such as the ones cast by water.
+
+use bevy::{
+    camera::visibility::RenderLayers,
+    platform::collections::HashMap,
+    prelude::*,
+    render::{
+        Extract, ExtractSchedule, Render, RenderApp, RenderSystems,
+        render_asset::RenderAssets,
+        render_phase::TrackedRenderPass,
+        render_resource::*,
+        renderer::{RenderDevice, RenderQueue},
+        texture::GpuImage,
+        view::{ExtractedView, ViewUniformOffset},
+    },
+};
+
+use crate::{
+    data::{ExtractedCombineLightmapTo, FireflyConfig, FireflyTestClock},
+    lights::LightLut,
+    pipelines::CausticsPipeline,
+};
+
+/// Component that projects an animated, tiling caustics texture over a rectangular region, such as
+/// the bottom of a pool or the floor of an underwater level.
+///
+/// The pattern is composited into the lightmap and multiplied with the light already there, so it's brighter
+/// near strong lights and disappears in unlit or shadowed areas. The region is centered on the entity's
+/// [`Transform`] and follows its rotation and scale.
+///
+/// Only the red channel of the [image](Caustics2d::image) is used. The texture should tile seamlessly.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Caustics2d::new(asset_server.load("caustics.png"), vec2(400., 300.))
+///         .with_scroll(vec2(0.05, 0.02)),
+///     Transform::from_xyz(0., -100., 0.),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[require(Transform, Visibility, RenderLayers)]
+pub struct Caustics2d {
+    /// Tiling texture of the caustic pattern.
+    ///
+    /// **Default:** None.
+    pub image: Handle<Image>,
+
+    /// Size of the region covered by the pattern, in world units.
+    ///
+    /// **Default:** 256x256.
+    pub size: Vec2,
+
+    /// Size of one tile of the texture, in world units.
+    ///
+    /// **Default:** 64x64.
+    pub tile_size: Vec2,
+
+    /// Scrolling speed of the pattern, in tiles per second. Two copies of the texture scroll in different
+    /// directions based on this, which gives the pattern its shimmering motion.
+    ///
+    /// **Default:** 0.05x0.03.
+    pub scroll: Vec2,
+
+    /// How much the pattern brightens the light it's projected on. 1 doubles the light at the brightest
+    /// parts of the pattern.
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** 0.5.
+    pub intensity: f32,
+
+    /// Color of the pattern.
+    ///
+    /// **Default:** White.
+    pub color: Color,
+
+    /// Width of the fade at the edges of the region, in world units.
+    ///
+    /// **Default:** 16.
+    pub feather: f32,
+}
+
+impl Default for Caustics2d {
+    fn default() -> Self {
+        Self {
+            image: default(),
+            size: vec2(256., 256.),
+            tile_size: vec2(64., 64.),
+            scroll: vec2(0.05, 0.03),
+            intensity: 0.5,
+            color: Color::WHITE,
+            feather: 16.,
+        }
+    }
+}
+
+impl Caustics2d {
+    /// Construct new caustics with the given texture, covering a region of the given size.
+    pub fn new(image: Handle<Image>, size: Vec2) -> Self {
+        Self {
+            image,
+            size,
+            ..default()
+        }
+    }
+
+    /// Returns the caustics with the given [tile size](Caustics2d::tile_size).
+    pub fn with_tile_size(self, tile_size: Vec2) -> Self {
+        Self { tile_size, ..self }
+    }
+
+    /// Returns the caustics with the given [scrolling speed](Caustics2d::scroll).
+    pub fn with_scroll(self, scroll: Vec2) -> Self {
+        Self { scroll, ..self }
+    }
+
+    /// Returns the caustics with the given [intensity](Caustics2d::intensity).
+    pub fn with_intensity(self, intensity: f32) -> Self {
+        Self { intensity, ..self }
+    }
+
+    /// Returns the caustics with the given [color](Caustics2d::color).
+    pub fn with_color(self, color: Color) -> Self {
+        Self { color, ..self }
+    }
+
+    /// Returns the caustics with the given [feather](Caustics2d::feather).
+    pub fn with_feather(self, feather: f32) -> Self {
+        Self { feather, ..self }
+    }
+}
+
+/// Data of a [`Caustics2d`] that is sent to the GPU.
+#[derive(ShaderType, Clone, Copy)]
+pub struct UniformCaustics {
+    pub world_from_local: Mat4,
+    pub size: Vec2,
+    pub tile_size: Vec2,
+    pub offset_a: Vec2,
+    pub offset_b: Vec2,
+    pub color: Vec4,
+    pub feather: f32,
+}
+
+pub(crate) struct ExtractedCaustic {
+    pub transform: GlobalTransform,
+    pub image: AssetId<Image>,
+    pub render_layers: RenderLayers,
+    pub caustics: Caustics2d,
+    pub elapsed: f32,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedCaustics {
+    pub caustics: Vec<ExtractedCaustic>,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct CausticsBindGroups {
+    /// Uniforms of all caustics of the frame, each bound at its own offset.
+    pub uniforms: DynamicUniformBuffer<UniformCaustics>,
+    /// Bind group of each caustics image.
+    pub images: HashMap<AssetId<Image>, BindGroup>,
+    /// Render layers, image and uniform offset of each caustic, in drawing order.
+    pub values: Vec<(RenderLayers, AssetId<Image>, u32)>,
+}
+
+/// Lightmap pipeline used to draw caustics for a view.
+#[derive(Component)]
+pub(crate) struct ViewCausticsPipeline(pub CachedRenderPipelineId);
+
+/// Plugin that extracts and renders [`Caustics2d`]. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct CausticsPlugin;
+impl Plugin for CausticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Caustics2d>();
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<ExtractedCaustics>()
+            .init_resource::<CausticsBindGroups>()
+            .add_systems(ExtractSchedule, extract_caustics)
+            .add_systems(
+                Render,
+                (
+                    queue_caustics.in_set(RenderSystems::Queue),
+                    prepare_caustics_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                ),
+            );
+    }
+}
+
+fn extract_caustics(
+    mut extracted: ResMut<ExtractedCaustics>,
+    time: Extract<Res<Time>>,
+    test_clock: Extract<Option<Res<FireflyTestClock>>>,
+    caustics: Extract<
+        Query<(
+            &Caustics2d,
+            &GlobalTransform,
+            &InheritedVisibility,
+            &RenderLayers,
+        )>,
+    >,
+) {
+    extracted.caustics.clear();
+
+    let elapsed = test_clock.as_ref().map_or(time.elapsed_secs(), |clock| {
+        clock.frame as f32 * clock.delta.as_secs_f32()
+    });
+
+    for (caustic, transform, visibility, render_layers) in &caustics {
+        if !visibility.get() {
+            continue;
+        }
+
+        extracted.caustics.push(ExtractedCaustic {
+            transform: *transform,
+            image: caustic.image.id(),
+            render_layers: render_layers.clone(),
+            caustics: caustic.clone(),
+            elapsed,
+        });
+    }
+}
+
+fn queue_caustics(
+    mut commands: Commands,
+    pipeline: Res<CausticsPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<CausticsPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    extracted: Res<ExtractedCaustics>,
+    views: Query<(
+        Entity,
+        &ExtractedView,
+        &FireflyConfig,
+        Option<&ExtractedCombineLightmapTo>,
+    )>,
+) {
+    for (entity, view, config, combined_lightmap) in &views {
+        if extracted.caustics.is_empty() {
+            commands.entity(entity).remove::<ViewCausticsPipeline>();
+            continue;
+        }
+
+        // combined lightmaps are rendered into the target camera's lightmap array
+        let format = match combined_lightmap.and_then(|combined| views.get(combined.0).ok()) {
+            Some((_, target_view, target_config, _)) => {
+                target_config.lightmap_texture_format(target_view.hdr)
+            }
+            None => config.lightmap_texture_format(view.hdr),
+        };
+
+        let id = pipelines.specialize(&pipeline_cache, &pipeline, format);
+        commands.entity(entity).insert(ViewCausticsPipeline(id));
+    }
+}
+
+fn prepare_caustics_bind_groups(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    pipeline: Res<CausticsPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    extracted: Res<ExtractedCaustics>,
+    mut bind_groups: ResMut<CausticsBindGroups>,
+) {
+    let bind_groups = &mut *bind_groups;
+    bind_groups.uniforms.clear();
+    bind_groups.images.clear();
+    bind_groups.values.clear();
+
+    for caustic in &extracted.caustics {
+        if gpu_images.get(caustic.image).is_none() {
+            continue;
+        }
+
+        let caustics = &caustic.caustics;
+        let color = caustics.color.to_linear();
+
+        // the second copy scrolls perpendicular to the first and a bit slower, so the two never line up
+        let offset_a = caustics.scroll * caustic.elapsed;
+        let offset_b = -caustics.scroll.perp() * caustic.elapsed * 0.7;
+
+        let offset = bind_groups.uniforms.push(&UniformCaustics {
+            world_from_local: caustic.transform.to_matrix(),
+            size: caustics.size,
+            tile_size: caustics.tile_size.max(Vec2::splat(0.001)),
+            offset_a: offset_a - offset_a.floor(),
+            offset_b: offset_b - offset_b.floor(),
+            color: vec4(
+                color.red,
+                color.green,
+                color.blue,
+                color.alpha * caustics.intensity,
+            ),
+            feather: caustics.feather,
+        });
+
+        bind_groups
+            .values
+            .push((caustic.render_layers.clone(), caustic.image, offset));
+    }
+
+    if bind_groups.values.is_empty() {
+        return;
+    }
+
+    bind_groups
+        .uniforms
+        .write_buffer(&render_device, &render_queue);
+
+    let Some(uniform_binding) = bind_groups.uniforms.binding() else {
+        return;
+    };
+
+    // caustics of the same image share a bind group, and only differ in their uniform offset
+    for (_, image, _) in &bind_groups.values {
+        if bind_groups.images.contains_key(image) {
+            continue;
+        }
+
+        let Some(gpu_image) = gpu_images.get(*image) else {
+            continue;
+        };
+
+        let bind_group = render_device.create_bind_group(
+            "caustics_bind_group",
+            &pipeline_cache.get_bind_group_layout(&pipeline.layout),
+            &BindGroupEntries::sequential((
+                &gpu_image.texture_view,
+                &pipeline.sampler,
+                uniform_binding.clone(),
+            )),
+        );
+
+        bind_groups.images.insert(*image, bind_group);
+    }
+}
+
+/// Draws the caustics visible to the view into the lightmap pass. Called by the
+/// [`CreateLightmapNode`](crate::nodes::CreateLightmapNode) after the lights are drawn.
+pub(crate) fn render_caustics<'w>(
+    pass: &mut TrackedRenderPass<'w>,
+    world: &'w World,
+    view_entity: Entity,
+) {
+    let bind_groups = world.resource::<CausticsBindGroups>();
+    if bind_groups.values.is_empty() {
+        return;
+    }
+
+    let (Some(pipeline_id), Some(lut), Some(view_uniform_offset)) = (
+        world.get::<ViewCausticsPipeline>(view_entity),
+        world.get::<LightLut>(view_entity),
+        world.get::<ViewUniformOffset>(view_entity),
+    ) else {
+        return;
+    };
+
+    let Some(pipeline) = world
+        .resource::<PipelineCache>()
+        .get_render_pipeline(pipeline_id.0)
+    else {
+        return;
+    };
+
+    let view_layers = world
+        .get::<RenderLayers>(view_entity)
+        .cloned()
+        .unwrap_or_default();
+
+    pass.set_render_pipeline(pipeline);
+    pass.set_bind_group(0, &lut.0, &[view_uniform_offset.offset]);
+
+    for (layers, image, offset) in &bind_groups.values {
+        if !layers.intersects(&view_layers) {
+            continue;
+        }
+
+        let Some(bind_group) = bind_groups.images.get(image) else {
+            continue;
+        };
+
+        pass.set_bind_group(1, bind_group, &[*offset]);
+        pass.draw(0..6, 0..1);
+    }
+}
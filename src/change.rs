@@ -2,11 +2,60 @@
 
 use bevy::prelude::*;
 
-use crate::{lights::PointLight2d, prelude::Occluder2d};
+use crate::{
+    lights::{ImportantLight, LightGroup, LightModulator},
+    occluder_batch::OccluderBatch,
+};
 
-/// Component that stores whether an entity has changed or not.
-#[derive(Component, Clone, Default)]
-pub struct Changes(pub bool);
+/// Component that stores what changed about an entity since the last frame.
+///
+/// The kinds of changes are tracked separately so the render world can do as little work as possible:
+/// the occluder bins of lights only need to be rebuilt when something [moved](Changes::moved) or its
+/// [shape](Changes::shape) changed, while [parameter](Changes::parameters) changes only need the entity's
+/// data to be re-uploaded to the GPU.
+///
+/// Lights and occluders are also compared to their previous extraction in the render world, so their
+/// extracted changes only contain what actually changed.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    /// The entity's transform changed.
+    pub moved: bool,
+
+    /// Values that only affect how the entity is drawn changed, such as a light's color or an occluder's opacity.
+    pub parameters: bool,
+
+    /// The area covered by the entity changed, such as an occluder's shape or a light's range,
+    /// or anything else that decides which occluders a light bins, such as its render layers.
+    pub shape: bool,
+}
+
+impl Changes {
+    /// Changes with every kind of change set, used for newly created entities.
+    pub const ALL: Self = Self {
+        moved: true,
+        parameters: true,
+        shape: true,
+    };
+
+    /// Returns true if anything changed.
+    pub fn any(&self) -> bool {
+        self.moved || self.parameters || self.shape
+    }
+
+    /// Returns true if the occluder bins of lights affected by the entity need to be rebuilt.
+    pub fn needs_binning(&self) -> bool {
+        self.moved || self.shape
+    }
+
+    /// Returns the changes of both `self` and `other`.
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            moved: self.moved || other.moved,
+            parameters: self.parameters || other.parameters,
+            shape: self.shape || other.shape,
+        }
+    }
+}
 
 /// Plugin that handles change detection. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
 pub struct ChangePlugin;
@@ -14,28 +63,68 @@ pub struct ChangePlugin;
 impl Plugin for ChangePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(PreUpdate, reset_changes);
-        app.add_systems(Update, (changed_occluders, changed_lights));
+        app.add_systems(
+            Update,
+            (
+                moved_entities,
+                changed_occluder_batches,
+                changed_light_modulators,
+                changed_important_lights,
+                changed_light_groups,
+            ),
+        );
+    }
+}
+
+fn moved_entities(mut entities: Query<&mut Changes, Changed<GlobalTransform>>) {
+    for mut changes in &mut entities {
+        changes.moved = true;
+    }
+}
+
+// batches are re-extracted as a whole, so any change is treated as a change of shape
+fn changed_occluder_batches(mut batches: Query<&mut Changes, Changed<OccluderBatch>>) {
+    for mut changes in &mut batches {
+        changes.parameters = true;
+        changes.shape = true;
+    }
+}
+
+fn changed_light_modulators(
+    mut lights: Query<(&mut Changes, &LightModulator), Changed<LightModulator>>,
+) {
+    for (mut changes, modulator) in &mut lights {
+        changes.parameters = true;
+        changes.shape |= modulator.range;
     }
 }
 
-fn changed_occluders(
-    mut occluders: Query<&mut Changes, Or<(Changed<GlobalTransform>, Changed<Occluder2d>)>>,
+fn changed_important_lights(
+    mut lights: Query<&mut Changes>,
+    added: Query<Entity, Added<ImportantLight>>,
+    mut removed: RemovedComponents<ImportantLight>,
 ) {
-    for mut changed in &mut occluders {
-        changed.0 = true;
+    for entity in added.iter().chain(removed.read()) {
+        if let Ok(mut changes) = lights.get_mut(entity) {
+            changes.parameters = true;
+        }
     }
 }
 
-fn changed_lights(
-    mut lights: Query<&mut Changes, Or<(Changed<GlobalTransform>, Changed<PointLight2d>)>>,
+fn changed_light_groups(
+    mut lights: Query<&mut Changes>,
+    changed: Query<Entity, Changed<LightGroup>>,
+    mut removed: RemovedComponents<LightGroup>,
 ) {
-    for mut changed in &mut lights {
-        changed.0 = true;
+    for entity in changed.iter().chain(removed.read()) {
+        if let Ok(mut changes) = lights.get_mut(entity) {
+            changes.parameters = true;
+        }
     }
 }
 
 fn reset_changes(mut entities: Query<&mut Changes>) {
-    for mut changed in &mut entities {
-        *changed = default();
+    for mut changes in &mut entities {
+        *changes = default();
     }
 }
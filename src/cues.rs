@@ -0,0 +1,440 @@
+//! Module containing [`LightCueSequence`]s, for scripting the [light groups](crate::prelude::LightGroup) of a scene over time.
+
+#[cfg(feature = "ron")]
+use bevy::asset::{AssetLoader, LoadContext, io::Reader};
+use bevy::prelude::*;
+
+use crate::{
+    data::FireflyTestClock,
+    lights::{LightGroupSettings, LightGroups},
+};
+
+/// A single keyframe of a [`LightCueSequence`], setting the settings of a [`LightGroup`](crate::prelude::LightGroup).
+///
+/// The [intensity](LightCue::intensity) and [color](LightCue::color) are interpolated linearly between keyframes of the
+/// same group, while [enabled](LightCue::enabled) snaps when its keyframe is reached. Settings that are `None` are
+/// left as they are, and a group isn't touched until its first keyframe.
+#[derive(Debug, Clone, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightCue {
+    /// Time of the keyframe, in seconds since the start of the sequence.
+    pub time: f32,
+
+    /// Name of the [`LightGroup`](crate::prelude::LightGroup) the keyframe applies to. Lights can be cued individually
+    /// by giving them their own group.
+    pub group: String,
+
+    /// The group's [intensity](LightGroupSettings::intensity) at this keyframe.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub intensity: Option<f32>,
+
+    /// The group's [color](LightGroupSettings::color) at this keyframe.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub color: Option<Color>,
+
+    /// Whether the group is [enabled](LightGroupSettings::enabled) from this keyframe on.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub enabled: Option<bool>,
+}
+
+impl LightCue {
+    /// Construct a new keyframe for the given group that doesn't change any settings.
+    pub fn new(time: f32, group: impl Into<String>) -> Self {
+        Self {
+            time,
+            group: group.into(),
+            intensity: None,
+            color: None,
+            enabled: None,
+        }
+    }
+
+    /// Returns the keyframe with the given [intensity](LightCue::intensity).
+    pub fn with_intensity(self, intensity: f32) -> Self {
+        Self {
+            intensity: Some(intensity),
+            ..self
+        }
+    }
+
+    /// Returns the keyframe with the given [color](LightCue::color).
+    pub fn with_color(self, color: impl Into<Color>) -> Self {
+        Self {
+            color: Some(color.into()),
+            ..self
+        }
+    }
+
+    /// Returns the keyframe with the given [enabled](LightCue::enabled) flag.
+    pub fn with_enabled(self, enabled: bool) -> Self {
+        Self {
+            enabled: Some(enabled),
+            ..self
+        }
+    }
+}
+
+/// A named marker of a [`LightCueSequence`] that triggers a [`LightCueEvent`] when it's reached.
+#[derive(Debug, Clone, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightCueMarker {
+    /// Time of the marker, in seconds since the start of the sequence.
+    pub time: f32,
+    /// Name passed to the [`LightCueEvent`].
+    pub name: String,
+}
+
+/// Asset describing a timed sequence of [keyframes](LightCue) across multiple [light groups](crate::prelude::LightGroup),
+/// such as a concert light show or a scripted horror scene. Sequences are played by a [`LightCuePlayer`].
+///
+/// With the `ron` feature, sequences can be loaded from `.cues.ron` files, and are hot reloaded
+/// if Bevy's `file_watcher` feature is enabled:
+///
+/// ```ron
+/// (
+///     looping: true,
+///     cues: [
+///         (time: 0.0, group: "stage", intensity: Some(0.0)),
+///         (time: 2.0, group: "stage", intensity: Some(3.0)),
+///         (time: 2.0, group: "crowd", enabled: Some(false)),
+///     ],
+///     markers: [(time: 2.0, name: "drop")],
+/// )
+/// ```
+///
+/// # Example
+///
+/// ```
+/// let sequence = sequences.add(LightCueSequence::new(vec![
+///     LightCue::new(0.0, "hallway").with_intensity(1.0),
+///     LightCue::new(0.2, "hallway").with_intensity(0.1),
+///     LightCue::new(0.3, "hallway").with_enabled(false),
+/// ]));
+///
+/// commands.spawn(LightCuePlayer::new(sequence));
+/// ```
+#[derive(Asset, Debug, Clone, Reflect, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightCueSequence {
+    /// The keyframes of the sequence. They're sorted by time when the sequence is sampled.
+    pub cues: Vec<LightCue>,
+
+    /// The markers of the sequence.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub markers: Vec<LightCueMarker>,
+
+    /// Length of the sequence, in seconds. If `None`, the sequence ends at its last keyframe or marker.
+    ///
+    /// **Default:** None.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub duration: Option<f32>,
+
+    /// Whether the sequence starts over once it ends.
+    ///
+    /// **Default:** false.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub looping: bool,
+}
+
+impl LightCueSequence {
+    /// Construct a new sequence from a list of keyframes.
+    pub fn new(cues: Vec<LightCue>) -> Self {
+        Self { cues, ..default() }
+    }
+
+    /// Returns the sequence with the given [markers](LightCueSequence::markers).
+    pub fn with_markers(self, markers: Vec<LightCueMarker>) -> Self {
+        Self { markers, ..self }
+    }
+
+    /// Returns the sequence with the given [looping](LightCueSequence::looping) flag.
+    pub fn with_looping(self, looping: bool) -> Self {
+        Self { looping, ..self }
+    }
+
+    /// Returns the length of the sequence, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.duration.unwrap_or_else(|| {
+            self.cues
+                .iter()
+                .map(|cue| cue.time)
+                .chain(self.markers.iter().map(|marker| marker.time))
+                .fold(0., f32::max)
+        })
+    }
+
+    /// Applies the keyframes of the group at the given time on top of `settings`.
+    pub fn sample(&self, group: &str, time: f32, settings: &mut LightGroupSettings) {
+        let mut cues = self
+            .cues
+            .iter()
+            .filter(|cue| cue.group == group)
+            .collect::<Vec<_>>();
+        cues.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let (before, after) = cues.split_at(cues.partition_point(|cue| cue.time <= time));
+
+        if let Some(intensity) = interpolate(before, after, time, |cue| cue.intensity, f32::lerp) {
+            settings.intensity = intensity;
+        }
+
+        if let Some(color) = interpolate(
+            before,
+            after,
+            time,
+            |cue| cue.color.map(|color| color.to_linear()),
+            |a, b, t| a.mix(&b, t),
+        ) {
+            settings.color = color.into();
+        }
+
+        if let Some(enabled) = before.iter().rev().find_map(|cue| cue.enabled) {
+            settings.enabled = enabled;
+        }
+    }
+
+    /// Returns the names of the groups the sequence has keyframes for.
+    pub fn groups(&self) -> Vec<&str> {
+        let mut groups = self
+            .cues
+            .iter()
+            .map(|cue| cue.group.as_str())
+            .collect::<Vec<_>>();
+        groups.sort_unstable();
+        groups.dedup();
+        groups
+    }
+}
+
+/// Interpolates between the last keyframe before the time and the first one after it that set a value.
+fn interpolate<T: Copy>(
+    before: &[&LightCue],
+    after: &[&LightCue],
+    time: f32,
+    value: impl Fn(&LightCue) -> Option<T>,
+    lerp: impl Fn(T, T, f32) -> T,
+) -> Option<T> {
+    let (start_time, start) = before
+        .iter()
+        .rev()
+        .find_map(|cue| value(cue).map(|value| (cue.time, value)))?;
+
+    let Some((end_time, end)) = after
+        .iter()
+        .find_map(|cue| value(cue).map(|value| (cue.time, value)))
+    else {
+        return Some(start);
+    };
+
+    let span = end_time - start_time;
+    match span <= 0. {
+        true => Some(end),
+        false => Some(lerp(start, end, (time - start_time) / span)),
+    }
+}
+
+/// Component that plays a [`LightCueSequence`], writing its keyframes to the [`LightGroups`] resource.
+///
+/// A [`LightCueEvent`] is triggered on the player's entity every time one of the sequence's [markers](LightCueMarker)
+/// is reached, e.g. to play a sound in sync with the lights.
+///
+/// # Example
+///
+/// ```
+/// commands
+///     .spawn(LightCuePlayer::new(asset_server.load("show.cues.ron")))
+///     .observe(|event: On<LightCueEvent>| {
+///         info!("reached {}", event.name);
+///     });
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct LightCuePlayer {
+    /// The sequence that is played.
+    pub sequence: Handle<LightCueSequence>,
+
+    /// Multiplier for the playback speed.
+    ///
+    /// **Default:** 1.
+    pub speed: f32,
+
+    /// Whether the playback is paused.
+    ///
+    /// **Default:** false.
+    pub paused: bool,
+
+    #[reflect(ignore)]
+    elapsed: f32,
+}
+
+impl LightCuePlayer {
+    /// Construct a new player for the given sequence, starting from its beginning.
+    pub fn new(sequence: Handle<LightCueSequence>) -> Self {
+        Self {
+            sequence,
+            speed: 1.,
+            paused: false,
+            elapsed: 0.,
+        }
+    }
+
+    /// Returns the player with the given [speed](LightCuePlayer::speed).
+    pub fn with_speed(self, speed: f32) -> Self {
+        Self { speed, ..self }
+    }
+
+    /// Returns the current time of the sequence, in seconds.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Jumps to the given time of the sequence. Markers that are skipped over don't trigger events.
+    pub fn seek(&mut self, time: f32) {
+        self.elapsed = time.max(0.);
+    }
+}
+
+/// Event triggered on a [`LightCuePlayer`]'s entity when its sequence reaches a [marker](LightCueMarker).
+#[derive(EntityEvent, Clone, Debug)]
+pub struct LightCueEvent {
+    /// The entity of the player.
+    pub entity: Entity,
+    /// Name of the marker.
+    pub name: String,
+}
+
+/// [`AssetLoader`] for [`LightCueSequence`]s stored as `.cues.ron` files.
+#[cfg(feature = "ron")]
+#[derive(Default, TypePath)]
+pub struct LightCueSequenceLoader;
+
+/// Error that can occur while loading a [`LightCueSequence`].
+#[cfg(feature = "ron")]
+#[derive(Debug)]
+pub enum LightCueSequenceLoaderError {
+    /// The file couldn't be read.
+    Io(std::io::Error),
+    /// The file isn't a valid RON sequence.
+    Ron(ron::error::SpannedError),
+}
+
+#[cfg(feature = "ron")]
+impl std::fmt::Display for LightCueSequenceLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "could not read light cue sequence: {err}"),
+            Self::Ron(err) => write!(f, "could not parse light cue sequence: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "ron")]
+impl std::error::Error for LightCueSequenceLoaderError {}
+
+#[cfg(feature = "ron")]
+impl From<std::io::Error> for LightCueSequenceLoaderError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(feature = "ron")]
+impl From<ron::error::SpannedError> for LightCueSequenceLoaderError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        Self::Ron(err)
+    }
+}
+
+#[cfg(feature = "ron")]
+impl AssetLoader for LightCueSequenceLoader {
+    type Asset = LightCueSequence;
+    type Settings = ();
+    type Error = LightCueSequenceLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["cues.ron"]
+    }
+}
+
+/// Plugin that registers the [`LightCueSequence`] asset and plays [`LightCuePlayer`]s. Added automatically
+/// by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct CuePlugin;
+impl Plugin for CuePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<LightCueSequence>()
+            .register_asset_reflect::<LightCueSequence>();
+        app.register_type::<LightCuePlayer>();
+
+        #[cfg(feature = "ron")]
+        app.init_asset_loader::<LightCueSequenceLoader>();
+
+        app.add_systems(Update, play_light_cues);
+    }
+}
+
+fn play_light_cues(
+    mut commands: Commands,
+    time: Res<Time>,
+    test_clock: Option<Res<FireflyTestClock>>,
+    sequences: Res<Assets<LightCueSequence>>,
+    mut players: Query<(Entity, &mut LightCuePlayer)>,
+    mut groups: ResMut<LightGroups>,
+) {
+    let delta = test_clock.map_or(time.delta(), |clock| clock.delta);
+
+    for (entity, mut player) in &mut players {
+        let Some(sequence) = sequences.get(&player.sequence) else {
+            continue;
+        };
+        let player = player.bypass_change_detection();
+
+        let duration = sequence.duration();
+        let start = player.elapsed;
+        let end = match player.paused {
+            true => start,
+            false => start + delta.as_secs_f32() * player.speed.max(0.),
+        };
+
+        let wrapped = sequence.looping && duration > 0. && end >= duration;
+        player.elapsed = match wrapped {
+            true => end % duration,
+            false => end.min(duration),
+        };
+
+        // markers are reached once the time moves past them, including when the sequence wraps around
+        for marker in &sequence.markers {
+            let reached = match wrapped {
+                true => marker.time >= start || marker.time < player.elapsed,
+                false => marker.time >= start && marker.time < end && start < duration,
+            };
+
+            if reached {
+                commands.trigger(LightCueEvent {
+                    entity,
+                    name: marker.name.clone(),
+                });
+            }
+        }
+
+        // groups are only marked as changed if the sequence actually changed them
+        for group in sequence.groups() {
+            let mut settings = groups.get(group).cloned().unwrap_or_default();
+            sequence.sample(group, player.elapsed, &mut settings);
+
+            if groups.get(group) != Some(&settings) {
+                *groups.get_mut(group) = settings;
+            }
+        }
+    }
+}
@@ -1,10 +1,15 @@
-use std::usize;
+use std::{time::Duration, usize};
 
 use bevy::{
     camera::visibility::RenderLayers,
-    color::palettes::css::WHITE,
+    color::palettes::css::{WHITE, YELLOW},
     prelude::*,
-    render::{extract_component::ExtractComponent, render_resource::ShaderType},
+    render::{
+        extract_component::ExtractComponent,
+        extract_resource::ExtractResource,
+        render_resource::{ShaderType, TextureFormat},
+        view::ViewTarget,
+    },
 };
 
 #[derive(Component, Default, Clone, ExtractComponent, Reflect)]
@@ -30,21 +35,100 @@ pub struct FireflyConfig {
     /// **Default:** 0.
     pub ambient_brightness: f32,
 
+    /// Separate ambient light for sprites. If set, the [ambient color](FireflyConfig::ambient_color) and
+    /// [brightness](FireflyConfig::ambient_brightness) only apply to the ground (the background and tilemaps),
+    /// so characters can stay readable while the floor stays dark.
+    ///
+    /// Sprites are told apart from the ground through the sprite stencil, so this has no effect without the `sprites` feature.
+    ///
+    /// **Performance Impact:** Minor, the sprite stencil needs to be rendered.
+    ///
+    /// **Default:** None.
+    pub sprite_ambient: Option<SpriteAmbient>,
+
     /// Light bands will divide the lightmap into brackets of the given size.
     ///
     /// E.g. with `light_bands: Some(0.3)`, all color channels in the `[0-0.3]` interval will be the same color,
     /// in `[0.3-0.6]` another color, and so on.
     ///
+    /// Pixels reached by an [`ImportantLight`](crate::prelude::ImportantLight) aren't banded.
+    ///
     /// **Performance Impact:** None.
     ///
     /// **Default:** None.
     pub light_bands: Option<f32>,
 
+    /// Custom thresholds for the light bands, as an alternative to the evenly spaced [light bands](FireflyConfig::light_bands).
+    ///
+    /// Each color channel is lowered to the highest threshold below it, or to 0 if it's below all of them. This lets
+    /// the bands follow a reference palette, e.g. many bands near darkness and few near full brightness. Overrides
+    /// [light bands](FireflyConfig::light_bands) when not empty. Only the lowest [`MAX_BAND_THRESHOLDS`] thresholds are used.
+    ///
+    /// You can sample the thresholds from a curve with [`FireflyConfig::band_thresholds`].
+    ///
+    /// Pixels reached by an [`ImportantLight`](crate::prelude::ImportantLight) aren't banded.
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** Empty.
+    pub light_band_thresholds: Vec<f32>,
+
+    /// Tints multiplied with each light band, from the darkest band to the brightest, e.g. to shift shadows
+    /// towards purple and highlights towards yellow.
+    ///
+    /// The band of a pixel is picked by its luminance. Bands beyond the last tint use the last tint, and only the first
+    /// [`MAX_BAND_TINTS`] tints are used. Has no effect unless banding is enabled through the [light bands](FireflyConfig::light_bands)
+    /// or the [band thresholds](FireflyConfig::light_band_thresholds).
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** Empty.
+    pub light_band_tints: Vec<Color>,
+
     /// Whether you want to use soft shadows or not.
     ///
     /// **Default:** true.
     pub soft_shadows: bool,
 
+    /// How the penumbra of [soft shadows](FireflyConfig::soft_shadows) is computed.
+    ///
+    /// **Performance Impact:** [Stochastic](PenumbraMode::Stochastic) is cheaper for high [softness](FireflyConfig::softness)
+    /// values and large light [cores](crate::prelude::LightCore).
+    ///
+    /// **Default:** [Analytic](PenumbraMode::Analytic).
+    pub penumbra: PenumbraMode,
+
+    /// How wide the penumbra of [soft shadows](FireflyConfig::soft_shadows) is.
+    ///
+    /// By default it scales with each light's [core](crate::prelude::LightCore), use [`Softness::WorldUnits`] to
+    /// keep the same penumbra size for all lights.
+    ///
+    /// **Performance Impact:** Wider penumbras are more expensive with the [analytic](PenumbraMode::Analytic) penumbra.
+    ///
+    /// **Default:** [`Softness::CoreScale(1.)`](Softness::CoreScale).
+    pub softness: Softness,
+
+    /// How dark all shadows are, from 0 (no shadows) to 1 (fully dark).
+    ///
+    /// This is multiplied with each light's [shadow strength](crate::prelude::PointLight2d::shadow_strength), and is
+    /// meant for stylistic or accessibility settings, e.g. going from pitch-black noir shadows to softer, gameplay-friendly ones
+    /// without touching every occluder's [opacity](crate::prelude::Occluder2d::opacity).
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** 1.
+    pub shadow_strength: f32,
+
+    /// Shears the shadows cast by occluders towards the given direction, regardless of the position of the light casting them.
+    ///
+    /// Each shadow is shifted by `shadow_skew` world units per world unit of distance from its light, so e.g. `vec2(0.2, -0.2)`
+    /// makes all shadows lean slightly down-right, matching the hand-drawn shadows common in top-down pixel art.
+    ///
+    /// **Performance Impact:** Minor, since occluders are considered by a wider range of pixels.
+    ///
+    /// **Default:** [Vec2::ZERO].
+    pub shadow_skew: Vec2,
+
     /// Whether to use occlusion z-sorting or not.
     ///
     /// If this is enabled, shadows cast by occluders won't affect sprites with a higher z position.
@@ -58,11 +142,25 @@ pub struct FireflyConfig {
 
     pub z_sorting_error_margin: f32,
 
+    /// The position that [z-sorting](FireflyConfig::z_sorting) compares between sprites and occluders.
+    ///
+    /// Top-down games often derive each entity's z from its y position, so that lower entities are drawn on top.
+    /// [NegativeY](ZSortKey::NegativeY) gives the same shadow layering without having to run such a system.
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** [Z](ZSortKey::Z).
+    pub z_sorting_key: ZSortKey,
+
     /// Field that controls how the normal maps are applied relative to perspective.
     ///
+    /// Like the rest of the config, it's set per camera, so e.g. a minimap camera can render with
+    /// [None](NormalMode::None) next to a main camera using [TopDownY](NormalMode::TopDownY). Normal maps
+    /// can be disabled for all cameras at once with [`FireflyGlobalOverrides::disable_normal_maps`].
+    ///
     /// **Performance Impact:** Very minor.
     ///
-    /// **Default:** [None](NormalMapMode::None).
+    /// **Default:** [None](NormalMode::None).
     pub normal_mode: NormalMode,
 
     /// This will control how much the normal map is attenuated before being applied.
@@ -100,6 +198,22 @@ pub struct FireflyConfig {
     /// **Default**: true.
     pub lightmap_filtering: bool,
 
+    /// Overrides the texture format of the lightmap.
+    ///
+    /// By default, the lightmap uses the same format as the camera's target
+    /// ([`Rgba16Float`](TextureFormat::Rgba16Float) with [`Hdr`](bevy::render::view::Hdr), and
+    /// [`Rgba8UnormSrgb`](TextureFormat::Rgba8UnormSrgb) otherwise). Picking a smaller format such as
+    /// [`Rgba8UnormSrgb`](TextureFormat::Rgba8UnormSrgb) on an HDR camera halves the lightmap's memory and bandwidth,
+    /// which can be useful for mobile or web builds, at the cost of clamping the light to 1 and possible banding.
+    ///
+    /// The format must be renderable, blendable and filterable. Without an alpha channel,
+    /// [`ImportantLight`](crate::prelude::ImportantLight)s are banded like any other light.
+    ///
+    /// **Performance Impact:** Depends on the format.
+    ///
+    /// **Default:** None.
+    pub lightmap_format: Option<TextureFormat>,
+
     /// Enables 32 bit sizes for the sprite stencil textures
     /// (textures in which the sprite's z coordinate and other values are stored when
     /// used in e.g. occluion z-sorting).
@@ -112,6 +226,651 @@ pub struct FireflyConfig {
     ///
     /// **Default**: false.
     pub enable_32bit_stencils: bool,
+
+    /// Whether the lightmap is applied over the view before or after the camera's tonemapping.
+    ///
+    /// Applying it before tonemapping lets the tonemapper compress bright lights, while applying it after
+    /// keeps the lightmap's colors and bands exactly as they are computed, which can suit stylized games better.
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default**: true.
+    pub apply_before_tonemapping: bool,
+
+    /// Elevation angle of the lights above the ground, in degrees, used by the top-down
+    /// [normal modes](FireflyConfig::normal_mode).
+    ///
+    /// By default, the direction towards a light is derived from the difference between its [LightHeight](crate::prelude::LightHeight)
+    /// and the sprite's [SpriteHeight](crate::prelude::SpriteHeight), so how much the tops and sides of the sprites are lit depends
+    /// on how far the sprite is from the light. With a pitch, every light hits the sprites at the same angle instead:
+    /// 90 lights them from straight above (mostly the tops), while lower angles light the sides facing the light more.
+    ///
+    /// Can be overriden per light with [`PointLight2d::pitch`](crate::prelude::PointLight2d::pitch).
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** None.
+    pub light_pitch: Option<f32>,
+
+    /// Maximum number of lights rendered by this camera.
+    ///
+    /// If more lights are visible, only the ones closest to the camera are rendered.
+    /// [`ImportantLight`](crate::prelude::ImportantLight)s and [`DirectionalLight2d`](crate::prelude::DirectionalLight2d)s
+    /// are always rendered and don't count towards this limit.
+    ///
+    /// **Performance Impact:** Lowering it reduces the cost of rendering many lights at once.
+    ///
+    /// **Default:** None.
+    pub max_lights: Option<u32>,
+
+    /// How eagerly occluders outside of the camera's view are culled from shadow casting.
+    ///
+    /// **Performance Impact:** [Aggressive](ShadowCullingMode::Aggressive) culling processes fewer occluders
+    /// when lights are partially offscreen.
+    ///
+    /// **Default:** [Conservative](ShadowCullingMode::Conservative).
+    pub shadow_culling: ShadowCullingMode,
+
+    /// Enables short screen-space [contact shadows](ContactShadows) where sprites meet the ground.
+    ///
+    /// Requires the sprite stencil, so it has no effect without the `sprites` feature.
+    ///
+    /// **Performance Impact:** Depends on the number of [steps](ContactShadows::steps).
+    ///
+    /// **Default:** None.
+    pub contact_shadows: Option<ContactShadows>,
+
+    /// Distance by which all occluders are dilated when being sorted into the angular bins of each light, in world units.
+    ///
+    /// Increase this if light leaks through the seams or corners where thin occluders meet.
+    /// Can also be set per occluder with [`Occluder2d::dilation`](crate::prelude::Occluder2d::dilation).
+    ///
+    /// **Performance Impact:** Very minor.
+    ///
+    /// **Default:** 0.
+    pub occluder_dilation: f32,
+
+    /// Optional [accessibility assist](AccessibilityAssist), keeping shadowed areas readable.
+    ///
+    /// Meant to be exposed as an accessibility toggle in your game's settings.
+    ///
+    /// **Performance Impact:** Minor, scales with the [outline width](AccessibilityAssist::outline_width).
+    ///
+    /// **Default:** None.
+    pub accessibility: Option<AccessibilityAssist>,
+
+    /// Optional [auto exposure](AutoExposure), scaling the lightmap as the scene gets brighter or darker.
+    ///
+    /// **Performance Impact:** Minor, a histogram of the lightmap is computed every frame.
+    ///
+    /// **Default:** None.
+    pub auto_exposure: Option<AutoExposure>,
+
+    /// Debug view that outputs the raw lightmap (with ambient light and bands applied) instead of the lit scene.
+    ///
+    /// See `LightmapPreview` (requires the `preview` feature) for watching the lightmap next to the regular view.
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** false.
+    pub show_lightmap: bool,
+}
+
+/// Accessibility options applied when the lightmap is composited over the camera's view.
+///
+/// Shadowed areas are brightened up to a [minimum luminance](AccessibilityAssist::min_luminance), and sprites marked with
+/// [`AssistOutline`](crate::prelude::AssistOutline) (e.g. interactables, pickups, enemies) are outlined while lights don't reach them.
+///
+/// Outlines require the sprite stencil, so they have no effect without the `sprites` feature.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Camera2d,
+///     FireflyConfig {
+///         accessibility: Some(AccessibilityAssist::default().with_outline_color(Color::srgb(0.0, 1.0, 1.0))),
+///         ..default()
+///     },
+/// ));
+/// ```
+#[derive(Debug, Clone, Copy, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccessibilityAssist {
+    /// Minimum luminance of the lightmap, from 0 to 1. Darker pixels are brightened evenly across all channels, keeping their hue.
+    ///
+    /// **Default:** 0.15.
+    pub min_luminance: f32,
+
+    /// Color of the outlines drawn around [`AssistOutline`](crate::prelude::AssistOutline) sprites. The alpha is used as opacity.
+    ///
+    /// **Default:** Yellow.
+    pub outline_color: Color,
+
+    /// Width of the outlines, in pixels. 0 disables outlines. Capped at 8.
+    ///
+    /// **Default:** 2.
+    pub outline_width: f32,
+
+    /// Sprites are outlined while the luminance of the light reaching them (excluding the [ambient light](FireflyConfig::ambient_brightness))
+    /// is below this value.
+    ///
+    /// **Default:** 0.25.
+    pub outline_threshold: f32,
+}
+
+impl Default for AccessibilityAssist {
+    fn default() -> Self {
+        Self {
+            min_luminance: 0.15,
+            outline_color: Color::Srgba(YELLOW),
+            outline_width: 2.,
+            outline_threshold: 0.25,
+        }
+    }
+}
+
+impl AccessibilityAssist {
+    /// Returns the assist with the given [minimum luminance](AccessibilityAssist::min_luminance).
+    pub fn with_min_luminance(&self, min_luminance: f32) -> Self {
+        Self {
+            min_luminance,
+            ..*self
+        }
+    }
+
+    /// Returns the assist with the given [outline color](AccessibilityAssist::outline_color).
+    pub fn with_outline_color(&self, outline_color: Color) -> Self {
+        Self {
+            outline_color,
+            ..*self
+        }
+    }
+
+    /// Returns the assist with the given [outline width](AccessibilityAssist::outline_width).
+    pub fn with_outline_width(&self, outline_width: f32) -> Self {
+        Self {
+            outline_width,
+            ..*self
+        }
+    }
+
+    /// Returns true if outlines are drawn.
+    pub fn uses_outlines(&self) -> bool {
+        self.outline_width > 0. && self.outline_color.alpha() > 0.
+    }
+}
+
+/// Ambient light applied to sprites, separately from the ground. See [`FireflyConfig::sprite_ambient`].
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Camera2d,
+///     FireflyConfig {
+///         ambient_brightness: 0.02,
+///         sprite_ambient: Some(SpriteAmbient::new(Color::srgb(0.6, 0.7, 1.0), 0.25)),
+///         ..default()
+///     },
+/// ));
+/// ```
+#[derive(Debug, Clone, Copy, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpriteAmbient {
+    /// Color of the ambient light on sprites.
+    ///
+    /// **Default:** White.
+    pub color: Color,
+
+    /// Brightness of the ambient light on sprites.
+    ///
+    /// **Default:** 0.2.
+    pub brightness: f32,
+}
+
+impl Default for SpriteAmbient {
+    fn default() -> Self {
+        Self {
+            color: Color::Srgba(WHITE),
+            brightness: 0.2,
+        }
+    }
+}
+
+impl SpriteAmbient {
+    /// Construct a new sprite ambient light with the given color and brightness.
+    pub fn new(color: Color, brightness: f32) -> Self {
+        Self { color, brightness }
+    }
+}
+
+/// Short-range shadows computed by ray-marching the sprite stencil from each pixel towards the light.
+///
+/// They darken the ground right next to sprites, on the side facing away from the light, without needing any
+/// [`Occluder2d`](crate::prelude::Occluder2d). Sprites raised above the ground with [`SpriteHeight`](crate::prelude::SpriteHeight)
+/// cast shorter contact shadows, and sprites higher than the shadow's [length](ContactShadows::length) don't cast any.
+#[derive(Debug, Clone, Copy, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContactShadows {
+    /// Maximum length of the shadows, in world units.
+    ///
+    /// **Default:** 8.
+    pub length: f32,
+
+    /// Number of stencil samples taken per pixel.
+    ///
+    /// **Default:** 8.
+    pub steps: u32,
+
+    /// Opacity of the shadows, right next to the sprite casting them.
+    ///
+    /// **Default:** 0.6.
+    pub opacity: f32,
+}
+
+impl Default for ContactShadows {
+    fn default() -> Self {
+        Self {
+            length: 8.,
+            steps: 8,
+            opacity: 0.6,
+        }
+    }
+}
+
+/// Automatic exposure of the lightmap, adapting to the brightness of the scene like the eye does when walking out of a cave into daylight.
+///
+/// Every frame, a luminance histogram of the lightmap (ambient light included) is computed on the GPU, and the lightmap is
+/// scaled so that its average luminance slowly moves towards the [target](AutoExposure::target). The exposure is applied before
+/// the [light bands](FireflyConfig::light_bands) and the [accessibility assist](FireflyConfig::accessibility).
+///
+/// Only the camera's own lightmap is measured, not the ones [combined](crate::prelude::CombineLightmapTo) into it.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Camera2d,
+///     FireflyConfig {
+///         auto_exposure: Some(AutoExposure::default().with_range(0.5, 8.).with_speed(0.5, 2.)),
+///         ..default()
+///     },
+/// ));
+/// ```
+#[derive(Debug, Clone, Copy, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutoExposure {
+    /// Average luminance of the lightmap the exposure adapts towards.
+    ///
+    /// **Default:** 0.5.
+    pub target: f32,
+
+    /// Lowest factor the lightmap can be multiplied by, limiting how much bright scenes are darkened.
+    ///
+    /// **Default:** 0.25.
+    pub min: f32,
+
+    /// Highest factor the lightmap can be multiplied by, limiting how much dark scenes are brightened.
+    ///
+    /// **Default:** 4.
+    pub max: f32,
+
+    /// Speed at which the exposure rises after going into a darker area, in stops (doublings of the exposure) per second.
+    ///
+    /// **Default:** 1.
+    pub brighten_speed: f32,
+
+    /// Speed at which the exposure drops after going into a brighter area, in stops per second.
+    ///
+    /// **Default:** 3.
+    pub darken_speed: f32,
+
+    /// Range of luminance percentiles, from 0 to 1, that is averaged. The darker and brighter pixels outside of it
+    /// are left out, so that small lights and deep shadows don't skew the exposure.
+    ///
+    /// **Default:** (0.1, 0.9).
+    pub filter: Vec2,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            target: 0.5,
+            min: 0.25,
+            max: 4.,
+            brighten_speed: 1.,
+            darken_speed: 3.,
+            filter: vec2(0.1, 0.9),
+        }
+    }
+}
+
+impl AutoExposure {
+    /// Returns the auto exposure with the given [target](AutoExposure::target).
+    pub fn with_target(self, target: f32) -> Self {
+        Self { target, ..self }
+    }
+
+    /// Returns the auto exposure with the given [min](AutoExposure::min) and [max](AutoExposure::max) exposure.
+    pub fn with_range(self, min: f32, max: f32) -> Self {
+        Self { min, max, ..self }
+    }
+
+    /// Returns the auto exposure with the given [brighten](AutoExposure::brighten_speed) and [darken](AutoExposure::darken_speed) speeds.
+    pub fn with_speed(self, brighten_speed: f32, darken_speed: f32) -> Self {
+        Self {
+            brighten_speed,
+            darken_speed,
+            ..self
+        }
+    }
+}
+
+/// Options for how occluders outside of a camera's view are [culled](FireflyConfig::shadow_culling).
+///
+/// **Default:** [Conservative](ShadowCullingMode::Conservative).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShadowCullingMode {
+    /// Occluders within a light's [culling margin](crate::prelude::PointLight2d::culling_margin) of the view
+    /// keep casting shadows, so shadows of offscreen occluders reach into view correctly.
+    #[default]
+    Conservative,
+
+    /// Only occluders between the view and the light cast shadows. Cheaper, but shadows of occluders
+    /// just outside the view may pop in and out, especially with soft or skewed shadows.
+    Aggressive,
+}
+
+/// Options for which position [z-sorting](FireflyConfig::z_sorting) is based on.
+///
+/// **Default:** [Z](ZSortKey::Z).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ZSortKey {
+    /// Sprites and occluders are sorted by their z position.
+    #[default]
+    Z,
+
+    /// Sprites and occluders are sorted by their negated y position, as if their z was -y.
+    ///
+    /// The sprite's y is the y of its transform, while the occluder's y is the y of its position, including its offset.
+    /// Shadows cast by occluders won't affect sprites that are lower on the screen.
+    NegativeY,
+}
+
+/// Options for how the penumbra of soft shadows is computed.
+///
+/// **Default:** [Analytic](PenumbraMode::Analytic).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PenumbraMode {
+    /// The penumbra is computed exactly from the extreme angles of each occluder, as seen from the light's core.
+    ///
+    /// This gives smooth shadows, but gets more expensive the wider the penumbras are.
+    #[default]
+    Analytic,
+
+    /// Each pixel samples the shadow from a single point inside the light's core, picked with a blue-noise-like pattern.
+    ///
+    /// The penumbra is a dithered mix of lit and shadowed pixels, at the cost of a hard shadow regardless of its width.
+    ///
+    /// The pattern changes every frame, but Firefly doesn't accumulate frames, and Bevy's 2D pipeline has no temporal
+    /// anti-aliasing, so the penumbra stays visibly noisy. This suits stylized, dithered looks, or apps that blend the
+    /// lightmap over time with an accumulation pass of their own.
+    Stochastic,
+}
+
+/// Size of the penumbra of soft shadows, see [`FireflyConfig::softness`].
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Softness {
+    /// The penumbra is cast from each light's [core](crate::prelude::LightCore), with its radius multiplied by the given value.
+    ///
+    /// Lights with bigger cores cast softer shadows, and 0 gives hard shadows.
+    CoreScale(f32),
+
+    /// The penumbra is cast from a core with the given radius in world units, regardless of the light's own core.
+    ///
+    /// The angular spread of the penumbra then only depends on how far each occluder is from the light, so shadows
+    /// keep the same softness relative to the world as the camera zooms and across lights of different sizes.
+    WorldUnits(f32),
+}
+
+impl Default for Softness {
+    fn default() -> Self {
+        Self::CoreScale(1.)
+    }
+}
+
+impl Softness {
+    /// Returns the radius of the core that shadows are cast from, for a light with the given core radius.
+    pub fn core_radius(self, light_core_radius: f32) -> f32 {
+        match self {
+            Softness::CoreScale(scale) => light_core_radius * scale.max(0.),
+            Softness::WorldUnits(radius) => radius.max(0.),
+        }
+    }
+}
+
+/// Resource with global overrides applied on top of every [`FireflyConfig`].
+///
+/// Meant for live-tuning, e.g. through an inspector, since it affects all cameras at once.
+#[derive(Resource, Debug, Clone, ExtractResource, Reflect)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FireflyGlobalOverrides {
+    /// Multiplier for the [softness](FireflyConfig::softness) of shadows.
+    ///
+    /// **Default:** 1.
+    pub softness: f32,
+
+    /// Multiplier for the [ambient brightness](FireflyConfig::ambient_brightness).
+    ///
+    /// **Default:** 1.
+    pub ambient: f32,
+
+    /// Overrides the [light bands](FireflyConfig::light_bands) and [band thresholds](FireflyConfig::light_band_thresholds)
+    /// with the given number of evenly spaced bands. 0 disables banding.
+    ///
+    /// **Default:** None.
+    pub band_count: Option<u32>,
+
+    /// Disables [soft shadows](FireflyConfig::soft_shadows).
+    ///
+    /// **Default:** false.
+    pub disable_soft_shadows: bool,
+
+    /// Disables [z-sorting](FireflyConfig::z_sorting).
+    ///
+    /// **Default:** false.
+    pub disable_z_sorting: bool,
+
+    /// Disables normal maps, as if the [normal mode](FireflyConfig::normal_mode) was [None](NormalMode::None).
+    ///
+    /// **Default:** false.
+    pub disable_normal_maps: bool,
+}
+
+impl Default for FireflyGlobalOverrides {
+    fn default() -> Self {
+        Self {
+            softness: 1.0,
+            ambient: 1.0,
+            band_count: None,
+            disable_soft_shadows: false,
+            disable_z_sorting: false,
+            disable_normal_maps: false,
+        }
+    }
+}
+
+/// Resource that amortizes the shadow binning of low-priority lights across frames.
+///
+/// The occluders around each light are sorted into bins that the shaders sweep through, and are re-binned whenever
+/// the light, an occluder or the camera changes. With this resource present, lights stop being re-binned once the
+/// frame's binning has taken longer than the [budget](FireflyBinningBudget::budget), and reuse their bins from a
+/// previous frame instead. This keeps the worst-case frame time bounded when the number of occluders spikes, at the
+/// cost of the shadows of skipped lights lagging behind.
+///
+/// [`ImportantLight`](crate::prelude::ImportantLight)s and [`DirectionalLight2d`](crate::prelude::DirectionalLight2d)s
+/// are always re-binned, and the lights that were skipped the longest
+/// are re-binned first. Lights whose bins contain an occluder that moved or changed shape are re-binned as well, since
+/// their bins would point to the occluder's new data. Has no effect while [`FireflyTestClock`] is present, to keep rendering deterministic.
+///
+/// # Example
+///
+/// ```
+/// app.insert_resource(FireflyBinningBudget::new(Duration::from_millis(1)));
+/// ```
+#[derive(Resource, Debug, Clone, ExtractResource, Reflect)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FireflyBinningBudget {
+    /// CPU time after which the remaining low-priority lights aren't re-binned this frame.
+    ///
+    /// **Default:** 2 milliseconds.
+    pub budget: Duration,
+
+    /// Maximum number of consecutive frames a light can be skipped for, before it's re-binned regardless of the budget.
+    ///
+    /// **Default:** 4.
+    pub max_skipped_frames: u32,
+}
+
+impl Default for FireflyBinningBudget {
+    fn default() -> Self {
+        Self {
+            budget: Duration::from_millis(2),
+            max_skipped_frames: 4,
+        }
+    }
+}
+
+impl FireflyBinningBudget {
+    /// Construct a new binning budget with the given [CPU time](FireflyBinningBudget::budget).
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            ..default()
+        }
+    }
+
+    /// Construct a new binning budget with the specified [maximum skipped frames](FireflyBinningBudget::max_skipped_frames).
+    pub fn with_max_skipped_frames(self, max_skipped_frames: u32) -> Self {
+        Self {
+            max_skipped_frames,
+            ..self
+        }
+    }
+}
+
+/// Resource that makes Firefly's rendering deterministic, for automated tests and replays.
+///
+/// While this resource is present:
+/// - The visibility timers of lights and occluders advance by a fixed [delta](FireflyTestClock::delta) each frame,
+///   instead of the frame's wall-clock time.
+/// - Temporal effects, such as the noise of [stochastic penumbras](PenumbraMode::Stochastic), use the clock's
+///   [frame](FireflyTestClock::frame), which only changes when [stepped](FireflyTestClock::step).
+/// - [Auto exposure](FireflyConfig::auto_exposure) adapts by the clock's delta each frame.
+/// - [`FireflyDynamicResolution`](crate::prelude::FireflyDynamicResolution) controllers stop adapting the lightmap size.
+/// - [`FireflyBinningBudget`] stops skipping lights.
+///
+/// The same frames then render bit-identically between runs.
+///
+/// # Example
+///
+/// ```
+/// app.insert_resource(FireflyTestClock::default());
+///
+/// for _ in 0..10 {
+///     app.update();
+///     app.world_mut().resource_mut::<FireflyTestClock>().step();
+/// }
+/// ```
+#[derive(Resource, Debug, Clone, ExtractResource, Reflect)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FireflyTestClock {
+    /// Time by which the visibility timers advance each frame.
+    ///
+    /// **Default:** 1 / 60 seconds.
+    pub delta: Duration,
+
+    /// Frame number used by temporal effects.
+    ///
+    /// **Default:** 0.
+    pub frame: u32,
+}
+
+impl Default for FireflyTestClock {
+    fn default() -> Self {
+        Self {
+            delta: Duration::from_secs_f32(1. / 60.),
+            frame: 0,
+        }
+    }
+}
+
+impl FireflyTestClock {
+    /// Advances the clock's [frame](FireflyTestClock::frame) by one.
+    pub fn step(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
+
+/// Maximum number of [light band thresholds](FireflyConfig::light_band_thresholds) a camera can use.
+pub const MAX_BAND_THRESHOLDS: usize = 16;
+
+/// Maximum number of [light band tints](FireflyConfig::light_band_tints) a camera can use.
+pub const MAX_BAND_TINTS: usize = 16;
+
+impl FireflyConfig {
+    /// Returns `bands` [light band thresholds](FireflyConfig::light_band_thresholds) sampled from a curve over `[0, 1]`.
+    ///
+    /// The `i`-th threshold is the curve sampled at `i / bands`, so an ease-in curve places more bands near darkness.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// FireflyConfig {
+    ///     light_band_thresholds: FireflyConfig::band_thresholds(6, EasingCurve::new(0., 1., EaseFunction::QuadraticIn)),
+    ///     ..default()
+    /// }
+    /// ```
+    pub fn band_thresholds(bands: usize, curve: impl Curve<f32>) -> Vec<f32> {
+        (1..=bands)
+            .map(|i| curve.sample_clamped(i as f32 / bands as f32))
+            .collect()
+    }
+
+    /// Returns true if the config uses the sprite stencil and normal map textures, via [z-sorting](FireflyConfig::z_sorting),
+    /// [normal maps](FireflyConfig::normal_mode), [contact shadows](FireflyConfig::contact_shadows),
+    /// [accessibility outlines](AccessibilityAssist::outline_width) or a [sprite ambient](FireflyConfig::sprite_ambient).
+    ///
+    /// If it doesn't (and no light uses [sprite occlusion](crate::prelude::PointLight2d::sprite_occlusion)
+    /// and no occluder has [shadow receivers](crate::prelude::Occluder2d::receivers)), the pass rendering
+    /// sprites to these textures is skipped.
+    pub fn uses_sprite_stencil(&self) -> bool {
+        self.z_sorting
+            || !matches!(self.normal_mode, NormalMode::None)
+            || self.contact_shadows.is_some()
+            || self.sprite_ambient.is_some()
+            || self
+                .accessibility
+                .is_some_and(|accessibility| accessibility.uses_outlines())
+    }
+
+    /// Returns the texture format of the lightmap, for a camera with or without HDR.
+    ///
+    /// This is the [`lightmap_format`](FireflyConfig::lightmap_format) if set, and the camera's format otherwise.
+    pub fn lightmap_texture_format(&self, hdr: bool) -> TextureFormat {
+        self.lightmap_format.unwrap_or(match hdr {
+            true => ViewTarget::TEXTURE_FORMAT_HDR,
+            false => TextureFormat::bevy_default(),
+        })
+    }
 }
 
 /// Specifies how multiple textures will be combined.
@@ -141,7 +900,7 @@ pub enum LightmapSize {
 ///
 /// In order to fully use normal maps, you will need to add the [NormalMap](crate::prelude::NormalMap) component to Sprites.
 ///
-/// **Default:** [None](NormalMapMode::None).
+/// **Default:** [None](NormalMode::None).
 #[derive(Debug, Clone, Reflect)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NormalMode {
@@ -162,6 +921,16 @@ pub enum NormalMode {
     TopDownY,
 
     TopDownZ,
+
+    /// This will make the normal mapping treat the scene as a vertical plane facing the camera, like the levels of platformers.
+    ///
+    /// The light direction is based on the difference between the light's and sprite's x-axis and y-axis, while the
+    /// [LightHeight](crate::prelude::LightHeight) is the light's distance **toward the viewer**, in front of the plane.
+    /// [SpriteHeight](crate::prelude::SpriteHeight) likewise pushes sprites toward the viewer. E.g. a torch with a height of
+    /// 20 lights up the faces of the bricks behind it, while a torch with a height of 0 only lights their sides.
+    ///
+    /// This is recommended for side-scroller games.
+    Sidescroller,
 }
 
 impl Default for FireflyConfig {
@@ -169,21 +938,42 @@ impl Default for FireflyConfig {
         Self {
             ambient_color: Color::Srgba(WHITE),
             ambient_brightness: 0.0,
+            sprite_ambient: None,
             light_bands: None,
+            light_band_thresholds: vec![],
+            light_band_tints: vec![],
             soft_shadows: true,
+            penumbra: PenumbraMode::Analytic,
+            softness: Softness::default(),
+            shadow_strength: 1.0,
+            shadow_skew: Vec2::ZERO,
             z_sorting: true,
             z_sorting_error_margin: 0.0,
+            z_sorting_key: ZSortKey::Z,
             normal_mode: NormalMode::None,
             normal_attenuation: 0.5,
             combination_mode: CombinationMode::Multiply,
             lightmap_size: LightmapSize::Window,
             lightmap_filtering: true,
+            lightmap_format: None,
             enable_32bit_stencils: false,
+            apply_before_tonemapping: true,
+            light_pitch: None,
+            max_lights: None,
+            shadow_culling: ShadowCullingMode::Conservative,
+            contact_shadows: None,
+            occluder_dilation: 0.,
+            accessibility: None,
+            auto_exposure: None,
+            show_lightmap: false,
         }
     }
 }
 
 /// GPU-alligned data from [`FireflyConfig`].
+///
+/// Matches `FireflyConfig` in `firefly::types`. Unlike the other [layouts](crate::layout), it's a uniform buffer, so its offsets
+/// follow the uniform layout rules computed by [`ShaderType`] rather than the Rust struct.
 #[derive(ShaderType, Clone)]
 pub struct UniformFireflyConfig {
     pub ambient_color: Vec3,
@@ -197,6 +987,31 @@ pub struct UniformFireflyConfig {
     pub n_combined_lightmaps: u32,
     pub combination_mode: u32,
     pub texture_scale: Vec2,
+    pub light_pitch: f32,
+    pub softness: f32,
+    pub contact_shadow_length: f32,
+    pub contact_shadow_steps: u32,
+    pub contact_shadow_opacity: f32,
+    pub frame: u32,
+    pub shadow_strength: f32,
+    pub assist_min_luminance: f32,
+    pub assist_outline_width: f32,
+    pub assist_outline_threshold: f32,
+    pub assist_outline_color: Vec4,
+    pub shadow_skew: Vec2,
+    pub show_lightmap: u32,
+    pub world_from_clip: Mat4,
+    pub weather_tile_size: Vec2,
+    pub weather_offset: Vec2,
+    pub weather_ambient: f32,
+    pub weather_lights: f32,
+    pub shadow_core_radius: f32,
+    pub sprite_ambient_color: Vec3,
+    pub sprite_ambient_brightness: f32,
+    pub band_thresholds: [Vec4; MAX_BAND_THRESHOLDS / 4],
+    pub n_band_thresholds: u32,
+    pub band_tints: [Vec4; MAX_BAND_TINTS],
+    pub n_band_tints: u32,
 }
 
 /// Add this **relationship** component to a camera in order to combine it's lightmap into the result of another lightmap.
@@ -0,0 +1,287 @@
+//! Module containing [`FireflyDebug`], for dumping the render state of cameras into bug reports.
+
+use std::sync::{Arc, Mutex};
+
+use bevy::{
+    ecs::entity::EntityHashMap,
+    platform::collections::HashMap,
+    prelude::*,
+    render::{
+        Render, RenderApp, RenderSystems,
+        sync_world::MainEntity,
+        texture::CachedTexture,
+        view::{ExtractedView, RenderVisibleEntities},
+    },
+};
+
+use crate::{
+    AssistMaskTexture, CombinedLightMapTextures, LightMapTexture, NormalMapTexture,
+    SpriteIdTexture, SpriteStencilTexture,
+    buffers::{BinBuffers, BufferIndex, OccluderPointer},
+    data::FireflyConfig,
+    lights::{ExtractedPointLight, PointLight2d, ViewLightmapCreationKey},
+    memory::FireflyMemoryStats,
+    occluder_batch::{ExtractedOccluderBatch, OccluderBatchIndices},
+    occluders::{ExtractedOccluder, PolyOccluderIndex, RoundOccluderIndex},
+    pipelines::SpecializedApplicationPipeline,
+};
+
+/// Resource for dumping what Firefly rendered for a camera, added by the [`FireflyDebugPlugin`].
+///
+/// The reports are gathered in the render world, so they lag one frame behind. With the `serde` feature, they
+/// can be serialized, e.g. to attach them to a bug report.
+///
+/// # Example
+///
+/// ```
+/// fn dump_on_f12(keys: Res<ButtonInput<KeyCode>>, debug: Res<FireflyDebug>, camera: Single<Entity, With<Camera2d>>) {
+///     if keys.just_pressed(KeyCode::F12) {
+///         info!("{:#?}", debug.dump(*camera));
+///     }
+/// }
+/// ```
+#[derive(Resource, Clone, Debug, Default)]
+pub struct FireflyDebug {
+    reports: EntityHashMap<FireflyDebugReport>,
+}
+
+impl FireflyDebug {
+    /// Returns the report of the camera's last rendered frame, or `None` if the camera isn't rendered by Firefly.
+    pub fn dump(&self, camera: Entity) -> Option<FireflyDebugReport> {
+        self.reports.get(&camera).cloned()
+    }
+
+    /// Returns the reports of every camera rendered by Firefly.
+    pub fn dump_all(&self) -> Vec<FireflyDebugReport> {
+        let mut reports = self.reports.values().cloned().collect::<Vec<_>>();
+        reports.sort_by_key(|report| report.camera);
+        reports
+    }
+}
+
+/// The state of a camera's lighting over a single frame, as dumped by [`FireflyDebug`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FireflyDebugReport {
+    /// The camera entity.
+    pub camera: Entity,
+
+    /// The lights visible to the camera.
+    pub lights: Vec<LightDebugReport>,
+
+    /// Number of occluders extracted to the render world, across all cameras.
+    pub occluders: usize,
+
+    /// Number of [occluder batches](crate::prelude::OccluderBatch) extracted to the render world, across all cameras.
+    pub occluder_batches: usize,
+
+    /// GPU memory used by Firefly, across all cameras.
+    pub memory: FireflyMemoryStats,
+
+    /// The camera's textures.
+    pub textures: Vec<TextureDebugReport>,
+
+    /// Keys of the pipelines used by the camera, formatted with their [`Debug`] implementation.
+    pub pipeline_keys: Vec<String>,
+}
+
+/// A light drawn by a camera, as part of a [`FireflyDebugReport`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightDebugReport {
+    /// The light entity.
+    pub entity: Entity,
+
+    /// The light's position, including its offset.
+    pub pos: Vec2,
+
+    /// The light's range, including its [modulator](crate::prelude::LightModulator).
+    pub radius: f32,
+
+    /// The occluders and [occluder batches](crate::prelude::OccluderBatch) binned for the light, each listed once.
+    pub binned_occluders: Vec<Entity>,
+}
+
+/// A texture of a camera, as part of a [`FireflyDebugReport`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextureDebugReport {
+    /// What the texture is used for.
+    pub label: String,
+
+    /// Width, height and number of layers of the texture.
+    pub size: UVec3,
+
+    /// The texture's format, formatted with its [`Debug`] implementation.
+    pub format: String,
+}
+
+impl TextureDebugReport {
+    fn new(label: &str, texture: &CachedTexture) -> Self {
+        let size = texture.texture.size();
+        Self {
+            label: label.to_string(),
+            size: uvec3(size.width, size.height, size.depth_or_array_layers),
+            format: format!("{:?}", texture.texture.format()),
+        }
+    }
+}
+
+/// Reports shared between the main world and the render world.
+#[derive(Resource, Clone, Default)]
+struct SharedDebugReports(Arc<Mutex<EntityHashMap<FireflyDebugReport>>>);
+
+/// Plugin that gathers the reports of [`FireflyDebug`].
+///
+/// Not added by [`FireflyPlugin`](crate::prelude::FireflyPlugin), since gathering the reports has a small cost every frame.
+pub struct FireflyDebugPlugin;
+
+impl Plugin for FireflyDebugPlugin {
+    fn build(&self, app: &mut App) {
+        let shared = SharedDebugReports::default();
+
+        app.init_resource::<FireflyDebug>();
+        app.insert_resource(shared.clone());
+        app.add_systems(First, read_debug_reports);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.insert_resource(shared);
+        render_app.add_systems(Render, write_debug_reports.in_set(RenderSystems::Cleanup));
+    }
+}
+
+fn read_debug_reports(
+    shared: Res<SharedDebugReports>,
+    memory: Res<FireflyMemoryStats>,
+    mut debug: ResMut<FireflyDebug>,
+) {
+    let Ok(shared) = shared.0.lock() else {
+        return;
+    };
+
+    debug.reports = shared.clone();
+    for report in debug.reports.values_mut() {
+        report.memory = *memory;
+    }
+}
+
+fn write_debug_reports(
+    shared: Res<SharedDebugReports>,
+    views: Query<
+        (
+            &MainEntity,
+            &ExtractedView,
+            &RenderVisibleEntities,
+            Option<&ViewLightmapCreationKey>,
+            Option<&SpecializedApplicationPipeline>,
+            (
+                Option<&LightMapTexture>,
+                Option<&CombinedLightMapTextures>,
+                Option<&SpriteStencilTexture>,
+                Option<&NormalMapTexture>,
+                Option<&AssistMaskTexture>,
+                Option<&SpriteIdTexture>,
+            ),
+        ),
+        With<FireflyConfig>,
+    >,
+    lights: Query<(&ExtractedPointLight, &BinBuffers)>,
+    occluders: Query<
+        (&MainEntity, &RoundOccluderIndex, &PolyOccluderIndex),
+        With<ExtractedOccluder>,
+    >,
+    occluder_batches: Query<(&MainEntity, &OccluderBatchIndices), With<ExtractedOccluderBatch>>,
+) {
+    let mut reports = EntityHashMap::default();
+
+    // the entity behind each pointer index, so occluders binned more than once are reported once
+    let mut pointers = HashMap::new();
+    let mut insert = |entity: &MainEntity, round: &RoundOccluderIndex, poly: &PolyOccluderIndex| {
+        let slots = [(round.0, false), (poly.occluder, true)];
+        for (index, is_poly) in slots {
+            if let Some(BufferIndex { index, .. }) = index {
+                pointers.insert(
+                    OccluderPointer::encode_index(index as u32, is_poly),
+                    entity.id(),
+                );
+            }
+        }
+    };
+    for (entity, round, poly) in &occluders {
+        insert(entity, round, poly);
+    }
+    for (entity, indices) in &occluder_batches {
+        for (round, poly, _) in &indices.0 {
+            insert(entity, round, poly);
+        }
+    }
+
+    for (main_entity, view, visible_entities, creation_key, application_pipeline, textures) in
+        &views
+    {
+        let lights = visible_entities
+            .iter::<PointLight2d>()
+            .filter_map(|(render_entity, main_entity)| {
+                let (light, bins) = lights.get(*render_entity).ok()?;
+
+                let mut binned_occluders = bins
+                    .0
+                    .get(&view.retained_view_entity)
+                    .into_iter()
+                    .flat_map(|bins| bins.pointer_indices())
+                    .filter_map(|index| pointers.get(&index).copied())
+                    .collect::<Vec<_>>();
+                binned_occluders.sort();
+                binned_occluders.dedup();
+
+                Some(LightDebugReport {
+                    entity: main_entity.id(),
+                    pos: light.pos,
+                    radius: light.radius,
+                    binned_occluders,
+                })
+            })
+            .collect();
+
+        let (lightmap, combined_lightmaps, stencil, normal_map, assist_mask, sprite_ids) = textures;
+        let textures = [
+            ("lightmap", lightmap.map(|t| &t.0)),
+            ("combined lightmaps", combined_lightmaps.map(|t| &t.0)),
+            ("sprite stencil", stencil.map(|t| &t.0)),
+            ("normal map", normal_map.map(|t| &t.0)),
+            ("assist mask", assist_mask.map(|t| &t.0)),
+            ("sprite ids", sprite_ids.map(|t| &t.0)),
+        ]
+        .into_iter()
+        .filter_map(|(label, texture)| Some(TextureDebugReport::new(label, texture?)))
+        .collect();
+
+        let mut pipeline_keys = vec![];
+        if let Some(key) = creation_key {
+            pipeline_keys.push(format!("{:?}", key.0));
+        }
+        if let Some(pipeline) = application_pipeline {
+            pipeline_keys.push(format!("{:?}", pipeline.key));
+        }
+
+        reports.insert(
+            main_entity.id(),
+            FireflyDebugReport {
+                camera: main_entity.id(),
+                lights,
+                occluders: occluders.iter().len(),
+                occluder_batches: occluder_batches.iter().len(),
+                memory: default(),
+                textures,
+                pipeline_keys,
+            },
+        );
+    }
+
+    if let Ok(mut shared) = shared.0.lock() {
+        *shared = reports;
+    }
+}
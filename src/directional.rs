@@ -0,0 +1,267 @@
+//! Module containing the [`DirectionalLight2d`] component, a light that illuminates the whole view from a single direction.
+
+use bevy::{camera::CameraUpdateSystems, prelude::*, transform::TransformSystems};
+
+use crate::{
+    data::FireflyConfig,
+    lights::{Falloff, LightAngle, PointLight2d},
+    visibility::view_world_rect,
+};
+
+/// How far away the light is placed from the center of the view, relative to the view's radius.
+///
+/// The shadow rays of a [`DirectionalLight2d`] diverge by `1 / DIRECTIONAL_DISTANCE` radians over the view.
+const DIRECTIONAL_DISTANCE: f32 = 500.0;
+
+/// Light that illuminates the whole view from a single direction, with parallel shadows. Useful for the sun or moon
+/// of outdoor scenes, where a [point light](PointLight2d) with a huge range is both inaccurate and expensive.
+///
+/// The light is rendered as a [`PointLight2d`] with no falloff, placed far behind the view of the camera it follows.
+/// That point light is managed by [`DirectionalLightPlugin`] every frame, so changes to it are overwritten, except
+/// for fields that don't have an equivalent here, such as the [core](PointLight2d::core) and the
+/// [sprite occlusion](PointLight2d::sprite_occlusion).
+///
+/// Shadows are only accurate for the [camera](DirectionalLight2d::camera) the light follows. Other cameras
+/// still see the light, but might get mismatched shadows.
+///
+/// [Shadow skew](crate::prelude::FireflyConfig::shadow_skew) is ignored by directional lights.
+///
+/// # Example
+///
+/// ```
+/// // late afternoon sun, casting long shadows towards the bottom-right
+/// commands.spawn(DirectionalLight2d {
+///     color: Color::srgb(1.0, 0.85, 0.6),
+///     direction: Dir2::from_xy(1.0, -0.5).unwrap(),
+///     pitch: Some(20.0),
+///     ..default()
+/// });
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(PointLight2d = directional_point_light(), ComputedDirectionalLight)]
+pub struct DirectionalLight2d {
+    /// Color of the light. Alpha is ignored.
+    ///
+    /// **Default:** White.
+    pub color: Color,
+
+    /// Intensity of the light.
+    ///
+    /// **Default:** 1.
+    pub intensity: f32,
+
+    /// The direction the light travels in. Shadows are cast in this direction.
+    ///
+    /// **Default:** [`Dir2::NEG_Y`].
+    pub direction: Dir2,
+
+    /// Whether this light should cast shadows.
+    ///
+    /// **Default:** true.
+    pub cast_shadows: bool,
+
+    /// How dark the shadows cast by this light are, from 0 to 1.
+    ///
+    /// **Default:** 1.
+    pub shadow_strength: f32,
+
+    /// Elevation of the light above the ground, in degrees, used by [normal maps](crate::prelude::FireflyConfig::normal_mode).
+    /// Overrides the config's [light pitch](crate::prelude::FireflyConfig::light_pitch) for this light.
+    ///
+    /// **Default:** None.
+    pub pitch: Option<f32>,
+
+    /// How far outside the camera's view occluders can be and still cast shadows into it.
+    /// Shadows of occluders further away than this are culled. If None, the view's
+    /// [radius](ComputedDirectionalLight::view_radius) is used.
+    ///
+    /// Ignored by cameras with [aggressive](crate::prelude::ShadowCullingMode::Aggressive) shadow culling.
+    ///
+    /// **Performance Impact:** Major, every occluder within this distance of the view is binned.
+    ///
+    /// **Default:** None.
+    pub max_shadow_length: Option<f32>,
+
+    /// The camera whose view is lit. If None, the active camera with a [`FireflyConfig`]
+    /// and the highest [order](Camera::order) is used.
+    ///
+    /// **Default:** None.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub camera: Option<Entity>,
+}
+
+impl Default for DirectionalLight2d {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 1.,
+            direction: Dir2::NEG_Y,
+            cast_shadows: true,
+            shadow_strength: 1.,
+            pitch: None,
+            max_shadow_length: None,
+            camera: None,
+        }
+    }
+}
+
+impl DirectionalLight2d {
+    /// Construct a new directional light travelling in the given [direction](DirectionalLight2d::direction).
+    pub fn new(direction: Dir2) -> Self {
+        Self {
+            direction,
+            ..default()
+        }
+    }
+
+    /// Construct a new directional light with the specified [color](DirectionalLight2d::color).
+    pub fn with_color(self, color: Color) -> Self {
+        Self { color, ..self }
+    }
+
+    /// Construct a new directional light with the specified [intensity](DirectionalLight2d::intensity).
+    pub fn with_intensity(self, intensity: f32) -> Self {
+        Self { intensity, ..self }
+    }
+
+    /// Construct a new directional light with the specified [pitch](DirectionalLight2d::pitch).
+    pub fn with_pitch(self, pitch: f32) -> Self {
+        Self {
+            pitch: Some(pitch),
+            ..self
+        }
+    }
+
+    /// Construct a new directional light with the specified [max shadow length](DirectionalLight2d::max_shadow_length).
+    pub fn with_max_shadow_length(self, max_shadow_length: f32) -> Self {
+        Self {
+            max_shadow_length: Some(max_shadow_length),
+            ..self
+        }
+    }
+}
+
+fn directional_point_light() -> PointLight2d {
+    PointLight2d {
+        falloff: Falloff::None,
+        angle: LightAngle::FULL,
+        ..default()
+    }
+}
+
+/// The view a [`DirectionalLight2d`] was last placed behind. Computed every frame by [`DirectionalLightPlugin`].
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct ComputedDirectionalLight {
+    /// World-space center of the lit view.
+    pub view_center: Vec2,
+    /// Distance from the view's center to its corners.
+    pub view_radius: f32,
+}
+
+impl ComputedDirectionalLight {
+    /// The angles, as seen from `light_pos`, that the lit view covers, as the start angle and the span, in radians.
+    ///
+    /// Occluders are only binned over this sector, so the bins stay fine-grained even though the light is far away.
+    pub fn sector(&self, light_pos: Vec2) -> Vec2 {
+        let to_view = self.view_center - light_pos;
+        let distance = to_view.length();
+        if self.view_radius <= 0. || distance <= self.view_radius {
+            return vec2(-std::f32::consts::PI, std::f32::consts::TAU);
+        }
+
+        // slightly widened, so soft shadows at the edges of the view still find their occluders
+        let half = (self.view_radius / distance).asin() * 1.1;
+        vec2(to_view.to_angle() - half, half * 2.)
+    }
+}
+
+/// Plugin that places every [`DirectionalLight2d`] behind its camera's view. Added automatically
+/// by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct DirectionalLightPlugin;
+
+impl Plugin for DirectionalLightPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DirectionalLight2d>();
+        app.register_type::<ComputedDirectionalLight>();
+        app.add_systems(
+            PostUpdate,
+            update_directional_lights
+                .after(TransformSystems::Propagate)
+                .after(CameraUpdateSystems)
+                .before(bevy::camera::visibility::VisibilitySystems::CheckVisibility),
+        );
+    }
+}
+
+fn update_directional_lights(
+    mut lights: Query<(
+        &GlobalTransform,
+        &DirectionalLight2d,
+        &mut PointLight2d,
+        &mut ComputedDirectionalLight,
+    )>,
+    cameras: Query<(Entity, &Camera, &GlobalTransform), With<FireflyConfig>>,
+) {
+    let default_camera = cameras
+        .iter()
+        .filter(|(_, camera, _)| camera.is_active)
+        .max_by_key(|(_, camera, _)| camera.order)
+        .map(|(entity, ..)| entity);
+
+    for (transform, directional, mut light, mut computed) in &mut lights {
+        let Some((_, camera, camera_transform)) = directional
+            .camera
+            .or(default_camera)
+            .and_then(|camera| cameras.get(camera).ok())
+        else {
+            continue;
+        };
+
+        let clip_from_view = camera.clip_from_view();
+        if clip_from_view.determinant() == 0. {
+            continue;
+        }
+
+        let view_rect = view_world_rect(camera_transform.to_matrix() * clip_from_view.inverse());
+        let view_center = view_rect.center();
+        let view_radius = view_rect.half_size().length().max(1.);
+        let distance = view_radius * DIRECTIONAL_DISTANCE;
+
+        let pos = view_center - *directional.direction * distance;
+        let offset = (pos - transform.translation().truncate()).extend(0.);
+        let radius = distance + view_radius * 2.;
+        // the light's range covers far more than the view, so its margin is bounded explicitly
+        let culling_margin = Some(directional.max_shadow_length.unwrap_or(view_radius).max(0.));
+
+        computed.set_if_neq(ComputedDirectionalLight {
+            view_center,
+            view_radius,
+        });
+
+        // only writing changed values, so the light's parameters aren't re-uploaded every frame
+        let changed = light.offset != offset
+            || light.radius != radius
+            || light.culling_margin != culling_margin
+            || light.color != directional.color
+            || light.intensity != directional.intensity
+            || light.cast_shadows != directional.cast_shadows
+            || light.shadow_strength != directional.shadow_strength
+            || light.pitch != directional.pitch
+            || !matches!(light.falloff, Falloff::None);
+
+        if changed {
+            light.offset = offset;
+            light.radius = radius;
+            light.culling_margin = culling_margin;
+            light.color = directional.color;
+            light.intensity = directional.intensity;
+            light.cast_shadows = directional.cast_shadows;
+            light.shadow_strength = directional.shadow_strength;
+            light.pitch = directional.pitch;
+            light.falloff = Falloff::None;
+        }
+    }
+}
@@ -0,0 +1,491 @@
+//! Module containing structs and functions relevant to drop shadows.
+//!
+//! A [`DropShadow2d`] grounds a sprite visually by drawing a soft, squashed silhouette of it underneath,
+//! pointing away from the strongest light that reaches it. Unlike [occluders](crate::prelude::Occluder2d),
+//! drop shadows don't block any light, so they're cheap enough to put on every character.
+
+use bevy::{
+    camera::visibility::RenderLayers,
+    color::palettes::css::BLACK,
+    core_pipeline::core_2d::Transparent2d,
+    ecs::{
+        query::ROQueryItem,
+        system::{SystemParamItem, lifetimeless::*},
+    },
+    math::FloatOrd,
+    platform::collections::HashMap,
+    prelude::*,
+    render::{
+        Extract, ExtractSchedule, Render, RenderApp, RenderSystems,
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
+            RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+        },
+        render_resource::*,
+        renderer::{RenderDevice, RenderQueue},
+        sync_world::RenderEntity,
+        texture::GpuImage,
+        view::{ExtractedView, RenderVisibleEntities},
+    },
+    sprite::Anchor,
+};
+
+use fixedbitset::FixedBitSet;
+
+use crate::{
+    data::FireflyConfig,
+    extract::sprite_rect,
+    lights::ExtractedPointLight,
+    pipelines::{DropShadowPipeline, SpritePipelineKey},
+    sprite::FireflySprite,
+    sprites::SetSpriteViewBindGroup,
+    visibility::NotVisible,
+};
+
+/// How far below its sprite a drop shadow is sorted.
+const DROP_SHADOW_Z_OFFSET: f32 = 0.001;
+
+/// Component you can add to a [`FireflySprite`] to render a soft drop shadow underneath it.
+///
+/// The shadow is a blurred silhouette of the sprite, squashed vertically and standing at the sprite's
+/// bottom edge. Every frame it leans away from the strongest [`PointLight2d`](crate::prelude::PointLight2d)
+/// reaching the sprite, getting longer and fainter as the sprite moves towards the edge of the light.
+///
+/// If no light reaches the sprite, no shadow is drawn.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     FireflySprite::from_image(asset_server.load("player.png")),
+///     DropShadow2d::default().with_opacity(0.6),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(FireflySprite)]
+pub struct DropShadow2d {
+    /// Color of the shadow.
+    ///
+    /// **Default:** Black.
+    pub color: Color,
+
+    /// Opacity of the shadow when the sprite is fully lit. This is multiplied with the strength
+    /// of the light casting it.
+    ///
+    /// **Default:** 0.5.
+    pub opacity: f32,
+
+    /// Vertical scale of the silhouette, relative to the sprite's height.
+    ///
+    /// **Default:** 0.3.
+    pub squash: f32,
+
+    /// How far (in world units) the top of the silhouette leans away from the light when the sprite is at
+    /// the edge of the light's radius. Sprites closer to the light get proportionally shorter shadows.
+    ///
+    /// **Default:** 24.
+    pub length: f32,
+
+    /// Radius of the blur, in texels of the sprite's image.
+    ///
+    /// **Default:** 2.
+    pub blur: f32,
+}
+
+impl Default for DropShadow2d {
+    fn default() -> Self {
+        Self {
+            color: Color::Srgba(BLACK),
+            opacity: 0.5,
+            squash: 0.3,
+            length: 24.,
+            blur: 2.,
+        }
+    }
+}
+
+impl DropShadow2d {
+    /// Returns the drop shadow with the given [color](DropShadow2d::color).
+    pub fn with_color(&self, color: Color) -> Self {
+        Self { color, ..*self }
+    }
+
+    /// Returns the drop shadow with the given [opacity](DropShadow2d::opacity).
+    pub fn with_opacity(&self, opacity: f32) -> Self {
+        Self { opacity, ..*self }
+    }
+
+    /// Returns the drop shadow with the given [squash](DropShadow2d::squash).
+    pub fn with_squash(&self, squash: f32) -> Self {
+        Self { squash, ..*self }
+    }
+
+    /// Returns the drop shadow with the given [length](DropShadow2d::length).
+    pub fn with_length(&self, length: f32) -> Self {
+        Self { length, ..*self }
+    }
+
+    /// Returns the drop shadow with the given [blur](DropShadow2d::blur).
+    pub fn with_blur(&self, blur: f32) -> Self {
+        Self { blur, ..*self }
+    }
+}
+
+/// Data of a [`DropShadow2d`] that is sent to the GPU.
+#[derive(ShaderType, Clone, Copy)]
+pub struct UniformDropShadow {
+    pub world_from_local: Mat4,
+    pub uv_offset_scale: Vec4,
+    pub color: Vec4,
+    pub blur: Vec2,
+}
+
+pub(crate) struct ExtractedDropShadow {
+    pub main_entity: Entity,
+    pub render_entity: Entity,
+    pub transform: GlobalTransform,
+    pub image: AssetId<Image>,
+    pub rect: Option<Rect>,
+    pub custom_size: Option<Vec2>,
+    pub anchor: Vec2,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub shadow: DropShadow2d,
+    pub render_layers: RenderLayers,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedDropShadows {
+    pub shadows: Vec<ExtractedDropShadow>,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct DropShadowBindGroups {
+    /// Uniforms of all drop shadows of the frame, each bound at its own offset.
+    pub uniforms: DynamicUniformBuffer<UniformDropShadow>,
+    /// Bind group of each sprite image that has a drop shadow.
+    pub images: HashMap<AssetId<Image>, BindGroup>,
+    /// Image and uniform offset of each drop shadow.
+    pub values: HashMap<Entity, (AssetId<Image>, u32)>,
+}
+
+/// Plugin that extracts and renders [`DropShadow2d`]s. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct DropShadowPlugin;
+impl Plugin for DropShadowPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DropShadow2d>();
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<ExtractedDropShadows>()
+            .init_resource::<DropShadowBindGroups>()
+            .add_render_command::<Transparent2d, DrawDropShadow>()
+            .add_systems(ExtractSchedule, extract_drop_shadows)
+            .add_systems(
+                Render,
+                (
+                    queue_drop_shadows.in_set(RenderSystems::Queue),
+                    prepare_drop_shadow_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                ),
+            );
+    }
+}
+
+fn extract_drop_shadows(
+    mut extracted: ResMut<ExtractedDropShadows>,
+    texture_atlases: Extract<Res<Assets<TextureAtlasLayout>>>,
+    sprites: Extract<
+        Query<(
+            Entity,
+            RenderEntity,
+            &ViewVisibility,
+            &FireflySprite,
+            &DropShadow2d,
+            &Anchor,
+            &GlobalTransform,
+            Option<&RenderLayers>,
+        )>,
+    >,
+) {
+    extracted.shadows.clear();
+
+    for (
+        main_entity,
+        render_entity,
+        visibility,
+        sprite,
+        shadow,
+        anchor,
+        transform,
+        render_layers,
+    ) in &sprites
+    {
+        if !visibility.get() {
+            continue;
+        }
+
+        extracted.shadows.push(ExtractedDropShadow {
+            main_entity,
+            render_entity,
+            transform: *transform,
+            image: sprite.image.id(),
+            rect: sprite_rect(sprite, &texture_atlases),
+            custom_size: sprite.custom_size,
+            anchor: anchor.as_vec(),
+            flip_x: sprite.flip_x,
+            flip_y: sprite.flip_y,
+            shadow: *shadow,
+            render_layers: render_layers.cloned().unwrap_or_default(),
+        });
+    }
+}
+
+fn queue_drop_shadows(
+    mut view_entities: Local<FixedBitSet>,
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    pipeline: Res<DropShadowPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<DropShadowPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    extracted: Res<ExtractedDropShadows>,
+    mut phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
+    views: Query<(&RenderVisibleEntities, &ExtractedView, &Msaa), With<FireflyConfig>>,
+) {
+    if extracted.shadows.is_empty() {
+        return;
+    }
+
+    let draw_function = draw_functions.read().id::<DrawDropShadow>();
+
+    for (visible_entities, view, msaa) in &views {
+        let Some(phase) = phases.get_mut(&view.retained_view_entity) else {
+            continue;
+        };
+
+        let key = SpritePipelineKey::from_hdr(view.hdr)
+            | SpritePipelineKey::from_msaa_samples(msaa.samples());
+        let pipeline = pipelines.specialize(&pipeline_cache, &pipeline, key);
+
+        view_entities.clear();
+        view_entities.extend(
+            visible_entities
+                .iter::<FireflySprite>()
+                .map(|(_, e)| e.index_u32() as usize),
+        );
+
+        for shadow in &extracted.shadows {
+            if !view_entities.contains(shadow.main_entity.index_u32() as usize) {
+                continue;
+            }
+
+            phase.add(Transparent2d {
+                draw_function,
+                pipeline,
+                entity: (shadow.render_entity, shadow.main_entity.into()),
+                sort_key: FloatOrd(shadow.transform.translation().z - DROP_SHADOW_Z_OFFSET),
+                batch_range: 0..1,
+                extra_index: PhaseItemExtraIndex::None,
+                // an invalid index, so bevy's sprite batching doesn't mistake this for the sprite itself
+                extracted_index: usize::MAX,
+                indexed: false,
+            });
+        }
+    }
+}
+
+fn prepare_drop_shadow_bind_groups(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    pipeline: Res<DropShadowPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    extracted: Res<ExtractedDropShadows>,
+    lights: Query<&ExtractedPointLight, Without<NotVisible>>,
+    mut bind_groups: ResMut<DropShadowBindGroups>,
+) {
+    let bind_groups = &mut *bind_groups;
+    bind_groups.uniforms.clear();
+    bind_groups.images.clear();
+    bind_groups.values.clear();
+
+    for shadow in &extracted.shadows {
+        let Some(gpu_image) = gpu_images.get(shadow.image) else {
+            continue;
+        };
+
+        let image_size = gpu_image.size_2d().as_vec2();
+        let rect = shadow
+            .rect
+            .unwrap_or(Rect::from_corners(Vec2::ZERO, image_size));
+        let size = shadow.custom_size.unwrap_or(rect.size());
+
+        // bottom edge of the sprite, in world space
+        let center = -shadow.anchor * size;
+        let bottom_left = shadow
+            .transform
+            .transform_point((center + vec2(-0.5, -0.5) * size).extend(0.))
+            .truncate();
+        let bottom_right = shadow
+            .transform
+            .transform_point((center + vec2(0.5, -0.5) * size).extend(0.))
+            .truncate();
+        let top_left = shadow
+            .transform
+            .transform_point((center + vec2(-0.5, 0.5) * size).extend(0.))
+            .truncate();
+        let feet = (bottom_left + bottom_right) * 0.5;
+
+        // the strongest light is the one with the highest intensity left at the sprite's feet,
+        // among the lights on the sprite's layers whose cone reaches them
+        let Some((light, strength)) = lights
+            .iter()
+            .filter(|light| {
+                let offset = feet - light.pos;
+                light.radius > 0.
+                    && light.render_layers.intersects(&shadow.render_layers)
+                    && (light.angle.outer >= 360.
+                        || offset == Vec2::ZERO
+                        || light.dir.angle_to(offset).abs().to_degrees()
+                            <= (light.angle.outer + light.angle.feather) / 2.)
+            })
+            .map(|light| {
+                let t = light.pos.distance(feet) / light.radius;
+                (light, light.intensity * (1. - t))
+            })
+            .filter(|(_, strength)| *strength > 0.)
+            .max_by_key(|(_, strength)| FloatOrd(*strength))
+        else {
+            continue;
+        };
+
+        let t = (light.pos.distance(feet) / light.radius).min(1.);
+        let dir = (feet - light.pos).normalize_or_zero();
+
+        let width = bottom_left.distance(bottom_right);
+        let height = bottom_left.distance(top_left) * shadow.shadow.squash;
+        let lean = dir * shadow.shadow.length * t;
+
+        let world_from_local = Mat4::from_cols(
+            vec4(width, 0., 0., 0.),
+            vec4(lean.x, height + lean.y, 0., 0.),
+            Vec4::Z,
+            feet.extend(0.).extend(1.),
+        );
+
+        let mut uv_offset = rect.min / image_size;
+        let mut uv_scale = rect.size() / image_size;
+        if shadow.flip_x {
+            uv_offset.x += uv_scale.x;
+            uv_scale.x = -uv_scale.x;
+        }
+        if shadow.flip_y {
+            uv_offset.y += uv_scale.y;
+            uv_scale.y = -uv_scale.y;
+        }
+
+        let color = shadow.shadow.color.to_linear();
+        let alpha = color.alpha * shadow.shadow.opacity * strength.min(1.);
+
+        let offset = bind_groups.uniforms.push(&UniformDropShadow {
+            world_from_local,
+            uv_offset_scale: vec4(uv_offset.x, uv_offset.y, uv_scale.x, uv_scale.y),
+            color: vec4(color.red, color.green, color.blue, alpha),
+            blur: shadow.shadow.blur / image_size,
+        });
+
+        bind_groups
+            .values
+            .insert(shadow.render_entity, (shadow.image, offset));
+    }
+
+    if bind_groups.values.is_empty() {
+        return;
+    }
+
+    bind_groups
+        .uniforms
+        .write_buffer(&render_device, &render_queue);
+
+    let Some(uniform_binding) = bind_groups.uniforms.binding() else {
+        return;
+    };
+
+    // shadows of the same image share a bind group, and only differ in their uniform offset
+    for (image, _) in bind_groups.values.values() {
+        if bind_groups.images.contains_key(image) {
+            continue;
+        }
+
+        let Some(gpu_image) = gpu_images.get(*image) else {
+            continue;
+        };
+
+        let bind_group = render_device.create_bind_group(
+            "drop_shadow_bind_group",
+            &pipeline_cache.get_bind_group_layout(&pipeline.layout),
+            &BindGroupEntries::sequential((
+                &gpu_image.texture_view,
+                &gpu_image.sampler,
+                uniform_binding.clone(),
+            )),
+        );
+
+        bind_groups.images.insert(*image, bind_group);
+    }
+}
+
+pub(crate) type DrawDropShadow = (
+    SetItemPipeline,
+    SetSpriteViewBindGroup<0>,
+    SetDropShadowBindGroup<1>,
+    DrawDropShadowQuad,
+);
+
+pub(crate) struct SetDropShadowBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetDropShadowBindGroup<I> {
+    type Param = SRes<DropShadowBindGroups>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &P,
+        _view: ROQueryItem<'w, '_, Self::ViewQuery>,
+        _entity: Option<()>,
+        bind_groups: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let bind_groups = bind_groups.into_inner();
+        let Some((bind_group, offset)) = bind_groups
+            .values
+            .get(&item.entity())
+            .and_then(|(image, offset)| Some((bind_groups.images.get(image)?, *offset)))
+        else {
+            return RenderCommandResult::Skip;
+        };
+
+        pass.set_bind_group(I, bind_group, &[offset]);
+        RenderCommandResult::Success
+    }
+}
+
+pub(crate) struct DrawDropShadowQuad;
+impl<P: PhaseItem> RenderCommand<P> for DrawDropShadowQuad {
+    type Param = ();
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        _view: ROQueryItem<'w, '_, Self::ViewQuery>,
+        _entity: Option<()>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        pass.draw(0..6, 0..1);
+        RenderCommandResult::Success
+    }
+}
@@ -0,0 +1,203 @@
+//! Module containing the GPU side of [`AutoExposure`](crate::prelude::AutoExposure).
+
+use bevy::{
+    prelude::*,
+    render::{
+        Render, RenderApp, RenderSystems,
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::CachedTexture,
+    },
+};
+
+use crate::{
+    data::{FireflyConfig, FireflyTestClock},
+    pipelines::AutoExposurePipeline,
+};
+
+/// Data of an [`AutoExposure`](crate::prelude::AutoExposure) that is sent to the GPU.
+#[derive(ShaderType, Clone, Copy, Default)]
+pub struct UniformAutoExposure {
+    pub ambient: Vec3,
+    /// Target average luminance, in stops.
+    pub target_luminance: f32,
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    /// Maximum change of the exposure this frame, in stops.
+    pub brighten_step: f32,
+    pub darken_step: f32,
+    pub filter_range: Vec2,
+}
+
+/// Buffers holding the auto exposure state of a view, kept across frames.
+#[derive(Component)]
+pub(crate) struct ViewAutoExposure {
+    histogram: Buffer,
+    exposure: Buffer,
+    settings: UniformBuffer<UniformAutoExposure>,
+}
+
+/// Exposure bound to the lightmap application pass of views without auto exposure. It's 0 stops, leaving the lightmap unchanged.
+#[derive(Resource)]
+pub(crate) struct NeutralExposureBuffer(Buffer);
+
+impl FromWorld for NeutralExposureBuffer {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        Self(device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("neutral exposure buffer"),
+            contents: bytemuck::bytes_of(&0f32),
+            usage: BufferUsages::STORAGE,
+        }))
+    }
+}
+
+/// Returns the binding of the view's exposure, read by the lightmap application pass.
+pub(crate) fn exposure_binding(world: &World, view_entity: Entity) -> BindingResource<'_> {
+    match world.get::<ViewAutoExposure>(view_entity) {
+        Some(view_exposure) => view_exposure.exposure.as_entire_binding(),
+        None => world
+            .resource::<NeutralExposureBuffer>()
+            .0
+            .as_entire_binding(),
+    }
+}
+
+/// Plugin that prepares the buffers used by [`AutoExposure`](crate::prelude::AutoExposure). Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct ExposurePlugin;
+impl Plugin for ExposurePlugin {
+    fn build(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.add_systems(
+            Render,
+            prepare_auto_exposure.in_set(RenderSystems::PrepareResources),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.init_resource::<NeutralExposureBuffer>();
+    }
+}
+
+fn prepare_auto_exposure(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    time: Res<Time>,
+    test_clock: Option<Res<FireflyTestClock>>,
+    mut views: Query<(Entity, &FireflyConfig, Option<&mut ViewAutoExposure>)>,
+) {
+    let delta = test_clock
+        .as_ref()
+        .map_or(time.delta_secs(), |clock| clock.delta.as_secs_f32());
+
+    for (entity, config, view_exposure) in &mut views {
+        let Some(auto_exposure) = config.auto_exposure else {
+            if view_exposure.is_some() {
+                commands.entity(entity).remove::<ViewAutoExposure>();
+            }
+            continue;
+        };
+
+        let ambient = config.ambient_color.to_linear().to_vec3() * config.ambient_brightness;
+        let settings = UniformAutoExposure {
+            ambient,
+            target_luminance: auto_exposure.target.max(0.001).log2(),
+            min_exposure: auto_exposure.min.max(0.001).log2(),
+            max_exposure: auto_exposure.max.max(auto_exposure.min).max(0.001).log2(),
+            brighten_step: auto_exposure.brighten_speed.max(0.) * delta,
+            darken_step: auto_exposure.darken_speed.max(0.) * delta,
+            filter_range: auto_exposure.filter,
+        };
+
+        match view_exposure {
+            Some(mut view_exposure) => {
+                view_exposure.settings.set(settings);
+                view_exposure
+                    .settings
+                    .write_buffer(&render_device, &render_queue);
+            }
+            None => {
+                let histogram = render_device.create_buffer(&BufferDescriptor {
+                    label: Some("auto exposure histogram buffer"),
+                    size: 64 * 4,
+                    usage: BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                });
+
+                // starts out at the neutral exposure
+                let exposure = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                    label: Some("auto exposure buffer"),
+                    contents: bytemuck::bytes_of(&0f32),
+                    usage: BufferUsages::STORAGE,
+                });
+
+                let mut uniform = UniformBuffer::from(settings);
+                uniform.write_buffer(&render_device, &render_queue);
+
+                commands.entity(entity).insert(ViewAutoExposure {
+                    histogram,
+                    exposure,
+                    settings: uniform,
+                });
+            }
+        }
+    }
+}
+
+/// Measures the lightmap and adapts the view's exposure. Called by the
+/// [`CreateLightmapNode`](crate::nodes::CreateLightmapNode) after the lightmap is drawn.
+pub(crate) fn compute_auto_exposure(
+    render_context: &mut RenderContext,
+    world: &World,
+    view_entity: Entity,
+    lightmap: &CachedTexture,
+) {
+    let Some(view_exposure) = world.get::<ViewAutoExposure>(view_entity) else {
+        return;
+    };
+
+    let pipeline_cache = world.resource::<PipelineCache>();
+    let pipeline = world.resource::<AutoExposurePipeline>();
+
+    let (Some(histogram_pipeline), Some(average_pipeline), Some(settings)) = (
+        pipeline_cache.get_compute_pipeline(pipeline.histogram_pipeline),
+        pipeline_cache.get_compute_pipeline(pipeline.average_pipeline),
+        view_exposure.settings.binding(),
+    ) else {
+        return;
+    };
+
+    let bind_group = render_context.render_device().create_bind_group(
+        "auto exposure bind group",
+        &pipeline_cache.get_bind_group_layout(&pipeline.layout),
+        &BindGroupEntries::sequential((
+            &lightmap.default_view,
+            settings,
+            view_exposure.histogram.as_entire_binding(),
+            view_exposure.exposure.as_entire_binding(),
+        )),
+    );
+
+    let size = lightmap.texture.size();
+
+    let mut pass = render_context
+        .command_encoder()
+        .begin_compute_pass(&ComputePassDescriptor {
+            label: Some("auto exposure pass"),
+            timestamp_writes: None,
+        });
+
+    pass.set_bind_group(0, &bind_group, &[]);
+    pass.set_pipeline(histogram_pipeline);
+    pass.dispatch_workgroups(size.width.div_ceil(16), size.height.div_ceil(16), 1);
+    pass.set_pipeline(average_pipeline);
+    pass.dispatch_workgroups(1, 1, 1);
+}
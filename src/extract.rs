@@ -1,17 +1,22 @@
 //! This module extracts data from the Main World to the Render World.
 
+use std::{
+    f32::consts::{PI, TAU},
+    sync::Arc,
+};
+
 use bevy::{
     camera::visibility::RenderLayers,
     platform::collections::HashSet,
     prelude::*,
     render::{
-        Extract, RenderApp,
-        batching::gpu_preprocessing::{GpuPreprocessingMode, GpuPreprocessingSupport},
-        extract_component::ExtractComponentPlugin,
-        render_phase::{ViewBinnedRenderPhases, ViewSortedRenderPhases},
-        sync_world::RenderEntity,
-        view::{NoIndirectDrawing, RetainedViewEntity},
+        Extract, RenderApp, extract_component::ExtractComponentPlugin,
+        extract_resource::ExtractResourcePlugin, render_phase::ViewSortedRenderPhases,
+        sync_world::RenderEntity, view::RetainedViewEntity,
     },
+};
+#[cfg(feature = "sprites")]
+use bevy::{
     sprite::Anchor,
     sprite_render::{
         ExtractedSlices, ExtractedSprite, ExtractedSpriteKind, ExtractedSprites, SpriteSystems,
@@ -23,18 +28,32 @@ use crate::{
     change::Changes,
     data::{
         CombineLightmapTo, CombinedLightmaps, ExtractedCombineLightmapTo,
-        ExtractedCombinedLightmaps, ExtractedWorldData, FireflyConfig,
+        ExtractedCombinedLightmaps, ExtractedWorldData, FireflyBinningBudget, FireflyConfig,
+        FireflyGlobalOverrides, FireflyTestClock,
     },
-    lights::{ExtractedPointLight, LightHeight, PointLight2d},
+    directional::ComputedDirectionalLight,
+    lights::{
+        ExtractedPointLight, ImportantLight, LightGroup, LightGroups, LightHeight, LightModulator,
+        LightSortKey, MovingLight, PointLight2d,
+    },
+    line::LineLightSegment,
+    occluder_batch::{ExtractedOccluderBatch, OccluderBatch},
+    occluder_group::{ExtractedOccluderGroup, OccluderGroup},
     occluders::ExtractedOccluder,
-    phases::SpritePhase,
     prelude::Occluder2d,
+    stencil::StencilIds,
+    visibility::{NotVisible, OccluderAabb, VisibilityTimer},
+};
+#[cfg(feature = "sprites")]
+use crate::{
+    phases::SpritePhase,
     sprite::FireflySprite,
     sprites::{
-        ExtractedFireflySprite, ExtractedFireflySpriteKind, ExtractedFireflySprites, NormalMap,
-        SpriteAssetEvents, SpriteHeight,
+        AssistOutline, Decal2d, DetailNormalMap, EmissiveThreshold, ExtractedDetailNormalMap,
+        ExtractedFireflySprite, ExtractedFireflySpriteKind, ExtractedFireflySprites, MinimumLight,
+        NormalMap, SpriteAssetEvents, SpriteHeight,
     },
-    visibility::{NotVisible, OccluderAabb, VisibilityTimer},
+    stencil::StencilId,
 };
 
 /// Plugin that handles extracting data from the Main World to the Render World. Automatically
@@ -44,56 +63,68 @@ impl Plugin for ExtractPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(ExtractComponentPlugin::<FireflyConfig>::default());
 
+        app.init_resource::<FireflyGlobalOverrides>();
+        app.add_plugins(ExtractResourcePlugin::<FireflyGlobalOverrides>::default());
+        app.register_type::<FireflyTestClock>();
+        app.add_plugins(ExtractResourcePlugin::<FireflyTestClock>::default());
+        app.register_type::<FireflyBinningBudget>();
+        app.add_plugins(ExtractResourcePlugin::<FireflyBinningBudget>::default());
+
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
         };
+        #[cfg(feature = "sprites")]
         render_app.add_systems(
             ExtractSchedule,
             (
-                extract_camera_phases,
                 extract_sprites.after(SpriteSystems::ExtractSprites),
                 extract_sprite_events,
+            ),
+        );
+
+        render_app.add_systems(
+            ExtractSchedule,
+            (
+                extract_camera_phases,
                 extract_world_data,
                 extract_lights,
                 extract_occluders,
+                extract_occluder_batches,
+                extract_occluder_groups,
             ),
         );
     }
 }
 
 fn extract_camera_phases(
-    mut sprite_phases: ResMut<ViewSortedRenderPhases<SpritePhase>>,
-    mut lightmap_phases: ResMut<ViewBinnedRenderPhases<LightmapPhase>>,
-    cameras: Extract<Query<(Entity, &Camera, Has<NoIndirectDrawing>), With<Camera2d>>>,
+    #[cfg(feature = "sprites")] mut sprite_phases: ResMut<ViewSortedRenderPhases<SpritePhase>>,
+    mut lightmap_phases: ResMut<ViewSortedRenderPhases<LightmapPhase>>,
+    cameras: Extract<Query<(Entity, &Camera), With<Camera2d>>>,
     mut live_entities: Local<HashSet<RetainedViewEntity>>,
-    gpu_preprocessing_support: Res<GpuPreprocessingSupport>,
 ) {
     live_entities.clear();
-    for (main_entity, camera, no_indirect_drawing) in &cameras {
+    for (main_entity, camera) in &cameras {
         if !camera.is_active {
             continue;
         }
         // This is the main camera, so we use the first subview index (0)
         let retained_view_entity = RetainedViewEntity::new(main_entity.into(), None, 0);
 
+        #[cfg(feature = "sprites")]
         sprite_phases.insert_or_clear(retained_view_entity);
 
-        let gpu_preprocessing_mode = gpu_preprocessing_support.min(if !no_indirect_drawing {
-            GpuPreprocessingMode::Culling
-        } else {
-            GpuPreprocessingMode::PreprocessingOnly
-        });
-
-        lightmap_phases.prepare_for_new_frame(retained_view_entity, gpu_preprocessing_mode);
+        lightmap_phases.insert_or_clear(retained_view_entity);
 
         live_entities.insert(retained_view_entity);
     }
 
     // Clear out all dead views.
+    #[cfg(feature = "sprites")]
     sprite_phases.retain(|camera_entity, _| live_entities.contains(camera_entity));
     lightmap_phases.retain(|camera_entity, _| live_entities.contains(camera_entity));
 }
 
+#[cfg(feature = "sprites")]
 fn extract_sprite_events(
     mut events: ResMut<SpriteAssetEvents>,
     mut image_events: Extract<MessageReader<AssetEvent<Image>>>,
@@ -106,6 +137,7 @@ fn extract_sprite_events(
     }
 }
 
+#[cfg(feature = "sprites")]
 fn extract_sprites(
     mut extracted_firefly_sprites: ResMut<ExtractedFireflySprites>,
     mut extracted_sprites: ResMut<ExtractedSprites>,
@@ -120,12 +152,93 @@ fn extract_sprites(
             &Anchor,
             Option<&SpriteHeight>,
             Option<&NormalMap>,
+            Option<&DetailNormalMap>,
+            Has<Decal2d>,
+            Has<AssistOutline>,
+            Option<&StencilId>,
+            Option<&MinimumLight>,
+            Option<&EmissiveThreshold>,
             &GlobalTransform,
             Option<&super::utils::ComputedTextureSlices>,
         )>,
     >,
+    plain_sprite_query: Extract<
+        Query<
+            (
+                Option<&SpriteHeight>,
+                Option<&NormalMap>,
+                Option<&DetailNormalMap>,
+                Has<Decal2d>,
+                Has<AssistOutline>,
+                Option<&StencilId>,
+                Option<&MinimumLight>,
+                Option<&EmissiveThreshold>,
+            ),
+            (With<Sprite>, Without<FireflySprite>),
+        >,
+    >,
 ) {
     extracted_firefly_sprites.sprites.clear();
+
+    // Plain Bevy sprites have already been extracted (and visibility-checked) by Bevy at this point,
+    // so we reuse that data instead of recomputing it.
+    for sprite in &extracted_sprites.sprites {
+        let Ok((
+            height,
+            normal_map,
+            detail_normal_map,
+            decal,
+            outline,
+            stencil_id,
+            min_light,
+            emissive,
+        )) = plain_sprite_query.get(sprite.main_entity)
+        else {
+            continue;
+        };
+
+        let kind = match &sprite.kind {
+            ExtractedSpriteKind::Single {
+                anchor,
+                rect,
+                scaling_mode,
+                custom_size,
+            } => ExtractedFireflySpriteKind::Single {
+                anchor: *anchor,
+                rect: *rect,
+                scaling_mode: *scaling_mode,
+                custom_size: *custom_size,
+            },
+            ExtractedSpriteKind::Slices { indices } => ExtractedFireflySpriteKind::Slices {
+                indices: indices.clone(),
+            },
+        };
+
+        extracted_firefly_sprites
+            .sprites
+            .push(ExtractedFireflySprite {
+                main_entity: sprite.main_entity,
+                render_entity: sprite.render_entity,
+                transform: sprite.transform,
+                flip_x: sprite.flip_x,
+                flip_y: sprite.flip_y,
+                image_handle_id: sprite.image_handle_id,
+                normal_handle_id: normal_map.map(|x| x.handle().id()),
+                detail: detail_normal_map.map(|x| ExtractedDetailNormalMap {
+                    image_handle_id: x.handle().id(),
+                    strength: x.strength,
+                    tiling: x.tiling,
+                }),
+                decal,
+                outline,
+                stencil_id: stencil_id.map_or(0, |id| id.get()),
+                min_light: min_light.map_or(0., |min_light| min_light.0.clamp(0., 1.)),
+                emissive: emissive.copied(),
+                kind,
+                height: height.map_or(0., |h| h.0),
+            });
+    }
+
     for (
         main_entity,
         render_entity,
@@ -134,6 +247,12 @@ fn extract_sprites(
         anchor,
         height,
         normal_map,
+        detail_normal_map,
+        decal,
+        outline,
+        stencil_id,
+        min_light,
+        emissive,
         transform,
         slices,
     ) in sprite_query.iter()
@@ -143,6 +262,13 @@ fn extract_sprites(
         }
 
         let height = height.map_or(0., |h| h.0);
+        let stencil_id = stencil_id.map_or(0, |id| id.get());
+        let min_light = min_light.map_or(0., |min_light| min_light.0.clamp(0., 1.));
+        let detail = detail_normal_map.map(|x| ExtractedDetailNormalMap {
+            image_handle_id: x.handle().id(),
+            strength: x.strength,
+            tiling: x.tiling,
+        });
 
         if let Some(slices) = slices {
             let start = extracted_slices.slices.len();
@@ -161,6 +287,12 @@ fn extract_sprites(
                     flip_y: sprite.flip_y,
                     image_handle_id: sprite.image.id(),
                     normal_handle_id: normal_map.map(|x| x.handle().id()),
+                    detail,
+                    decal,
+                    outline,
+                    stencil_id,
+                    min_light,
+                    emissive: emissive.copied(),
                     kind: ExtractedFireflySpriteKind::Slices {
                         indices: start..end,
                     },
@@ -179,20 +311,7 @@ fn extract_sprites(
                 },
             })
         } else {
-            let atlas_rect = sprite
-                .texture_atlas
-                .as_ref()
-                .and_then(|s| s.texture_rect(&texture_atlases).map(|r| r.as_rect()));
-            let rect = match (atlas_rect, sprite.rect) {
-                (None, None) => None,
-                (None, Some(sprite_rect)) => Some(sprite_rect),
-                (Some(atlas_rect), None) => Some(atlas_rect),
-                (Some(atlas_rect), Some(mut sprite_rect)) => {
-                    sprite_rect.min += atlas_rect.min;
-                    sprite_rect.max += atlas_rect.min;
-                    Some(sprite_rect)
-                }
-            };
+            let rect = sprite_rect(sprite, &texture_atlases);
 
             // PERF: we don't check in this function that the `Image` asset is ready, since it should be in most cases and hashing the handle is expensive
             extracted_firefly_sprites
@@ -205,6 +324,12 @@ fn extract_sprites(
                     flip_y: sprite.flip_y,
                     image_handle_id: sprite.image.id(),
                     normal_handle_id: normal_map.map(|x| x.handle().id()),
+                    detail,
+                    decal,
+                    outline,
+                    stencil_id,
+                    min_light,
+                    emissive: emissive.copied(),
                     kind: ExtractedFireflySpriteKind::Single {
                         anchor: anchor.as_vec(),
                         rect,
@@ -234,6 +359,28 @@ fn extract_sprites(
     }
 }
 
+/// Region of the sprite's image that is rendered, combining its texture atlas and rect.
+#[cfg(feature = "sprites")]
+pub(crate) fn sprite_rect(
+    sprite: &FireflySprite,
+    texture_atlases: &Assets<TextureAtlasLayout>,
+) -> Option<Rect> {
+    let atlas_rect = sprite
+        .texture_atlas
+        .as_ref()
+        .and_then(|s| s.texture_rect(texture_atlases).map(|r| r.as_rect()));
+    match (atlas_rect, sprite.rect) {
+        (None, None) => None,
+        (None, Some(sprite_rect)) => Some(sprite_rect),
+        (Some(atlas_rect), None) => Some(atlas_rect),
+        (Some(atlas_rect), Some(mut sprite_rect)) => {
+            sprite_rect.min += atlas_rect.min;
+            sprite_rect.max += atlas_rect.min;
+            Some(sprite_rect)
+        }
+    }
+}
+
 fn extract_world_data(
     mut commands: Commands,
     cameras: Extract<Query<(&RenderEntity, &Camera), With<CombineLightmapTo>>>,
@@ -274,21 +421,45 @@ fn extract_world_data(
 
 fn extract_lights(
     mut commands: Commands,
+    previous_lights: Query<&ExtractedPointLight>,
+    groups: Extract<Res<LightGroups>>,
     lights: Extract<
         Query<(
             RenderEntity,
             &GlobalTransform,
             &PointLight2d,
             &LightHeight,
+            Option<&MovingLight>,
+            Option<&LightSortKey>,
+            Option<&LightModulator>,
+            Has<ImportantLight>,
+            Option<&LightGroup>,
             &ViewVisibility,
             &VisibilityTimer,
             &Changes,
             &RenderLayers,
+            Option<&ComputedDirectionalLight>,
+            Option<&LineLightSegment>,
         )>,
     >,
 ) {
-    for (entity, transform, light, height, visibility, visibility_timer, changes, render_layers) in
-        &lights
+    for (
+        entity,
+        transform,
+        light,
+        height,
+        moving,
+        sort_key,
+        modulator,
+        important,
+        group,
+        visibility,
+        visibility_timer,
+        changes,
+        render_layers,
+        directional,
+        segment,
+    ) in &lights
     {
         if !visibility.get() {
             if visibility_timer.0.just_finished() {
@@ -297,28 +468,74 @@ fn extract_lights(
             continue;
         }
 
+        let group = group.map(|group| groups.resolve(&group.0));
+
+        let intensity = modulator.map_or(light.intensity, |modulator| {
+            modulator.modulate_intensity(light.intensity)
+        });
+
+        let radius = modulator.map_or(light.radius, |modulator| {
+            modulator.modulate_range(light.radius)
+        });
+
         let pos = transform.translation().truncate() /*+ vec2(0.0, height.0)*/ + light.offset.xy();
-        commands.entity(entity).insert(ExtractedPointLight {
+        let mut extracted = ExtractedPointLight {
             pos,
-            color: light.color,
-            intensity: light.intensity,
-            radius: light.radius,
+            color: group.as_ref().map_or(light.color, |group| {
+                LinearRgba::from_vec4(
+                    light.color.to_linear().to_vec4() * group.color.to_linear().to_vec4(),
+                )
+                .into()
+            }),
+            intensity: group
+                .as_ref()
+                .map_or(intensity, |group| intensity * group.intensity),
+            radius,
             z: transform.translation().z + light.offset.z,
             core: light.core,
             falloff: light.falloff,
             angle: light.angle,
             cast_shadows: light.cast_shadows,
+            shadow_strength: light.shadow_strength,
             dir: (transform.rotation() * Vec3::Y).xy(),
-            height: height.0,
-            changes: changes.clone(),
+            height: height.height(transform.translation().z + light.offset.z),
+            color_ramp: light.color_ramp.as_ref().map(|x| x.id()),
+            sprite_occlusion: light.sprite_occlusion,
+            affect_normals: light.affect_normals,
+            pitch: light.pitch,
+            angular_mask: light.angular_mask,
+            glow: light.glow,
+            culling_margin: light.culling_margin.unwrap_or(radius).max(0.),
+            z_range: light.z_range,
+            bin_sector: directional.map_or(vec2(-PI, TAU), |directional| directional.sector(pos)),
+            streak: segment.map_or(
+                moving.map_or(Vec2::ZERO, |moving| moving.streak()),
+                |segment| segment.vector,
+            ),
+            line: segment.map(|segment| segment.end_cap),
+            sort_key: sort_key.copied().unwrap_or_default(),
+            important,
+            directional: directional.is_some(),
+            changes: *changes,
             render_layers: render_layers.clone(),
-        });
+        };
+
+        // only what differs from the previous extraction is flagged, newly extracted lights change entirely
+        extracted.changes = previous_lights
+            .get(entity)
+            .map_or(Changes::ALL, |previous| {
+                extracted.changes.union(extracted.changes_since(previous))
+            });
+
+        commands.entity(entity).insert(extracted);
     }
 }
 
 fn extract_occluders(
     mut commands: Commands,
     mut previous_len: Local<usize>,
+    previous_occluders: Query<&ExtractedOccluder>,
+    stencil_ids: Extract<Res<StencilIds>>,
     occluders: Extract<
         Query<(
             RenderEntity,
@@ -329,8 +546,10 @@ fn extract_occluders(
             &VisibilityTimer,
             &Changes,
             &RenderLayers,
+            Option<&ChildOf>,
         )>,
     >,
+    groups: Extract<Query<RenderEntity, With<OccluderGroup>>>,
 ) {
     let mut values = Vec::with_capacity(*previous_len);
 
@@ -343,6 +562,7 @@ fn extract_occluders(
         visibility_timer,
         changes,
         render_layers,
+        child_of,
     ) in &occluders
     {
         if !visibility.get() {
@@ -354,22 +574,119 @@ fn extract_occluders(
 
         let pos = global_transform.translation().truncate() + occluder.offset.xy();
 
-        let extracted_occluder = ExtractedOccluder {
+        let mut extracted_occluder = ExtractedOccluder {
             pos,
             rot: global_transform.rotation().to_euler(EulerRot::XYZ).2,
             shape: occluder.shape().clone(),
             aabb: aabb.0,
             z: global_transform.translation().z + occluder.offset.z,
+            y: pos.y,
             color: occluder.color,
             opacity: occluder.opacity,
             z_sorting: occluder.z_sorting,
-            changes: changes.clone(),
+            shadow_falloff: occluder.shadow_falloff,
+            distance_opacity: occluder.distance_opacity,
+            dilation: occluder.dilation,
+            receivers: occluder
+                .receivers
+                .as_ref()
+                .map(|receivers| receivers.extract(&stencil_ids)),
+            one_way: occluder
+                .one_way
+                .map(|dir| (global_transform.rotation() * dir.extend(0.)).truncate()),
+            group: child_of.and_then(|child_of| groups.get(child_of.parent()).ok()),
+            changes: *changes,
             render_layers: render_layers.clone(),
         };
 
+        // only what differs from the previous extraction is flagged, newly extracted occluders change entirely
+        extracted_occluder.changes =
+            previous_occluders
+                .get(entity)
+                .map_or(Changes::ALL, |previous| {
+                    extracted_occluder
+                        .changes
+                        .union(extracted_occluder.changes_since(previous))
+                });
+
         values.push((entity, extracted_occluder));
     }
 
     *previous_len = values.len();
     commands.try_insert_batch(values);
 }
+
+fn extract_occluder_groups(
+    mut commands: Commands,
+    groups: Extract<Query<(RenderEntity, &GlobalTransform), With<OccluderGroup>>>,
+) {
+    for (entity, transform) in &groups {
+        commands.entity(entity).insert(ExtractedOccluderGroup {
+            translation: transform.translation().truncate(),
+        });
+    }
+}
+
+fn extract_occluder_batches(
+    mut commands: Commands,
+    mut extracted_batches: Query<&mut ExtractedOccluderBatch>,
+    stencil_ids: Extract<Res<StencilIds>>,
+    batches: Extract<
+        Query<(
+            RenderEntity,
+            &OccluderBatch,
+            &GlobalTransform,
+            &ViewVisibility,
+            &VisibilityTimer,
+            &Changes,
+            &RenderLayers,
+        )>,
+    >,
+) {
+    for (entity, batch, transform, visibility, visibility_timer, changes, render_layers) in &batches
+    {
+        if !visibility.get() {
+            if visibility_timer.0.just_finished() {
+                commands.entity(entity).insert(NotVisible);
+            }
+            continue;
+        }
+
+        // the batch's occluders are only moved to world space when it changes
+        if let Ok(mut extracted) = extracted_batches.get_mut(entity) {
+            // receivers are resolved to stencil IDs, which sprites get when they're spawned
+            let receivers_changed = stencil_ids.is_changed()
+                && extracted
+                    .occluders
+                    .iter()
+                    .any(|occluder| occluder.receivers.is_some());
+
+            if !changes.any() && extracted.render_layers == *render_layers && !receivers_changed {
+                extracted.changes = default();
+                extracted.reallocate = false;
+                extracted
+                    .occluders
+                    .iter_mut()
+                    .for_each(|occluder| occluder.changes = default());
+                continue;
+            }
+
+            let reallocate = !Arc::ptr_eq(&extracted.data, batch.data());
+            *extracted = ExtractedOccluderBatch::new(
+                batch.data().clone(),
+                transform,
+                render_layers.clone(),
+                &stencil_ids,
+            );
+            extracted.reallocate = reallocate;
+            continue;
+        }
+
+        commands.entity(entity).insert(ExtractedOccluderBatch::new(
+            batch.data().clone(),
+            transform,
+            render_layers.clone(),
+            &stencil_ids,
+        ));
+    }
+}
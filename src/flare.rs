@@ -0,0 +1,442 @@
+//! Module containing [`LensFlare`], starburst and halo sprites drawn over visible light sources.
+
+use std::ops::Range;
+
+use bevy::{
+    ecs::entity::EntityHashMap,
+    prelude::*,
+    render::{
+        Extract, ExtractSchedule, Render, RenderApp, RenderSystems,
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        sync_world::MainEntity,
+        view::{ExtractedView, Msaa},
+    },
+    transform::TransformSystems,
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    data::{FireflyConfig, FireflyTestClock},
+    lights::{LightGroup, LightGroups, PointLight2d},
+    pipelines::{LensFlarePipeline, LightPipelineKey},
+    raycast::OccluderRaycast,
+};
+
+/// Component that draws a lens flare over a [`PointLight2d`], while the light's source is in view.
+///
+/// The flare is a soft halo and a starburst of streaks, drawn additively over the lit scene, so it's never darkened
+/// by the lightmap. Its size is in pixels, so it stays the same as the camera zooms, like the flare of a real lens.
+///
+/// The source is visible if no [`Occluder2d`](crate::prelude::Occluder2d) is between it and the camera's position,
+/// tested with the same shapes that cast shadows. It's sampled over the [source radius](LensFlare::source_radius),
+/// so the flare fades out as the source is partially blocked.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     PointLight2d::default(),
+///     LensFlare::default().with_streaks(6, 128.),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LensFlare {
+    /// Color of the flare, multiplied with the light's color.
+    ///
+    /// **Default:** White.
+    pub tint: Color,
+
+    /// Brightness of the flare, multiplied with the light's intensity.
+    ///
+    /// **Default:** 1.
+    pub intensity: f32,
+
+    /// Radius of the halo, in pixels. 0 disables the halo.
+    ///
+    /// **Default:** 48.
+    pub halo_radius: f32,
+
+    /// Number of streaks of the starburst. 0 disables the starburst.
+    ///
+    /// **Default:** 4.
+    pub streaks: u32,
+
+    /// Length of each streak, from the center of the flare, in pixels.
+    ///
+    /// **Default:** 96.
+    pub streak_length: f32,
+
+    /// Width of each streak, in pixels.
+    ///
+    /// **Default:** 2.
+    pub streak_width: f32,
+
+    /// Rotation of the starburst, in degrees.
+    ///
+    /// **Default:** 45.
+    pub streak_rotation: f32,
+
+    /// Radius around the light's position that is tested for occluders, in world units.
+    /// 0 only tests the light's position, so the flare pops in and out instead of fading.
+    ///
+    /// **Performance Impact:** Minor, a few rays are cast towards the source each frame.
+    ///
+    /// **Default:** 4.
+    pub source_radius: f32,
+
+    /// Speed at which the flare fades in and out, in full fades per second.
+    ///
+    /// **Default:** 8.
+    pub fade_speed: f32,
+
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    visibility: EntityHashMap<f32>,
+}
+
+impl Default for LensFlare {
+    fn default() -> Self {
+        Self {
+            tint: Color::WHITE,
+            intensity: 1.,
+            halo_radius: 48.,
+            streaks: 4,
+            streak_length: 96.,
+            streak_width: 2.,
+            streak_rotation: 45.,
+            source_radius: 4.,
+            fade_speed: 8.,
+            visibility: default(),
+        }
+    }
+}
+
+impl LensFlare {
+    /// Returns the flare with the given [halo radius](LensFlare::halo_radius).
+    pub fn with_halo(self, halo_radius: f32) -> Self {
+        Self {
+            halo_radius,
+            ..self
+        }
+    }
+
+    /// Returns the flare with the given number of [streaks](LensFlare::streaks) and [streak length](LensFlare::streak_length).
+    pub fn with_streaks(self, streaks: u32, streak_length: f32) -> Self {
+        Self {
+            streaks,
+            streak_length,
+            ..self
+        }
+    }
+
+    /// Returns the flare with the given [tint](LensFlare::tint).
+    pub fn with_tint(self, tint: Color) -> Self {
+        Self { tint, ..self }
+    }
+
+    /// Returns how visible the light's source currently is from the camera, from 0 (fully blocked) to 1.
+    pub fn visibility(&self, camera: Entity) -> f32 {
+        self.visibility.get(&camera).copied().unwrap_or(0.)
+    }
+}
+
+/// Data of a [`LensFlare`] that is sent to the GPU.
+#[repr(C)]
+#[derive(ShaderType, Clone, Copy, Pod, Zeroable, Default)]
+pub struct UniformLensFlare {
+    /// Center of the flare, in NDC.
+    pub center: Vec2,
+    /// Size of a pixel, in NDC.
+    pub pixel_size: Vec2,
+    pub color: Vec4,
+    pub halo_radius: f32,
+    pub streak_length: f32,
+    pub streak_width: f32,
+    pub streak_rotation: f32,
+    pub streaks: u32,
+    pub _pad: [u32; 3],
+}
+
+pub(crate) struct ExtractedLensFlare {
+    /// Main World camera the flare is visible from.
+    pub camera: Entity,
+    pub pos: Vec2,
+    pub color: LinearRgba,
+    pub flare: LensFlare,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedLensFlares {
+    pub flares: Vec<ExtractedLensFlare>,
+}
+
+/// Buffer with the flares of every view, read by the lens flare pass.
+#[derive(Resource)]
+pub(crate) struct LensFlareBuffer {
+    flares: RawBufferVec<UniformLensFlare>,
+    bind_group: Option<BindGroup>,
+}
+
+impl Default for LensFlareBuffer {
+    fn default() -> Self {
+        let mut flares = RawBufferVec::<UniformLensFlare>::new(BufferUsages::STORAGE);
+        flares.set_label("lens flare buffer".into());
+
+        Self {
+            flares,
+            bind_group: None,
+        }
+    }
+}
+
+/// Flares drawn by a view, as a range of the [`LensFlareBuffer`].
+#[derive(Component)]
+pub(crate) struct ViewLensFlares {
+    pipeline: CachedRenderPipelineId,
+    range: Range<u32>,
+}
+
+/// Plugin that updates, extracts and renders [`LensFlare`]s. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct FlarePlugin;
+impl Plugin for FlarePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LensFlare>();
+        app.add_systems(
+            PostUpdate,
+            update_lens_flares.after(TransformSystems::Propagate),
+        );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<ExtractedLensFlares>()
+            .init_resource::<LensFlareBuffer>()
+            .add_systems(ExtractSchedule, extract_lens_flares)
+            .add_systems(
+                Render,
+                (
+                    prepare_lens_flares.in_set(RenderSystems::PrepareResources),
+                    prepare_lens_flare_bind_group.in_set(RenderSystems::PrepareBindGroups),
+                ),
+            );
+    }
+}
+
+// number of points tested around the source, on top of its center
+const SOURCE_SAMPLES: usize = 8;
+
+fn update_lens_flares(
+    mut flares: Query<(&mut LensFlare, &PointLight2d, &GlobalTransform)>,
+    cameras: Query<(Entity, &GlobalTransform), With<FireflyConfig>>,
+    raycast: OccluderRaycast,
+    time: Res<Time>,
+    test_clock: Option<Res<FireflyTestClock>>,
+) {
+    let delta = test_clock
+        .as_ref()
+        .map_or(time.delta_secs(), |clock| clock.delta.as_secs_f32());
+
+    for (mut flare, light, transform) in &mut flares {
+        let pos = transform.translation().truncate() + light.offset.truncate();
+        let flare = flare.bypass_change_detection();
+
+        flare
+            .visibility
+            .retain(|camera, _| cameras.contains(*camera));
+
+        for (camera, camera_transform) in &cameras {
+            let eye = camera_transform.translation().truncate();
+
+            let mut visible = raycast.has_line_of_sight(eye, pos) as u32;
+            let mut samples = 1;
+            if flare.source_radius > 0. {
+                for i in 0..SOURCE_SAMPLES {
+                    let angle = i as f32 / SOURCE_SAMPLES as f32 * std::f32::consts::TAU;
+                    let point = pos + Vec2::from_angle(angle) * flare.source_radius;
+                    visible += raycast.has_line_of_sight(eye, point) as u32;
+                }
+                samples += SOURCE_SAMPLES as u32;
+            }
+
+            let target = visible as f32 / samples as f32;
+            let step = flare.fade_speed.max(0.) * delta;
+
+            let visibility = flare.visibility.entry(camera).or_insert(target);
+            *visibility += (target - *visibility).clamp(-step, step);
+        }
+    }
+}
+
+fn extract_lens_flares(
+    mut extracted: ResMut<ExtractedLensFlares>,
+    flares: Extract<
+        Query<(
+            &LensFlare,
+            &PointLight2d,
+            &GlobalTransform,
+            Option<&LightGroup>,
+        )>,
+    >,
+    groups: Extract<Res<LightGroups>>,
+) {
+    extracted.flares.clear();
+
+    for (flare, light, transform, group) in &flares {
+        let group = group.map(|group| groups.resolve(&group.0));
+        if group.as_ref().is_some_and(|group| !group.enabled) {
+            continue;
+        }
+
+        let mut color = light.color.to_linear().to_vec4() * flare.tint.to_linear().to_vec4();
+        let mut intensity = light.intensity * flare.intensity;
+        if let Some(group) = group {
+            color *= group.color.to_linear().to_vec4();
+            intensity *= group.intensity;
+        }
+
+        for (camera, visibility) in &flare.visibility {
+            if *visibility <= 0.001 {
+                continue;
+            }
+
+            extracted.flares.push(ExtractedLensFlare {
+                camera: *camera,
+                pos: transform.translation().truncate() + light.offset.truncate(),
+                color: LinearRgba::from_vec4(color * intensity * visibility),
+                flare: flare.clone(),
+            });
+        }
+    }
+}
+
+fn prepare_lens_flares(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    pipeline: Res<LensFlarePipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<LensFlarePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    extracted: Res<ExtractedLensFlares>,
+    mut buffer: ResMut<LensFlareBuffer>,
+    views: Query<(Entity, &MainEntity, &ExtractedView, &Msaa), With<FireflyConfig>>,
+) {
+    buffer.flares.clear();
+
+    for (entity, main_entity, view, msaa) in &views {
+        let start = buffer.flares.len() as u32;
+
+        let clip_from_world = view
+            .clip_from_world
+            .unwrap_or_else(|| view.clip_from_view * view.world_from_view.to_matrix().inverse());
+        let pixel_size = 2. / view.viewport.zw().as_vec2().max(Vec2::ONE);
+
+        for extracted in &extracted.flares {
+            if extracted.camera != main_entity.id() {
+                continue;
+            }
+
+            let flare = &extracted.flare;
+            let clip = clip_from_world * extracted.pos.extend(0.).extend(1.);
+            let center = clip.xy() / clip.w;
+
+            // flares are only drawn while their source is on screen
+            if center.abs().max_element() > 1. {
+                continue;
+            }
+
+            buffer.flares.push(UniformLensFlare {
+                center,
+                pixel_size,
+                color: extracted.color.to_vec4(),
+                halo_radius: flare.halo_radius.max(0.),
+                streak_length: match flare.streaks {
+                    0 => 0.,
+                    _ => flare.streak_length.max(0.),
+                },
+                streak_width: flare.streak_width.max(0.),
+                streak_rotation: flare.streak_rotation.to_radians(),
+                streaks: flare.streaks,
+                _pad: default(),
+            });
+        }
+
+        let end = buffer.flares.len() as u32;
+        if start == end {
+            commands.entity(entity).remove::<ViewLensFlares>();
+            continue;
+        }
+
+        let key = LightPipelineKey::from_hdr(view.hdr)
+            | LightPipelineKey::from_msaa_samples(msaa.samples());
+
+        commands.entity(entity).insert(ViewLensFlares {
+            pipeline: pipelines.specialize(&pipeline_cache, &pipeline, key),
+            range: start..end,
+        });
+    }
+
+    if !buffer.flares.is_empty() {
+        buffer.flares.write_buffer(&render_device, &render_queue);
+    }
+}
+
+fn prepare_lens_flare_bind_group(
+    render_device: Res<RenderDevice>,
+    pipeline: Res<LensFlarePipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    mut buffer: ResMut<LensFlareBuffer>,
+) {
+    buffer.bind_group = match buffer.flares.binding() {
+        Some(binding) if !buffer.flares.is_empty() => Some(render_device.create_bind_group(
+            "lens flare bind group",
+            &pipeline_cache.get_bind_group_layout(&pipeline.layout),
+            &BindGroupEntries::single(binding),
+        )),
+        _ => None,
+    };
+}
+
+/// Draws the view's lens flares over the lit view. Called by the
+/// [`ApplyLightmapNode`](crate::nodes::ApplyLightmapNode) after the lightmap is applied.
+pub(crate) fn render_lens_flares<'w>(
+    render_context: &mut RenderContext<'w>,
+    world: &'w World,
+    view_entity: Entity,
+    target: &TextureView,
+) {
+    let (Some(view_flares), Some(bind_group)) = (
+        world.get::<ViewLensFlares>(view_entity),
+        world.resource::<LensFlareBuffer>().bind_group.as_ref(),
+    ) else {
+        return;
+    };
+
+    let Some(pipeline) = world
+        .resource::<PipelineCache>()
+        .get_render_pipeline(view_flares.pipeline)
+    else {
+        return;
+    };
+
+    let mut pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+        label: Some("lens flare pass"),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: default(),
+            depth_slice: None,
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    pass.set_render_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..6, view_flares.range.clone());
+}
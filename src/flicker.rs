@@ -0,0 +1,226 @@
+//! Module containing [`AmbientFlicker`], for flickering the ambient light of a whole scene.
+
+use std::f32::consts::PI;
+
+use bevy::{
+    prelude::*,
+    render::{Extract, ExtractSchedule, RenderApp, sync_world::RenderEntity},
+};
+
+use crate::data::{FireflyConfig, FireflyTestClock};
+
+/// Camera component that flickers the [ambient brightness](FireflyConfig::ambient_brightness) with noise, such as
+/// the lights of a failing generator or a horror scene.
+///
+/// The flicker is computed in the main world, so its current [brightness](AmbientFlicker::brightness) can be read
+/// by gameplay systems, and an [`AmbientFlickerEvent`] is triggered on the camera every time the light dips,
+/// e.g. to play a buzzing sound in sync with it.
+///
+/// # Example
+///
+/// ```
+/// commands
+///     .spawn((
+///         Camera2d,
+///         FireflyConfig {
+///             ambient_brightness: 0.6,
+///             ..default()
+///         },
+///         AmbientFlicker::new(0.8, 6.).with_noise(FlickerNoise::Stepped),
+///     ))
+///     .observe(|_: On<AmbientFlickerEvent>| {
+///         info!("bzzt");
+///     });
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[require(FireflyConfig)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AmbientFlicker {
+    /// How much the ambient light is dimmed at the lowest point of the flicker. 0 disables the flicker,
+    /// 1 makes it go completely dark.
+    ///
+    /// **Default:** 0.5.
+    pub amplitude: f32,
+
+    /// How many times per second the flicker changes.
+    ///
+    /// **Default:** 8.
+    pub frequency: f32,
+
+    /// The kind of noise used for the flicker.
+    ///
+    /// **Default:** [Smooth](FlickerNoise::Smooth).
+    pub noise: FlickerNoise,
+
+    /// How deep the flicker has to dip, from 0 to 1, for an [`AmbientFlickerEvent`] to be triggered.
+    ///
+    /// **Default:** 0.7.
+    pub event_threshold: f32,
+
+    /// Seed of the noise, so that multiple cameras don't flicker in unison.
+    ///
+    /// **Default:** 0.
+    pub seed: u32,
+
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    elapsed: f32,
+
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    depth: f32,
+}
+
+impl Default for AmbientFlicker {
+    fn default() -> Self {
+        Self {
+            amplitude: 0.5,
+            frequency: 8.,
+            noise: FlickerNoise::Smooth,
+            event_threshold: 0.7,
+            seed: 0,
+            elapsed: 0.,
+            depth: 0.,
+        }
+    }
+}
+
+impl AmbientFlicker {
+    /// Construct a new flicker with the given amplitude and frequency.
+    pub fn new(amplitude: f32, frequency: f32) -> Self {
+        Self {
+            amplitude,
+            frequency,
+            ..default()
+        }
+    }
+
+    /// Returns the flicker with the given [noise](AmbientFlicker::noise).
+    pub fn with_noise(self, noise: FlickerNoise) -> Self {
+        Self { noise, ..self }
+    }
+
+    /// Returns the flicker with the given [event threshold](AmbientFlicker::event_threshold).
+    pub fn with_event_threshold(self, event_threshold: f32) -> Self {
+        Self {
+            event_threshold,
+            ..self
+        }
+    }
+
+    /// Returns the flicker with the given [seed](AmbientFlicker::seed).
+    pub fn with_seed(self, seed: u32) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Returns how deep the flicker currently is, from 0 (not dimmed) to 1 (at its lowest point).
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    /// Returns the multiplier currently applied to the ambient brightness.
+    pub fn brightness(&self) -> f32 {
+        1. - self.amplitude.clamp(0., 1.) * self.depth
+    }
+}
+
+/// Noise used by an [`AmbientFlicker`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlickerNoise {
+    /// Random values that are smoothly interpolated, like an unstable power supply.
+    #[default]
+    Smooth,
+
+    /// Random values that snap from one to the next, like a failing fluorescent tube.
+    Stepped,
+
+    /// A regular pulse, like an alarm light.
+    Sine,
+}
+
+/// Event triggered on a camera when its [`AmbientFlicker`] dips past its [threshold](AmbientFlicker::event_threshold).
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct AmbientFlickerEvent {
+    /// The camera whose ambient light flickered.
+    pub entity: Entity,
+    /// How deep the flicker is, from 0 to 1.
+    pub depth: f32,
+}
+
+/// Ambient brightness multiplier of a camera, extracted to the render world.
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct ExtractedAmbientFlicker(pub f32);
+
+/// Plugin that updates and extracts [`AmbientFlicker`]s. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct FlickerPlugin;
+impl Plugin for FlickerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AmbientFlicker>();
+        app.add_systems(Update, update_ambient_flickers);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.add_systems(ExtractSchedule, extract_ambient_flickers);
+    }
+}
+
+fn update_ambient_flickers(
+    mut commands: Commands,
+    time: Res<Time>,
+    test_clock: Option<Res<FireflyTestClock>>,
+    mut flickers: Query<(Entity, &mut AmbientFlicker)>,
+) {
+    let delta = test_clock.map_or(time.delta(), |clock| clock.delta);
+
+    for (entity, mut flicker) in &mut flickers {
+        let flicker = flicker.bypass_change_detection();
+
+        flicker.elapsed += delta.as_secs_f32();
+
+        let t = flicker.elapsed * flicker.frequency.max(0.);
+        let depth = match flicker.noise {
+            FlickerNoise::Smooth => {
+                let step = t.floor();
+                let a = hash(step as u32, flicker.seed);
+                let b = hash(step as u32 + 1, flicker.seed);
+                a.lerp(b, EaseFunction::SmoothStep.sample_clamped(t - step))
+            }
+            FlickerNoise::Stepped => hash(t.floor() as u32, flicker.seed),
+            FlickerNoise::Sine => 0.5 - 0.5 * (t * 2. * PI).cos(),
+        };
+
+        // only triggered when the threshold is crossed, not for every frame spent below it
+        if depth >= flicker.event_threshold && flicker.depth < flicker.event_threshold {
+            commands.trigger(AmbientFlickerEvent { entity, depth });
+        }
+
+        flicker.depth = depth;
+    }
+}
+
+fn extract_ambient_flickers(
+    mut commands: Commands,
+    cameras: Extract<Query<(RenderEntity, Option<&AmbientFlicker>), With<FireflyConfig>>>,
+) {
+    for (entity, flicker) in &cameras {
+        match flicker {
+            Some(flicker) => commands
+                .entity(entity)
+                .insert(ExtractedAmbientFlicker(flicker.brightness())),
+            None => commands.entity(entity).remove::<ExtractedAmbientFlicker>(),
+        };
+    }
+}
+
+/// Hashes an integer into a random value in the `[0, 1]` range.
+fn hash(x: u32, seed: u32) -> f32 {
+    let mut x = x ^ seed.wrapping_mul(0x9e37_79b9);
+    x = (x ^ (x >> 16)).wrapping_mul(0x7feb_352d);
+    x = (x ^ (x >> 15)).wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x as f32 / u32::MAX as f32
+}
@@ -0,0 +1,260 @@
+//! Module containing the [`ColorGradient`] asset and the GPU lookup tables it's baked into.
+
+#[cfg(feature = "ron")]
+use bevy::asset::{AssetLoader, LoadContext, io::Reader};
+use bevy::prelude::*;
+
+use crate::lut::{LutAsset, LutAtlas, LutPlugin};
+
+/// Number of samples each [`ColorGradient`] is baked into on the GPU.
+pub const COLOR_GRADIENT_LUT_SIZE: u32 = 64;
+
+/// A single keyframe of a [`ColorGradient`].
+#[derive(Debug, Clone, Copy, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorStop {
+    /// Position of the stop in the gradient, between 0 and 1.
+    pub position: f32,
+    /// Color of the gradient at this position.
+    pub color: Color,
+}
+
+impl ColorStop {
+    pub fn new(position: f32, color: impl Into<Color>) -> Self {
+        Self {
+            position,
+            color: color.into(),
+        }
+    }
+}
+
+/// How the colors between two [color stops](ColorStop) of a [`ColorGradient`] are interpolated.
+///
+/// **Default:** [Linear](GradientInterpolation::Linear).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GradientInterpolation {
+    /// Linear interpolation in linear RGB space.
+    #[default]
+    Linear,
+    /// Linear interpolation in sRGB space. Usually matches gradients made in image editors.
+    Srgb,
+    /// Linear interpolation in Oklab space. Perceptually uniform, avoids muddy transitions between hues.
+    Oklab,
+    /// Smoothstep interpolation in linear RGB space, easing in and out of every stop.
+    Smooth,
+    /// No interpolation. Each stop's color is held until the next stop.
+    Constant,
+}
+
+/// Asset describing a color gradient through a list of keyframed [color stops](ColorStop).
+///
+/// With the `ron` feature, gradients can be loaded from `.gradient.ron` files, and are hot reloaded
+/// if Bevy's `file_watcher` feature is enabled:
+///
+/// ```ron
+/// (
+///     interpolation: Oklab,
+///     stops: [
+///         (position: 0.0, color: Srgba((red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0))),
+///         (position: 1.0, color: Srgba((red: 1.0, green: 0.4, blue: 0.0, alpha: 1.0))),
+///     ],
+/// )
+/// ```
+///
+/// Used by [`PointLight2d::color_ramp`](crate::prelude::PointLight2d::color_ramp) to change a light's color over distance.
+///
+/// # Example
+///
+/// ```
+/// let ramp = gradients.add(ColorGradient::new(vec![
+///     ColorStop::new(0.0, Color::WHITE),
+///     ColorStop::new(1.0, Color::srgb(1.0, 0.4, 0.0)),
+/// ]));
+/// ```
+#[derive(Asset, Debug, Clone, Reflect, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorGradient {
+    /// The color stops of this gradient. They're sorted by position when the gradient is sampled.
+    pub stops: Vec<ColorStop>,
+    /// How colors are interpolated between stops.
+    ///
+    /// **Default:** [Linear](GradientInterpolation::Linear).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub interpolation: GradientInterpolation,
+}
+
+impl ColorGradient {
+    /// Construct a new gradient from a list of color stops.
+    pub fn new(stops: Vec<ColorStop>) -> Self {
+        Self {
+            stops,
+            interpolation: default(),
+        }
+    }
+
+    /// Construct a new gradient from colors that are spaced evenly between 0 and 1.
+    pub fn from_colors(colors: impl IntoIterator<Item = impl Into<Color>>) -> Self {
+        let colors: Vec<Color> = colors.into_iter().map(Into::into).collect();
+        let last = colors.len().saturating_sub(1).max(1) as f32;
+
+        Self {
+            stops: colors
+                .into_iter()
+                .enumerate()
+                .map(|(i, color)| ColorStop::new(i as f32 / last, color))
+                .collect(),
+            interpolation: default(),
+        }
+    }
+
+    /// Returns the gradient with the given [interpolation](GradientInterpolation).
+    pub fn with_interpolation(&self, interpolation: GradientInterpolation) -> Self {
+        Self {
+            interpolation,
+            ..self.clone()
+        }
+    }
+
+    /// Sample the gradient at the given position, between 0 and 1.
+    ///
+    /// Colors are interpolated based on the gradient's [interpolation](GradientInterpolation). Returns white if the gradient has no stops.
+    pub fn sample(&self, t: f32) -> LinearRgba {
+        let mut stops = self.stops.clone();
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+            return LinearRgba::WHITE;
+        };
+
+        if t <= first.position {
+            return first.color.to_linear();
+        }
+        if t >= last.position {
+            return last.color.to_linear();
+        }
+
+        for pair in stops.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if t > b.position {
+                continue;
+            }
+
+            let span = b.position - a.position;
+            let x = if span <= 0.0 {
+                1.0
+            } else {
+                (t - a.position) / span
+            };
+            return self.interpolate(a.color, b.color, x);
+        }
+
+        last.color.to_linear()
+    }
+
+    fn interpolate(&self, a: Color, b: Color, x: f32) -> LinearRgba {
+        match self.interpolation {
+            GradientInterpolation::Linear => a.to_linear().mix(&b.to_linear(), x),
+            GradientInterpolation::Srgb => a.to_srgba().mix(&b.to_srgba(), x).into(),
+            GradientInterpolation::Oklab => Oklaba::from(a).mix(&Oklaba::from(b), x).into(),
+            GradientInterpolation::Smooth => {
+                a.to_linear().mix(&b.to_linear(), x * x * (3.0 - 2.0 * x))
+            }
+            GradientInterpolation::Constant => match x >= 1.0 {
+                true => b.to_linear(),
+                false => a.to_linear(),
+            },
+        }
+    }
+}
+
+/// [`AssetLoader`] for [`ColorGradient`]s stored as `.gradient.ron` files.
+#[cfg(feature = "ron")]
+#[derive(Default, TypePath)]
+pub struct ColorGradientLoader;
+
+/// Error that can occur while loading a [`ColorGradient`].
+#[cfg(feature = "ron")]
+#[derive(Debug)]
+pub enum ColorGradientLoaderError {
+    /// The file couldn't be read.
+    Io(std::io::Error),
+    /// The file isn't a valid RON gradient.
+    Ron(ron::error::SpannedError),
+}
+
+#[cfg(feature = "ron")]
+impl std::fmt::Display for ColorGradientLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "could not read color gradient: {err}"),
+            Self::Ron(err) => write!(f, "could not parse color gradient: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "ron")]
+impl std::error::Error for ColorGradientLoaderError {}
+
+#[cfg(feature = "ron")]
+impl From<std::io::Error> for ColorGradientLoaderError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(feature = "ron")]
+impl From<ron::error::SpannedError> for ColorGradientLoaderError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        Self::Ron(err)
+    }
+}
+
+#[cfg(feature = "ron")]
+impl AssetLoader for ColorGradientLoader {
+    type Asset = ColorGradient;
+    type Settings = ();
+    type Error = ColorGradientLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["gradient.ron"]
+    }
+}
+
+impl LutAsset for ColorGradient {
+    const LUT_SIZE: u32 = COLOR_GRADIENT_LUT_SIZE;
+    const LABEL: &'static str = "color gradient luts";
+
+    fn sample(&self, t: f32) -> LinearRgba {
+        ColorGradient::sample(self, t)
+    }
+}
+
+/// Render World resource containing the texture each [`ColorGradient`] is baked into, one row per gradient.
+pub type ColorGradientLuts = LutAtlas<ColorGradient>;
+
+/// Plugin that registers the [`ColorGradient`] asset and bakes it into [`ColorGradientLuts`]. Added automatically
+/// by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct GradientPlugin;
+impl Plugin for GradientPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ColorGradient>()
+            .register_asset_reflect::<ColorGradient>();
+
+        #[cfg(feature = "ron")]
+        app.init_asset_loader::<ColorGradientLoader>();
+
+        app.add_plugins(LutPlugin::<ColorGradient>::default());
+    }
+}
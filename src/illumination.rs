@@ -0,0 +1,152 @@
+//! Module containing [`IlluminationGrid`], a coarse map of the light in a region that can be read by gameplay systems.
+
+use bevy::{prelude::*, transform::TransformSystems};
+
+use crate::visibility::LitVisibility;
+
+/// Resource that rasterizes the light reaching a region of the world into a grid of cells on the CPU, e.g. so that
+/// stealth AI can weight its pathfinding costs by how lit each cell is without reading the lightmap back from the GPU.
+///
+/// Each cell stores the light reaching its center, computed the same way as [`LitVisibility::illumination_at`].
+/// The grid isn't inserted by [`FireflyPlugin`](crate::prelude::FireflyPlugin), it's updated as long as it's inserted.
+///
+/// # Example
+///
+/// ```
+/// commands.insert_resource(
+///     IlluminationGrid::new(Rect::new(-500., -500., 500., 500.), 32.).with_interval(10),
+/// );
+///
+/// fn path_cost(grid: Res<IlluminationGrid>, cell: Vec2) -> f32 {
+///     // lit cells are avoided by sneaking guards
+///     1. + 10. * grid.at(cell).unwrap_or(0.).min(1.)
+/// }
+/// ```
+#[derive(Resource, Clone, Debug, Reflect)]
+#[reflect(Resource)]
+pub struct IlluminationGrid {
+    /// The region of the world covered by the grid.
+    pub bounds: Rect,
+
+    /// Width and height of each cell, in world units.
+    ///
+    /// **Performance Impact:** Major, every cell is tested against every light and the occluders between them.
+    pub cell_size: f32,
+
+    /// Number of frames between updates of the grid. It's also updated whenever it's changed by another system.
+    ///
+    /// **Performance Impact:** Raising it reduces how often the grid is recomputed.
+    ///
+    /// **Default:** 15.
+    pub interval: u32,
+
+    #[reflect(ignore)]
+    size: UVec2,
+    #[reflect(ignore)]
+    cells: Vec<f32>,
+    #[reflect(ignore)]
+    frames: u32,
+}
+
+impl IlluminationGrid {
+    /// Construct a new grid covering the given region, with the given cell size.
+    pub fn new(bounds: Rect, cell_size: f32) -> Self {
+        Self {
+            bounds,
+            cell_size,
+            interval: 15,
+            size: UVec2::ZERO,
+            cells: vec![],
+            frames: 0,
+        }
+    }
+
+    /// Returns the grid with the given [interval](IlluminationGrid::interval).
+    pub fn with_interval(self, interval: u32) -> Self {
+        Self { interval, ..self }
+    }
+
+    /// Returns the number of columns and rows of the grid, as of its last update.
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// Returns the light of every cell, row by row, starting from the bottom left corner of the [bounds](IlluminationGrid::bounds).
+    pub fn cells(&self) -> &[f32] {
+        &self.cells
+    }
+
+    /// Returns the cell containing the world position, if it's inside the grid.
+    pub fn cell(&self, pos: Vec2) -> Option<UVec2> {
+        if !self.bounds.contains(pos) || self.cell_size <= 0. {
+            return None;
+        }
+
+        let cell = ((pos - self.bounds.min) / self.cell_size).as_uvec2();
+        (cell.x < self.size.x && cell.y < self.size.y).then_some(cell)
+    }
+
+    /// Returns the world position of the cell's center.
+    pub fn cell_center(&self, cell: UVec2) -> Vec2 {
+        self.bounds.min + (cell.as_vec2() + 0.5) * self.cell_size
+    }
+
+    /// Returns the light of the cell, or `None` if it's outside of the grid.
+    pub fn get(&self, cell: UVec2) -> Option<f32> {
+        if cell.x >= self.size.x || cell.y >= self.size.y {
+            return None;
+        }
+
+        self.cells
+            .get((cell.y * self.size.x + cell.x) as usize)
+            .copied()
+    }
+
+    /// Returns the light of the cell containing the world position, or `None` if it's outside of the grid.
+    pub fn at(&self, pos: Vec2) -> Option<f32> {
+        self.get(self.cell(pos)?)
+    }
+}
+
+/// Plugin that updates the [`IlluminationGrid`]. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct IlluminationPlugin;
+
+impl Plugin for IlluminationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<IlluminationGrid>();
+        app.add_systems(
+            PostUpdate,
+            update_illumination_grid
+                .after(TransformSystems::Propagate)
+                .run_if(resource_exists::<IlluminationGrid>),
+        );
+    }
+}
+
+fn update_illumination_grid(mut grid: ResMut<IlluminationGrid>, lit: LitVisibility) {
+    let changed = grid.is_changed();
+
+    let frames = &mut grid.bypass_change_detection().frames;
+    *frames += 1;
+    if !changed && *frames < grid.interval.max(1) {
+        return;
+    }
+
+    // marked as changed, so systems reading the grid can tell when it was updated
+    let grid = grid.into_inner();
+    grid.frames = 0;
+
+    grid.size = match grid.cell_size > 0. {
+        true => (grid.bounds.size() / grid.cell_size).ceil().as_uvec2(),
+        false => UVec2::ZERO,
+    };
+
+    let mut cells = std::mem::take(&mut grid.cells);
+    cells.clear();
+    for y in 0..grid.size.y {
+        for x in 0..grid.size.x {
+            cells.push(lit.illumination_at(grid.cell_center(uvec2(x, y))));
+        }
+    }
+    grid.cells = cells;
+}
@@ -0,0 +1,52 @@
+//! Module containing the GPU layouts of Firefly's buffers, for companion shaders that read them.
+//!
+//! The structs re-exported here are the exact Rust counterparts of the structs in the `firefly::types` shader
+//! import, so a custom compute or render shader can bind Firefly's buffers and read them with:
+//!
+//! ```wgsl
+//! #import firefly::types::{PointLight, RoundOccluder, PolyOccluder, OccluderPointer, BinIndices, FireflyConfig}
+//! ```
+//!
+//! The buffers live in the render world. The light and occluder buffers are the [`BufferManager`](crate::buffers::BufferManager)
+//! resources of [`UniformPointLight`], [`UniformOccluder`] and [`UniformRoundOccluder`], while the bins of every light
+//! are in its [`BinBuffers`](crate::buffers::BinBuffers) component.
+//!
+//! For reference, the lightmap creation shader binds them to group 1 as:
+//!
+//! | Binding | Content                                           |
+//! |---------|---------------------------------------------------|
+//! | 0       | `sampler`                                         |
+//! | 1       | `array<PointLight>`                               |
+//! | 2       | `u32`, index of the light being drawn             |
+//! | 3       | `array<RoundOccluder>`                            |
+//! | 4       | `array<PolyOccluder>`                             |
+//! | 5       | `array<vec2f>`, vertices of polygonal occluders   |
+//! | 6       | `array<OccluderPointer>`, binned occluders        |
+//! | 7       | `BinIndices`                                      |
+//! | 8       | sprite stencil texture                            |
+//! | 9       | normal map texture                                |
+//! | 10      | `FireflyConfig` uniform                           |
+//! | 11      | gradient lookup textures                          |
+//! | 12      | sprite id texture                                 |
+//! | 13      | `array<u32>`, shadow receivers                    |
+//!
+//! Field names, order and offsets only change together with [`LAYOUT_VERSION`], which is also available to
+//! shaders as `firefly::types::LAYOUT_VERSION`. Fields named with a leading underscore are padding and may be
+//! repurposed without a version bump.
+
+pub use crate::{
+    buffers::{BinIndices, N_BINS, OccluderPointer},
+    data::UniformFireflyConfig,
+    lights::UniformPointLight,
+    occluders::{UniformOccluder, UniformRoundOccluder},
+};
+
+/// Version of the layouts in this module. Bumped whenever a field or offset of one of them changes.
+pub const LAYOUT_VERSION: u32 = 9;
+
+// Storage buffer structs are read as raw bytes, so their sizes must match the shader side.
+const _: () = assert!(std::mem::size_of::<UniformPointLight>() == 176);
+const _: () = assert!(std::mem::size_of::<UniformOccluder>() == 80);
+const _: () = assert!(std::mem::size_of::<UniformRoundOccluder>() == 96);
+const _: () = assert!(std::mem::size_of::<OccluderPointer>() == 20);
+const _: () = assert!(std::mem::size_of::<BinIndices>() == 4 * (N_BINS + 1));
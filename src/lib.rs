@@ -54,30 +54,35 @@
 //!
 //! Occluders can be moved and rotated via the [Transform] component.   
 //!
+//! Occluders can also be traced from the opaque parts of a sprite with the [Occluder2dSprite](crate::occluders::Occluder2dSprite) component,
+//! instead of authoring polygons by hand.
+//!
 //! # Lights
 //!
 //! You can create lights by spawning entities with the [PointLight2d](crate::prelude::PointLight2d) component.
 //!
 //! Lights have adjustable [range](crate::prelude::PointLight2d::range), [falloff mode](crate::prelude::PointLight2d::falloff) and a variety of other features.
+//! The sun or moon of outdoor scenes can be added with the [DirectionalLight2d](crate::prelude::DirectionalLight2d) component instead,
+//! and neon signs or glowing strips with the [LineLight2d](crate::prelude::LineLight2d) component.
 //!
 //! # Features
 //!
 //! Here are some of the main features currently implemented :
 //!
 //! - **Soft Shadows**:
-//! [FireflyConfig](crate::prelude::FireflyConfig) has a [Softness](crate::prelude::FireflyConfig::softness) field
-//! that can be adjusted to disable / enable soft shadows, as well as give it a value (0 to 1) to set how soft the shadows should be.
+//! [FireflyConfig](crate::prelude::FireflyConfig) has a [soft shadows](crate::prelude::FireflyConfig::soft_shadows) field to disable / enable soft shadows,
+//! and a [softness](crate::prelude::FireflyConfig::softness) field to set how soft they are, relative to each light's core or in [world units](crate::prelude::Softness::WorldUnits).
 //!
 //! - **Occlusion Z-Sorting**: You can enable [z-sorting](crate::prelude::FireflyConfig::z_sorting) on [FireflyConfig](crate::prelude::FireflyConfig) to have shadows
 //! only render over sprites with a lower z position than the occluder that cast them. This is extremely useful for certain 2d games, such as top-down games.
 //!
-//! - **Normal maps**: You can enable normal maps by changing the [normal mode](crate::prelude::FireflyConfig::normal_mode) field. You can then
+//! - **Normal maps**: You can enable normal maps by changing the [normal mode](crate::prelude::FireflyConfig::normal_mode) field of a camera's config. You can then
 //! add the [NormalMap](crate::prelude::NormalMap) component to sprites. Normal maps need to have the same exact layout as their entity's sprite image.
 //! If [normal mode](crate::prelude::FireflyConfig::normal_mode) is set to [top down](crate::prelude::NormalMode::TopDown),
 //! you can use [LightHeight](crate::prelude::LightHeight) and [SpriteHeight](crate::prelude::SpriteHeight) to emulate 3d dimensions for the normal maps.  
 //!
 //! - **Light Banding**: You can enable [light bands](crate::prelude::FireflyConfig::light_bands) on [FireflyConfig](crate::prelude::FireflyConfig) to
-//! reduce the lightmap to a certain number of 'bands', creating a stylized look.
+//! reduce the lightmap to a certain number of 'bands' (or to bands at custom [thresholds](crate::prelude::FireflyConfig::light_band_thresholds)), creating a stylized look.
 //!
 //! - **Render Layers**: You can put lights, occluders, and cameras on different [RenderLayers](bevy::camera::visibility::RenderLayers) to alter
 //! what lights each occluder blocks and what cameras are the lights rendered to.
@@ -86,12 +91,12 @@
 //! combined into another. This can be used to achieve, for instance, an FOV effect, where there's a visbility lightmap multiplied over the main lightmap.
 //!
 //! - **Debug**: The [FireflyGizmosPlugin](crate::prelude::FireflyGizmosPlugin) shows the exact range and shape of lights and occluders. It can be configured
-//! via the [FireflyGizmoStyle](crate::prelude::FireflyGizmoStyle) resource.
+//! via the [FireflyGizmoStyle](crate::prelude::FireflyGizmoStyle) resource. The [FireflyDebugPlugin](crate::prelude::FireflyDebugPlugin)
+//!   can [dump](crate::prelude::FireflyDebug::dump) what was rendered for a camera, to be attached to bug reports.
 //!
 //! # Upcoming Features
 //!
 //! Here are some of the features that are currently planned:
-//! - Sprite-based shadows.
 //! - Light textures.
 
 use bevy::{
@@ -100,36 +105,133 @@ use bevy::{
 };
 
 pub mod app;
+#[cfg(all(feature = "aseprite", feature = "sprites"))]
+pub mod aseprite;
+pub mod bake;
 pub mod buffers;
+pub mod caustics;
 pub mod change;
+pub mod cues;
 pub mod data;
+pub mod debug;
+pub mod directional;
+#[cfg(feature = "sprites")]
+pub mod drop_shadow;
+pub mod exposure;
+pub mod flare;
+pub mod flicker;
+pub mod gradient;
+pub mod illumination;
+pub mod layout;
 pub mod lights;
+pub mod line;
+pub mod lut;
+pub mod memory;
+pub mod occluder_batch;
+pub mod occluder_group;
 pub mod occluders;
+pub mod outline;
+pub mod parallax;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod profile;
+pub mod quality;
+pub mod raycast;
+pub mod sensor;
+pub mod stencil;
+pub mod stress;
+#[cfg(feature = "svg")]
+pub mod svg;
 pub mod visibility;
+pub mod weather;
+pub mod ysort;
 
 pub mod extract;
 pub mod nodes;
 pub mod phases;
 pub mod pipelines;
 pub mod prepare;
+#[cfg(feature = "sprites")]
 pub mod sprite;
+#[cfg(feature = "sprites")]
 pub mod sprites;
+#[cfg(feature = "sprites")]
+pub mod tilemap;
 
+#[cfg(feature = "sprites")]
 mod utils;
 
 pub(crate) use phases::*;
 
 pub mod prelude {
-    pub use crate::app::{FireflyGizmoStyle, FireflyGizmosPlugin, FireflyPlugin};
+    pub use crate::app::{
+        FireflyGizmoStyle, FireflyGizmosPlugin, FireflyLightHandles, FireflyLightRecorder,
+        FireflyPlugin, RecordedLight,
+    };
+    #[cfg(all(feature = "aseprite", feature = "sprites"))]
+    pub use crate::aseprite::{AsepriteLighting, AsepriteSheet};
+    pub use crate::bake::{BakedShadow, BakedShadowOf, ShadowBaker, Static};
+    pub use crate::caustics::Caustics2d;
+    pub use crate::cues::{
+        LightCue, LightCueEvent, LightCueMarker, LightCuePlayer, LightCueSequence,
+    };
     pub use crate::data::{
-        CombinationMode, CombineLightmapTo, CombinedLightmaps, FireflyConfig, LightmapSize,
-        NormalMode,
+        AccessibilityAssist, AutoExposure, CombinationMode, CombineLightmapTo, CombinedLightmaps,
+        ContactShadows, FireflyBinningBudget, FireflyConfig, FireflyGlobalOverrides,
+        FireflyTestClock, LightmapSize, NormalMode, PenumbraMode, ShadowCullingMode, Softness,
+        SpriteAmbient, ZSortKey,
+    };
+    pub use crate::debug::{FireflyDebug, FireflyDebugPlugin, FireflyDebugReport};
+    pub use crate::directional::{ComputedDirectionalLight, DirectionalLight2d};
+    #[cfg(feature = "sprites")]
+    pub use crate::drop_shadow::DropShadow2d;
+    pub use crate::flare::LensFlare;
+    pub use crate::flicker::{AmbientFlicker, AmbientFlickerEvent, FlickerNoise};
+    pub use crate::gradient::{ColorGradient, ColorStop, GradientInterpolation};
+    pub use crate::illumination::IlluminationGrid;
+    pub use crate::lights::{
+        AngularMask, Falloff, GlowSettings, ImportantLight, LightAngle, LightCore, LightGroup,
+        LightGroupSettings, LightGroups, LightHeight, LightModulator, LightSortKey, MovingLight,
+        PointLight2d, SpriteOcclusion,
+    };
+    pub use crate::line::{LineLight2d, LineLightSegment};
+    pub use crate::lut::{LutAsset, LutAtlas, LutPlugin};
+    pub use crate::memory::FireflyMemoryStats;
+    pub use crate::occluder_batch::OccluderBatch;
+    pub use crate::occluder_group::OccluderGroup;
+    pub use crate::occluders::{
+        ChainJoint, CustomOccluderShape, CustomOccluderShapePlugin, DistanceOpacity,
+        FitOccluderToSprite, Occluder2d, Occluder2dChain, Occluder2dSprite, Occluder2dSpritePart,
+        ShadowReceivers,
     };
-    pub use crate::lights::{Falloff, LightAngle, LightCore, LightHeight, PointLight2d};
-    pub use crate::occluders::Occluder2d;
-    pub use crate::sprite::{FireflySprite, FireflySpriteImageMode, SpriteInstance};
-    pub use crate::sprites::{NormalMap, SpriteHeight};
-    pub use crate::{ApplyLightmapLabel, CreateLightmapLabel};
+    pub use crate::outline::DarknessOutline;
+    pub use crate::parallax::{ParallaxLayer, ParallaxLight, ParallaxLightOf};
+    #[cfg(feature = "preview")]
+    pub use crate::preview::{LightmapPreview, PreviewCorner};
+    pub use crate::profile::{
+        FireflyConfigTransition, LightingProfile, LightingProfileBlend, LightingProfileCommands,
+    };
+    pub use crate::quality::{FireflyDynamicResolution, FireflyQuality};
+    pub use crate::raycast::{OccluderHit, OccluderRaycast};
+    pub use crate::sensor::{LightEnterEvent, LightExitEvent, LightSensor};
+    #[cfg(feature = "sprites")]
+    pub use crate::sprite::{
+        CompositeSprite, FireflySprite, FireflySpriteImageMode, SpriteInstance,
+    };
+    #[cfg(feature = "sprites")]
+    pub use crate::sprites::{
+        AssistOutline, Decal2d, DetailNormalMap, EmissiveThreshold, MinimumLight, NormalMap,
+        NormalMapSrgbHandling, SpriteHeight,
+    };
+    pub use crate::stencil::{StencilId, StencilIds};
+    #[cfg(feature = "svg")]
+    pub use crate::svg::{SvgOccluder2d, SvgOccluders, SvgOccludersSettings};
+    #[cfg(feature = "sprites")]
+    pub use crate::tilemap::TilemapNormalLayer;
+    pub use crate::visibility::{ComputedLightBounds, ComputedLightVisibility, LitVisibility};
+    pub use crate::weather::WeatherOverlay;
+    pub use crate::ysort::{YSort, YSortPlugin};
+    pub use crate::{ApplyLightmapAfterTonemappingLabel, ApplyLightmapLabel, CreateLightmapLabel};
 }
 
 /// Camera component that stores the texture of the lightmap.
@@ -141,13 +243,30 @@ pub struct LightMapTexture(pub CachedTexture);
 pub struct CombinedLightMapTextures(pub CachedTexture);
 
 /// Camera component that stores the sprite stencil.
+///
+/// Without the `sprites` feature, this is an empty 1x1 placeholder.
 #[derive(Component)]
 pub struct SpriteStencilTexture(pub CachedTexture);
 
 /// Camera component that stores the normal map texture.  
+///
+/// Without the `sprites` feature, this is an empty 1x1 placeholder.
 #[derive(Component)]
 pub struct NormalMapTexture(pub CachedTexture);
 
+/// Camera component that stores the mask of sprites outlined by the [accessibility assist](crate::prelude::AccessibilityAssist).
+///
+/// Without the `sprites` feature, this is an empty 1x1 placeholder.
+#[derive(Component)]
+pub struct AssistMaskTexture(pub CachedTexture);
+
+/// Camera component that stores the [stencil ID](crate::prelude::StencilId) of the sprite drawn at each pixel,
+/// used by [shadow receivers](crate::prelude::ShadowReceivers).
+///
+/// Without the `sprites` feature, this is an empty 1x1 placeholder.
+#[derive(Component)]
+pub struct SpriteIdTexture(pub CachedTexture);
+
 /// Render graph label for creating the lightmap.
 ///
 /// Useful if you want to add your own render passes before / after it.   
@@ -160,8 +279,17 @@ pub struct CreateLightmapLabel;
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
 pub struct ApplyLightmapLabel;
 
+/// Render graph label for when the lightmap is applied after tonemapping.
+///
+/// This is only used by cameras that have [`apply_before_tonemapping`](crate::prelude::FireflyConfig::apply_before_tonemapping)
+/// set to false.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub struct ApplyLightmapAfterTonemappingLabel;
+
 /// Render graph label for when the normal maps and sprite stencils are created.
 ///
 /// Useful if you want to add your own render passes before / after it.
+///
+/// The pass is skipped for cameras that don't need these textures (see [`FireflyConfig::uses_sprite_stencil`](crate::prelude::FireflyConfig::uses_sprite_stencil)).
 #[derive(RenderLabel, Debug, Clone, Hash, PartialEq, Eq)]
 pub struct SpriteLabel;
@@ -3,7 +3,6 @@ use bevy::{
     color::palettes::css::WHITE,
     core_pipeline::tonemapping::{DebandDither, Tonemapping},
     ecs::{
-        change_detection::Tick,
         query::ROQueryItem,
         system::{
             SystemParamItem,
@@ -14,11 +13,10 @@ use bevy::{
     prelude::*,
     render::{
         Render, RenderApp, RenderSystems,
-        batching::sort_binned_render_phase,
         render_phase::{
-            AddRenderCommand, BinnedRenderPhaseType, DrawFunctions, InputUniformIndex, PhaseItem,
-            RenderCommand, RenderCommandResult, SetItemPipeline, TrackedRenderPass,
-            ViewBinnedRenderPhases,
+            AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
+            RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+            sort_phase_system,
         },
         render_resource::{
             BindGroup, PipelineCache, ShaderType, SpecializedRenderPipelines, StorageBuffer,
@@ -27,15 +25,15 @@ use bevy::{
         view::{ExtractedView, RenderVisibleEntities, RetainedViewEntity, ViewUniformOffset},
     },
 };
-use bytemuck::NoUninit;
+use bytemuck::{Pod, Zeroable};
 
 use crate::{
-    LightBatchSetKey,
-    buffers::{BinBuffers, BufferIndex},
+    buffers::{BinBuffers, BufferIndex, SkippedBinning},
     change::Changes,
-    data::ExtractedCombineLightmapTo,
+    data::{ExtractedCombineLightmapTo, FireflyConfig},
+    gradient::ColorGradient,
     phases::LightmapPhase,
-    pipelines::{LightPipelineKey, LightmapCreationPipeline},
+    pipelines::{LightPipelineKey, LightmapCreationPipeline, LightmapCreationPipelineKey},
     visibility::VisibilityTimer,
 };
 
@@ -94,6 +92,17 @@ pub struct PointLight2d {
     /// **Default:** true.
     pub cast_shadows: bool,
 
+    /// How dark this light's shadows are, from 0 (no shadows) to 1 (fully dark).
+    ///
+    /// This scales all shadows cast by this light, independently of each occluder's [opacity](crate::prelude::Occluder2d::opacity),
+    /// including [sprite occlusion](PointLight2d::sprite_occlusion) and [contact shadows](crate::prelude::ContactShadows).
+    /// Useful for gameplay-critical lights that should always leave some visibility in their shadows.
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** 1.
+    pub shadow_strength: f32,
+
     /// Offset position of the light.
     ///
     /// Useful if you want to add a light component on an entity and change it's position,
@@ -101,6 +110,91 @@ pub struct PointLight2d {
     ///
     /// **Default:** [Vec3::ZERO].
     pub offset: Vec3,
+
+    /// Optional [`ColorGradient`] that is sampled by the normalized distance from the light's center,
+    /// 0 being the center and 1 being the light's radius.
+    ///
+    /// The sampled color is multiplied with the light's [color](PointLight2d::color), so you can leave that
+    /// white to use the gradient as-is. Useful e.g. for a light that is white-hot at its center and fades to
+    /// deep orange towards its edge, without stacking multiple lights.
+    ///
+    /// **Default:** None.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub color_ramp: Option<Handle<ColorGradient>>,
+
+    /// Optional screen-space occlusion by sprites.
+    ///
+    /// If set, after the regular occluder shadows, the light also ray-marches the `Stencil Texture` towards
+    /// each pixel, blocking light wherever an opaque sprite is in the way. This results in pixel-perfect shadows
+    /// from irregular sprites (trees, characters, etc.) without needing to author occluder shapes for them.
+    ///
+    /// Sprites only block light for pixels with a lower z (same as [z-sorting](crate::prelude::FireflyConfig::z_sorting)),
+    /// or [taller walls](SpriteOcclusion::compare_heights), and the sprite the light is on (e.g. a character holding
+    /// a torch) is ignored.
+    ///
+    /// Has no effect if [`cast_shadows`](PointLight2d::cast_shadows) is disabled.
+    ///
+    /// **Performance Impact:** Medium, scales with [steps](SpriteOcclusion::steps).
+    ///
+    /// **Default:** None.
+    pub sprite_occlusion: Option<SpriteOcclusion>,
+
+    /// Whether this light responds to the `Normal Map` or not.
+    ///
+    /// Disabling it makes the light shade every pixel as if it had no normal, which is useful for
+    /// stylized fill lights: when many of them are combined with a normal mode like
+    /// [TopDownY](crate::prelude::NormalMode::TopDownY), they otherwise shade the sprites over and over,
+    /// making them look too bright. The key lights can then keep using the normals.
+    ///
+    /// Has no effect if the [normal mode](crate::prelude::FireflyConfig::normal_mode) is [None](crate::prelude::NormalMode::None).
+    ///
+    /// **Default:** true.
+    pub affect_normals: bool,
+
+    /// Overrides the config's [light pitch](crate::prelude::FireflyConfig::light_pitch) for this light, in degrees.
+    ///
+    /// **Default:** None.
+    pub pitch: Option<f32>,
+
+    /// Optional angular mask, repeating a lit / unlit pattern around the light.
+    ///
+    /// Useful for simulating slatted lampshades, vent grilles or (by animating the [phase](AngularMask::phase))
+    /// rotating beacons. The pattern follows the **UP** direction of the entity, same as the light's [angle](PointLight2d::angle).
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** None.
+    pub angular_mask: Option<AngularMask>,
+
+    /// Optional [glow](GlowSettings) drawn around the light's position, so the light itself visibly shines
+    /// without relying on the camera's bloom.
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default:** None.
+    pub glow: Option<GlowSettings>,
+
+    /// How far outside the camera's view occluders are still considered for this light's shadows.
+    ///
+    /// Shadows cast by occluders further than this outside the view are culled, which can make them pop in
+    /// when the light is offscreen. If None, the light's [radius](PointLight2d::radius) is used, since no shadow
+    /// can reach further than that.
+    ///
+    /// Ignored by cameras with [aggressive](crate::prelude::ShadowCullingMode::Aggressive) shadow culling.
+    ///
+    /// **Performance Impact:** Smaller margins bin fewer occluders for lights that are mostly offscreen.
+    ///
+    /// **Default:** None.
+    pub culling_margin: Option<f32>,
+
+    /// If set, the light only fully affects sprites whose z is within this distance of the light's z, fading out
+    /// until twice the distance. This keeps lights from leaking across the depth planes of 2.5D scenes.
+    ///
+    /// Pixels that aren't covered by sprites are unaffected. The z of each sprite is read from the sprite stencil,
+    /// so this renders the stencil even if the camera's config doesn't otherwise use it.
+    ///
+    /// **Default:** None.
+    pub z_range: Option<f32>,
 }
 
 impl Default for PointLight2d {
@@ -113,7 +207,175 @@ impl Default for PointLight2d {
             core: default(),
             angle: LightAngle::FULL,
             cast_shadows: true,
+            shadow_strength: 1.,
             offset: Vec3::ZERO,
+            color_ramp: None,
+            sprite_occlusion: None,
+            affect_normals: true,
+            pitch: None,
+            angular_mask: None,
+            glow: None,
+            culling_margin: None,
+            z_range: None,
+        }
+    }
+}
+
+/// An [angular mask](PointLight2d::angular_mask) that repeats a lit / unlit pattern around a light.
+///
+/// All angles are in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AngularMask {
+    /// Size of one lit + unlit section of the pattern. E.g. 45 makes 8 slats around the light.
+    ///
+    /// **Default:** 45.
+    pub period: f32,
+    /// Fraction of each period that is lit, between 0 and 1.
+    ///
+    /// **Default:** 0.5.
+    pub duty_cycle: f32,
+    /// Rotation of the pattern, relative to the **UP** direction of the light.
+    ///
+    /// **Default:** 0.
+    pub phase: f32,
+    /// Width of the transition between the lit and unlit sections.
+    ///
+    /// **Default:** 2.
+    pub softness: f32,
+}
+
+impl Default for AngularMask {
+    fn default() -> Self {
+        Self {
+            period: 45.,
+            duty_cycle: 0.5,
+            phase: 0.,
+            softness: 2.,
+        }
+    }
+}
+
+impl AngularMask {
+    /// Construct a new mask with the given period and duty cycle.
+    pub fn new(period: f32, duty_cycle: f32) -> Self {
+        Self {
+            period,
+            duty_cycle,
+            ..default()
+        }
+    }
+
+    /// Returns the mask with the given [phase](AngularMask::phase).
+    pub fn with_phase(&self, phase: f32) -> Self {
+        Self { phase, ..*self }
+    }
+
+    /// Returns the mask with the given [softness](AngularMask::softness).
+    pub fn with_softness(&self, softness: f32) -> Self {
+        Self { softness, ..*self }
+    }
+}
+
+/// A soft radial [glow](PointLight2d::glow) drawn into the lightmap around a light, like the halo around a lamp.
+///
+/// The glow is part of the light, so it takes the light's color and intensity and is blocked by the same shadows.
+/// Unlike the light itself, it ignores the light's [angle](PointLight2d::angle), [angular mask](PointLight2d::angular_mask)
+/// and the normal map.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn(PointLight2d {
+///     radius: 150.,
+///     glow: Some(GlowSettings::new(24., 1.5)),
+///     ..default()
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GlowSettings {
+    /// Radius of the glow, in world units. Capped at the light's [radius](PointLight2d::radius).
+    ///
+    /// **Default:** 16.
+    pub radius: f32,
+    /// Brightness of the glow at its center, multiplied with the light's [intensity](PointLight2d::intensity).
+    ///
+    /// **Default:** 1.
+    pub intensity: f32,
+    /// Exponent of the glow's falloff. Higher values concentrate the glow around its center.
+    ///
+    /// **Default:** 2.
+    pub falloff: f32,
+}
+
+impl Default for GlowSettings {
+    fn default() -> Self {
+        Self {
+            radius: 16.,
+            intensity: 1.,
+            falloff: 2.,
+        }
+    }
+}
+
+impl GlowSettings {
+    /// Construct a new glow with the given radius and intensity.
+    pub fn new(radius: f32, intensity: f32) -> Self {
+        Self {
+            radius,
+            intensity,
+            ..default()
+        }
+    }
+
+    /// Returns the glow with the given [falloff](GlowSettings::falloff).
+    pub fn with_falloff(&self, falloff: f32) -> Self {
+        Self { falloff, ..*self }
+    }
+}
+
+/// Settings for a light's screen-space [sprite occlusion](PointLight2d::sprite_occlusion).
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpriteOcclusion {
+    /// Number of samples taken between each pixel and the light.
+    ///
+    /// Small sprites can be skipped over if this is too low compared to the light's on-screen size.
+    ///
+    /// **Default:** 32.
+    pub steps: u32,
+    /// How much light is blocked by sprites, between 0 and 1.
+    ///
+    /// **Default:** 1.
+    pub opacity: f32,
+    /// If true, sprites are treated as walls: they only block the light where their [height](crate::prelude::SpriteHeight)
+    /// rises above the ray between the pixel and the light's [height](LightHeight), instead of comparing z positions.
+    ///
+    /// This keeps the light of e.g. a torch behind a tall wall from glowing through it onto the ground in front,
+    /// while low sprites such as rugs or puddles don't block anything.
+    ///
+    /// **Default:** false.
+    pub compare_heights: bool,
+}
+
+impl Default for SpriteOcclusion {
+    fn default() -> Self {
+        Self {
+            steps: 32,
+            opacity: 1.0,
+            compare_heights: false,
+        }
+    }
+}
+
+impl SpriteOcclusion {
+    /// Construct new sprite occlusion that [compares heights](SpriteOcclusion::compare_heights), masking the light
+    /// behind tall sprites.
+    pub fn walls() -> Self {
+        Self {
+            compare_heights: true,
+            ..default()
         }
     }
 }
@@ -122,13 +384,393 @@ impl Default for PointLight2d {
 ///
 /// Describes the light's 2d height, useful for emulating 3d lighting in top-down 2d games.
 ///
-/// This is currently used along with the normal maps.
+/// This is currently used along with the normal maps. With the [Sidescroller](crate::prelude::NormalMode::Sidescroller)
+/// normal mode, it's the light's distance toward the viewer instead.
+///
+/// **Default:** [Fixed(0)](LightHeight::Fixed).
 ///
-/// **Default:** 0.   
-#[derive(Component, Default, Reflect)]
-pub struct LightHeight(pub f32);
+/// # Example
+///
+/// ```
+/// // top-down games that already store elevation in the z position can derive the height from it
+/// commands.spawn((
+///     PointLight2d::default(),
+///     LightHeight::FromZ { scale: 10., offset: 0. },
+///     Transform::from_xyz(0., 0., 2.),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub enum LightHeight {
+    /// A fixed height.
+    Fixed(f32),
+
+    /// The height is derived from the light's z position (including its [offset](PointLight2d::offset)),
+    /// as `z * scale + offset`. See [`LightHeight::from_z`].
+    FromZ {
+        /// Height per unit of z.
+        ///
+        /// **Default:** 1.
+        scale: f32,
 
-#[derive(Debug, Clone, Copy, Reflect)]
+        /// Height of a light at a z of 0.
+        ///
+        /// **Default:** 0.
+        offset: f32,
+    },
+}
+
+impl Default for LightHeight {
+    fn default() -> Self {
+        Self::Fixed(0.)
+    }
+}
+
+impl From<f32> for LightHeight {
+    fn from(height: f32) -> Self {
+        Self::Fixed(height)
+    }
+}
+
+impl LightHeight {
+    /// Construct a height [derived from the z position](LightHeight::FromZ), with the default scale and offset.
+    pub fn from_z() -> Self {
+        Self::FromZ {
+            scale: 1.,
+            offset: 0.,
+        }
+    }
+
+    /// Returns the effective height of a light at the given z position.
+    pub fn height(&self, z: f32) -> f32 {
+        match *self {
+            Self::Fixed(height) => height,
+            Self::FromZ { scale, offset } => z * scale + offset,
+        }
+    }
+}
+
+/// Optional component you can add to lights that move fast, such as projectiles.
+///
+/// The light is stretched along the distance it moved over the last frame, with its falloff computed from the
+/// resulting capsule instead of a single point. This leaves a smooth streak behind the light instead of a series of
+/// disconnected flashes at low frame rates. Shadows are still cast from the light's current position.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     PointLight2d::default(),
+///     MovingLight::default().with_max_length(200.),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(PointLight2d)]
+pub struct MovingLight {
+    /// Multiplier for the streak's length, relative to the distance the light moved over the last frame.
+    ///
+    /// **Default:** 1.
+    pub length_scale: f32,
+
+    /// Maximum length of the streak, in world units. Useful for lights that might be teleported.
+    ///
+    /// **Default:** None.
+    pub max_length: Option<f32>,
+
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    previous_pos: Option<Vec2>,
+
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    streak: Vec2,
+}
+
+impl Default for MovingLight {
+    fn default() -> Self {
+        Self {
+            length_scale: 1.,
+            max_length: None,
+            previous_pos: None,
+            streak: Vec2::ZERO,
+        }
+    }
+}
+
+impl MovingLight {
+    /// Construct a new moving light with the given maximum streak length.
+    pub fn with_max_length(self, max_length: f32) -> Self {
+        Self {
+            max_length: Some(max_length),
+            ..self
+        }
+    }
+
+    /// Construct a new moving light with the given streak length multiplier.
+    pub fn with_length_scale(self, length_scale: f32) -> Self {
+        Self {
+            length_scale,
+            ..self
+        }
+    }
+
+    /// The light's streak over the current frame, as an offset from the light's position to the end of the streak.
+    pub fn streak(&self) -> Vec2 {
+        self.streak
+    }
+}
+
+/// Optional component that sets the order in which a light is drawn into the lightmap.
+///
+/// Lights are drawn in ascending order of their key, and lights with the same key (including ones without this
+/// component, which have a key of 0) are drawn in order of their entity. The draw order doesn't depend on ECS
+/// iteration or parallel scheduling, so the same scene renders the same lightmap every time, which keeps
+/// screenshots and golden tests reproducible.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((PointLight2d::default(), LightSortKey(-1)));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightSortKey(pub i32);
+
+/// Marker component for lights whose exact extent matters to gameplay, such as the lights of a puzzle
+/// that the player has to stand in.
+///
+/// Important lights are always rendered, even if the camera's [light budget](crate::prelude::FireflyConfig::max_lights)
+/// is exceeded, and the pixels they reach aren't quantized by [light bands](crate::prelude::FireflyConfig::light_bands),
+/// so the visible edge of the light is exactly where its range ends.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((PointLight2d::default(), ImportantLight));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImportantLight;
+
+/// Optional component that scales a light's [intensity](PointLight2d::intensity) and [range](PointLight2d::radius)
+/// by a [multiplier](LightModulator::multiplier) when it's extracted to the render world.
+///
+/// This is meant to be written every frame by external systems, such as music analysis for beat-synced club
+/// lighting or gameplay effects, without mutating the [`PointLight2d`] itself and triggering change detection
+/// for systems that watch it.
+///
+/// The modulated range is taken into account when culling the light, but [`LitVisibility`](crate::prelude::LitVisibility)
+/// and [`LightSensor`](crate::prelude::LightSensor)s use the light's unmodulated values.
+///
+/// # Example
+///
+/// ```
+/// fn pulse(mut modulators: Query<&mut LightModulator>, beat: Res<Beat>) {
+///     for mut modulator in &mut modulators {
+///         modulator.multiplier = 1. + beat.strength;
+///     }
+/// }
+/// ```
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightModulator {
+    /// The multiplier applied to the light.
+    ///
+    /// **Default:** 1.
+    pub multiplier: f32,
+
+    /// Whether the multiplier is applied to the light's [intensity](PointLight2d::intensity).
+    ///
+    /// **Default:** true.
+    pub intensity: bool,
+
+    /// Whether the multiplier is applied to the light's [range](PointLight2d::radius).
+    ///
+    /// **Performance Impact:** Minor, since the bins of the light's occluders are rebuilt whenever the range changes.
+    ///
+    /// **Default:** false.
+    pub range: bool,
+}
+
+impl Default for LightModulator {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.,
+            intensity: true,
+            range: false,
+        }
+    }
+}
+
+impl LightModulator {
+    /// Construct a new modulator with the given multiplier, applied to the light's intensity.
+    pub fn new(multiplier: f32) -> Self {
+        Self {
+            multiplier,
+            ..default()
+        }
+    }
+
+    /// Returns the modulator with the given [intensity](LightModulator::intensity) and [range](LightModulator::range) settings.
+    pub fn with_targets(self, intensity: bool, range: bool) -> Self {
+        Self {
+            intensity,
+            range,
+            ..self
+        }
+    }
+
+    /// Returns the given light intensity, modulated.
+    pub fn modulate_intensity(&self, intensity: f32) -> f32 {
+        match self.intensity {
+            true => intensity * self.multiplier.max(0.),
+            false => intensity,
+        }
+    }
+
+    /// Returns the given light range, modulated.
+    pub fn modulate_range(&self, range: f32) -> f32 {
+        match self.range {
+            true => range * self.multiplier.max(0.),
+            false => range,
+        }
+    }
+}
+
+/// Optional component that puts a light in a named group, whose [settings](LightGroupSettings) are controlled
+/// through the [`LightGroups`] resource.
+///
+/// Groups can be nested by giving them a [parent](LightGroupSettings::parent), so a whole building can be dimmed while
+/// its basement is switched off separately. Like [`LightModulator`]s, group settings are applied when lights are
+/// extracted to the render world, so [`LitVisibility`](crate::prelude::LitVisibility) and
+/// [`LightSensor`](crate::prelude::LightSensor)s aren't affected by them.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((PointLight2d::default(), LightGroup::new("basement")));
+///
+/// // later, when the breaker trips
+/// fn trip_breaker(mut groups: ResMut<LightGroups>) {
+///     groups.set_enabled("basement", false);
+/// }
+/// ```
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightGroup(pub String);
+
+impl LightGroup {
+    /// Construct a new group with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+}
+
+/// Settings of a [`LightGroup`], stored in the [`LightGroups`] resource.
+#[derive(Clone, Debug, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightGroupSettings {
+    /// Whether the lights of the group are rendered. Disabled lights are culled like lights outside the camera's view.
+    ///
+    /// **Default:** true.
+    pub enabled: bool,
+
+    /// Multiplier applied to the [intensity](PointLight2d::intensity) of the group's lights.
+    ///
+    /// **Default:** 1.
+    pub intensity: f32,
+
+    /// Color multiplied with the [color](PointLight2d::color) of the group's lights.
+    ///
+    /// **Default:** White.
+    pub color: Color,
+
+    /// Name of the group this group is nested in. The settings of every ancestor are applied on top of the group's own.
+    ///
+    /// **Default:** None.
+    pub parent: Option<String>,
+}
+
+impl Default for LightGroupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 1.,
+            color: Color::WHITE,
+            parent: None,
+        }
+    }
+}
+
+/// Resource with the [settings](LightGroupSettings) of every [`LightGroup`].
+///
+/// Groups that were never configured use the default settings.
+#[derive(Resource, Clone, Debug, Default, Reflect)]
+#[reflect(Resource)]
+pub struct LightGroups(HashMap<String, LightGroupSettings>);
+
+impl LightGroups {
+    /// Returns the settings of the group, if it was configured.
+    pub fn get(&self, name: &str) -> Option<&LightGroupSettings> {
+        self.0.get(name)
+    }
+
+    /// Returns the settings of the group, inserting the default settings if it wasn't configured yet.
+    pub fn get_mut(&mut self, name: &str) -> &mut LightGroupSettings {
+        self.0.entry(name.to_string()).or_default()
+    }
+
+    /// Enables or disables every light in the group and its nested groups.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        self.get_mut(name).enabled = enabled;
+    }
+
+    /// Sets the [intensity](LightGroupSettings::intensity) multiplier of the group.
+    pub fn set_intensity(&mut self, name: &str, intensity: f32) {
+        self.get_mut(name).intensity = intensity;
+    }
+
+    /// Sets the [color](LightGroupSettings::color) multiplier of the group.
+    pub fn set_color(&mut self, name: &str, color: Color) {
+        self.get_mut(name).color = color;
+    }
+
+    /// Nests the group in the given [parent](LightGroupSettings::parent), or makes it a top-level group.
+    pub fn set_parent(&mut self, name: &str, parent: Option<&str>) {
+        self.get_mut(name).parent = parent.map(str::to_string);
+    }
+
+    /// Returns the settings that are applied to the lights of the group, combined with the settings of its ancestors.
+    /// The returned settings don't have a parent.
+    pub fn resolve(&self, name: &str) -> LightGroupSettings {
+        let mut res = LightGroupSettings::default();
+        let mut color = Vec4::ONE;
+
+        let mut current = self.0.get(name);
+        // bounded by the number of groups, so parent cycles can't loop forever
+        for _ in 0..self.0.len() {
+            let Some(settings) = current else {
+                break;
+            };
+
+            res.enabled &= settings.enabled;
+            res.intensity *= settings.intensity.max(0.);
+            color *= settings.color.to_linear().to_vec4();
+
+            current = settings
+                .parent
+                .as_ref()
+                .and_then(|parent| self.0.get(parent));
+        }
+
+        res.color = LinearRgba::from_vec4(color).into();
+        res
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The angle of the light. Value is interpolated between inner and outer angles to create a smooth transition.
 pub struct LightAngle {
@@ -136,6 +778,13 @@ pub struct LightAngle {
     pub inner: f32,
     /// The outer angle of a light, in degrees. Should be greater than or equal to the inner angle.
     pub outer: f32,
+    /// Width of the smooth fade at the edge of the light, in degrees.
+    ///
+    /// With a feather, the transition between the inner and outer angles is widened by half of the feather on
+    /// each side and eased with a smoothstep, so even lights with equal inner and outer angles don't cut off hard.
+    ///
+    /// **Default:** 0.
+    pub feather: f32,
 }
 
 impl Default for LightAngle {
@@ -148,11 +797,26 @@ impl LightAngle {
     pub const FULL: Self = Self {
         inner: 360.0,
         outer: 360.0,
+        feather: 0.0,
     };
+
+    /// Construct a new light angle with the given inner and outer angles, in degrees.
+    pub fn new(inner: f32, outer: f32) -> Self {
+        Self {
+            inner,
+            outer,
+            feather: 0.0,
+        }
+    }
+
+    /// Returns the angle with the given [feather](LightAngle::feather), in degrees.
+    pub fn with_feather(&self, feather: f32) -> Self {
+        Self { feather, ..*self }
+    }
 }
 
 /// An enum describing the falloff of a light's intensity.
-#[derive(Debug, Clone, Copy, Reflect)]
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Falloff {
     /// The light decreases inversely proportial to the square distance towards the source.  
@@ -191,10 +855,25 @@ impl Falloff {
             Falloff::None => 0.0,
         }
     }
+
+    /// Evaluates the falloff at the given normalized distance, 0 being the light's center and 1 its edge.
+    ///
+    /// This matches the falloff computed in the lightmap shader.
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let x = x.clamp(0., 1.);
+        match *self {
+            Falloff::InverseSquare { intensity } => {
+                let x2 = x * x;
+                (1. - x2) * (1. - x2) / (1. + intensity * x2)
+            }
+            Falloff::Linear { intensity } => (1. - x) / (1. + intensity * x),
+            Falloff::None => 1.,
+        }
+    }
 }
 
 /// The light's core. This is what determines the softness of shadows if [soft_shadows](crate::prelude::FireflyConfig::soft_shadows) is enabled.
-#[derive(Clone, Copy, Debug, Reflect)]
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LightCore {
     /// The radius of the core. This must be less than the actual radius of the light.
@@ -260,7 +939,7 @@ impl LightCore {
 
 /// The data that is extracted to the render world from a [`PointLight2d`].
 #[derive(Component, Clone)]
-#[require(BinBuffers, LightIndex, LightPointer)]
+#[require(BinBuffers, SkippedBinning, LightIndex, LightPointer)]
 pub struct ExtractedPointLight {
     pub pos: Vec2,
     pub color: Color,
@@ -270,9 +949,30 @@ pub struct ExtractedPointLight {
     pub core: LightCore,
     pub angle: LightAngle,
     pub cast_shadows: bool,
+    pub shadow_strength: f32,
     pub dir: Vec2,
     pub z: f32,
     pub height: f32,
+    pub color_ramp: Option<AssetId<ColorGradient>>,
+    pub sprite_occlusion: Option<SpriteOcclusion>,
+    pub affect_normals: bool,
+    pub pitch: Option<f32>,
+    pub angular_mask: Option<AngularMask>,
+    pub glow: Option<GlowSettings>,
+    pub culling_margin: f32,
+    /// Start angle and span of the sector the light's occluders are binned over, in radians.
+    /// Only narrower than the full circle for [directional lights](crate::prelude::DirectionalLight2d).
+    pub bin_sector: Vec2,
+    pub z_range: Option<f32>,
+    pub streak: Vec2,
+    /// For segments of [line lights](crate::prelude::LineLight2d), whether the segment has an end cap.
+    /// Their shadows are cast from the [streak](ExtractedPointLight::streak) instead of the light's position.
+    pub line: Option<bool>,
+    pub sort_key: LightSortKey,
+    pub important: bool,
+    /// Whether the light is managed by a [`DirectionalLight2d`](crate::prelude::DirectionalLight2d).
+    /// Like important lights, directional lights are exempt from the binning budget.
+    pub directional: bool,
     pub changes: Changes,
     pub render_layers: RenderLayers,
 }
@@ -283,9 +983,44 @@ impl PartialEq for ExtractedPointLight {
     }
 }
 
+impl ExtractedPointLight {
+    /// Returns what changed since the light's previous extraction.
+    pub(crate) fn changes_since(&self, previous: &Self) -> Changes {
+        Changes {
+            moved: self.pos != previous.pos || self.dir != previous.dir || self.z != previous.z,
+            parameters: self.color != previous.color
+                || self.intensity != previous.intensity
+                || self.falloff != previous.falloff
+                || self.angle != previous.angle
+                || self.shadow_strength != previous.shadow_strength
+                || self.height != previous.height
+                || self.color_ramp != previous.color_ramp
+                || self.sprite_occlusion != previous.sprite_occlusion
+                || self.affect_normals != previous.affect_normals
+                || self.pitch != previous.pitch
+                || self.angular_mask != previous.angular_mask
+                || self.glow != previous.glow
+                || self.z_range != previous.z_range
+                || self.sort_key != previous.sort_key
+                || self.important != previous.important,
+            // everything that decides which occluders are binned, and how
+            shape: self.radius != previous.radius
+                || self.core != previous.core
+                || self.cast_shadows != previous.cast_shadows
+                || self.culling_margin != previous.culling_margin
+                || self.bin_sector != previous.bin_sector
+                || self.streak != previous.streak
+                || self.line != previous.line
+                || self.render_layers != previous.render_layers,
+        }
+    }
+}
+
 /// Data that is sent to the GPU for each visible [`PointLight2d`].
+///
+/// Matches `PointLight` in `firefly::types`. See the [`layout`](crate::layout) module for the stability guarantees.
 #[repr(C)]
-#[derive(Default, Clone, Copy, ShaderType, NoUninit)]
+#[derive(Default, Clone, Copy, ShaderType, Pod, Zeroable)]
 pub struct UniformPointLight {
     pub pos: Vec2,
     pub intensity: f32,
@@ -308,6 +1043,32 @@ pub struct UniformPointLight {
 
     pub z: f32,
     pub height: f32,
+
+    pub color_ramp: u32,
+    pub sprite_occlusion_steps: u32,
+    pub sprite_occlusion_opacity: f32,
+    pub affect_normals: u32,
+    pub pitch: f32,
+    pub mask_period: f32,
+    pub mask_duty: f32,
+    pub mask_phase: f32,
+    pub mask_softness: f32,
+    pub angle_feather: f32,
+    pub cast_shadows: u32,
+    pub shadow_strength: f32,
+
+    pub streak: Vec2,
+    pub important: u32,
+
+    pub glow_radius: f32,
+    pub glow_intensity: f32,
+    pub glow_falloff: f32,
+    pub bin_start: f32,
+    pub bin_span: f32,
+    pub z_range: f32,
+    pub line: u32,
+    pub sprite_occlusion_heights: u32,
+    pub _pad1: u32,
 }
 
 /// Render World component that contains the buffer a [`PointLight2d`] writes to each frame.   
@@ -319,16 +1080,28 @@ pub struct LightPointer(pub StorageBuffer<u32>);
 pub struct LightPlugin;
 impl Plugin for LightPlugin {
     fn build(&self, app: &mut App) {
+        app.register_type::<MovingLight>();
+        app.register_type::<LightSortKey>();
+        app.register_type::<ImportantLight>();
+        app.register_type::<LightGroup>();
+        app.register_type::<LightGroups>();
+        app.init_resource::<LightGroups>();
+        app.register_type::<LightModulator>();
+        app.add_systems(
+            PostUpdate,
+            update_moving_lights.after(TransformSystems::Propagate),
+        );
+
         if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app.init_resource::<LightBindGroups>();
             render_app.init_resource::<DrawFunctions<LightmapPhase>>();
-            render_app.init_resource::<ViewBinnedRenderPhases<LightmapPhase>>();
+            render_app.init_resource::<ViewSortedRenderPhases<LightmapPhase>>();
 
             render_app.add_render_command::<LightmapPhase, DrawLightmap>();
 
             render_app.add_systems(
                 Render,
-                sort_binned_render_phase::<LightmapPhase>.in_set(RenderSystems::PhaseSort),
+                sort_phase_system::<LightmapPhase>.in_set(RenderSystems::PhaseSort),
             );
 
             render_app.add_systems(Render, queue_lights.in_set(RenderSystems::Queue));
@@ -342,6 +1115,36 @@ impl Plugin for LightPlugin {
     }
 }
 
+fn update_moving_lights(
+    mut lights: Query<(
+        &GlobalTransform,
+        &PointLight2d,
+        &mut MovingLight,
+        &mut Changes,
+    )>,
+) {
+    for (transform, light, mut moving, mut changes) in &mut lights {
+        let pos = transform.translation().truncate() + light.offset.xy();
+
+        let mut streak = moving.previous_pos.map_or(Vec2::ZERO, |previous| {
+            (previous - pos) * moving.length_scale.max(0.)
+        });
+        if let Some(max_length) = moving.max_length {
+            streak = streak.clamp_length_max(max_length.max(0.));
+        }
+
+        // the streak is part of the light's uniform, so it needs to be re-uploaded when it changes
+        if streak != moving.streak {
+            changes.parameters = true;
+        }
+
+        if moving.previous_pos != Some(pos) || streak != moving.streak {
+            moving.previous_pos = Some(pos);
+            moving.streak = streak;
+        }
+    }
+}
+
 #[derive(Resource, Deref, DerefMut, Default)]
 pub(crate) struct LightBatches(pub HashMap<(RetainedViewEntity, Entity), LightBatch>);
 
@@ -358,33 +1161,52 @@ pub(crate) struct LightBindGroups {
 #[derive(Component)]
 pub(crate) struct LightLut(pub BindGroup);
 
+/// Render World component storing the key of the pipeline a view's lights are drawn with.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ViewLightmapCreationKey(pub LightmapCreationPipelineKey);
+
 fn queue_lights(
+    mut commands: Commands,
     light_draw_functions: Res<DrawFunctions<LightmapPhase>>,
     pipeline: Res<LightmapCreationPipeline>,
     mut pipelines: ResMut<SpecializedRenderPipelines<LightmapCreationPipeline>>,
-    mut lightmap_phases: ResMut<ViewBinnedRenderPhases<LightmapPhase>>,
+    mut lightmap_phases: ResMut<ViewSortedRenderPhases<LightmapPhase>>,
     views: Query<(
+        Entity,
         &ExtractedView,
         &RenderVisibleEntities,
         &Msaa,
+        &FireflyConfig,
         Option<&Tonemapping>,
         Option<&DebandDither>,
         Option<&ExtractedCombineLightmapTo>,
     )>,
+    lights: Query<&ExtractedPointLight>,
     pipeline_cache: Res<PipelineCache>,
 ) {
     let draw_lightmap_function = light_draw_functions.read().id::<DrawLightmap>();
 
-    for (view, visible_entities, msaa, tonemapping, dither, combined_lightmap) in &views {
+    for (
+        view_entity,
+        view,
+        visible_entities,
+        msaa,
+        config,
+        tonemapping,
+        dither,
+        combined_lightmap,
+    ) in &views
+    {
         let Some(lightmap_phase) = lightmap_phases.get_mut(&view.retained_view_entity) else {
             continue;
         };
 
-        let (hdr, msaa) = if let Some(combined_lightmap) = combined_lightmap {
+        // combined lightmaps are rendered into the target camera's lightmap array
+        let (hdr, msaa, config) = if let Some(combined_lightmap) = combined_lightmap {
             let view = views.get(combined_lightmap.0).unwrap();
-            (view.0.hdr, view.2)
+            (view.1.hdr, view.3, view.4)
         } else {
-            (view.hdr, msaa)
+            (view.hdr, msaa, config)
         };
 
         let msaa_key = LightPipelineKey::from_msaa_samples(msaa.samples());
@@ -413,22 +1235,30 @@ fn queue_lights(
             }
         }
 
-        let pipeline = pipelines.specialize(&pipeline_cache, &pipeline, view_key);
+        let key = LightmapCreationPipelineKey {
+            view_key,
+            format: config.lightmap_texture_format(hdr),
+        };
+        let pipeline = pipelines.specialize(&pipeline_cache, &pipeline, key);
+
+        commands
+            .entity(view_entity)
+            .insert(ViewLightmapCreationKey(key));
 
         for (render_entity, visible_entity) in visible_entities.iter::<PointLight2d>() {
-            let batch_set_key = LightBatchSetKey {
+            let sort_key = lights
+                .get(*render_entity)
+                .map_or(LightSortKey::default(), |light| light.sort_key);
+
+            // sorted so the draw order doesn't depend on the order the visible entities were collected in
+            lightmap_phase.add(LightmapPhase {
+                sort_key: (sort_key, visible_entity.id()),
+                entity: (*render_entity, *visible_entity),
                 pipeline,
                 draw_function: draw_lightmap_function,
-            };
-
-            lightmap_phase.add(
-                batch_set_key,
-                (),
-                (*render_entity, *visible_entity),
-                InputUniformIndex::default(),
-                BinnedRenderPhaseType::NonMesh,
-                Tick::new(10),
-            );
+                batch_range: 0..1,
+                extra_index: PhaseItemExtraIndex::None,
+            });
         }
     }
 }
@@ -0,0 +1,232 @@
+//! Module containing the [`LineLight2d`] component, a light emitted along a line or polyline.
+
+use bevy::{prelude::*, transform::TransformSystems};
+
+use crate::lights::{Falloff, LightCore, PointLight2d};
+
+/// Light emitted along a line or polyline, such as a neon sign or a glowing floor strip.
+///
+/// Every segment of the line is rendered as its own [`PointLight2d`], spawned as a child of this entity, whose
+/// falloff is measured from the segment instead of a single point. Shadows are cast from the point of the segment
+/// closest to each pixel, so occluders along the line shadow only the parts of the scene they actually block.
+///
+/// The segments are joined by round caps. The inner side of sharp bends is lit by both of its segments, so it can be
+/// brighter than the rest of the line.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     LineLight2d::polyline(vec![vec2(-50., 0.), vec2(0., 20.), vec2(50., 0.)])
+///         .with_color(Color::srgb(1.0, 0.2, 0.8))
+///         .with_width(4.),
+///     Transform::from_translation(vec3(0., 100., 0.)),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(Transform, Visibility)]
+pub struct LineLight2d {
+    /// Points of the line, relative to the entity's [`Transform`]. Each pair of consecutive points is a segment.
+    ///
+    /// **Default:** A horizontal line, 100 units long.
+    pub points: Vec<Vec2>,
+
+    /// Width of the fully lit line itself. Used as the [core](PointLight2d::core) of every segment.
+    ///
+    /// **Default:** 10.
+    pub width: f32,
+
+    /// Color of the light. Alpha is ignored.
+    ///
+    /// **Default:** White.
+    pub color: Color,
+
+    /// Intensity of the light.
+    ///
+    /// **Default:** 1.
+    pub intensity: f32,
+
+    /// How far the light reaches from the line.
+    ///
+    /// **Default:** 100.
+    pub radius: f32,
+
+    /// Falloff of the light, measured from the line.
+    ///
+    /// **Default:** InverseSquare.
+    pub falloff: Falloff,
+
+    /// Whether this light should cast shadows.
+    ///
+    /// **Default:** true.
+    pub cast_shadows: bool,
+
+    /// How dark the shadows cast by this light are, from 0 to 1.
+    ///
+    /// **Default:** 1.
+    pub shadow_strength: f32,
+}
+
+impl Default for LineLight2d {
+    fn default() -> Self {
+        Self {
+            points: vec![vec2(-50., 0.), vec2(50., 0.)],
+            width: 10.,
+            color: Color::WHITE,
+            intensity: 1.,
+            radius: 100.,
+            falloff: Falloff::InverseSquare { intensity: 0.0 },
+            cast_shadows: true,
+            shadow_strength: 1.,
+        }
+    }
+}
+
+impl LineLight2d {
+    /// Construct a new line light with a single segment between the given points.
+    pub fn new(start: Vec2, end: Vec2) -> Self {
+        Self::polyline(vec![start, end])
+    }
+
+    /// Construct a new line light along the given [points](LineLight2d::points).
+    pub fn polyline(points: Vec<Vec2>) -> Self {
+        Self {
+            points,
+            ..default()
+        }
+    }
+
+    /// Construct a new line light with the specified [width](LineLight2d::width).
+    pub fn with_width(self, width: f32) -> Self {
+        Self { width, ..self }
+    }
+
+    /// Construct a new line light with the specified [color](LineLight2d::color).
+    pub fn with_color(self, color: Color) -> Self {
+        Self { color, ..self }
+    }
+
+    /// Construct a new line light with the specified [intensity](LineLight2d::intensity).
+    pub fn with_intensity(self, intensity: f32) -> Self {
+        Self { intensity, ..self }
+    }
+
+    /// Construct a new line light with the specified [radius](LineLight2d::radius).
+    pub fn with_radius(self, radius: f32) -> Self {
+        Self { radius, ..self }
+    }
+
+    /// Construct a new line light with the specified [falloff](LineLight2d::falloff).
+    pub fn with_falloff(self, falloff: Falloff) -> Self {
+        Self { falloff, ..self }
+    }
+
+    fn segment_light(&self, offset: Vec3) -> PointLight2d {
+        PointLight2d {
+            color: self.color,
+            intensity: self.intensity,
+            radius: self.radius,
+            falloff: self.falloff,
+            core: LightCore {
+                radius: self.width.max(0.) / 2.,
+                ..default()
+            },
+            cast_shadows: self.cast_shadows,
+            shadow_strength: self.shadow_strength,
+            offset,
+            ..default()
+        }
+    }
+}
+
+/// Component added to the segments of a [`LineLight2d`].
+#[derive(Component, Clone, Copy, Debug)]
+pub struct LineLightSegment {
+    /// The line light this is a segment of.
+    pub line: Entity,
+    /// Index of the segment's first point in the line's [points](LineLight2d::points).
+    pub index: usize,
+    /// Offset from the start of the segment to its end, in world space.
+    pub vector: Vec2,
+    /// Whether the segment has a round cap at its end. Only the last segment does, since the others
+    /// are capped by the start of the next segment.
+    pub end_cap: bool,
+}
+
+/// Plugin that keeps the segments of every [`LineLight2d`] in sync with their line. Added automatically
+/// by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct LineLightPlugin;
+
+impl Plugin for LineLightPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LineLight2d>();
+        app.add_systems(
+            PostUpdate,
+            update_line_lights
+                .after(TransformSystems::Propagate)
+                .before(bevy::camera::visibility::VisibilitySystems::CheckVisibility),
+        );
+    }
+}
+
+fn update_line_lights(
+    mut commands: Commands,
+    lines: Query<(Entity, Ref<LineLight2d>, Ref<GlobalTransform>)>,
+    mut segments: Query<(Entity, &mut LineLightSegment, &mut PointLight2d)>,
+) {
+    for (entity, mut segment, mut light) in &mut segments {
+        // segments of removed or changed lines are respawned below
+        let Ok((_, line, transform)) = lines.get(segment.line) else {
+            commands.entity(entity).try_despawn();
+            continue;
+        };
+        if line.is_changed() {
+            commands.entity(entity).try_despawn();
+            continue;
+        }
+        if !transform.is_changed() {
+            continue;
+        }
+
+        let start = transform.transform_point(line.points[segment.index].extend(0.));
+        let end = transform.transform_point(line.points[segment.index + 1].extend(0.));
+
+        let offset = (start - transform.translation()).truncate().extend(0.);
+        if light.offset != offset {
+            light.offset = offset;
+        }
+
+        let vector = (end - start).truncate();
+        if segment.vector != vector {
+            segment.vector = vector;
+        }
+    }
+
+    for (entity, line, transform) in &lines {
+        if !line.is_changed() {
+            continue;
+        }
+
+        let n_segments = line.points.len().saturating_sub(1);
+        for index in 0..n_segments {
+            let start = transform.transform_point(line.points[index].extend(0.));
+            let end = transform.transform_point(line.points[index + 1].extend(0.));
+
+            commands.spawn((
+                LineLightSegment {
+                    line: entity,
+                    index,
+                    vector: (end - start).truncate(),
+                    end_cap: index + 1 == n_segments,
+                },
+                line.segment_light((start - transform.translation()).truncate().extend(0.)),
+                Transform::default(),
+                // already placed, so the segment isn't lit from the origin until the next propagation
+                *transform,
+                ChildOf(entity),
+            ));
+        }
+    }
+}
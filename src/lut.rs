@@ -0,0 +1,246 @@
+//! Module containing [`LutAtlas`], which bakes assets into rows of a GPU lookup texture.
+//!
+//! This is the machinery behind the [`ColorGradient`](crate::prelude::ColorGradient) lookup tables, exposed so that
+//! custom light types and shaders can bake their own curves, such as falloffs, with the same caching and hot reloading.
+
+use std::marker::PhantomData;
+
+use bevy::{
+    platform::collections::HashMap,
+    prelude::*,
+    render::{
+        Extract, ExtractSchedule, Render, RenderApp, RenderSystems,
+        render_resource::{
+            Extent3d, TextureDataOrder, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureUsages, TextureView, TextureViewDescriptor,
+        },
+        renderer::{RenderDevice, RenderQueue},
+    },
+};
+
+/// Asset that can be baked into a row of a [`LutAtlas`].
+///
+/// # Example
+///
+/// ```
+/// #[derive(Asset, TypePath, Clone)]
+/// struct CustomFalloff(Falloff);
+///
+/// impl LutAsset for CustomFalloff {
+///     const LABEL: &'static str = "custom falloff luts";
+///
+///     fn sample(&self, t: f32) -> LinearRgba {
+///         LinearRgba::gray(self.0.evaluate(t))
+///     }
+/// }
+///
+/// app.add_plugins(LutPlugin::<CustomFalloff>::default());
+/// ```
+pub trait LutAsset: Asset + Clone {
+    /// Number of samples each asset is baked into.
+    const LUT_SIZE: u32 = 64;
+
+    /// Label of the lookup texture, shown in graphics debuggers.
+    const LABEL: &'static str = "luts";
+
+    /// Sample the asset at the given position, between 0 and 1.
+    fn sample(&self, t: f32) -> LinearRgba;
+}
+
+/// Render World resource containing all the assets of type `T` that changed or were removed this frame.
+#[derive(Resource)]
+struct ExtractedLutAssets<T: LutAsset> {
+    changed: Vec<(AssetId<T>, T)>,
+    removed: Vec<AssetId<T>>,
+}
+
+impl<T: LutAsset> Default for ExtractedLutAssets<T> {
+    fn default() -> Self {
+        Self {
+            changed: vec![],
+            removed: vec![],
+        }
+    }
+}
+
+/// Render World resource containing the texture every [`LutAsset`] of type `T` is baked into, one row per asset.
+///
+/// The texture is [`Rgba32Float`](TextureFormat::Rgba32Float), [`LutAsset::LUT_SIZE`] texels wide, and isn't filterable,
+/// so it has to be read with `textureLoad`. Assets are rebaked whenever they're modified, including when they're hot reloaded.
+#[derive(Resource)]
+pub struct LutAtlas<T: LutAsset> {
+    texture_view: TextureView,
+    rows: HashMap<AssetId<T>, u32>,
+    free_rows: Vec<u32>,
+    data: Vec<[f32; 4]>,
+    changed: bool,
+}
+
+impl<T: LutAsset> FromWorld for LutAtlas<T> {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        let queue = world.resource::<RenderQueue>();
+
+        let data = vec![[1.0; 4]; T::LUT_SIZE as usize];
+        let texture_view = Self::create_texture(device, queue, &data);
+
+        Self {
+            texture_view,
+            rows: default(),
+            free_rows: vec![],
+            data,
+            changed: false,
+        }
+    }
+}
+
+impl<T: LutAsset> LutAtlas<T> {
+    /// Get the row an asset was baked into. Rows start at 1, 0 meaning the asset isn't (yet) available.
+    pub fn row(&self, id: AssetId<T>) -> u32 {
+        self.rows.get(&id).map_or(0, |row| row + 1)
+    }
+
+    /// Get the view of the lookup texture. It's recreated every frame any asset [changed](LutAtlas::changed),
+    /// so bind groups using it have to be recreated as well.
+    pub fn texture_view(&self) -> &TextureView {
+        &self.texture_view
+    }
+
+    /// Returns true if any asset was baked or removed this frame, in which case the texture was recreated.
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+
+    fn create_texture(
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        data: &[[f32; 4]],
+    ) -> TextureView {
+        let texture = device.create_texture_with_data(
+            queue,
+            &TextureDescriptor {
+                label: Some(T::LABEL),
+                size: Extent3d {
+                    width: T::LUT_SIZE,
+                    height: (data.len() as u32 / T::LUT_SIZE).max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba32Float,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(data),
+        );
+
+        texture.create_view(&TextureViewDescriptor::default())
+    }
+}
+
+/// Plugin that extracts the assets of type `T` and bakes them into a [`LutAtlas<T>`].
+///
+/// [`ColorGradient`](crate::prelude::ColorGradient)s are baked automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin),
+/// other [`LutAsset`]s need this plugin to be added. The asset itself has to be initialized separately.
+pub struct LutPlugin<T: LutAsset>(PhantomData<T>);
+
+impl<T: LutAsset> Default for LutPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: LutAsset> Plugin for LutPlugin<T> {
+    fn build(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<ExtractedLutAssets<T>>()
+            .add_systems(ExtractSchedule, extract_lut_assets::<T>)
+            .add_systems(
+                Render,
+                prepare_lut_atlas::<T>.in_set(RenderSystems::PrepareAssets),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.init_resource::<LutAtlas<T>>();
+    }
+}
+
+fn extract_lut_assets<T: LutAsset>(
+    mut extracted: ResMut<ExtractedLutAssets<T>>,
+    mut events: Extract<MessageReader<AssetEvent<T>>>,
+    assets: Extract<Res<Assets<T>>>,
+) {
+    extracted.changed.clear();
+    extracted.removed.clear();
+
+    for event in events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                if let Some(asset) = assets.get(*id) {
+                    extracted.changed.push((*id, asset.clone()));
+                }
+            }
+            AssetEvent::Removed { id } => extracted.removed.push(*id),
+            AssetEvent::Unused { .. } | AssetEvent::LoadedWithDependencies { .. } => {}
+        }
+    }
+}
+
+// bakes changed assets into their row of the lookup texture
+fn prepare_lut_atlas<T: LutAsset>(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    extracted: Res<ExtractedLutAssets<T>>,
+    mut atlas: ResMut<LutAtlas<T>>,
+) {
+    let atlas = &mut *atlas;
+    atlas.changed = false;
+
+    for id in &extracted.removed {
+        if let Some(row) = atlas.rows.remove(id) {
+            atlas.free_rows.push(row);
+            atlas.changed = true;
+        }
+    }
+
+    for (id, asset) in &extracted.changed {
+        let row = match atlas.rows.get(id) {
+            Some(row) => *row,
+            None => {
+                // without free rows, every allocated row is in use
+                let row = atlas.free_rows.pop().unwrap_or(atlas.rows.len() as u32);
+                atlas.rows.insert(*id, row);
+                row
+            }
+        };
+
+        let start = (row * T::LUT_SIZE) as usize;
+        let end = start + T::LUT_SIZE as usize;
+        if atlas.data.len() < end {
+            atlas.data.resize(end, [1.0; 4]);
+        }
+
+        for (i, texel) in atlas.data[start..end].iter_mut().enumerate() {
+            let t = i as f32 / (T::LUT_SIZE - 1) as f32;
+            *texel = asset.sample(t).to_f32_array();
+        }
+
+        atlas.changed = true;
+    }
+
+    if atlas.changed {
+        atlas.texture_view =
+            LutAtlas::<T>::create_texture(&render_device, &render_queue, &atlas.data);
+    }
+}
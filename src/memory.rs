@@ -0,0 +1,158 @@
+//! Module containing [`FireflyMemoryStats`], for tracking how much VRAM Firefly uses.
+
+use std::sync::{Arc, Mutex};
+
+use bevy::{
+    prelude::*,
+    render::{Render, RenderApp, RenderSystems, texture::CachedTexture},
+};
+
+use crate::{
+    AssistMaskTexture, CombinedLightMapTextures, LightMapTexture, NormalMapTexture,
+    SpriteIdTexture, SpriteStencilTexture,
+    buffers::{BinBuffers, BufferManager, VertexBuffer},
+    lights::UniformPointLight,
+    occluders::{UniformOccluder, UniformRoundOccluder},
+};
+
+/// Resource containing the GPU memory used by Firefly, in bytes. Updated every frame.
+///
+/// The values are gathered in the render world, so they lag one frame behind.
+/// Sizes are computed from the dimensions and formats of textures and buffers, and don't
+/// include any driver overhead.
+///
+/// # Example
+///
+/// ```
+/// fn log_memory(stats: Res<FireflyMemoryStats>) {
+///     info!("Firefly is using {} KiB of VRAM", stats.total() / 1024);
+/// }
+/// ```
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FireflyMemoryStats {
+    /// Lightmaps of all cameras, including the arrays of [combined lightmaps](crate::prelude::CombinedLightmaps).
+    pub lightmaps: u64,
+
+    /// Sprite stencils of all cameras.
+    pub sprite_stencils: u64,
+
+    /// Normal map textures of all cameras.
+    pub normal_maps: u64,
+
+    /// Masks of the [accessibility assist](crate::prelude::AccessibilityAssist) of all cameras.
+    pub assist_masks: u64,
+
+    /// Global buffer of lights.
+    pub light_buffer: u64,
+
+    /// Global buffers of round occluders, polygonal occluders and their vertices.
+    pub occluder_buffers: u64,
+
+    /// Per-light, per-camera buffers of binned occluders.
+    pub bin_buffers: u64,
+}
+
+impl FireflyMemoryStats {
+    /// Total of all textures, in bytes.
+    pub fn textures(&self) -> u64 {
+        self.lightmaps + self.sprite_stencils + self.normal_maps + self.assist_masks
+    }
+
+    /// Total of all buffers, in bytes.
+    pub fn buffers(&self) -> u64 {
+        self.light_buffer + self.occluder_buffers + self.bin_buffers
+    }
+
+    /// Total GPU memory used by Firefly, in bytes.
+    pub fn total(&self) -> u64 {
+        self.textures() + self.buffers()
+    }
+}
+
+/// Stats shared between the main world and the render world.
+#[derive(Resource, Clone, Default)]
+struct SharedMemoryStats(Arc<Mutex<FireflyMemoryStats>>);
+
+/// Plugin that keeps [`FireflyMemoryStats`] up to date. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct MemoryStatsPlugin;
+
+impl Plugin for MemoryStatsPlugin {
+    fn build(&self, app: &mut App) {
+        let shared = SharedMemoryStats::default();
+
+        app.register_type::<FireflyMemoryStats>();
+        app.init_resource::<FireflyMemoryStats>();
+        app.insert_resource(shared.clone());
+        app.add_systems(First, read_memory_stats);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.insert_resource(shared);
+        render_app.add_systems(Render, write_memory_stats.in_set(RenderSystems::Cleanup));
+    }
+}
+
+fn read_memory_stats(shared: Res<SharedMemoryStats>, mut stats: ResMut<FireflyMemoryStats>) {
+    let Ok(shared) = shared.0.lock() else {
+        return;
+    };
+
+    stats.set_if_neq(*shared);
+}
+
+fn write_memory_stats(
+    shared: Res<SharedMemoryStats>,
+    lightmaps: Query<&LightMapTexture>,
+    combined_lightmaps: Query<&CombinedLightMapTextures>,
+    stencils: Query<&SpriteStencilTexture>,
+    normal_maps: Query<&NormalMapTexture>,
+    assist_masks: Query<&AssistMaskTexture>,
+    sprite_ids: Query<&SpriteIdTexture>,
+    bins: Query<&BinBuffers>,
+    lights: Option<Res<BufferManager<UniformPointLight>>>,
+    round_occluders: Option<Res<BufferManager<UniformRoundOccluder>>>,
+    poly_occluders: Option<Res<BufferManager<UniformOccluder>>>,
+    vertices: Option<Res<VertexBuffer>>,
+) {
+    let stats = FireflyMemoryStats {
+        lightmaps: lightmaps.iter().map(|t| texture_size(&t.0)).sum::<u64>()
+            + combined_lightmaps
+                .iter()
+                .map(|t| texture_size(&t.0))
+                .sum::<u64>(),
+        sprite_stencils: stencils.iter().map(|t| texture_size(&t.0)).sum::<u64>()
+            + sprite_ids.iter().map(|t| texture_size(&t.0)).sum::<u64>(),
+        normal_maps: normal_maps.iter().map(|t| texture_size(&t.0)).sum(),
+        assist_masks: assist_masks.iter().map(|t| texture_size(&t.0)).sum(),
+        light_buffer: lights.map_or(0, |lights| lights.gpu_size()),
+        occluder_buffers: round_occluders.map_or(0, |buffer| buffer.gpu_size())
+            + poly_occluders.map_or(0, |buffer| buffer.gpu_size())
+            + vertices.map_or(0, |buffer| buffer.gpu_size()),
+        bin_buffers: bins
+            .iter()
+            .flat_map(|bins| bins.0.values())
+            .map(|bin| bin.gpu_size())
+            .sum(),
+    };
+
+    if let Ok(mut shared) = shared.0.lock() {
+        *shared = stats;
+    }
+}
+
+/// Size of a texture's first mip level, in bytes.
+fn texture_size(texture: &CachedTexture) -> u64 {
+    let texture = &texture.texture;
+    let size = texture.size();
+    let block_size = texture.format().block_copy_size(None).unwrap_or_default() as u64;
+
+    size.width as u64
+        * size.height as u64
+        * size.depth_or_array_layers as u64
+        * texture.sample_count() as u64
+        * block_size
+}
@@ -4,25 +4,34 @@ use bevy::{
     ecs::{query::QueryItem, system::lifetimeless::Read},
     prelude::*,
     render::{
+        render_asset::RenderAssets,
         render_graph::{NodeRunError, RenderGraphContext, ViewNode},
-        render_phase::{ViewBinnedRenderPhases, ViewSortedRenderPhases},
+        render_phase::ViewSortedRenderPhases,
         render_resource::{
             BindGroupEntries, PipelineCache, RenderPassColorAttachment, RenderPassDescriptor,
             TextureAspect, TextureFormat, TextureUsages, TextureViewDescriptor,
             TextureViewDimension,
         },
         renderer::RenderContext,
+        texture::{FallbackImage, GpuImage},
         view::{ExtractedView, ViewTarget},
     },
 };
 
 use crate::{
-    CombinedLightMapTextures, LightMapTexture, LightmapPhase, NormalMapTexture,
-    SpriteStencilTexture,
-    data::ExtractedCombineLightmapTo,
-    phases::SpritePhase,
+    AssistMaskTexture, CombinedLightMapTextures, LightMapTexture, LightmapPhase, SpriteIdTexture,
+    caustics::render_caustics,
+    data::{ExtractedCombineLightmapTo, FireflyConfig},
+    exposure::{compute_auto_exposure, exposure_binding},
+    flare::render_lens_flares,
+    outline::DarknessOutlineBuffer,
     pipelines::{LightmapApplicationPipeline, SpecializedApplicationPipeline},
     prepare::BufferedFireflyConfig,
+    weather::ExtractedWeatherOverlay,
+};
+#[cfg(feature = "sprites")]
+use crate::{
+    NormalMapTexture, SpriteStencilTexture, phases::SpritePhase, prepare::SkipSpriteStencil,
 };
 
 /// Node used to create the lightmap.
@@ -43,7 +52,7 @@ impl ViewNode for CreateLightmapNode {
         (view, lightmap_texture, combine_lightmap_to): QueryItem<'w, '_, Self::ViewQuery>,
         world: &'w World,
     ) -> Result<(), NodeRunError> {
-        let Some(lightmap_phases) = world.get_resource::<ViewBinnedRenderPhases<LightmapPhase>>()
+        let Some(lightmap_phases) = world.get_resource::<ViewSortedRenderPhases<LightmapPhase>>()
         else {
             return Ok(());
         };
@@ -64,10 +73,10 @@ impl ViewNode for CreateLightmapNode {
                 .unwrap()
                 .hdr;
 
-            let format = match hdr {
-                true => ViewTarget::TEXTURE_FORMAT_HDR,
-                false => TextureFormat::bevy_default(),
-            };
+            let format = world
+                .get::<FireflyConfig>(combine_lightmap_to.0)
+                .unwrap()
+                .lightmap_texture_format(hdr);
             // &lightmap.0.default_view
             &lightmap.0.texture.create_view(&TextureViewDescriptor {
                 label: "layer of combined lightmap texture array".into(),
@@ -99,39 +108,58 @@ impl ViewNode for CreateLightmapNode {
         if let Err(err) = lightmap_phase.render(&mut render_pass, world, view_entity) {
             error!("Error encountered while rendering the stencil phase {err:?}");
         }
+
+        render_caustics(&mut render_pass, world, view_entity);
+        drop(render_pass);
+
+        // combined lightmaps are measured as part of the view they're combined into
+        if combine_lightmap_to.is_none() {
+            compute_auto_exposure(render_context, world, view_entity, &lightmap_texture.0);
+        }
         Ok(())
     }
 }
 
 /// Node used to apply the lightmap over the fullscreen view.
+///
+/// Two instances of this node are added to the render graph, one before and one after tonemapping.
+/// Only the one matching [`apply_before_tonemapping`](crate::prelude::FireflyConfig::apply_before_tonemapping) runs.
 #[derive(Default)]
-pub struct ApplyLightmapNode;
+pub struct ApplyLightmapNode<const AFTER_TONEMAPPING: bool>;
 
-impl ViewNode for ApplyLightmapNode {
+impl<const AFTER_TONEMAPPING: bool> ViewNode for ApplyLightmapNode<AFTER_TONEMAPPING> {
     type ViewQuery = (
         Read<SpecializedApplicationPipeline>,
+        Read<FireflyConfig>,
         Read<BufferedFireflyConfig>,
         Read<ViewTarget>,
         Read<LightMapTexture>,
+        Read<AssistMaskTexture>,
+        Read<SpriteIdTexture>,
         Option<Read<CombinedLightMapTextures>>,
         Has<ExtractedCombineLightmapTo>,
+        Option<Read<ExtractedWeatherOverlay>>,
     );
 
     fn run<'w>(
         &self,
-        _graph: &mut bevy::render::render_graph::RenderGraphContext,
+        graph: &mut bevy::render::render_graph::RenderGraphContext,
         render_context: &mut RenderContext<'w>,
         (
             pipeline_id,
+            firefly_config,
             config,
             view_target,
             light_map_texture,
+            assist_mask_texture,
+            sprite_id_texture,
             combined_textures,
-            is_combined_to
+            is_combined_to,
+            weather,
         ): bevy::ecs::query::QueryItem<'w, '_, Self::ViewQuery>,
         world: &'w World,
     ) -> std::result::Result<(), NodeRunError> {
-        if is_combined_to {
+        if is_combined_to || firefly_config.apply_before_tonemapping == AFTER_TONEMAPPING {
             return Ok(());
         }
 
@@ -152,6 +180,19 @@ impl ViewNode for ApplyLightmapNode {
             false => TextureFormat::bevy_default(),
         };
 
+        let outlines = world.resource::<DarknessOutlineBuffer>();
+        let exposure = exposure_binding(world, graph.view_entity());
+
+        // without a weather overlay, the shader skips sampling the texture
+        let fallback_image = world.resource::<FallbackImage>();
+        let weather_texture = weather
+            .and_then(|weather| {
+                world
+                    .resource::<RenderAssets<GpuImage>>()
+                    .get(weather.image)
+            })
+            .map_or(&fallback_image.d2.texture_view, |image| &image.texture_view);
+
         let bind_group = if !pipeline_id.is_combined {
             render_context.render_device().create_bind_group(
                 "apply lightmap bind group simple",
@@ -159,16 +200,25 @@ impl ViewNode for ApplyLightmapNode {
                     &pipeline
                         .specialize_layout(pipeline_id.is_combined, pipeline_id.filter_lightmap),
                 ),
-                &BindGroupEntries::sequential((
-                    post_process.source,
-                    &light_map_texture.0.default_view,
-                    &pipeline.filtering_sampler,
-                    if pipeline_id.filter_lightmap {
-                        &pipeline.filtering_sampler
-                    } else {
-                        &pipeline.non_filtering_sampler
-                    },
-                    config,
+                &BindGroupEntries::with_indices((
+                    (0, post_process.source),
+                    (1, &light_map_texture.0.default_view),
+                    (2, &pipeline.filtering_sampler),
+                    (
+                        3,
+                        if pipeline_id.filter_lightmap {
+                            &pipeline.filtering_sampler
+                        } else {
+                            &pipeline.non_filtering_sampler
+                        },
+                    ),
+                    (4, config),
+                    (5, &assist_mask_texture.0.default_view),
+                    (7, weather_texture),
+                    (8, &pipeline.repeat_sampler),
+                    (9, &sprite_id_texture.0.default_view),
+                    (10, outlines.binding()),
+                    (11, exposure.clone()),
                 )),
             )
         } else {
@@ -197,13 +247,19 @@ impl ViewNode for ApplyLightmapNode {
                     &pipeline
                         .specialize_layout(pipeline_id.is_combined, pipeline_id.filter_lightmap),
                 ),
-                &BindGroupEntries::sequential((
-                    post_process.source,
-                    &light_map_texture.0.default_view,
-                    &pipeline.filtering_sampler,
-                    &pipeline.filtering_sampler,
-                    config,
-                    &combined_view,
+                &BindGroupEntries::with_indices((
+                    (0, post_process.source),
+                    (1, &light_map_texture.0.default_view),
+                    (2, &pipeline.filtering_sampler),
+                    (3, &pipeline.filtering_sampler),
+                    (4, config),
+                    (5, &assist_mask_texture.0.default_view),
+                    (6, &combined_view),
+                    (7, weather_texture),
+                    (8, &pipeline.repeat_sampler),
+                    (9, &sprite_id_texture.0.default_view),
+                    (10, outlines.binding()),
+                    (11, exposure.clone()),
                 )),
             )
         };
@@ -224,26 +280,48 @@ impl ViewNode for ApplyLightmapNode {
         render_pass.set_render_pipeline(render_pipeline);
         render_pass.set_bind_group(0, &bind_group, &[]);
         render_pass.draw(0..3, 0..1);
+        drop(render_pass);
+
+        render_lens_flares(
+            render_context,
+            world,
+            graph.view_entity(),
+            post_process.destination,
+        );
         Ok(())
     }
 }
 
+#[cfg(feature = "sprites")]
 #[derive(Default)]
 pub(crate) struct SpriteNode;
+
+#[cfg(feature = "sprites")]
 impl ViewNode for SpriteNode {
     type ViewQuery = (
         &'static ExtractedView,
         Read<SpriteStencilTexture>,
         Read<NormalMapTexture>,
+        Read<AssistMaskTexture>,
+        Read<SpriteIdTexture>,
+        Has<SkipSpriteStencil>,
     );
 
     fn run<'w>(
         &self,
         graph: &mut RenderGraphContext,
         render_context: &mut RenderContext<'w>,
-        (view, stencil_texture, normal_map_texture): QueryItem<'w, '_, Self::ViewQuery>,
+        (view, stencil_texture, normal_map_texture, assist_mask_texture, sprite_id_texture, skip): QueryItem<
+            'w,
+            '_,
+            Self::ViewQuery,
+        >,
         world: &'w World,
     ) -> Result<(), NodeRunError> {
+        if skip {
+            return Ok(());
+        }
+
         let Some(sprite_phases) = world.get_resource::<ViewSortedRenderPhases<SpritePhase>>()
         else {
             return Ok(());
@@ -270,6 +348,18 @@ impl ViewNode for SpriteNode {
                     ops: default(),
                     depth_slice: None,
                 }),
+                Some(RenderPassColorAttachment {
+                    view: &assist_mask_texture.0.default_view,
+                    resolve_target: None,
+                    ops: default(),
+                    depth_slice: None,
+                }),
+                Some(RenderPassColorAttachment {
+                    view: &sprite_id_texture.0.default_view,
+                    resolve_target: None,
+                    ops: default(),
+                    depth_slice: None,
+                }),
             ],
             depth_stencil_attachment: None,
             timestamp_writes: None,
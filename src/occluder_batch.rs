@@ -0,0 +1,349 @@
+//! Module containing [`OccluderBatch`], a single component holding many static occluder shapes.
+//!
+//! Every [`Occluder2d`] entity is extracted, indexed and binned on its own, which adds up for procedural levels
+//! that generate thousands of small static occluders. An [`OccluderBatch`] is extracted as a single unit with one
+//! [`Changes`] entry, its shapes are only written to the GPU when the batch changes, and each light only bins the
+//! shapes its range overlaps, found through an internal [`AabbTree`].
+
+use std::sync::Arc;
+
+use bevy::{
+    camera::visibility::{RenderLayers, VisibilityClass, add_visibility_class},
+    math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume},
+    prelude::*,
+    render::sync_world::SyncToRenderWorld,
+};
+
+use crate::{
+    change::Changes,
+    occluders::{ExtractedOccluder, PolyOccluderIndex, RoundOccluderIndex},
+    prelude::Occluder2d,
+    stencil::StencilIds,
+    visibility::{OccluderAabb, VisibilityTimer, shape_aabb},
+};
+
+/// Maximum number of shapes stored in a leaf of an [`AabbTree`].
+const MAX_LEAF_SIZE: usize = 4;
+
+/// A single component holding many static [`Occluder2d`]s.
+///
+/// The occluders are placed relative to the entity's transform using their [offset](Occluder2d::offset),
+/// and the whole batch can be moved and rotated via the [Transform] component. However, since any change
+/// re-uploads all of its shapes, batches are meant for static geometry, such as procedurally generated levels.
+///
+/// # Example
+///
+/// ```
+/// let batch = OccluderBatch::from_occluders(walls.iter().map(|wall| {
+///     Occluder2d::rectangle(16., 16.).with_offset(wall.extend(0.))
+/// }));
+///
+/// commands.spawn(batch);
+/// ```
+#[derive(Component, Clone, Default)]
+#[require(
+    SyncToRenderWorld,
+    Transform,
+    VisibilityClass,
+    ViewVisibility,
+    VisibilityTimer,
+    OccluderAabb,
+    Changes,
+    RenderLayers
+)]
+#[component(on_add = add_visibility_class::<OccluderBatch>)]
+pub struct OccluderBatch {
+    data: Arc<OccluderBatchData>,
+}
+
+/// The shapes of an [`OccluderBatch`] and the tree used to query them, in the batch's local space.
+///
+/// This is shared between the Main World and the Render World, so it's only copied when the batch is modified.
+#[derive(Clone, Default)]
+pub struct OccluderBatchData {
+    occluders: Vec<Occluder2d>,
+    tree: AabbTree,
+}
+
+impl OccluderBatchData {
+    /// Get the batch's occluders.
+    pub fn occluders(&self) -> &[Occluder2d] {
+        &self.occluders
+    }
+
+    /// Get the batch's tree.
+    pub fn tree(&self) -> &AabbTree {
+        &self.tree
+    }
+
+    fn rebuild(&mut self) {
+        let aabbs = self
+            .occluders
+            .iter()
+            .map(|occluder| {
+                shape_aabb(
+                    occluder.shape(),
+                    Isometry2d::from_translation(occluder.offset.truncate()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        self.tree = AabbTree::new(&aabbs);
+    }
+}
+
+impl OccluderBatch {
+    /// Construct a new batch from the given occluders.
+    pub fn from_occluders(occluders: impl IntoIterator<Item = Occluder2d>) -> Self {
+        let mut data = OccluderBatchData {
+            occluders: occluders.into_iter().collect(),
+            tree: default(),
+        };
+        data.rebuild();
+
+        Self {
+            data: Arc::new(data),
+        }
+    }
+
+    /// Construct a new batch with the given occluder added to it.
+    pub fn with_occluder(&self, occluder: Occluder2d) -> Self {
+        let mut res = self.clone();
+        res.push(occluder);
+        res
+    }
+
+    /// Add an occluder to the batch. This rebuilds the batch's tree, so prefer [`extend`](OccluderBatch::extend)
+    /// or [`from_occluders`](OccluderBatch::from_occluders) when adding many.
+    pub fn push(&mut self, occluder: Occluder2d) {
+        self.extend([occluder]);
+    }
+
+    /// Add multiple occluders to the batch.
+    pub fn extend(&mut self, occluders: impl IntoIterator<Item = Occluder2d>) {
+        let data = Arc::make_mut(&mut self.data);
+        data.occluders.extend(occluders);
+        data.rebuild();
+    }
+
+    /// Remove all occluders from the batch.
+    pub fn clear(&mut self) {
+        self.data = default();
+    }
+
+    /// Get the batch's occluders.
+    pub fn occluders(&self) -> &[Occluder2d] {
+        &self.data.occluders
+    }
+
+    /// Number of occluders in the batch.
+    pub fn len(&self) -> usize {
+        self.data.occluders.len()
+    }
+
+    /// Returns true if the batch has no occluders.
+    pub fn is_empty(&self) -> bool {
+        self.data.occluders.is_empty()
+    }
+
+    /// Bounding box of all of the batch's occluders, in the batch's local space.
+    pub fn aabb(&self) -> Aabb2d {
+        self.data.tree.aabb()
+    }
+
+    /// Get the shared data of the batch.
+    pub fn data(&self) -> &Arc<OccluderBatchData> {
+        &self.data
+    }
+}
+
+/// The isometry a batch's occluders are placed with. Like single occluders, only z-axis rotations are allowed.
+pub(crate) fn batch_isometry(transform: &GlobalTransform) -> Isometry2d {
+    Isometry2d {
+        translation: transform.translation().truncate(),
+        rotation: Rot2::radians(transform.rotation().to_euler(EulerRot::XYZ).2),
+    }
+}
+
+/// A static bounding volume hierarchy over a set of [`Aabb2d`]s.
+#[derive(Debug, Clone, Default)]
+pub struct AabbTree {
+    nodes: Vec<AabbNode>,
+    indices: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct AabbNode {
+    aabb: Aabb2d,
+    /// Leaf: range inside `indices`. Internal: index of the right child in `start`, the left child being the next node.
+    start: u32,
+    len: u32,
+}
+
+impl AabbTree {
+    /// Build a tree over the given bounding boxes. Queries return indices into this slice.
+    pub fn new(aabbs: &[Aabb2d]) -> Self {
+        let mut tree = Self {
+            nodes: Vec::with_capacity(aabbs.len().div_ceil(MAX_LEAF_SIZE) * 2),
+            indices: (0..aabbs.len() as u32).collect(),
+        };
+
+        if !aabbs.is_empty() {
+            tree.build(aabbs, 0, aabbs.len());
+        }
+
+        tree
+    }
+
+    fn build(&mut self, aabbs: &[Aabb2d], start: usize, end: usize) {
+        let indices = &mut self.indices[start..end];
+
+        let aabb = indices
+            .iter()
+            .map(|i| aabbs[*i as usize])
+            .reduce(|a, b| a.merge(&b))
+            .unwrap();
+
+        let node = self.nodes.len();
+        self.nodes.push(AabbNode {
+            aabb,
+            start: start as u32,
+            len: (end - start) as u32,
+        });
+
+        if end - start <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        // split at the median along the longest axis
+        let axis = match aabb.half_size().x >= aabb.half_size().y {
+            true => 0,
+            false => 1,
+        };
+        let mid = (end - start) / 2;
+        indices.select_nth_unstable_by(mid, |a, b| {
+            let a = aabbs[*a as usize].center()[axis];
+            let b = aabbs[*b as usize].center()[axis];
+            a.total_cmp(&b)
+        });
+
+        self.build(aabbs, start, start + mid);
+        let right = self.nodes.len() as u32;
+        self.build(aabbs, start + mid, end);
+
+        self.nodes[node].start = right;
+        self.nodes[node].len = 0;
+    }
+
+    /// Bounding box of the whole tree.
+    pub fn aabb(&self) -> Aabb2d {
+        self.nodes
+            .first()
+            .map_or(Aabb2d::new(Vec2::ZERO, Vec2::ZERO), |node| node.aabb)
+    }
+
+    /// Calls `f` with the index of every bounding box that intersects the given one.
+    pub fn query(&self, aabb: &Aabb2d, mut f: impl FnMut(usize)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0u32];
+        while let Some(node) = stack.pop() {
+            let node_index = node;
+            let node = &self.nodes[node as usize];
+            if !node.aabb.intersects(aabb) {
+                continue;
+            }
+
+            if node.len > 0 {
+                let start = node.start as usize;
+                for index in &self.indices[start..start + node.len as usize] {
+                    f(*index as usize);
+                }
+            } else {
+                stack.push(node_index + 1);
+                stack.push(node.start);
+            }
+        }
+    }
+}
+
+/// Component with data extracted to the Render World from an [`OccluderBatch`].
+///
+/// The occluders are only transformed into world space when the batch changes.
+#[derive(Component, Clone)]
+#[require(OccluderBatchIndices)]
+pub struct ExtractedOccluderBatch {
+    pub data: Arc<OccluderBatchData>,
+    pub isometry: Isometry2d,
+    pub occluders: Vec<ExtractedOccluder>,
+    /// True if the batch's shapes were replaced, which means their buffer slots need to be reallocated.
+    pub reallocate: bool,
+    pub changes: Changes,
+    pub render_layers: RenderLayers,
+}
+
+impl ExtractedOccluderBatch {
+    /// Construct the extracted batch, transforming all of its occluders to world space.
+    pub fn new(
+        data: Arc<OccluderBatchData>,
+        transform: &GlobalTransform,
+        render_layers: RenderLayers,
+        stencil_ids: &StencilIds,
+    ) -> Self {
+        let isometry = batch_isometry(transform);
+        let z = transform.translation().z;
+
+        let occluders = data
+            .occluders
+            .iter()
+            .map(|occluder| {
+                let pos = isometry.transform_point(occluder.offset.truncate());
+                ExtractedOccluder {
+                    pos,
+                    rot: isometry.rotation.as_radians(),
+                    shape: occluder.shape().clone(),
+                    aabb: shape_aabb(occluder.shape(), Isometry2d::new(pos, isometry.rotation)),
+                    z: z + occluder.offset.z,
+                    y: pos.y,
+                    color: occluder.color,
+                    opacity: occluder.opacity,
+                    z_sorting: occluder.z_sorting,
+                    shadow_falloff: occluder.shadow_falloff,
+                    distance_opacity: occluder.distance_opacity,
+                    dilation: occluder.dilation,
+                    receivers: occluder
+                        .receivers
+                        .as_ref()
+                        .map(|receivers| receivers.extract(stencil_ids)),
+                    one_way: occluder.one_way.map(|dir| isometry.rotation * *dir),
+                    group: None,
+                    changes: Changes::ALL,
+                    render_layers: render_layers.clone(),
+                }
+            })
+            .collect();
+
+        Self {
+            data,
+            isometry,
+            occluders,
+            reallocate: true,
+            changes: Changes::ALL,
+            render_layers,
+        }
+    }
+
+    /// Calls `f` with the index of every occluder of the batch that intersects the given world-space box.
+    pub fn query(&self, aabb: &Aabb2d, f: impl FnMut(usize)) {
+        let inverse = self.isometry.inverse();
+        let local = aabb.transformed_by(inverse.translation, inverse.rotation);
+        self.data.tree.query(&local, f);
+    }
+}
+
+/// Render World component storing the buffer slots of each occluder in an [`ExtractedOccluderBatch`],
+/// along with their number of vertices so they can be freed once the shapes are gone.
+#[derive(Component, Default)]
+pub struct OccluderBatchIndices(pub Vec<(RoundOccluderIndex, PolyOccluderIndex, u32)>);
@@ -0,0 +1,78 @@
+//! Module containing [`OccluderGroup`], a parent entity whose child occluders move along with it on the GPU.
+//!
+//! Occluders are stored in world space on the GPU, so every occluder that moves has its silhouette written again.
+//! The direct [`Occluder2d`](crate::prelude::Occluder2d) children of an [`OccluderGroup`] are instead stored relative
+//! to the group, which has a single offset in the vertex buffer. When only the group is translated, that offset is
+//! the only value written, and the shader translates the children's cached silhouettes.
+
+use bevy::{prelude::*, render::sync_world::SyncToRenderWorld};
+
+use crate::buffers::BufferIndex;
+
+/// Component for parent entities that own many child occluders, such as vehicles or moving platforms made of
+/// many parts.
+///
+/// Translating the group doesn't re-upload its children's shapes. Rotating or scaling it, or moving a child
+/// relative to it, still does for the affected children. Only direct children are part of the group. Occluder
+/// bins are still rebuilt for the lights around the group, since its children did move.
+///
+/// # Example
+///
+/// ```
+/// commands
+///     .spawn((OccluderGroup, Transform::default()))
+///     .with_children(|train| {
+///         for i in 0..20 {
+///             train.spawn((
+///                 Occluder2d::rectangle(30., 10.),
+///                 Transform::from_translation(vec3(i as f32 * 35., 0., 0.)),
+///             ));
+///         }
+///     });
+/// ```
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(SyncToRenderWorld, Transform)]
+pub struct OccluderGroup;
+
+/// Component with data extracted to the Render World from an [`OccluderGroup`].
+#[derive(Component, Clone, Copy, Debug)]
+#[require(OccluderGroupSlot)]
+pub struct ExtractedOccluderGroup {
+    /// World translation of the group.
+    pub translation: Vec2,
+}
+
+/// The slot of an [`OccluderGroup`]'s offset in the vertex buffer.
+#[derive(Component, Clone, Copy, Default)]
+pub struct OccluderGroupSlot {
+    /// Slot of the offset, None until it's first written.
+    pub index: Option<BufferIndex>,
+    /// Translation written to the slot.
+    pub translation: Vec2,
+}
+
+/// Render World component storing the pose an occluder was last written to the buffers with: its position relative
+/// to its [group](OccluderGroup) along with its z, its rotation and the index of the group's slot.
+///
+/// If it's the same as the occluder's current pose, only the group moved and the occluder isn't written again.
+#[derive(Component, Clone, Copy, Default, PartialEq)]
+pub struct OccluderGroupPose(pub Option<(Vec3, f32, usize)>);
+
+impl OccluderGroupPose {
+    /// Returns true if the given pose is the same as this one, up to the floating point errors caused by
+    /// moving the group.
+    pub fn matches(&self, pose: Option<(Vec3, f32, usize)>) -> bool {
+        match (self.0, pose) {
+            (None, None) => true,
+            (Some((pos, rot, slot)), Some((other_pos, other_rot, other_slot))) => {
+                slot == other_slot && rot == other_rot && pos.abs_diff_eq(other_pos, POSE_EPSILON)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Largest difference between two positions of an occluder relative to its group that are considered the same.
+const POSE_EPSILON: f32 = 0.001;
@@ -1,17 +1,25 @@
 //! Module containing structs and functions relevant to Occluders.
 
 use bevy::{
+    asset::AssetEventSystems,
     camera::visibility::{RenderLayers, VisibilityClass, add_visibility_class},
     color::palettes::css::BLACK,
     math::bounding::{Aabb2d, BoundingVolume},
+    platform::collections::{HashMap, HashSet},
     prelude::*,
     render::{render_resource::ShaderType, sync_world::SyncToRenderWorld},
 };
-use bytemuck::{NoUninit, Pod, Zeroable};
+use bytemuck::{Pod, Zeroable};
 use core::f32;
-
-use crate::visibility::{OccluderAabb, VisibilityTimer};
-use crate::{buffers::BufferIndex, change::Changes};
+use std::marker::PhantomData;
+
+#[cfg(feature = "sprites")]
+use crate::sprite::FireflySprite;
+use crate::stencil::{StencilId, StencilIds};
+use crate::visibility::{OccluderAabb, VisibilityTimer, shape_aabb};
+use crate::{
+    buffers::BufferIndex, change::Changes, lights::PointLight2d, occluder_group::OccluderGroupPose,
+};
 
 /// An occluder that blocks light.
 ///
@@ -57,6 +65,65 @@ pub struct Occluder2d {
     ///
     /// **Default**: [Vec3::ZERO].
     pub offset: Vec3,
+
+    /// How much of the shadow's opacity is lost by the end of its length, between 0 and 1.
+    ///
+    /// The shadow starts at full [opacity](Occluder2d::opacity) at the occluder and fades linearly towards
+    /// `opacity * (1 - shadow_falloff)` at the edge of the light. Useful for softer long shadows, e.g. at dawn and dusk.
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default**: 0.
+    pub shadow_falloff: f32,
+
+    /// Makes the occluder's opacity depend on its distance from each light, letting light through when the light is close.
+    ///
+    /// Useful for fog banks and bushes, which a torch held right next to them can shine through, while far away lights can't.
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default**: None.
+    pub distance_opacity: Option<DistanceOpacity>,
+
+    /// If true and the occluder's entity also has a [`Sprite`] or [`FireflySprite`], the occluder's [color](Occluder2d::color)
+    /// is set to the average color of the sprite's image (weighted by alpha and multiplied with the sprite's tint).
+    ///
+    /// The average is computed once per image, when it's loaded. This makes e.g. stained-glass props cast
+    /// colored shadows without picking their tint by hand. Images that aren't kept in the main world
+    /// (i.e. don't have [`RenderAssetUsages::MAIN_WORLD`](bevy::asset::RenderAssetUsages::MAIN_WORLD)) can't be read.
+    ///
+    /// **Default**: false.
+    pub color_from_sprite: bool,
+
+    /// Distance by which the occluder is dilated when being sorted into the angular bins of each light, in world units.
+    ///
+    /// Increase this if light leaks through the seams or corners where thin occluders (e.g. the walls of a closed room) meet.
+    /// This is added on top of the config's [occluder dilation](crate::prelude::FireflyConfig::occluder_dilation).
+    ///
+    /// **Performance Impact:** Very minor.
+    ///
+    /// **Default**: 0.
+    pub dilation: f32,
+
+    /// Restricts which sprites the occluder's shadow can darken, e.g. to keep a specific prop readable
+    /// in a scripted scene. See [`ShadowReceivers`].
+    ///
+    /// **Performance Impact:** Minor, since the occluder's data is re-uploaded every frame.
+    ///
+    /// **Default**: None.
+    pub receivers: Option<ShadowReceivers>,
+
+    /// Makes the occluder only cast shadows in the given direction, relative to its rotation.
+    ///
+    /// Lights on the side the direction points away from are blocked, while lights on the other side pass through.
+    /// E.g. with [`Dir2::NEG_Y`], a platform blocks lights above it, casting its shadow downward, but lets lights
+    /// below it shine through, matching the semantics of one-way platforms. The side of each light is determined
+    /// relative to the occluder's position, so this is meant for thin occluders such as [platforms](Occluder2d::platform).
+    ///
+    /// **Performance Impact:** None.
+    ///
+    /// **Default**: None.
+    pub one_way: Option<Dir2>,
 }
 
 impl Occluder2d {
@@ -72,6 +139,12 @@ impl Occluder2d {
             color: bevy::prelude::Color::Srgba(BLACK),
             z_sorting: true,
             offset: default(),
+            shadow_falloff: 0.,
+            distance_opacity: None,
+            color_from_sprite: false,
+            dilation: 0.,
+            receivers: None,
+            one_way: None,
         }
     }
 
@@ -103,6 +176,103 @@ impl Occluder2d {
         res
     }
 
+    /// Construct a new occluder with the specified [shadow falloff](Occluder2d::shadow_falloff).
+    pub fn with_shadow_falloff(&self, shadow_falloff: f32) -> Self {
+        let mut res = self.clone();
+        res.shadow_falloff = shadow_falloff;
+        res
+    }
+
+    /// Construct a new occluder with the specified [distance-based opacity](Occluder2d::distance_opacity).
+    pub fn with_distance_opacity(&self, distance_opacity: DistanceOpacity) -> Self {
+        let mut res = self.clone();
+        res.distance_opacity = Some(distance_opacity);
+        res
+    }
+
+    /// Construct a new occluder with the specified [dilation](Occluder2d::dilation).
+    pub fn with_dilation(&self, dilation: f32) -> Self {
+        let mut res = self.clone();
+        res.dilation = dilation;
+        res
+    }
+
+    /// Construct a new occluder that only casts shadows in the given [direction](Occluder2d::one_way).
+    pub fn with_one_way(&self, direction: Dir2) -> Self {
+        let mut res = self.clone();
+        res.one_way = Some(direction);
+        res
+    }
+
+    /// Construct a new occluder whose shadow only darkens the given sprites, see [`ShadowReceivers::Only`].
+    pub fn only_shadow(&self, entities: Vec<Entity>) -> Self {
+        let mut res = self.clone();
+        res.receivers = Some(ShadowReceivers::Only(entities));
+        res
+    }
+
+    /// Construct a new occluder whose shadow never darkens the given sprites, see [`ShadowReceivers::Never`].
+    pub fn never_shadow(&self, entities: Vec<Entity>) -> Self {
+        let mut res = self.clone();
+        res.receivers = Some(ShadowReceivers::Never(entities));
+        res
+    }
+
+    /// Construct a new occluder that [takes its color from its sprite](Occluder2d::color_from_sprite).
+    pub fn with_color_from_sprite(&self, color_from_sprite: bool) -> Self {
+        let mut res = self.clone();
+        res.color_from_sprite = color_from_sprite;
+        res
+    }
+
+    /// Construct a new occluder whose [offset](Occluder2d::offset) is set so that the given anchor point
+    /// of its shape's bounding box is at the entity's position.
+    ///
+    /// The anchor uses the same convention as [`Anchor`](bevy::sprite::Anchor): `(-0.5, -0.5)` is the bottom left corner
+    /// and `(0.5, 0.5)` is the top right. This makes it easy to align an occluder with a sprite that uses the same anchor.
+    ///
+    /// The z component of the offset is kept.
+    pub fn with_anchor(&self, anchor: Vec2) -> Self {
+        let aabb = shape_aabb(&self.shape, Isometry2d::IDENTITY);
+        let offset = -(aabb.center() + anchor * (aabb.max - aabb.min));
+        self.with_offset(offset.extend(self.offset.z))
+    }
+
+    /// Construct an anchored occluder from another occluder. See [`with_anchor`](Occluder2d::with_anchor).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// commands.spawn((
+    ///     Sprite::from_image(asset_server.load("crate.png")),
+    ///     Anchor::BOTTOM_CENTER,
+    ///     // the bottom of the occluder lines up with the bottom of the sprite
+    ///     Occluder2d::anchored(Occluder2d::rectangle(12., 5.), Anchor::BOTTOM_CENTER.as_vec()),
+    /// ));
+    /// ```
+    pub fn anchored(occluder: Self, anchor: Vec2) -> Self {
+        occluder.with_anchor(anchor)
+    }
+
+    /// Construct a rectangle occluder matching the drawn size of a sprite, shrunk by `shrink` on each side.
+    ///
+    /// Takes the sprite's [custom size](Sprite::custom_size), [rect](Sprite::rect) and texture atlas into account,
+    /// and is [anchored](Occluder2d::with_anchor) with the sprite's [`Anchor`](bevy::sprite::Anchor).
+    /// Useful for quick prototyping, before authoring precise silhouettes.
+    ///
+    /// Returns `None` if the sprite's size depends on an image or atlas layout that isn't loaded yet.
+    /// See [`FitOccluderToSprite`] for keeping the occluder in sync with the sprite.
+    pub fn from_sprite_rect(
+        sprite: &Sprite,
+        anchor: Vec2,
+        shrink: f32,
+        images: &Assets<Image>,
+        texture_atlases: &Assets<TextureAtlasLayout>,
+    ) -> Option<Self> {
+        let size = (sprite_size(sprite, images, texture_atlases)? - 2. * shrink).max(Vec2::ZERO);
+        Some(Self::rectangle(size.x, size.y).with_anchor(anchor))
+    }
+
     /// Construct a polygonal occluder from the given points.
     ///
     /// The points can form a convex or concave polygon. However,
@@ -205,6 +375,17 @@ impl Occluder2d {
         Some(Self::from_shape(Occluder2dShape::Polyline { vertices }))
     }
 
+    /// Construct a horizontal one-way platform occluder of the given width, centered on the entity's translation.
+    ///
+    /// The platform is a flat [polyline](Occluder2d::polyline) that [only casts shadows](Occluder2d::one_way) downward,
+    /// so lights above it are blocked, while lights below it shine through.
+    pub fn platform(width: f32) -> Self {
+        Self::from_shape(Occluder2dShape::Polyline {
+            vertices: vec![vec2(-width * 0.5, 0.), vec2(width * 0.5, 0.)],
+        })
+        .with_one_way(Dir2::NEG_Y)
+    }
+
     /// Construct a rectangle occluder from width and height.
     pub fn rectangle(width: f32, height: f32) -> Self {
         Self::round_rectangle(width, height, 0.)
@@ -245,18 +426,137 @@ impl Occluder2d {
     }
 }
 
+/// Opacity of an [occluder](Occluder2d::distance_opacity) that depends on its distance from the light.
+///
+/// Lights within [`near`](DistanceOpacity::near) world units of the occluder see it at [`near_opacity`](DistanceOpacity::near_opacity)
+/// times its regular opacity. This ramps up linearly to the full opacity for lights [`far`](DistanceOpacity::far) world units away.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DistanceOpacity {
+    /// Distance up to which the occluder has its near opacity.
+    ///
+    /// **Default**: 0.
+    pub near: f32,
+
+    /// Distance from which the occluder has its full opacity.
+    ///
+    /// **Default**: 100.
+    pub far: f32,
+
+    /// Multiplier of the occluder's opacity for close lights, between 0 and 1.
+    ///
+    /// **Default**: 0.
+    pub near_opacity: f32,
+}
+
+impl Default for DistanceOpacity {
+    fn default() -> Self {
+        Self {
+            near: 0.,
+            far: 100.,
+            near_opacity: 0.,
+        }
+    }
+}
+
+impl DistanceOpacity {
+    /// Construct a new distance-based opacity that goes from fully transparent at `near` to fully opaque at `far`.
+    pub fn new(near: f32, far: f32) -> Self {
+        Self {
+            near,
+            far,
+            ..default()
+        }
+    }
+
+    /// Construct a new distance-based opacity with the specified [near opacity](DistanceOpacity::near_opacity).
+    pub fn with_near_opacity(self, near_opacity: f32) -> Self {
+        Self {
+            near_opacity,
+            ..self
+        }
+    }
+
+    /// Multiplier of the occluder's opacity for a light at the given distance.
+    pub fn multiplier(&self, distance: f32) -> f32 {
+        let t = ((distance - self.near) / (self.far - self.near).max(0.0001)).clamp(0., 1.);
+        self.near_opacity.clamp(0., 1.).lerp(1., t)
+    }
+}
+
+/// Restricts the sprites an [`Occluder2d`]'s shadow can darken.
+///
+/// Sprites are told apart through the [`StencilId`]s they write to the sprite stencil, so this only affects
+/// cameras that render the stencil (see [`FireflyConfig::uses_sprite_stencil`](crate::prelude::FireflyConfig::uses_sprite_stencil)),
+/// which are enabled automatically while any occluder has receivers. The entities must have a [`Sprite`] or [`FireflySprite`].
+///
+/// # Example
+///
+/// ```
+/// // the crate keeps its shadow off the quest item, so the player can always see it
+/// commands.spawn((
+///     Occluder2d::rectangle(40., 40.).never_shadow(vec![quest_item]),
+///     Transform::from_xyz(50., 0., 0.),
+/// ));
+/// ```
+#[derive(Debug, Clone, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShadowReceivers {
+    /// The shadow only darkens the given sprites, leaving everything else (including the background) lit.
+    Only(Vec<Entity>),
+
+    /// The shadow darkens everything except the given sprites.
+    Never(Vec<Entity>),
+}
+
+/// [`ShadowReceivers`] of an occluder, extracted to the Render World as stencil IDs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedShadowReceivers {
+    pub only: bool,
+    pub ids: Vec<u32>,
+}
+
+impl ShadowReceivers {
+    /// Extracts the receivers, skipping the entities that don't have a [`StencilId`](crate::prelude::StencilId).
+    pub(crate) fn extract(&self, stencil_ids: &StencilIds) -> ExtractedShadowReceivers {
+        let (only, entities) = match self {
+            ShadowReceivers::Only(entities) => (true, entities),
+            ShadowReceivers::Never(entities) => (false, entities),
+        };
+
+        ExtractedShadowReceivers {
+            only,
+            ids: entities
+                .iter()
+                .filter_map(|entity| stencil_ids.get(*entity))
+                .map(StencilId::get)
+                .collect(),
+        }
+    }
+}
+
 /// Component with data extracted to the Render World from Occluders.
 #[derive(Component, Clone)]
-#[require(RoundOccluderIndex, PolyOccluderIndex)]
+#[require(RoundOccluderIndex, PolyOccluderIndex, OccluderGroupPose)]
 pub struct ExtractedOccluder {
     pub pos: Vec2,
     pub rot: f32,
     pub shape: Occluder2dShape,
     pub aabb: Aabb2d,
     pub z: f32,
+    /// World y of the occluder, used by the [negative y](crate::prelude::ZSortKey::NegativeY) sort key.
+    pub y: f32,
     pub color: Color,
     pub opacity: f32,
     pub z_sorting: bool,
+    pub shadow_falloff: f32,
+    pub distance_opacity: Option<DistanceOpacity>,
+    pub dilation: f32,
+    pub receivers: Option<ExtractedShadowReceivers>,
+    /// World-space [one-way](Occluder2d::one_way) direction.
+    pub one_way: Option<Vec2>,
+    /// Render entity of the [group](crate::prelude::OccluderGroup) the occluder is part of.
+    pub group: Option<Entity>,
     pub changes: Changes,
     pub render_layers: RenderLayers,
 }
@@ -268,6 +568,26 @@ impl PartialEq for ExtractedOccluder {
 }
 
 impl ExtractedOccluder {
+    /// Returns what changed since the occluder's previous extraction.
+    pub(crate) fn changes_since(&self, previous: &Self) -> Changes {
+        Changes {
+            moved: self.pos != previous.pos || self.rot != previous.rot || self.z != previous.z,
+            parameters: self.color != previous.color
+                || self.opacity != previous.opacity
+                || self.z_sorting != previous.z_sorting
+                || self.shadow_falloff != previous.shadow_falloff
+                || self.distance_opacity != previous.distance_opacity
+                || self.receivers != previous.receivers
+                || self.group != previous.group,
+            // everything that decides which lights bin the occluder, and how
+            shape: self.shape != previous.shape
+                || self.aabb != previous.aabb
+                || self.dilation != previous.dilation
+                || self.one_way != previous.one_way
+                || self.render_layers != previous.render_layers,
+        }
+    }
+
     /// Get the occluder's vertices. This will be an empty Vec if the occluder has no vertices.
     pub fn vertices(&self) -> Vec<Vec2> {
         self.shape.vertices(self.pos, Rot2::radians(self.rot))
@@ -383,12 +703,937 @@ pub(crate) fn point_inside_poly(p: Vec2, poly: &Vec<Vec2>, aabb: Aabb2d, concave
 pub struct OccluderPlugin;
 
 impl Plugin for OccluderPlugin {
-    fn build(&self, _app: &mut App) {}
+    fn build(&self, app: &mut App) {
+        app.register_type::<FitOccluderToSprite>();
+        app.register_type::<Occluder2dSprite>();
+        app.register_type::<Occluder2dChain>();
+        app.add_systems(
+            PostUpdate,
+            (
+                inherit_sprite_colors,
+                fit_occluders_to_sprites,
+                update_sprite_occluders,
+            )
+                .after(AssetEventSystems),
+        );
+        // joints are read after last frame's transform propagation, so that the new shape is picked up by change detection
+        app.add_systems(PreUpdate, update_occluder_chains);
+    }
+}
+
+/// Component that keeps the entity's [`Occluder2d`] a rectangle matching the drawn size of its [`Sprite`].
+///
+/// The occluder is refreshed whenever the sprite's size or anchor changes (or its image finishes loading).
+/// See [`Occluder2d::from_sprite_rect`].
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Sprite::from_image(asset_server.load("crate.png")),
+///     FitOccluderToSprite { shrink: 1. },
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(Occluder2d = Occluder2d::rectangle(0., 0.))]
+pub struct FitOccluderToSprite {
+    /// How much the occluder is shrunk on each side, compared to the sprite.
+    ///
+    /// **Default:** 0.
+    pub shrink: f32,
+}
+
+fn fit_occluders_to_sprites(
+    mut occluders: Query<(
+        &FitOccluderToSprite,
+        &mut Occluder2d,
+        &Sprite,
+        Option<&bevy::sprite::Anchor>,
+    )>,
+    images: Res<Assets<Image>>,
+    texture_atlases: Res<Assets<TextureAtlasLayout>>,
+) {
+    for (fit, mut occluder, sprite, anchor) in &mut occluders {
+        let anchor = anchor.map_or(Vec2::ZERO, |a| a.as_vec());
+        let Some(fitted) =
+            Occluder2d::from_sprite_rect(sprite, anchor, fit.shrink, &images, &texture_atlases)
+        else {
+            continue;
+        };
+
+        if occluder.shape != fitted.shape || occluder.offset.xy() != fitted.offset.xy() {
+            occluder.shape = fitted.shape;
+            occluder.offset = fitted.offset.xy().extend(occluder.offset.z);
+        }
+    }
+}
+
+/// Component that shapes the entity's [`Occluder2d`] after the opaque parts of its [`Sprite`], instead of an analytic polygon.
+///
+/// The outlines of the regions whose alpha is at least the [threshold](Occluder2dSprite::threshold) are traced and
+/// [simplified](Occluder2dSprite::tolerance) into polygons. The largest region becomes the entity's own occluder, and every
+/// other region gets an occluder of its own, spawned as a child of the entity with an [`Occluder2dSpritePart`].
+/// The children copy every other field of the entity's occluder, such as its color or opacity.
+///
+/// Outlines are traced once per image and region of it, so animated sprites only trace each frame of their atlas once.
+/// They are traced again whenever the sprite, its anchor or the image changes. Holes inside a region are filled,
+/// so light doesn't shine through gaps enclosed by the sprite, e.g. between the rails of a fence.
+///
+/// Images that aren't kept in the main world (i.e. don't have
+/// [`RenderAssetUsages::MAIN_WORLD`](bevy::asset::RenderAssetUsages::MAIN_WORLD)) can't be read.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Sprite::from_image(asset_server.load("tree.png")),
+///     Occluder2dSprite::default(),
+/// ));
+///
+/// // the fence's shadow comes from a separate, hand-painted mask
+/// commands.spawn((
+///     Sprite::from_image(asset_server.load("fence.png")),
+///     Occluder2dSprite::from_mask(asset_server.load("fence_mask.png")).with_threshold(0.1),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(Occluder2d = Occluder2d::rectangle(0., 0.))]
+pub struct Occluder2dSprite {
+    /// Image used as the occluding shape instead of the sprite's image. White, opaque pixels occlude, so both
+    /// black and white masks and masks with transparency work.
+    ///
+    /// The mask needs to have the same layout as the sprite's image, since the same [rect](Sprite::rect) or texture atlas
+    /// region is read from it. Masks of sprites that don't use either can have any resolution.
+    ///
+    /// **Default:** None.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub mask: Option<Handle<Image>>,
+
+    /// Alpha from which a pixel is part of the occluder, between 0 and 1.
+    ///
+    /// **Default:** 0.5.
+    pub threshold: f32,
+
+    /// How far the simplified outlines can stray from the traced ones, in pixels of the image.
+    ///
+    /// Higher values give occluders fewer vertices, at the cost of accuracy. At 0, only the vertices in the middle
+    /// of straight edges are removed, leaving pixel-perfect, but staircase-shaped, diagonals.
+    ///
+    /// **Performance Impact:** Lower values increase the number of vertices of the occluders.
+    ///
+    /// **Default:** 1.
+    pub tolerance: f32,
+
+    /// Area, in pixels of the image, below which regions are ignored. Useful to avoid an occluder for every stray speck.
+    ///
+    /// **Default:** 4.
+    pub min_area: f32,
+}
+
+impl Default for Occluder2dSprite {
+    fn default() -> Self {
+        Self {
+            mask: None,
+            threshold: 0.5,
+            tolerance: 1.,
+            min_area: 4.,
+        }
+    }
+}
+
+impl Occluder2dSprite {
+    /// Construct a new sprite occluder shaped after the given [mask](Occluder2dSprite::mask).
+    pub fn from_mask(mask: Handle<Image>) -> Self {
+        Self {
+            mask: Some(mask),
+            ..default()
+        }
+    }
+
+    /// Construct a new sprite occluder with the specified [threshold](Occluder2dSprite::threshold).
+    pub fn with_threshold(self, threshold: f32) -> Self {
+        Self { threshold, ..self }
+    }
+
+    /// Construct a new sprite occluder with the specified [tolerance](Occluder2dSprite::tolerance).
+    pub fn with_tolerance(self, tolerance: f32) -> Self {
+        Self { tolerance, ..self }
+    }
+
+    /// Construct a new sprite occluder with the specified [minimum area](Occluder2dSprite::min_area).
+    pub fn with_min_area(self, min_area: f32) -> Self {
+        Self { min_area, ..self }
+    }
+}
+
+/// Component added to the occluders of an [`Occluder2dSprite`]'s regions, other than the largest one.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Occluder2dSpritePart {
+    /// The sprite occluder this is a part of.
+    pub sprite: Entity,
+    /// Index of the region, with the largest other region being 0.
+    pub index: usize,
+}
+
+/// Image, region of it, whether it's a mask, and the bits of the threshold, tolerance and minimum area
+/// that outlines were traced with.
+type OutlineKey = (AssetId<Image>, URect, bool, [u32; 3]);
+
+fn update_sprite_occluders(
+    mut commands: Commands,
+    mut events: MessageReader<AssetEvent<Image>>,
+    mut sprites: Query<(
+        Entity,
+        Ref<Occluder2dSprite>,
+        &mut Occluder2d,
+        Ref<Sprite>,
+        Option<Ref<bevy::sprite::Anchor>>,
+        &GlobalTransform,
+        Ref<RenderLayers>,
+    )>,
+    mut parts: Query<
+        (
+            Entity,
+            &Occluder2dSpritePart,
+            &mut Occluder2d,
+            &mut RenderLayers,
+        ),
+        Without<Occluder2dSprite>,
+    >,
+    images: Res<Assets<Image>>,
+    texture_atlases: Res<Assets<TextureAtlasLayout>>,
+    mut outlines: Local<HashMap<OutlineKey, Vec<Vec<Vec2>>>>,
+) {
+    let mut changed_images = vec![];
+    for event in events.read() {
+        match event {
+            AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id }
+            | AssetEvent::Removed { id } => {
+                outlines.retain(|(image, ..), _| image != id);
+                changed_images.push(*id);
+            }
+            _ => (),
+        }
+    }
+
+    // shapes of the other regions of the sprites traced this frame
+    let mut traced = HashMap::<Entity, Vec<Occluder2dShape>>::default();
+
+    for (entity, sprite_occluder, mut occluder, sprite, anchor, ..) in &mut sprites {
+        let mask = sprite_occluder.mask.as_ref();
+        let image_id = mask.map_or(sprite.image.id(), Handle::id);
+
+        let changed = sprite_occluder.is_changed()
+            || sprite.is_changed()
+            || anchor.as_ref().is_some_and(|anchor| anchor.is_changed())
+            || changed_images.contains(&image_id);
+        if !changed {
+            continue;
+        }
+
+        // not loaded yet, traced once it is
+        let Some(image) = images.get(image_id) else {
+            continue;
+        };
+        let (Some(size), Some(region)) = (
+            sprite_size(&sprite, &images, &texture_atlases),
+            sprite_region(&sprite, image, &texture_atlases),
+        ) else {
+            continue;
+        };
+
+        let key = (
+            image_id,
+            region,
+            mask.is_some(),
+            [
+                sprite_occluder.threshold.to_bits(),
+                sprite_occluder.tolerance.to_bits(),
+                sprite_occluder.min_area.to_bits(),
+            ],
+        );
+        if !outlines.contains_key(&key) {
+            let Some(traced) = trace_outlines(image, region, mask.is_some(), &sprite_occluder)
+            else {
+                warn!("The image of the sprite occluder of {entity} can't be read");
+                continue;
+            };
+            outlines.insert(key, traced);
+        }
+
+        let anchor = anchor.map_or(Vec2::ZERO, |a| a.as_vec());
+        let scale = size / region.size().as_vec2();
+
+        let mut shapes = outlines[&key].iter().filter_map(|outline| {
+            let vertices = outline
+                .iter()
+                .map(|pixel| {
+                    let mut pos = vec2(
+                        pixel.x * scale.x - size.x * 0.5,
+                        size.y * 0.5 - pixel.y * scale.y,
+                    );
+                    if sprite.flip_x {
+                        pos.x = -pos.x;
+                    }
+                    if sprite.flip_y {
+                        pos.y = -pos.y;
+                    }
+                    pos - anchor * size
+                })
+                .collect::<Vec<_>>();
+
+            Occluder2d::polygon(vertices).map(|occluder| occluder.shape)
+        });
+
+        // fully transparent sprites keep an empty occluder
+        let shape = shapes
+            .next()
+            .unwrap_or_else(|| Occluder2d::rectangle(0., 0.).shape);
+        if occluder.shape != shape {
+            occluder.shape = shape;
+        }
+
+        traced.insert(entity, shapes.collect());
+    }
+
+    let mut existing = HashSet::new();
+
+    for (entity, part, mut occluder, mut render_layers) in &mut parts {
+        // parts of removed sprite occluders, or of regions that no longer exist, are despawned
+        let Ok((_, _, parent, _, _, _, parent_layers)) = sprites.get_mut(part.sprite) else {
+            commands.entity(entity).try_despawn();
+            continue;
+        };
+
+        let shape = match traced.get(&part.sprite) {
+            Some(shapes) => match shapes.get(part.index) {
+                Some(shape) => shape.clone(),
+                None => {
+                    commands.entity(entity).try_despawn();
+                    continue;
+                }
+            },
+            None => occluder.shape.clone(),
+        };
+        existing.insert((part.sprite, part.index));
+
+        if parent.is_changed() || occluder.shape != shape {
+            *occluder = Occluder2d {
+                shape,
+                ..parent.clone()
+            };
+        }
+
+        if parent_layers.is_changed() {
+            *render_layers = parent_layers.clone();
+        }
+    }
+
+    for (entity, shapes) in traced {
+        let Ok((_, _, parent, _, _, transform, render_layers)) = sprites.get(entity) else {
+            continue;
+        };
+
+        for (index, shape) in shapes.into_iter().enumerate() {
+            if existing.contains(&(entity, index)) {
+                continue;
+            }
+
+            commands.spawn((
+                Occluder2dSpritePart {
+                    sprite: entity,
+                    index,
+                },
+                Occluder2d {
+                    shape,
+                    ..parent.clone()
+                },
+                render_layers.clone(),
+                Transform::default(),
+                // already placed, so the part doesn't cast a shadow from the origin until the next propagation
+                *transform,
+                ChildOf(entity),
+            ));
+        }
+    }
+}
+
+/// Component that keeps the entity's [`Occluder2d`] a single polyline running through a list of joints.
+///
+/// Joints can be entities (e.g. the segments of a rope or bridge driven by physics), whose global positions
+/// are read each frame, or fixed points in world space. This avoids spawning a separate occluder for every segment.
+///
+/// The polyline is rebuilt whenever any of the joints move. Joints whose entities don't exist are skipped.
+///
+/// # Example
+///
+/// ```
+/// let links: Vec<Entity> = (0..10)
+///     .map(|i| commands.spawn(Transform::from_xyz(i as f32 * 10., 0., 0.)).id())
+///     .collect();
+///
+/// // the rope is anchored to a fixed point on the left
+/// commands.spawn(Occluder2dChain::new(
+///     std::iter::once(ChainJoint::Point(vec2(-10., 0.))).chain(links.into_iter().map(ChainJoint::Entity)),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(Occluder2d = Occluder2d::from_shape(Occluder2dShape::Polyline { vertices: vec![Vec2::ZERO, Vec2::ZERO] }))]
+pub struct Occluder2dChain {
+    /// The joints the polyline runs through, in order.
+    pub joints: Vec<ChainJoint>,
+}
+
+impl Occluder2dChain {
+    /// Construct a new chain from the given joints.
+    pub fn new(joints: impl IntoIterator<Item = impl Into<ChainJoint>>) -> Self {
+        Self {
+            joints: joints.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// A single joint of an [`Occluder2dChain`].
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChainJoint {
+    /// The global position of an entity.
+    Entity(Entity),
+    /// A fixed point, in world space.
+    Point(Vec2),
+}
+
+impl From<Entity> for ChainJoint {
+    fn from(entity: Entity) -> Self {
+        Self::Entity(entity)
+    }
+}
+
+impl From<Vec2> for ChainJoint {
+    fn from(point: Vec2) -> Self {
+        Self::Point(point)
+    }
+}
+
+fn update_occluder_chains(
+    mut chains: Query<(&Occluder2dChain, &mut Occluder2d, &GlobalTransform)>,
+    joints: Query<&GlobalTransform>,
+) {
+    for (chain, mut occluder, transform) in &mut chains {
+        let isometry = Isometry2d {
+            rotation: Rot2::radians(transform.rotation().to_euler(EulerRot::XYZ).2),
+            translation: transform.translation().truncate() + occluder.offset.truncate(),
+        }
+        .inverse();
+
+        let points = chain
+            .joints
+            .iter()
+            .filter_map(|joint| match joint {
+                ChainJoint::Entity(entity) => joints
+                    .get(*entity)
+                    .ok()
+                    .map(|joint| joint.translation().truncate()),
+                ChainJoint::Point(point) => Some(*point),
+            })
+            .map(|point| isometry.transform_point(point))
+            .collect::<Vec<_>>();
+
+        let Some(polyline) = Occluder2d::polyline(points) else {
+            continue;
+        };
+
+        if occluder.shape != polyline.shape {
+            occluder.shape = polyline.shape;
+        }
+    }
+}
+
+/// Trait for components that define their own occluder shape, such as Bezier outlines or superellipses.
+///
+/// The component produces an outline in local space, which becomes the entity's [`Occluder2d`] shape. Shadows are then
+/// computed the same way as for other polygonal occluders: the silhouette facing each light is derived from the outline
+/// on the GPU, and the occluder goes through the usual binning and culling. The outline is only rebuilt when the
+/// component changes, so it can be as detailed as needed.
+///
+/// Shapes that are [light dependent](CustomOccluderShape::light_dependent) get the position of the closest light that
+/// casts shadows, and are rebuilt whenever it moves relative to them. An occluder has a single shape for all lights,
+/// so the outline produced for the closest light is also used for the others.
+///
+/// Each shape has to be registered with a [`CustomOccluderShapePlugin`].
+///
+/// # Example
+///
+/// ```
+/// #[derive(Component)]
+/// struct Superellipse {
+///     size: Vec2,
+///     exponent: f32,
+/// }
+///
+/// impl CustomOccluderShape for Superellipse {
+///     fn outline(&self, _light: Option<Vec2>) -> Vec<Vec2> {
+///         (0..64)
+///             .map(|i| {
+///                 let (sin, cos) = (i as f32 / 64. * TAU).sin_cos();
+///                 let e = 2. / self.exponent;
+///                 vec2(cos.signum() * cos.abs().powf(e), sin.signum() * sin.abs().powf(e)) * self.size * 0.5
+///             })
+///             .collect()
+///     }
+/// }
+///
+/// app.add_plugins(CustomOccluderShapePlugin::<Superellipse>::default());
+///
+/// commands.spawn(Superellipse { size: vec2(40., 20.), exponent: 4. });
+///
+/// // a pillar seen from above, whose silhouette is always a segment facing the light
+/// #[derive(Component)]
+/// struct Pillar(f32);
+///
+/// impl CustomOccluderShape for Pillar {
+///     fn outline(&self, light: Option<Vec2>) -> Vec<Vec2> {
+///         let side = light.and_then(|light| light.perp().try_normalize()).unwrap_or(Vec2::X) * self.0;
+///         vec![-side, side]
+///     }
+///
+///     fn closed(&self) -> bool {
+///         false
+///     }
+///
+///     fn light_dependent(&self) -> bool {
+///         true
+///     }
+/// }
+/// ```
+pub trait CustomOccluderShape: Component {
+    /// Outline of the shape, relative to the entity's translation. Needs at least 2 vertices.
+    ///
+    /// Closed outlines can be in either winding order, like with [`Occluder2d::polygon`].
+    ///
+    /// `light` is the position of the closest light that casts shadows, in the same space as the outline, for
+    /// [light dependent](CustomOccluderShape::light_dependent) shapes. It's None for other shapes, or if there's no such light.
+    fn outline(&self, light: Option<Vec2>) -> Vec<Vec2>;
+
+    /// Whether the outline is a closed polygon, or an open polyline.
+    fn closed(&self) -> bool {
+        true
+    }
+
+    /// Whether the outline depends on the position of the light, in which case it's rebuilt whenever
+    /// the closest light moves relative to the entity.
+    ///
+    /// **Performance Impact:** The outline can be rebuilt every frame.
+    fn light_dependent(&self) -> bool {
+        false
+    }
+}
+
+/// Plugin that keeps the [`Occluder2d`] of entities with the custom shape `S` up to date.
+///
+/// Entities with `S` that don't have an occluder get a default one with the custom shape. The other fields of
+/// existing occluders, such as their color or opacity, are kept.
+pub struct CustomOccluderShapePlugin<S: CustomOccluderShape>(PhantomData<S>);
+
+impl<S: CustomOccluderShape> Default for CustomOccluderShapePlugin<S> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<S: CustomOccluderShape> Plugin for CustomOccluderShapePlugin<S> {
+    fn build(&self, app: &mut App) {
+        // same as chains, so the new shape is picked up by change detection this frame
+        app.add_systems(PreUpdate, update_custom_occluder_shapes::<S>);
+    }
+}
+
+fn update_custom_occluder_shapes<S: CustomOccluderShape>(
+    mut commands: Commands,
+    mut shapes: Query<(
+        Entity,
+        Ref<S>,
+        Option<&mut Occluder2d>,
+        Option<&GlobalTransform>,
+    )>,
+    lights: Query<(&GlobalTransform, &PointLight2d)>,
+    mut last_lights: Local<HashMap<Entity, Vec2>>,
+) {
+    last_lights.retain(|entity, _| shapes.contains(*entity));
+
+    for (entity, shape, occluder, transform) in &mut shapes {
+        let light = match shape.light_dependent() {
+            true => {
+                let transform = transform.copied().unwrap_or_default();
+                let offset = occluder
+                    .as_ref()
+                    .map_or(Vec2::ZERO, |o| o.offset.truncate());
+                let isometry = Isometry2d {
+                    rotation: Rot2::radians(transform.rotation().to_euler(EulerRot::XYZ).2),
+                    translation: transform.translation().truncate() + offset,
+                }
+                .inverse();
+
+                lights
+                    .iter()
+                    .filter(|(_, light)| light.cast_shadows)
+                    .map(|(light_transform, light)| {
+                        isometry.transform_point(
+                            (light_transform.translation() + light.offset).truncate(),
+                        )
+                    })
+                    .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+            }
+            false => None,
+        };
+
+        let light_moved = match light {
+            Some(light) => last_lights.insert(entity, light) != Some(light),
+            None => last_lights.remove(&entity).is_some(),
+        };
+
+        if !shape.is_changed() && !light_moved {
+            continue;
+        }
+
+        let outline = match shape.closed() {
+            true => Occluder2d::polygon(shape.outline(light)),
+            false => Occluder2d::polyline(shape.outline(light)),
+        };
+
+        let Some(outline) = outline else {
+            warn!("Custom occluder shape of {entity} has less than 2 vertices");
+            continue;
+        };
+
+        match occluder {
+            Some(mut occluder) => {
+                if occluder.shape != outline.shape {
+                    occluder.shape = outline.shape;
+                }
+            }
+            None => {
+                commands.entity(entity).insert(outline);
+            }
+        }
+    }
+}
+
+/// Drawn size of a sprite, or None if it depends on an asset that isn't loaded.
+fn sprite_size(
+    sprite: &Sprite,
+    images: &Assets<Image>,
+    texture_atlases: &Assets<TextureAtlasLayout>,
+) -> Option<Vec2> {
+    if let Some(size) = sprite.custom_size {
+        return Some(size);
+    }
+    if let Some(rect) = sprite.rect {
+        return Some(rect.size());
+    }
+    if let Some(atlas) = &sprite.texture_atlas {
+        return atlas
+            .texture_rect(texture_atlases)
+            .map(|rect| rect.size().as_vec2());
+    }
+    images.get(&sprite.image).map(|image| image.size_f32())
+}
+
+/// Sets the color of occluders with [`color_from_sprite`](Occluder2d::color_from_sprite) enabled
+/// to the average color of their sprite's image.
+fn inherit_sprite_colors(
+    mut events: MessageReader<AssetEvent<Image>>,
+    images: Res<Assets<Image>>,
+    mut occluders: Query<(Entity, &mut Occluder2d, Option<&Sprite>)>,
+    #[cfg(feature = "sprites")] firefly_sprites: Query<&FireflySprite>,
+    mut averages: Local<HashMap<AssetId<Image>, Option<Vec3>>>,
+) {
+    for event in events.read() {
+        match event {
+            AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id }
+            | AssetEvent::Removed { id } => {
+                averages.remove(id);
+            }
+            _ => (),
+        }
+    }
+
+    for (entity, mut occluder, sprite) in &mut occluders {
+        if !occluder.color_from_sprite {
+            continue;
+        }
+
+        #[cfg(feature = "sprites")]
+        let firefly_sprite = firefly_sprites.get(entity).ok();
+        #[cfg(not(feature = "sprites"))]
+        let firefly_sprite: Option<&Sprite> = {
+            let _ = entity;
+            None
+        };
+
+        let (image, tint) = match (sprite, firefly_sprite) {
+            (Some(sprite), _) => (sprite.image.id(), sprite.color),
+            (None, Some(sprite)) => (sprite.image.id(), sprite.color),
+            (None, None) => continue,
+        };
+
+        let average = match averages.get(&image) {
+            Some(average) => *average,
+            None => {
+                // not loaded yet
+                let Some(data) = images.get(image) else {
+                    continue;
+                };
+                let average = average_color(data);
+                averages.insert(image, average);
+                average
+            }
+        };
+
+        let Some(average) = average else {
+            continue;
+        };
+
+        let color = Color::LinearRgba(LinearRgba::from_vec3(average * tint.to_linear().to_vec3()));
+        if occluder.color != color {
+            occluder.color = color;
+        }
+    }
+}
+
+/// Average linear color of an image, weighted by alpha. None if the image can't be read or is fully transparent.
+fn average_color(image: &Image) -> Option<Vec3> {
+    let size = image.size();
+    let mut sum = Vec3::ZERO;
+    let mut total_alpha = 0.;
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let color = image.get_color_at(x, y).ok()?.to_linear();
+            sum += color.to_vec3() * color.alpha;
+            total_alpha += color.alpha;
+        }
+    }
+
+    if total_alpha <= 0. {
+        return None;
+    }
+
+    Some(sum / total_alpha)
+}
+
+/// Region of an image a sprite is drawn from, in pixels. None if it depends on an atlas layout that isn't loaded.
+fn sprite_region(
+    sprite: &Sprite,
+    image: &Image,
+    texture_atlases: &Assets<TextureAtlasLayout>,
+) -> Option<URect> {
+    let bounds = URect::from_corners(UVec2::ZERO, image.size());
+    let mut region = match &sprite.texture_atlas {
+        Some(atlas) => atlas.texture_rect(texture_atlases)?,
+        None => bounds,
+    };
+    // the rect is relative to the atlas region
+    if let Some(rect) = sprite.rect {
+        region = URect::from_corners(
+            region.min + rect.min.as_uvec2(),
+            region.min + rect.max.as_uvec2(),
+        );
+    }
+    Some(region.intersect(bounds))
+}
+
+/// Outlines of the covered parts of an image's region, in pixels relative to the region's top left corner,
+/// simplified with the [`Occluder2dSprite`]'s settings. Holes are skipped, and the largest outline comes first.
+///
+/// Returns None if the image can't be read.
+fn trace_outlines(
+    image: &Image,
+    region: URect,
+    mask: bool,
+    sprite_occluder: &Occluder2dSprite,
+) -> Option<Vec<Vec<Vec2>>> {
+    let size = region.size();
+    let (width, height) = (size.x as usize, size.y as usize);
+
+    let mut covered = vec![false; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let color = image
+                .get_color_at(region.min.x + x as u32, region.min.y + y as u32)
+                .ok()?;
+            let coverage = match mask {
+                true => color.alpha() * color.luminance(),
+                false => color.alpha(),
+            };
+            covered[y * width + x] = coverage >= sprite_occluder.threshold;
+        }
+    }
+
+    let is_covered = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < width
+            && (y as usize) < height
+            && covered[y as usize * width + x as usize]
+    };
+    let vertex = |x: i32, y: i32| y as usize * (width + 1) + x as usize;
+    let position =
+        |vertex: usize| ivec2((vertex % (width + 1)) as i32, (vertex / (width + 1)) as i32);
+
+    // edges between covered and uncovered pixels, indexed by their start vertex. They go clockwise around
+    // covered regions, and counter-clockwise around holes. Each vertex starts at most 2 edges.
+    let mut edges = vec![[None::<usize>; 2]; (width + 1) * (height + 1)];
+    let mut add_edge = |from: usize, to: usize| {
+        let slot = &mut edges[from];
+        match slot[0] {
+            None => slot[0] = Some(to),
+            Some(_) => slot[1] = Some(to),
+        }
+    };
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            if !is_covered(x, y) {
+                continue;
+            }
+            if !is_covered(x, y - 1) {
+                add_edge(vertex(x, y), vertex(x + 1, y));
+            }
+            if !is_covered(x + 1, y) {
+                add_edge(vertex(x + 1, y), vertex(x + 1, y + 1));
+            }
+            if !is_covered(x, y + 1) {
+                add_edge(vertex(x + 1, y + 1), vertex(x, y + 1));
+            }
+            if !is_covered(x - 1, y) {
+                add_edge(vertex(x, y + 1), vertex(x, y));
+            }
+        }
+    }
+
+    let mut used = vec![[false; 2]; edges.len()];
+    let mut outlines = vec![];
+
+    for start in 0..edges.len() {
+        for start_slot in 0..2 {
+            if edges[start][start_slot].is_none() || used[start][start_slot] {
+                continue;
+            }
+
+            let mut outline = vec![];
+            let (mut from, mut slot) = (start, start_slot);
+
+            while let Some(to) = edges[from][slot] {
+                used[from][slot] = true;
+                outline.push(position(from).as_vec2());
+
+                // where two regions touch diagonally, turning right keeps them apart
+                let dir = position(to) - position(from);
+                let right = ivec2(-dir.y, dir.x);
+                slot = match edges[to] {
+                    [Some(_), Some(second)] if position(second) - position(to) == right => 1,
+                    _ => 0,
+                };
+                from = to;
+
+                if used[from][slot] {
+                    break;
+                }
+            }
+
+            outlines.push(outline);
+        }
+    }
+
+    let min_area = sprite_occluder.min_area.max(0.);
+    let mut outlines = outlines
+        .into_iter()
+        .map(|outline| (signed_area(&outline), outline))
+        .filter(|(area, _)| *area > 0. && *area >= min_area)
+        .collect::<Vec<_>>();
+    outlines.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    Some(
+        outlines
+            .into_iter()
+            .map(|(_, outline)| simplify_outline(&outline, sprite_occluder.tolerance))
+            .filter(|outline| outline.len() >= 3)
+            .collect(),
+    )
+}
+
+/// Area of a closed outline, positive if it's clockwise in image space (with y pointing down).
+fn signed_area(outline: &[Vec2]) -> f32 {
+    outline
+        .iter()
+        .zip(outline.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum::<f32>()
+        * 0.5
+}
+
+/// Simplifies a closed outline with the Ramer-Douglas-Peucker algorithm, keeping it within `tolerance` of the original.
+fn simplify_outline(outline: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    let n = outline.len();
+    if n < 3 {
+        return outline.to_vec();
+    }
+
+    // split at the vertex farthest from the first one, so both halves are open polylines
+    let far = (1..n)
+        .max_by(|a, b| {
+            let a = outline[*a].distance_squared(outline[0]);
+            let b = outline[*b].distance_squared(outline[0]);
+            a.total_cmp(&b)
+        })
+        .unwrap_or(1);
+
+    let mut keep = vec![false; n];
+    keep[0] = true;
+    keep[far] = true;
+
+    let mut ranges = vec![(0, far), (far, n)];
+    while let Some((start, end)) = ranges.pop() {
+        let (a, b) = (outline[start], outline[end % n]);
+        let farthest = (start + 1..end)
+            .map(|i| (i, segment_distance(outline[i], a, b)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((i, distance)) = farthest
+            && distance > tolerance
+        {
+            keep[i] = true;
+            ranges.push((start, i));
+            ranges.push((i, end));
+        }
+    }
+
+    outline
+        .iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(*point))
+        .collect()
+}
+
+/// Distance from a point to the segment between `a` and `b`.
+fn segment_distance(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = ((point - a).dot(ab) / ab.length_squared().max(f32::EPSILON)).clamp(0., 1.);
+    point.distance(a + ab * t)
 }
 
 /// Data that is transferred to the GPU to be read inside shaders.
+///
+/// Matches `PolyOccluder` in `firefly::types`. See the [`layout`](crate::layout) module for the stability guarantees.
 #[repr(C)]
-#[derive(ShaderType, Clone, Copy, Default, NoUninit)]
+#[derive(ShaderType, Clone, Copy, Default, Pod, Zeroable)]
 pub struct UniformOccluder {
     pub vertex_start: u32,
     pub n_vertices: u32,
@@ -396,12 +1641,25 @@ pub struct UniformOccluder {
     pub opacity: f32,
     pub color: Vec4,
     pub z_sorting: u32,
-    pub _pad1: [u32; 3],
+    pub shadow_falloff: f32,
+    pub near_distance: f32,
+    pub far_distance: f32,
+    pub near_opacity: f32,
+    /// 0 if the shadow darkens every sprite, 1 if it only darkens the receivers, 2 if it never darkens them.
+    pub receiver_mode: u32,
+    pub receiver_start: u32,
+    pub receiver_count: u32,
+    pub y: f32,
+    /// Index of the [group](crate::prelude::OccluderGroup)'s offset in the vertex buffer, 0 if the occluder isn't grouped.
+    pub group: u32,
+    pub _pad: [u32; 2],
 }
 
 /// Data that is transferred to the GPU to be read inside shaders.
+///
+/// Matches `RoundOccluder` in `firefly::types`. See the [`layout`](crate::layout) module for the stability guarantees.
 #[repr(C)]
-#[derive(ShaderType, Clone, Copy, Default, NoUninit)]
+#[derive(ShaderType, Clone, Copy, Default, Pod, Zeroable)]
 pub struct UniformRoundOccluder {
     pub pos: Vec2,
     pub rot: f32,
@@ -412,7 +1670,18 @@ pub struct UniformRoundOccluder {
     pub opacity: f32,
     pub color: Vec4,
     pub z_sorting: u32,
-    pub _pad1: [u32; 3],
+    pub shadow_falloff: f32,
+    pub near_distance: f32,
+    pub far_distance: f32,
+    pub near_opacity: f32,
+    /// 0 if the shadow darkens every sprite, 1 if it only darkens the receivers, 2 if it never darkens them.
+    pub receiver_mode: u32,
+    pub receiver_start: u32,
+    pub receiver_count: u32,
+    pub y: f32,
+    /// Index of the [group](crate::prelude::OccluderGroup)'s offset in the vertex buffer, 0 if the occluder isn't grouped.
+    pub group: u32,
+    pub _pad: [u32; 2],
 }
 
 #[repr(C)]
@@ -514,4 +1783,6 @@ pub struct RoundOccluderIndex(pub Option<BufferIndex>);
 pub struct PolyOccluderIndex {
     pub occluder: Option<BufferIndex>,
     pub vertices: Option<BufferIndex>,
+    /// Number of vertices written to the vertex buffer.
+    pub n_vertices: u32,
 }
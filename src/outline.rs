@@ -0,0 +1,229 @@
+//! Module containing [`DarknessOutline`], for keeping important sprites visible in dark scenes.
+
+use bevy::{
+    prelude::*,
+    render::{
+        Extract, ExtractSchedule, Render, RenderApp, RenderSystems,
+        render_resource::{BindingResource, BufferUsages, RawBufferVec, ShaderType},
+        renderer::{RenderDevice, RenderQueue},
+    },
+    transform::TransformSystems,
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{stencil::StencilId, visibility::LitVisibility};
+
+/// Component that outlines a sprite while the light reaching it is below a [threshold](DarknessOutline::threshold),
+/// so that the player can always see it, even in the darkest parts of a level.
+///
+/// Meant for gameplay-relevant sprites, such as the player character, enemies or projectiles. The light reaching the sprite
+/// is computed the same way as [`LitVisibility::entity_average_illumination`], which averages it over the whole sprite,
+/// so the outline doesn't flicker on and off as a shadow's edge moves across it.
+///
+/// The outline is drawn when the lightmap is applied, around the pixels the sprite wrote to the
+/// [sprite ID texture](crate::SpriteIdTexture), so the entity needs a [`StencilId`]. This is the case for every
+/// [`Sprite`] and [`FireflySprite`](crate::prelude::FireflySprite), but it has no effect without the `sprites` feature.
+///
+/// Unlike the [accessibility assist](crate::prelude::AccessibilityAssist)'s outlines, these are configured per sprite.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Sprite::from_image(asset_server.load("player.png")),
+///     DarknessOutline::new(Color::srgb(0.4, 0.8, 1.0)).with_width(3.),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DarknessOutline {
+    /// Color of the outline. The alpha is used as opacity.
+    ///
+    /// **Default:** White.
+    pub color: Color,
+
+    /// Width of the outline, in pixels. Capped at 8.
+    ///
+    /// **Performance Impact:** Minor, the pixels around the sprite are sampled within this width.
+    ///
+    /// **Default:** 2.
+    pub width: f32,
+
+    /// The sprite is outlined while the light reaching it, averaged over its bounds, is below this value.
+    /// The [ambient light](crate::prelude::FireflyConfig::ambient_brightness) isn't taken into account.
+    ///
+    /// **Default:** 0.2.
+    pub threshold: f32,
+
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    illumination: f32,
+}
+
+impl Default for DarknessOutline {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            width: 2.,
+            threshold: 0.2,
+            illumination: 0.,
+        }
+    }
+}
+
+impl DarknessOutline {
+    /// Construct a new outline with the given color.
+    pub fn new(color: Color) -> Self {
+        Self { color, ..default() }
+    }
+
+    /// Returns the outline with the given [width](DarknessOutline::width).
+    pub fn with_width(self, width: f32) -> Self {
+        Self { width, ..self }
+    }
+
+    /// Returns the outline with the given [threshold](DarknessOutline::threshold).
+    pub fn with_threshold(self, threshold: f32) -> Self {
+        Self { threshold, ..self }
+    }
+
+    /// Returns the light currently reaching the sprite, averaged over its bounds.
+    pub fn illumination(&self) -> f32 {
+        self.illumination
+    }
+
+    /// Returns true if the sprite is currently outlined.
+    pub fn is_outlined(&self) -> bool {
+        self.illumination < self.threshold && self.width > 0. && self.color.alpha() > 0.
+    }
+}
+
+/// Data of an outlined [`DarknessOutline`] sprite that is sent to the GPU.
+#[repr(C)]
+#[derive(ShaderType, Clone, Copy, Pod, Zeroable, Default)]
+pub struct UniformDarknessOutline {
+    pub color: Vec4,
+    /// [`StencilId`] of the sprite.
+    pub id: u32,
+    pub width: f32,
+    pub _pad: [u32; 2],
+}
+
+/// Sprites that are currently outlined, extracted to the render world.
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedDarknessOutlines {
+    /// True if any sprite has a [`DarknessOutline`], outlined or not. The sprite stencil is kept
+    /// rendered while this is true, so outlines don't have to wait for it to be reallocated.
+    pub any: bool,
+    pub outlines: Vec<UniformDarknessOutline>,
+}
+
+/// Buffer with the outlined sprites, read by the lightmap application pass.
+#[derive(Resource)]
+pub struct DarknessOutlineBuffer {
+    outlines: RawBufferVec<UniformDarknessOutline>,
+}
+
+impl FromWorld for DarknessOutlineBuffer {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        let queue = world.resource::<RenderQueue>();
+
+        let mut outlines = RawBufferVec::<UniformDarknessOutline>::new(BufferUsages::STORAGE);
+        outlines.set_label("darkness outline buffer".into());
+
+        // empty value is added so the buffer can be written to VRAM from the start,
+        // its ID of 0 never matches a sprite
+        outlines.push(default());
+        outlines.write_buffer(device, queue);
+
+        Self { outlines }
+    }
+}
+
+impl DarknessOutlineBuffer {
+    /// Get the binding of this buffer. It is guaranteed to exist.
+    pub fn binding(&self) -> BindingResource<'_> {
+        self.outlines.binding().unwrap()
+    }
+}
+
+/// Plugin that updates, extracts and prepares [`DarknessOutline`]s. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct OutlinePlugin;
+impl Plugin for OutlinePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DarknessOutline>();
+        app.add_systems(
+            PostUpdate,
+            update_darkness_outlines.after(TransformSystems::Propagate),
+        );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<ExtractedDarknessOutlines>()
+            .add_systems(ExtractSchedule, extract_darkness_outlines)
+            .add_systems(
+                Render,
+                prepare_darkness_outlines.in_set(RenderSystems::PrepareResources),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.init_resource::<DarknessOutlineBuffer>();
+    }
+}
+
+fn update_darkness_outlines(
+    mut outlines: Query<(Entity, &mut DarknessOutline)>,
+    lit: LitVisibility,
+) {
+    for (entity, mut outline) in &mut outlines {
+        outline.bypass_change_detection().illumination = lit.entity_average_illumination(entity);
+    }
+}
+
+fn extract_darkness_outlines(
+    mut extracted: ResMut<ExtractedDarknessOutlines>,
+    outlines: Extract<Query<(&DarknessOutline, &StencilId, &ViewVisibility)>>,
+) {
+    extracted.outlines.clear();
+    extracted.any = cfg!(feature = "sprites") && !outlines.is_empty();
+
+    if !extracted.any {
+        return;
+    }
+
+    for (outline, id, visibility) in &outlines {
+        if !visibility.get() || !outline.is_outlined() {
+            continue;
+        }
+
+        extracted.outlines.push(UniformDarknessOutline {
+            color: outline.color.to_linear().to_vec4(),
+            id: id.get(),
+            width: outline.width.min(8.),
+            _pad: default(),
+        });
+    }
+}
+
+fn prepare_darkness_outlines(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    extracted: Res<ExtractedDarknessOutlines>,
+    mut buffer: ResMut<DarknessOutlineBuffer>,
+) {
+    buffer.outlines.truncate(1);
+    for outline in &extracted.outlines {
+        buffer.outlines.push(*outline);
+    }
+    buffer.outlines.write_buffer(&render_device, &render_queue);
+}
@@ -0,0 +1,194 @@
+//! Module containing the [`ParallaxLight`] component, which lights parallax layers from a position that scrolls with them.
+
+use bevy::{
+    camera::{CameraUpdateSystems, visibility::RenderLayers},
+    prelude::*,
+    transform::TransformSystems,
+};
+
+use crate::{data::FireflyConfig, lights::PointLight2d};
+
+/// A parallax layer lit by a [`ParallaxLight`].
+#[derive(Clone, Copy, Debug, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParallaxLayer {
+    /// The [render layer](RenderLayers) the layer's sprites and occluders are on.
+    pub layer: usize,
+
+    /// How fast the layer scrolls relative to the camera. 1 scrolls with the foreground, 0 stays fixed on the screen.
+    pub factor: f32,
+}
+
+impl ParallaxLayer {
+    /// Construct a new parallax layer with the given [render layer](ParallaxLayer::layer) and [factor](ParallaxLayer::factor).
+    pub fn new(layer: usize, factor: f32) -> Self {
+        Self { layer, factor }
+    }
+}
+
+/// Component you can add to a [`PointLight2d`] to also light parallax layers, from an effective position that's
+/// adjusted by each layer's [parallax factor](ParallaxLayer::factor). This keeps e.g. background mountains lit
+/// consistently with a foreground campfire while the camera scrolls.
+///
+/// Parallax layers usually scroll by offsetting their entities by `(1 - factor) * camera_position`. For every layer,
+/// a copy of the light is spawned as a child of this entity, on the layer's [render layer](ParallaxLayer::layer),
+/// and offset the same way. Only the [`PointLight2d`] itself is copied, so components such as
+/// [`LightModulator`](crate::prelude::LightModulator) only affect the original light.
+///
+/// The light's own [`RenderLayers`] should exclude the parallax layers, or they will be lit twice.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     PointLight2d::default(),
+///     Transform::from_translation(vec3(100., 0., 0.)),
+///     // foreground on layer 0, mountains on layer 1, sky on layer 2
+///     RenderLayers::layer(0),
+///     ParallaxLight::new(vec![ParallaxLayer::new(1, 0.5), ParallaxLayer::new(2, 0.1)]),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(PointLight2d)]
+pub struct ParallaxLight {
+    /// The parallax layers lit by this light.
+    ///
+    /// **Default:** Empty.
+    pub layers: Vec<ParallaxLayer>,
+
+    /// The camera the layers scroll with. If None, the active camera with a [`FireflyConfig`]
+    /// and the highest [order](Camera::order) is used.
+    ///
+    /// **Default:** None.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub camera: Option<Entity>,
+}
+
+impl ParallaxLight {
+    /// Construct a new parallax light with the given [layers](ParallaxLight::layers).
+    pub fn new(layers: Vec<ParallaxLayer>) -> Self {
+        Self {
+            layers,
+            camera: None,
+        }
+    }
+
+    /// Construct a new parallax light with an additional [layer](ParallaxLight::layers).
+    pub fn with_layer(mut self, layer: usize, factor: f32) -> Self {
+        self.layers.push(ParallaxLayer::new(layer, factor));
+        self
+    }
+
+    /// Construct a new parallax light that scrolls with the specified [camera](ParallaxLight::camera).
+    pub fn with_camera(self, camera: Entity) -> Self {
+        Self {
+            camera: Some(camera),
+            ..self
+        }
+    }
+}
+
+/// Component added to the copies of a [`ParallaxLight`], one for each of its layers.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ParallaxLightOf {
+    /// The light this is a copy of.
+    pub light: Entity,
+    /// The parallax layer this copy lights.
+    pub layer: ParallaxLayer,
+}
+
+/// Plugin that keeps the copies of every [`ParallaxLight`] in sync with their light and camera. Added automatically
+/// by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct ParallaxLightPlugin;
+
+impl Plugin for ParallaxLightPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ParallaxLight>();
+        app.add_systems(
+            PostUpdate,
+            update_parallax_lights
+                .after(TransformSystems::Propagate)
+                .after(CameraUpdateSystems)
+                .before(bevy::camera::visibility::VisibilitySystems::CheckVisibility),
+        );
+    }
+}
+
+fn update_parallax_lights(
+    mut commands: Commands,
+    lights: Query<(
+        Entity,
+        Ref<ParallaxLight>,
+        Ref<PointLight2d>,
+        &GlobalTransform,
+    )>,
+    mut copies: Query<(Entity, &ParallaxLightOf, &mut PointLight2d), Without<ParallaxLight>>,
+    cameras: Query<(Entity, &Camera, &GlobalTransform), With<FireflyConfig>>,
+) {
+    let default_camera = cameras
+        .iter()
+        .filter(|(_, camera, _)| camera.is_active)
+        .max_by_key(|(_, camera, _)| camera.order)
+        .map(|(entity, ..)| entity);
+
+    let camera_pos = |parallax: &ParallaxLight| {
+        parallax
+            .camera
+            .or(default_camera)
+            .and_then(|camera| cameras.get(camera).ok())
+            .map_or(Vec2::ZERO, |(_, _, transform)| {
+                transform.translation().truncate()
+            })
+    };
+
+    for (entity, copy, mut copy_light) in &mut copies {
+        // copies of removed or changed parallax lights are respawned below
+        let Ok((_, parallax, light, _)) = lights.get(copy.light) else {
+            commands.entity(entity).try_despawn();
+            continue;
+        };
+        if parallax.is_changed() {
+            commands.entity(entity).try_despawn();
+            continue;
+        }
+
+        let offset = light.offset + ((1. - copy.layer.factor) * camera_pos(&parallax)).extend(0.);
+
+        if light.is_changed() {
+            *copy_light = PointLight2d {
+                offset,
+                ..light.clone()
+            };
+        } else if copy_light.offset != offset {
+            copy_light.offset = offset;
+        }
+    }
+
+    for (entity, parallax, light, transform) in &lights {
+        if !parallax.is_changed() {
+            continue;
+        }
+
+        for layer in &parallax.layers {
+            let offset = light.offset + ((1. - layer.factor) * camera_pos(&parallax)).extend(0.);
+
+            commands.spawn((
+                ParallaxLightOf {
+                    light: entity,
+                    layer: *layer,
+                },
+                PointLight2d {
+                    offset,
+                    ..light.clone()
+                },
+                RenderLayers::layer(layer.layer),
+                Transform::default(),
+                // already placed, so the copy isn't lit from the origin until the next propagation
+                *transform,
+                ChildOf(entity),
+            ));
+        }
+    }
+}
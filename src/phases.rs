@@ -5,16 +5,20 @@ use std::ops::Range;
 use bevy::math::FloatOrd;
 use bevy::prelude::*;
 use bevy::render::render_phase::{
-    BinnedPhaseItem, CachedRenderPipelinePhaseItem, DrawFunctionId, PhaseItem,
-    PhaseItemBatchSetKey, PhaseItemExtraIndex, SortedPhaseItem,
+    CachedRenderPipelinePhaseItem, DrawFunctionId, PhaseItem, PhaseItemExtraIndex, SortedPhaseItem,
 };
 use bevy::render::render_resource::CachedRenderPipelineId;
 use bevy::render::sync_world::MainEntity;
 
-/// Binned Render Phase that uses lights to render the lightmap texture.
+use crate::prelude::LightSortKey;
+
+/// Sorted Render Phase that uses lights to render the lightmap texture.
 pub struct LightmapPhase {
-    batch_set_key: LightBatchSetKey,
+    /// Lights are drawn in order of their [`LightSortKey`], then their entity.
+    pub sort_key: (LightSortKey, Entity),
     pub entity: (Entity, MainEntity),
+    pub pipeline: CachedRenderPipelineId,
+    pub draw_function: DrawFunctionId,
     pub batch_range: Range<u32>,
     pub extra_index: PhaseItemExtraIndex,
 }
@@ -47,7 +51,7 @@ impl PhaseItem for LightmapPhase {
 
     #[inline]
     fn draw_function(&self) -> DrawFunctionId {
-        self.batch_set_key.draw_function
+        self.draw_function
     }
 
     #[inline]
@@ -71,42 +75,29 @@ impl PhaseItem for LightmapPhase {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct LightBatchSetKey {
-    pub pipeline: CachedRenderPipelineId,
-    pub draw_function: DrawFunctionId,
-}
+impl SortedPhaseItem for LightmapPhase {
+    type SortKey = (LightSortKey, Entity);
 
-impl PhaseItemBatchSetKey for LightBatchSetKey {
-    fn indexed(&self) -> bool {
-        false
+    #[inline]
+    fn sort_key(&self) -> Self::SortKey {
+        self.sort_key
     }
-}
 
-impl BinnedPhaseItem for LightmapPhase {
-    type BinKey = ();
-
-    type BatchSetKey = LightBatchSetKey;
-
-    fn new(
-        batch_set_key: Self::BatchSetKey,
-        _bin_key: Self::BinKey,
-        representative_entity: (Entity, MainEntity),
-        batch_range: Range<u32>,
-        extra_index: PhaseItemExtraIndex,
-    ) -> Self {
-        Self {
-            batch_set_key,
-            entity: representative_entity,
-            batch_range,
-            extra_index,
-        }
+    #[inline]
+    fn sort(items: &mut [Self]) {
+        items.sort_by_key(SortedPhaseItem::sort_key);
+    }
+
+    #[inline]
+    fn indexed(&self) -> bool {
+        false
     }
 }
 
 impl CachedRenderPipelinePhaseItem for LightmapPhase {
+    #[inline]
     fn cached_pipeline(&self) -> CachedRenderPipelineId {
-        self.batch_set_key.pipeline
+        self.pipeline
     }
 }
 
@@ -5,19 +5,20 @@ use std::borrow::Cow;
 use bevy::{
     asset::{embedded_asset, load_embedded_asset},
     core_pipeline::{FullscreenShader, tonemapping::get_lut_bind_group_layout_entries},
-    mesh::{PrimitiveTopology, VertexBufferLayout, VertexFormat},
     prelude::*,
     render::{
         RenderApp, RenderStartup,
         render_resource::{
-            BindGroupLayoutDescriptor, BindGroupLayoutEntries, BlendComponent, BlendFactor,
-            BlendOperation, BlendState, CachedRenderPipelineId, ColorTargetState, ColorWrites,
-            FilterMode, FragmentState, FrontFace, MultisampleState, PolygonMode, PrimitiveState,
-            RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages,
+            AddressMode, BindGroupLayoutDescriptor, BindGroupLayoutEntries, BlendComponent,
+            BlendFactor, BlendOperation, BlendState, CachedComputePipelineId,
+            CachedRenderPipelineId, ColorTargetState, ColorWrites, ComputePipelineDescriptor,
+            FilterMode, FragmentState, MultisampleState, PipelineCache, RenderPipelineDescriptor,
+            Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages,
             SpecializedRenderPipeline, SpecializedRenderPipelines, TextureFormat,
-            TextureSampleType, VertexAttribute, VertexState, VertexStepMode,
+            TextureSampleType, VertexState,
             binding_types::{
-                sampler, storage_buffer_read_only, texture_2d, texture_2d_array, uniform_buffer,
+                sampler, storage_buffer, storage_buffer_read_only, texture_2d, texture_2d_array,
+                uniform_buffer,
             },
         },
         renderer::RenderDevice,
@@ -28,11 +29,33 @@ use bevy::{
 
 use crate::{
     buffers::{BinIndices, OccluderPointer},
+    caustics::UniformCaustics,
     data::UniformFireflyConfig,
+    exposure::UniformAutoExposure,
+    flare::UniformLensFlare,
     lights::UniformPointLight,
     occluders::{UniformOccluder, UniformRoundOccluder},
+    outline::UniformDarknessOutline,
+};
+#[cfg(feature = "sprites")]
+use crate::{drop_shadow::UniformDropShadow, tilemap::UniformTilemapNormalLayer};
+#[cfg(feature = "sprites")]
+use bevy::{
+    core_pipeline::core_2d::CORE_2D_DEPTH_FORMAT,
+    mesh::{PrimitiveTopology, VertexBufferLayout, VertexFormat},
+    render::render_resource::{
+        CompareFunction, DepthBiasState, DepthStencilState, FrontFace, PolygonMode, PrimitiveState,
+        StencilFaceState, StencilState, VertexAttribute, VertexStepMode,
+    },
 };
 
+/// Format of the mask marking the sprites outlined by the [accessibility assist](crate::prelude::AccessibilityAssist)
+/// in its red channel, and the [minimum light](crate::prelude::MinimumLight) of sprites in its green channel.
+pub const ASSIST_MASK_FORMAT: TextureFormat = TextureFormat::Rg8Unorm;
+
+/// Format of the texture storing the stencil ID of the sprite drawn at each pixel, split in bytes over its color channels.
+pub const SPRITE_ID_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
 /// Plugin that initializes various Pipelines. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
 pub struct PipelinePlugin;
 
@@ -44,7 +67,15 @@ impl Plugin for PipelinePlugin {
         embedded_asset!(app, "shaders/create_lightmap.wgsl");
         embedded_asset!(app, "shaders/apply_lightmap.wgsl");
         embedded_asset!(app, "shaders/combine_lightmaps.wgsl");
-        embedded_asset!(app, "shaders/sprite.wgsl");
+        embedded_asset!(app, "shaders/caustics.wgsl");
+        embedded_asset!(app, "shaders/auto_exposure.wgsl");
+        embedded_asset!(app, "shaders/lens_flare.wgsl");
+        #[cfg(feature = "sprites")]
+        {
+            embedded_asset!(app, "shaders/sprite.wgsl");
+            embedded_asset!(app, "shaders/tilemap_normal.wgsl");
+            embedded_asset!(app, "shaders/drop_shadow.wgsl");
+        }
 
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
@@ -54,7 +85,8 @@ impl Plugin for PipelinePlugin {
             .init_resource::<SpecializedRenderPipelines<LightmapCreationPipeline>>()
             .init_resource::<SpecializedRenderPipelines<LightmapApplicationPipeline>>()
             .init_resource::<SpecializedRenderPipelines<LightmapCombinationPipeline>>()
-            .init_resource::<SpecializedRenderPipelines<SpritePipeline>>();
+            .init_resource::<SpecializedRenderPipelines<CausticsPipeline>>()
+            .init_resource::<SpecializedRenderPipelines<LensFlarePipeline>>();
 
         render_app.add_systems(
             RenderStartup,
@@ -62,9 +94,28 @@ impl Plugin for PipelinePlugin {
                 init_lightmap_creation_pipeline,
                 init_lightmap_application_pipeline,
                 init_lightmap_combination_pipeline,
-                init_sprite_pipeline,
+                init_caustics_pipeline.after(init_lightmap_creation_pipeline),
+                init_auto_exposure_pipeline,
+                init_lens_flare_pipeline,
             ),
         );
+
+        #[cfg(feature = "sprites")]
+        {
+            render_app
+                .init_resource::<SpecializedRenderPipelines<SpritePipeline>>()
+                .init_resource::<SpecializedRenderPipelines<TilemapNormalPipeline>>()
+                .init_resource::<SpecializedRenderPipelines<DropShadowPipeline>>();
+
+            render_app.add_systems(
+                RenderStartup,
+                (
+                    init_sprite_pipeline,
+                    init_tilemap_normal_pipeline.after(init_sprite_pipeline),
+                    init_drop_shadow_pipeline.after(init_sprite_pipeline),
+                ),
+            );
+        }
     }
 }
 
@@ -110,6 +161,18 @@ fn init_lightmap_creation_pipeline(
                 (9, texture_2d(TextureSampleType::Float { filterable: true })),
                 // config,
                 (10, uniform_buffer::<UniformFireflyConfig>(false)),
+                // color gradient luts
+                (
+                    11,
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                ),
+                // sprite stencil ids
+                (
+                    12,
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                ),
+                // shadow receivers
+                (13, storage_buffer_read_only::<u32>(false)),
             ),
         ),
     );
@@ -199,10 +262,25 @@ impl LightPipelineKey {
     }
 }
 
+/// Key of the [`LightmapCreationPipeline`].
+///
+/// The lightmap's format can differ from the view's (see [`FireflyConfig::lightmap_format`](crate::prelude::FireflyConfig::lightmap_format)),
+/// so it's part of the key along with the view's [`LightPipelineKey`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LightmapCreationPipelineKey {
+    pub view_key: LightPipelineKey,
+    pub format: TextureFormat,
+}
+
 impl SpecializedRenderPipeline for LightmapCreationPipeline {
-    type Key = LightPipelineKey;
+    type Key = LightmapCreationPipelineKey;
 
     fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let LightmapCreationPipelineKey {
+            view_key: key,
+            format,
+        } = key;
+
         let mut shader_defs = Vec::new();
         if key.contains(LightPipelineKey::TONEMAP_IN_SHADER) {
             shader_defs.push("TONEMAP_IN_SHADER".into());
@@ -242,11 +320,6 @@ impl SpecializedRenderPipeline for LightmapCreationPipeline {
             }
         }
 
-        let format = match key.contains(LightPipelineKey::HDR) {
-            true => ViewTarget::TEXTURE_FORMAT_HDR,
-            false => TextureFormat::bevy_default(),
-        };
-
         RenderPipelineDescriptor {
             label: Some(Cow::Borrowed("lightmap creation pipeline")),
             layout: vec![self.lut_layout.clone(), self.layout.clone()],
@@ -261,7 +334,12 @@ impl SpecializedRenderPipeline for LightmapCreationPipeline {
                             dst_factor: BlendFactor::One,
                             operation: BlendOperation::Max,
                         },
-                        alpha: BlendComponent::REPLACE,
+                        // pixels reached by any important light stay marked
+                        alpha: BlendComponent {
+                            src_factor: BlendFactor::One,
+                            dst_factor: BlendFactor::One,
+                            operation: BlendOperation::Max,
+                        },
                     }),
                     write_mask: ColorWrites::ALL,
                 })],
@@ -280,12 +358,111 @@ impl SpecializedRenderPipeline for LightmapCreationPipeline {
     }
 }
 
+/// Pipeline that composites [`Caustics2d`](crate::caustics::Caustics2d) patterns into the lightmap.
+///
+/// The patterns are multiplied with the light already in the lightmap, so they only show up where
+/// the region is lit.
+#[derive(Resource)]
+pub struct CausticsPipeline {
+    pub lut_layout: BindGroupLayoutDescriptor,
+    pub layout: BindGroupLayoutDescriptor,
+    pub sampler: Sampler,
+    pub shader: Handle<Shader>,
+}
+
+fn init_caustics_pipeline(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    creation_pipeline: Res<LightmapCreationPipeline>,
+    asset_server: Res<AssetServer>,
+) {
+    let layout = BindGroupLayoutDescriptor::new(
+        "caustics_layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::VERTEX_FRAGMENT,
+            (
+                // caustics texture
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                // caustics sampler
+                sampler(SamplerBindingType::Filtering),
+                // caustics data
+                uniform_buffer::<UniformCaustics>(true),
+            ),
+        ),
+    );
+
+    // the texture tiles over the whole region
+    let sampler = render_device.create_sampler(&SamplerDescriptor {
+        address_mode_u: AddressMode::Repeat,
+        address_mode_v: AddressMode::Repeat,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..default()
+    });
+
+    commands.insert_resource(CausticsPipeline {
+        lut_layout: creation_pipeline.lut_layout.clone(),
+        layout,
+        sampler,
+        shader: load_embedded_asset!(asset_server.as_ref(), "shaders/caustics.wgsl"),
+    });
+}
+
+impl SpecializedRenderPipeline for CausticsPipeline {
+    /// Format of the lightmap the caustics are composited into.
+    type Key = TextureFormat;
+
+    fn specialize(&self, format: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some(Cow::Borrowed("caustics pipeline")),
+            layout: vec![self.lut_layout.clone(), self.layout.clone()],
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                entry_point: Some("vertex".into()),
+                shader_defs: vec![],
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    // light * (1 + caustics), so unlit and shadowed areas stay dark
+                    blend: Some(BlendState {
+                        color: BlendComponent {
+                            src_factor: BlendFactor::Dst,
+                            dst_factor: BlendFactor::One,
+                            operation: BlendOperation::Add,
+                        },
+                        alpha: BlendComponent {
+                            src_factor: BlendFactor::Zero,
+                            dst_factor: BlendFactor::One,
+                            operation: BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: ColorWrites::ALL,
+                })],
+                shader_defs: vec![],
+                entry_point: Some(Cow::Borrowed("fragment")),
+            }),
+            push_constant_ranges: default(),
+            primitive: default(),
+            depth_stencil: default(),
+            multisample: MultisampleState {
+                count: 1,
+                ..default()
+            },
+            zero_initialize_workgroup_memory: default(),
+        }
+    }
+}
+
 /// Pipeline that applies the lightmap over the fullscreen view.
 #[derive(Resource)]
 pub struct LightmapApplicationPipeline {
     pub layout: BindGroupLayoutDescriptor,
     pub filtering_sampler: Sampler,
     pub non_filtering_sampler: Sampler,
+    pub repeat_sampler: Sampler,
     pub vertex_state: VertexState,
     pub shader: Handle<Shader>,
 }
@@ -306,7 +483,7 @@ impl LightmapApplicationPipeline {
         if combined {
             layout.entries.push(
                 texture_2d_array(TextureSampleType::Float { filterable: true })
-                    .build(5, ShaderStages::FRAGMENT),
+                    .build(6, ShaderStages::FRAGMENT),
             );
         }
 
@@ -317,6 +494,7 @@ impl LightmapApplicationPipeline {
 #[derive(Component)]
 pub struct SpecializedApplicationPipeline {
     pub id: CachedRenderPipelineId,
+    pub key: LightPipelineKey,
     pub is_combined: bool,
     pub filter_lightmap: bool,
 }
@@ -329,19 +507,38 @@ fn init_lightmap_application_pipeline(
 ) {
     let layout = BindGroupLayoutDescriptor::new(
         "apply lightmap layout simple",
-        &BindGroupLayoutEntries::sequential(
+        &BindGroupLayoutEntries::with_indices(
             ShaderStages::FRAGMENT,
             (
                 // screen texture
-                texture_2d(TextureSampleType::Float { filterable: true }),
+                (0, texture_2d(TextureSampleType::Float { filterable: true })),
                 // lightmap texture
-                texture_2d(TextureSampleType::Float { filterable: true }),
+                (1, texture_2d(TextureSampleType::Float { filterable: true })),
                 // screen filter
-                sampler(SamplerBindingType::Filtering),
+                (2, sampler(SamplerBindingType::Filtering)),
                 // lightmap filter
-                sampler(SamplerBindingType::Filtering),
+                (3, sampler(SamplerBindingType::Filtering)),
                 // config
-                uniform_buffer::<UniformFireflyConfig>(false),
+                (4, uniform_buffer::<UniformFireflyConfig>(false)),
+                // accessibility assist mask
+                (
+                    5,
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                ),
+                // weather overlay, 6 is taken by the combined lightmaps
+                (7, texture_2d(TextureSampleType::Float { filterable: true })),
+                (8, sampler(SamplerBindingType::Filtering)),
+                // sprite stencil ids and darkness outlines
+                (
+                    9,
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                ),
+                (
+                    10,
+                    storage_buffer_read_only::<UniformDarknessOutline>(false),
+                ),
+                // auto exposure, in stops
+                (11, storage_buffer_read_only::<f32>(false)),
             ),
         ),
     );
@@ -360,12 +557,22 @@ fn init_lightmap_application_pipeline(
         ..default()
     });
 
+    // the weather overlay tiles over the whole world
+    let repeat_sampler = render_device.create_sampler(&SamplerDescriptor {
+        address_mode_u: AddressMode::Repeat,
+        address_mode_v: AddressMode::Repeat,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..default()
+    });
+
     let vertex_state = fullscreen_shader.to_vertex_state();
 
     commands.insert_resource(LightmapApplicationPipeline {
         layout,
         filtering_sampler,
         non_filtering_sampler,
+        repeat_sampler,
         vertex_state,
         shader: load_embedded_asset!(asset_server.as_ref(), "shaders/apply_lightmap.wgsl"),
     });
@@ -392,6 +599,11 @@ impl SpecializedRenderPipeline for LightmapApplicationPipeline {
             shader_defs.push("FILTER_LIGHTMAP".into());
         }
 
+        // Only specialized for non-HDR targets, where 8-bit precision makes smooth falloffs band.
+        if key.contains(LightPipelineKey::DEBAND_DITHER) {
+            shader_defs.push("DEBAND_DITHER".into());
+        }
+
         let filter_lightmap = key.contains(LightPipelineKey::LIGHTMAP_FILTERING);
 
         RenderPipelineDescriptor {
@@ -420,6 +632,136 @@ impl SpecializedRenderPipeline for LightmapApplicationPipeline {
     }
 }
 
+/// Pipeline that draws [lens flares](crate::prelude::LensFlare) additively over the lit view.
+#[derive(Resource)]
+pub struct LensFlarePipeline {
+    pub layout: BindGroupLayoutDescriptor,
+    pub shader: Handle<Shader>,
+}
+
+fn init_lens_flare_pipeline(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let layout = BindGroupLayoutDescriptor::new(
+        "lens flare layout",
+        &BindGroupLayoutEntries::single(
+            ShaderStages::VERTEX_FRAGMENT,
+            storage_buffer_read_only::<UniformLensFlare>(false),
+        ),
+    );
+
+    commands.insert_resource(LensFlarePipeline {
+        layout,
+        shader: load_embedded_asset!(asset_server.as_ref(), "shaders/lens_flare.wgsl"),
+    });
+}
+
+impl SpecializedRenderPipeline for LensFlarePipeline {
+    /// Only the [`HDR`](LightPipelineKey::HDR) and MSAA bits of the view's key are used.
+    type Key = LightPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let format = match key.contains(LightPipelineKey::HDR) {
+            true => ViewTarget::TEXTURE_FORMAT_HDR,
+            false => TextureFormat::bevy_default(),
+        };
+
+        RenderPipelineDescriptor {
+            label: Some(Cow::Borrowed("lens flare pipeline")),
+            layout: vec![self.layout.clone()],
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                entry_point: Some("vertex".into()),
+                shader_defs: vec![],
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState {
+                        color: BlendComponent {
+                            src_factor: BlendFactor::One,
+                            dst_factor: BlendFactor::One,
+                            operation: BlendOperation::Add,
+                        },
+                        alpha: BlendComponent {
+                            src_factor: BlendFactor::Zero,
+                            dst_factor: BlendFactor::One,
+                            operation: BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: ColorWrites::ALL,
+                })],
+                shader_defs: vec![],
+                entry_point: Some(Cow::Borrowed("fragment")),
+            }),
+            push_constant_ranges: default(),
+            primitive: default(),
+            depth_stencil: default(),
+            multisample: MultisampleState {
+                count: key.msaa_samples(),
+                ..default()
+            },
+            zero_initialize_workgroup_memory: default(),
+        }
+    }
+}
+
+/// Compute pipelines that measure the lightmap and adapt the [auto exposure](crate::prelude::AutoExposure) of a view.
+///
+/// The first one accumulates a luminance histogram of the lightmap, the second one averages it and moves the exposure towards its target.
+#[derive(Resource)]
+pub struct AutoExposurePipeline {
+    pub layout: BindGroupLayoutDescriptor,
+    pub histogram_pipeline: CachedComputePipelineId,
+    pub average_pipeline: CachedComputePipelineId,
+}
+
+fn init_auto_exposure_pipeline(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    asset_server: Res<AssetServer>,
+) {
+    let layout = BindGroupLayoutDescriptor::new(
+        "auto exposure layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                // lightmap
+                texture_2d(TextureSampleType::Float { filterable: false }),
+                // settings
+                uniform_buffer::<UniformAutoExposure>(false),
+                // histogram
+                storage_buffer::<[u32; 64]>(false),
+                // exposure, in stops
+                storage_buffer::<f32>(false),
+            ),
+        ),
+    );
+
+    let shader = load_embedded_asset!(asset_server.as_ref(), "shaders/auto_exposure.wgsl");
+
+    let queue = |label: &'static str, entry_point: &'static str| {
+        pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some(Cow::Borrowed(label)),
+            layout: vec![layout.clone()],
+            push_constant_ranges: default(),
+            shader: shader.clone(),
+            shader_defs: default(),
+            entry_point: Some(Cow::Borrowed(entry_point)),
+            zero_initialize_workgroup_memory: true,
+        })
+    };
+
+    let histogram_pipeline = queue("auto exposure histogram pipeline", "compute_histogram");
+    let average_pipeline = queue("auto exposure average pipeline", "compute_average");
+
+    commands.insert_resource(AutoExposurePipeline {
+        layout,
+        histogram_pipeline,
+        average_pipeline,
+    });
+}
+
 /// Pipeline that multiplies an array of lightmaps.
 #[derive(Resource)]
 pub struct LightmapCombinationPipeline {
@@ -497,15 +839,22 @@ impl SpecializedRenderPipeline for LightmapCombinationPipeline {
 }
 
 /// Pipeline that produces the stencil and normal textures from the sprite bindings.
+#[cfg(feature = "sprites")]
 #[derive(Resource)]
 #[allow(dead_code)]
 pub struct SpritePipeline {
     pub view_layout: BindGroupLayoutDescriptor,
     pub material_layout: BindGroupLayoutDescriptor,
+    pub detail_sampler: Sampler,
     pub shader: Handle<Shader>,
 }
 
-fn init_sprite_pipeline(mut commands: Commands, asset_server: Res<AssetServer>) {
+#[cfg(feature = "sprites")]
+fn init_sprite_pipeline(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    asset_server: Res<AssetServer>,
+) {
     let tonemapping_lut_entries = get_lut_bind_group_layout_entries();
     let view_layout = BindGroupLayoutDescriptor::new(
         "sprite_view_layout",
@@ -538,17 +887,33 @@ fn init_sprite_pipeline(mut commands: Commands, asset_server: Res<AssetServer>)
                 sampler(SamplerBindingType::Filtering),
                 // dummy normal bool
                 uniform_buffer::<u32>(false),
+                // detail normal map texture
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                // detail normal map sampler
+                sampler(SamplerBindingType::Filtering),
             ),
         ),
     );
 
+    // detail normal maps are tiled across the sprite, so they need to repeat
+    let detail_sampler = render_device.create_sampler(&SamplerDescriptor {
+        address_mode_u: AddressMode::Repeat,
+        address_mode_v: AddressMode::Repeat,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        mipmap_filter: FilterMode::Linear,
+        ..default()
+    });
+
     commands.insert_resource(SpritePipeline {
         view_layout,
         material_layout,
+        detail_sampler,
         shader: load_embedded_asset!(asset_server.as_ref(), "shaders/sprite.wgsl"),
     });
 }
 
+#[cfg(feature = "sprites")]
 impl SpecializedRenderPipeline for SpritePipeline {
     type Key = SpritePipelineKey;
 
@@ -593,7 +958,7 @@ impl SpecializedRenderPipeline for SpritePipeline {
         }
 
         let instance_rate_vertex_buffer_layout = VertexBufferLayout {
-            array_stride: 80,
+            array_stride: 112,
             step_mode: VertexStepMode::Instance,
             attributes: vec![
                 // @location(0) i_model_transpose_col0: vec4<f32>,
@@ -638,6 +1003,42 @@ impl SpecializedRenderPipeline for SpritePipeline {
                     offset: 72,
                     shader_location: 6,
                 },
+                // @location(7) detail_strength: f32,
+                VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 76,
+                    shader_location: 7,
+                },
+                // @location(8) detail_tiling: vec2<f32>,
+                VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 80,
+                    shader_location: 8,
+                },
+                // @location(9) outline: f32,
+                VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 88,
+                    shader_location: 9,
+                },
+                // @location(10) id: u32,
+                VertexAttribute {
+                    format: VertexFormat::Uint32,
+                    offset: 92,
+                    shader_location: 10,
+                },
+                // @location(11) min_light: f32,
+                VertexAttribute {
+                    format: VertexFormat::Float32,
+                    offset: 96,
+                    shader_location: 11,
+                },
+                // @location(12) emissive: vec3<f32>,
+                VertexAttribute {
+                    format: VertexFormat::Float32x3,
+                    offset: 100,
+                    shader_location: 12,
+                },
             ],
         };
 
@@ -646,6 +1047,30 @@ impl SpecializedRenderPipeline for SpritePipeline {
             true => TextureFormat::Rgba32Float,
         };
 
+        let (stencil_write_mask, normal_blend) = match key.contains(SpritePipelineKey::DECAL) {
+            false => (ColorWrites::ALL, BlendState::ALPHA_BLENDING),
+            // decals keep the stencil of the sprites below them, and only blend their normals
+            // where a sprite has already been drawn (destination alpha).
+            true => {
+                shader_defs.push("DECAL".into());
+                (
+                    ColorWrites::empty(),
+                    BlendState {
+                        color: BlendComponent {
+                            src_factor: BlendFactor::DstAlpha,
+                            dst_factor: BlendFactor::OneMinusSrcAlpha,
+                            operation: BlendOperation::Add,
+                        },
+                        alpha: BlendComponent {
+                            src_factor: BlendFactor::Zero,
+                            dst_factor: BlendFactor::One,
+                            operation: BlendOperation::Add,
+                        },
+                    },
+                )
+            }
+        };
+
         RenderPipelineDescriptor {
             vertex: VertexState {
                 shader: self.shader.clone(),
@@ -661,13 +1086,23 @@ impl SpecializedRenderPipeline for SpritePipeline {
                     Some(ColorTargetState {
                         format: stencil_format,
                         blend: Some(BlendState::ALPHA_BLENDING),
-                        write_mask: ColorWrites::ALL,
+                        write_mask: stencil_write_mask,
                     }),
                     Some(ColorTargetState {
                         format: TextureFormat::Rgba16Float,
-                        blend: Some(BlendState::ALPHA_BLENDING),
+                        blend: Some(normal_blend),
                         write_mask: ColorWrites::ALL,
                     }),
+                    Some(ColorTargetState {
+                        format: ASSIST_MASK_FORMAT,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: stencil_write_mask,
+                    }),
+                    Some(ColorTargetState {
+                        format: SPRITE_ID_FORMAT,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: stencil_write_mask,
+                    }),
                 ],
             }),
             layout: vec![self.view_layout.clone(), self.material_layout.clone()],
@@ -690,6 +1125,7 @@ impl SpecializedRenderPipeline for SpritePipeline {
     }
 }
 
+#[cfg(feature = "sprites")]
 bitflags::bitflags! {
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
     #[repr(transparent)]
@@ -710,10 +1146,12 @@ bitflags::bitflags! {
         const TONEMAP_METHOD_SOMEWHAT_BORING_DISPLAY_TRANSFORM = 5 << Self::TONEMAP_METHOD_SHIFT_BITS;
         const TONEMAP_METHOD_TONY_MC_MAPFACE    = 6 << Self::TONEMAP_METHOD_SHIFT_BITS;
         const TONEMAP_METHOD_BLENDER_FILMIC     = 7 << Self::TONEMAP_METHOD_SHIFT_BITS;
+        const DECAL                 = 1 << 30;
         const ENABLED_32BIT_STENCIL = 1 << 31;
     }
 }
 
+#[cfg(feature = "sprites")]
 impl SpritePipelineKey {
     const MSAA_MASK_BITS: u32 = 0b111;
     const MSAA_SHIFT_BITS: u32 = 30 - Self::MSAA_MASK_BITS.count_ones();
@@ -742,3 +1180,204 @@ impl SpritePipelineKey {
         }
     }
 }
+
+/// Pipeline that renders a whole [`TilemapNormalLayer`](crate::tilemap::TilemapNormalLayer) chunk
+/// into the stencil and normal textures in a single draw.
+#[derive(Resource)]
+#[cfg(feature = "sprites")]
+pub struct TilemapNormalPipeline {
+    pub view_layout: BindGroupLayoutDescriptor,
+    pub layout: BindGroupLayoutDescriptor,
+    pub shader: Handle<Shader>,
+}
+
+#[cfg(feature = "sprites")]
+fn init_tilemap_normal_pipeline(
+    mut commands: Commands,
+    sprite_pipeline: Res<SpritePipeline>,
+    asset_server: Res<AssetServer>,
+) {
+    let layout = BindGroupLayoutDescriptor::new(
+        "tilemap_normal_layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::VERTEX_FRAGMENT,
+            (
+                // tileset
+                texture_2d_array(TextureSampleType::Float { filterable: true }),
+                // normal tileset
+                texture_2d_array(TextureSampleType::Float { filterable: true }),
+                // tileset sampler
+                sampler(SamplerBindingType::Filtering),
+                // tile data
+                texture_2d(TextureSampleType::Uint),
+                // layer data
+                uniform_buffer::<UniformTilemapNormalLayer>(true),
+            ),
+        ),
+    );
+
+    commands.insert_resource(TilemapNormalPipeline {
+        view_layout: sprite_pipeline.view_layout.clone(),
+        layout,
+        shader: load_embedded_asset!(asset_server.as_ref(), "shaders/tilemap_normal.wgsl"),
+    });
+}
+
+#[cfg(feature = "sprites")]
+impl SpecializedRenderPipeline for TilemapNormalPipeline {
+    type Key = SpritePipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let stencil_format = match key.contains(SpritePipelineKey::ENABLED_32BIT_STENCIL) {
+            false => TextureFormat::Rgba16Float,
+            true => TextureFormat::Rgba32Float,
+        };
+
+        RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                entry_point: Some("vertex".into()),
+                shader_defs: vec![],
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs: vec![],
+                entry_point: Some("fragment".into()),
+                targets: vec![
+                    Some(ColorTargetState {
+                        format: stencil_format,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    Some(ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    Some(ColorTargetState {
+                        format: ASSIST_MASK_FORMAT,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    Some(ColorTargetState {
+                        format: SPRITE_ID_FORMAT,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    }),
+                ],
+            }),
+            layout: vec![self.view_layout.clone(), self.layout.clone()],
+            primitive: PrimitiveState {
+                cull_mode: None,
+                topology: PrimitiveTopology::TriangleList,
+                ..default()
+            },
+            depth_stencil: None,
+            multisample: default(),
+            label: Some("tilemap_normal_pipeline".into()),
+            push_constant_ranges: Vec::new(),
+            zero_initialize_workgroup_memory: false,
+        }
+    }
+}
+
+/// Pipeline that renders the blurred silhouettes of [`DropShadow2d`](crate::drop_shadow::DropShadow2d)s
+/// in the main pass, underneath their sprites.
+#[derive(Resource)]
+#[cfg(feature = "sprites")]
+pub struct DropShadowPipeline {
+    pub view_layout: BindGroupLayoutDescriptor,
+    pub layout: BindGroupLayoutDescriptor,
+    pub shader: Handle<Shader>,
+}
+
+#[cfg(feature = "sprites")]
+fn init_drop_shadow_pipeline(
+    mut commands: Commands,
+    sprite_pipeline: Res<SpritePipeline>,
+    asset_server: Res<AssetServer>,
+) {
+    let layout = BindGroupLayoutDescriptor::new(
+        "drop_shadow_layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::VERTEX_FRAGMENT,
+            (
+                // sprite texture
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                // sprite sampler
+                sampler(SamplerBindingType::Filtering),
+                // shadow data
+                uniform_buffer::<UniformDropShadow>(true),
+            ),
+        ),
+    );
+
+    commands.insert_resource(DropShadowPipeline {
+        view_layout: sprite_pipeline.view_layout.clone(),
+        layout,
+        shader: load_embedded_asset!(asset_server.as_ref(), "shaders/drop_shadow.wgsl"),
+    });
+}
+
+#[cfg(feature = "sprites")]
+impl SpecializedRenderPipeline for DropShadowPipeline {
+    type Key = SpritePipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let format = match key.contains(SpritePipelineKey::HDR) {
+            true => ViewTarget::TEXTURE_FORMAT_HDR,
+            false => TextureFormat::bevy_default(),
+        };
+
+        RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                entry_point: Some("vertex".into()),
+                shader_defs: vec![],
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs: vec![],
+                entry_point: Some("fragment".into()),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            layout: vec![self.view_layout.clone(), self.layout.clone()],
+            primitive: PrimitiveState {
+                cull_mode: None,
+                topology: PrimitiveTopology::TriangleList,
+                ..default()
+            },
+            // same depth state as bevy's sprites, since they're drawn in the same pass
+            depth_stencil: Some(DepthStencilState {
+                format: CORE_2D_DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::GreaterEqual,
+                stencil: StencilState {
+                    front: StencilFaceState::IGNORE,
+                    back: StencilFaceState::IGNORE,
+                    read_mask: 0,
+                    write_mask: 0,
+                },
+                bias: DepthBiasState {
+                    constant: 0,
+                    slope_scale: 0.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: MultisampleState {
+                count: key.msaa_samples(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            label: Some("drop_shadow_pipeline".into()),
+            push_constant_ranges: Vec::new(),
+            zero_initialize_workgroup_memory: false,
+        }
+    }
+}
@@ -1,21 +1,39 @@
 //! Module that prepares BindGroups for GPU use.
 
 use core::f32;
-use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use std::{
+    cmp::Reverse,
+    f32::consts::{FRAC_PI_2, PI, TAU},
+};
 
 use crate::{
-    CombinedLightMapTextures, LightmapPhase, NormalMapTexture, SpriteStencilTexture,
-    buffers::{BinBuffer, BinBuffers, BufferManager, OccluderData, OccluderPointer, VertexBuffer},
+    AssistMaskTexture, CombinedLightMapTextures, NormalMapTexture, SpriteIdTexture,
+    SpriteStencilTexture,
+    buffers::{
+        BinBuffer, BinBuffers, BinnedView, BufferManager, OccluderData, OccluderPointer,
+        ShadowReceiverBuffer, SkippedBinning, StaleBins, VertexBuffer,
+    },
     data::{
-        CombinationMode, ExtractedCombinedLightmaps, ExtractedWorldData, LightmapSize, NormalMode,
+        CombinationMode, ExtractedCombinedLightmaps, FireflyBinningBudget, FireflyGlobalOverrides,
+        FireflyTestClock, LightmapSize, MAX_BAND_THRESHOLDS, MAX_BAND_TINTS, NormalMode,
+        PenumbraMode, ShadowCullingMode, Softness, ZSortKey,
     },
+    flicker::ExtractedAmbientFlicker,
+    gradient::ColorGradientLuts,
     lights::{LightBatch, LightBatches, LightBindGroups, LightIndex, LightLut, LightPointer},
+    occluder_batch::{ExtractedOccluderBatch, OccluderBatchIndices},
     occluders::{PolyOccluderIndex, RoundOccluderIndex, point_inside_poly, translate_vertices},
-    phases::SpritePhase,
+    outline::ExtractedDarknessOutlines,
     pipelines::{
-        LightPipelineKey, LightmapApplicationPipeline, LightmapCreationPipeline,
-        SpecializedApplicationPipeline, SpritePipeline,
+        ASSIST_MASK_FORMAT, LightPipelineKey, LightmapApplicationPipeline,
+        LightmapCreationPipeline, SPRITE_ID_FORMAT, SpecializedApplicationPipeline,
     },
+    weather::ExtractedWeatherOverlay,
+};
+#[cfg(feature = "sprites")]
+use crate::{
+    phases::SpritePhase,
+    pipelines::SpritePipeline,
     sprites::{
         ExtractedFireflySpriteKind, ExtractedFireflySprites, ImageBindGroups, SpriteAssetEvents,
         SpriteBatch, SpriteBatches, SpriteInstance, SpriteMeta, SpriteViewBindGroup,
@@ -25,20 +43,18 @@ use crate::{
 
 use bevy::{
     camera::visibility::RenderLayers,
-    core_pipeline::tonemapping::{Tonemapping, TonemappingLuts, get_lut_bindings},
-    math::{
-        Affine3A,
-        bounding::{Aabb2d, IntersectsVolume},
-    },
+    core_pipeline::tonemapping::{DebandDither, Tonemapping, TonemappingLuts, get_lut_bindings},
+    diagnostic::FrameCount,
+    math::bounding::{Aabb2d, IntersectsVolume},
     platform::{
         collections::{HashMap, HashSet},
         hash::FixedHasher,
+        time::Instant,
     },
     prelude::*,
     render::{
         Render, RenderApp, RenderSystems,
         render_asset::RenderAssets,
-        render_phase::{PhaseItem, ViewBinnedRenderPhases, ViewSortedRenderPhases},
         render_resource::{
             BindGroup, BindGroupEntries, Extent3d, PipelineCache, SpecializedRenderPipelines,
             TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, UniformBuffer,
@@ -47,17 +63,28 @@ use bevy::{
         texture::{FallbackImage, GpuImage, TextureCache},
         view::{ExtractedView, RetainedViewEntity, ViewTarget, ViewUniforms},
     },
-    sprite_render::ExtractedSlices,
     tasks::{ComputeTaskPool, ParallelSliceMut},
 };
+#[cfg(feature = "sprites")]
+use bevy::{
+    math::Affine3A,
+    render::render_phase::{PhaseItem, ViewSortedRenderPhases},
+    sprite_render::ExtractedSlices,
+};
 
 use crate::{
     LightMapTexture,
     data::{FireflyConfig, UniformFireflyConfig},
     lights::{ExtractedPointLight, UniformPointLight},
     occluders::{ExtractedOccluder, Occluder2dShape, UniformOccluder, UniformRoundOccluder},
+    visibility::view_world_rect,
 };
 
+/// Camera component added when the sprite stencil and normal map textures aren't needed,
+/// in which case the [`SpriteLabel`](crate::SpriteLabel) pass is skipped.
+#[derive(Component)]
+pub struct SkipSpriteStencil;
+
 /// Camera buffer component containing the data extracted from [`FireflyConfig`].
 #[derive(Component)]
 pub struct BufferedFireflyConfig(pub UniformBuffer<UniformFireflyConfig>);
@@ -84,10 +111,15 @@ impl Plugin for PreparePlugin {
         render_app.add_systems(Render, prepare_config.in_set(RenderSystems::Prepare));
         render_app.add_systems(Render, prepare_lightmap.in_set(RenderSystems::Prepare));
 
+        render_app.add_systems(
+            Render,
+            prepare_light_luts.in_set(RenderSystems::PrepareBindGroups),
+        );
+
+        #[cfg(feature = "sprites")]
         render_app.add_systems(
             Render,
             (
-                prepare_light_luts.in_set(RenderSystems::PrepareBindGroups),
                 prepare_sprite_view_bind_groups.in_set(RenderSystems::PrepareBindGroups),
                 prepare_sprite_image_bind_groups.in_set(RenderSystems::PrepareBindGroups),
             ),
@@ -101,6 +133,7 @@ fn specialize_light_application_pipeline(
         &ExtractedView,
         &Msaa,
         &FireflyConfig,
+        Option<&DebandDither>,
         Has<CombinedLightMapTextures>,
     )>,
     pipeline_cache: Res<PipelineCache>,
@@ -108,12 +141,18 @@ fn specialize_light_application_pipeline(
     mut pipelines: ResMut<SpecializedRenderPipelines<LightmapApplicationPipeline>>,
     mut commands: Commands,
 ) {
-    for (entity, view, _msaa, config, is_combined) in views {
+    for (entity, view, _msaa, config, dither, is_combined) in views {
         let mut key = LightPipelineKey::from_hdr(view.hdr);
         if is_combined {
             key |= LightPipelineKey::COMBINE_LIGHTMAPS;
         }
 
+        if !view.hdr
+            && let Some(DebandDither::Enabled) = dither
+        {
+            key |= LightPipelineKey::DEBAND_DITHER;
+        }
+
         if config.lightmap_filtering {
             key |= LightPipelineKey::LIGHTMAP_FILTERING;
         }
@@ -124,6 +163,7 @@ fn specialize_light_application_pipeline(
             .entity(entity)
             .insert(SpecializedApplicationPipeline {
                 id: pipeline_id,
+                key,
                 is_combined,
                 filter_lightmap: config.lightmap_filtering,
             });
@@ -137,11 +177,17 @@ fn prepare_config(
         Entity,
         &FireflyConfig,
         &ViewTarget,
+        &ExtractedView,
         Option<&ExtractedCombinedLightmaps>,
+        Option<&ExtractedWeatherOverlay>,
+        Option<&ExtractedAmbientFlicker>,
     )>,
+    overrides: Res<FireflyGlobalOverrides>,
+    frame_count: Res<FrameCount>,
+    test_clock: Option<Res<FireflyTestClock>>,
     mut commands: Commands,
 ) {
-    for (entity, config, view_target, combined_lightmap) in &configs {
+    for (entity, config, view_target, view, combined_lightmap, weather, flicker) in &configs {
         let window_size = view_target.main_texture().size();
         let scale = match config.lightmap_size {
             LightmapSize::Window => vec2(1.0, 1.0),
@@ -152,29 +198,46 @@ fn prepare_config(
             LightmapSize::Scaled(scale) => vec2(1.0 / scale, 1.0 / scale),
         };
 
-        let uniform = UniformFireflyConfig {
+        let ambient_multiplier = overrides.ambient * flicker.map_or(1.0, |flicker| flicker.0);
+
+        let mut uniform = UniformFireflyConfig {
             ambient_color: config.ambient_color.to_linear().to_vec3(),
-            ambient_brightness: config.ambient_brightness,
+            ambient_brightness: config.ambient_brightness * ambient_multiplier,
 
-            light_bands: config.light_bands.unwrap_or(0.0),
+            light_bands: match overrides.band_count {
+                Some(0) => 0.0,
+                Some(count) => 1.0 / count as f32,
+                None => config.light_bands.unwrap_or(0.0),
+            },
 
-            soft_shadows: match config.soft_shadows {
-                true => 1,
+            soft_shadows: match config.soft_shadows && !overrides.disable_soft_shadows {
                 false => 0,
+                true => match config.penumbra {
+                    PenumbraMode::Analytic => 1,
+                    PenumbraMode::Stochastic => 2,
+                },
             },
 
-            z_sorting: match config.z_sorting {
+            z_sorting: match config.z_sorting
+                && !overrides.disable_z_sorting
+                && cfg!(feature = "sprites")
+            {
                 false => 0,
-                true => 1,
+                true => match config.z_sorting_key {
+                    ZSortKey::Z => 1,
+                    ZSortKey::NegativeY => 2,
+                },
             },
 
             z_sorting_error_margin: config.z_sorting_error_margin,
 
             normal_mode: match config.normal_mode {
+                _ if overrides.disable_normal_maps || !cfg!(feature = "sprites") => 0,
                 NormalMode::None => 0,
                 NormalMode::Simple => 1,
                 NormalMode::TopDownY => 2,
                 NormalMode::TopDownZ => 3,
+                NormalMode::Sidescroller => 4,
             },
 
             normal_attenuation: config.normal_attenuation,
@@ -193,7 +256,98 @@ fn prepare_config(
             },
 
             texture_scale: scale,
+
+            light_pitch: config
+                .light_pitch
+                .map_or(-1.0, |pitch| pitch.clamp(0., 90.) / 180. * PI),
+
+            softness: match config.softness {
+                Softness::CoreScale(scale) => overrides.softness.max(0.) * scale.max(0.),
+                Softness::WorldUnits(_) => overrides.softness.max(0.),
+            },
+
+            contact_shadow_length: config.contact_shadows.map_or(0.0, |c| c.length.max(0.)),
+            contact_shadow_steps: match config.contact_shadows {
+                Some(_) if !cfg!(feature = "sprites") => 0,
+                Some(contact_shadows) => contact_shadows.steps,
+                None => 0,
+            },
+            contact_shadow_opacity: config.contact_shadows.map_or(0.0, |c| c.opacity),
+
+            frame: test_clock
+                .as_ref()
+                .map_or(frame_count.0, |clock| clock.frame),
+
+            shadow_strength: config.shadow_strength.clamp(0., 1.),
+
+            assist_min_luminance: config
+                .accessibility
+                .map_or(0.0, |assist| assist.min_luminance.clamp(0., 1.)),
+            assist_outline_width: match config.accessibility {
+                Some(assist) if assist.uses_outlines() && cfg!(feature = "sprites") => {
+                    assist.outline_width.min(8.)
+                }
+                _ => 0.0,
+            },
+            assist_outline_threshold: config
+                .accessibility
+                .map_or(0.0, |assist| assist.outline_threshold),
+            assist_outline_color: config.accessibility.map_or(Vec4::ZERO, |assist| {
+                assist.outline_color.to_linear().to_vec4()
+            }),
+
+            shadow_skew: config.shadow_skew,
+
+            show_lightmap: match config.show_lightmap {
+                true => 1,
+                false => 0,
+            },
+
+            world_from_clip: view.world_from_view.to_matrix() * view.clip_from_view.inverse(),
+            weather_tile_size: weather.map_or(Vec2::ONE, |weather| weather.tile_size),
+            weather_offset: weather.map_or(Vec2::ZERO, |weather| weather.offset),
+            weather_ambient: weather.map_or(0.0, |weather| weather.ambient_darkening),
+            weather_lights: weather.map_or(0.0, |weather| weather.light_darkening),
+
+            // negative values make the shader use each light's own core
+            shadow_core_radius: match config.softness {
+                Softness::CoreScale(_) => -1.0,
+                Softness::WorldUnits(radius) => radius.max(0.) * overrides.softness.max(0.),
+            },
+
+            sprite_ambient_color: config
+                .sprite_ambient
+                .map_or(Vec3::ZERO, |ambient| ambient.color.to_linear().to_vec3()),
+            sprite_ambient_brightness: match config.sprite_ambient {
+                Some(ambient) if cfg!(feature = "sprites") => {
+                    ambient.brightness.max(0.) * ambient_multiplier
+                }
+                _ => -1.0,
+            },
+
+            band_thresholds: default(),
+            n_band_thresholds: 0,
+
+            band_tints: default(),
+            n_band_tints: config.light_band_tints.len().min(MAX_BAND_TINTS) as u32,
         };
+
+        for (tint, color) in uniform.band_tints.iter_mut().zip(&config.light_band_tints) {
+            *tint = color.to_linear().to_vec4();
+        }
+
+        // the global band count overrides custom thresholds too
+        if overrides.band_count.is_none() && !config.light_band_thresholds.is_empty() {
+            let mut thresholds = config.light_band_thresholds.clone();
+            thresholds.sort_by(f32::total_cmp);
+            thresholds.truncate(MAX_BAND_THRESHOLDS);
+
+            for (i, threshold) in thresholds.iter().enumerate() {
+                uniform.band_thresholds[i / 4][i % 4] = *threshold;
+            }
+            uniform.n_band_thresholds = thresholds.len() as u32;
+        }
+
         let mut buffer = UniformBuffer::<UniformFireflyConfig>::from(uniform);
         buffer.write_buffer(&render_device, &render_queue);
         commands
@@ -214,12 +368,37 @@ fn prepare_lightmap(
         &FireflyConfig,
         &Msaa,
     )>,
+    lights: Query<&ExtractedPointLight>,
+    occluders: Query<&ExtractedOccluder>,
+    occluder_batches: Query<&ExtractedOccluderBatch>,
+    darkness_outlines: Res<ExtractedDarknessOutlines>,
+    #[cfg(feature = "sprites")] sprites: Res<ExtractedFireflySprites>,
 ) {
+    // sprite occlusion ray-marches the stencil, so it needs it regardless of the config
+    let any_sprite_occlusion = lights
+        .iter()
+        .any(|light| light.cast_shadows && light.sprite_occlusion.is_some());
+
+    // and lights with a z range, which read the z of the sprites from it
+    let any_z_range = lights.iter().any(|light| light.z_range.is_some());
+
+    // same for shadow receivers, which are told apart by the sprite IDs rendered alongside the stencil
+    let any_shadow_receivers = occluders
+        .iter()
+        .chain(occluder_batches.iter().flat_map(|batch| &batch.occluders))
+        .any(|occluder| occluder.receivers.is_some());
+
+    // and for sprites with a minimum light or emissive pixels, which are written to the assist mask
+    #[cfg(feature = "sprites")]
+    let any_minimum_light = sprites
+        .sprites
+        .iter()
+        .any(|sprite| sprite.min_light > 0. || sprite.emissive.is_some());
+    #[cfg(not(feature = "sprites"))]
+    let any_minimum_light = false;
+
     for (entity, view_target, view, combined_lightmaps, config, _msaa) in &view_targets {
-        let format = match view.hdr {
-            true => ViewTarget::TEXTURE_FORMAT_HDR,
-            false => TextureFormat::bevy_default(),
-        };
+        let format = config.lightmap_texture_format(view.hdr);
 
         let window_size = view_target.main_texture().size();
 
@@ -256,11 +435,30 @@ fn prepare_lightmap(
             true => TextureFormat::Rgba32Float,
         };
 
+        // if nothing reads the stencil and normal map textures (or without the `sprites` feature, where nothing
+        // is rendered to them), they're only kept as placeholders for the lightmap bind group
+        let skip_sprite_stencil = !cfg!(feature = "sprites")
+            || !(config.uses_sprite_stencil()
+                || any_sprite_occlusion
+                || any_z_range
+                || any_shadow_receivers
+                || darkness_outlines.any
+                || any_minimum_light);
+
+        let sprite_texture_size = match skip_sprite_stencil {
+            false => view_target.main_texture().size(),
+            true => Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        };
+
         let sprite_stencil_texture = texture_cache.get(
             &render_device,
             TextureDescriptor {
                 label: Some("sprite stencil"),
-                size: view_target.main_texture().size(),
+                size: sprite_texture_size,
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: TextureDimension::D2,
@@ -274,7 +472,7 @@ fn prepare_lightmap(
             &render_device,
             TextureDescriptor {
                 label: Some("normal map"),
-                size: view_target.main_texture().size(),
+                size: sprite_texture_size,
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: TextureDimension::D2,
@@ -284,12 +482,47 @@ fn prepare_lightmap(
             },
         );
 
+        let assist_mask_texture = texture_cache.get(
+            &render_device,
+            TextureDescriptor {
+                label: Some("accessibility assist mask"),
+                size: sprite_texture_size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: ASSIST_MASK_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+        );
+
+        let sprite_id_texture = texture_cache.get(
+            &render_device,
+            TextureDescriptor {
+                label: Some("sprite ids"),
+                size: sprite_texture_size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: SPRITE_ID_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+        );
+
         commands.entity(entity).insert((
             LightMapTexture(light_map_texture),
             SpriteStencilTexture(sprite_stencil_texture),
             NormalMapTexture(normal_map_texture),
+            AssistMaskTexture(assist_mask_texture),
+            SpriteIdTexture(sprite_id_texture),
         ));
 
+        match skip_sprite_stencil {
+            true => commands.entity(entity).insert(SkipSpriteStencil),
+            false => commands.entity(entity).remove::<SkipSpriteStencil>(),
+        };
+
         if let Some(combined_lightmaps) = combined_lightmaps
             && !combined_lightmaps.0.is_empty()
         {
@@ -326,39 +559,63 @@ pub(crate) fn prepare_data(
         &mut LightPointer,
         &LightIndex,
         &mut BinBuffers,
+        &mut SkippedBinning,
     )>,
     occluders: Query<(&ExtractedOccluder, &RoundOccluderIndex, &PolyOccluderIndex)>,
+    occluder_batches: Query<(&ExtractedOccluderBatch, &OccluderBatchIndices)>,
     cameras: Query<(
         &ExtractedView,
         &RenderLayers,
-        &ExtractedWorldData,
-        &Projection,
         &SpriteStencilTexture,
         &NormalMapTexture,
         &BufferedFireflyConfig,
         &FireflyConfig,
+        &SpriteIdTexture,
     )>,
-    _phases: Res<ViewBinnedRenderPhases<LightmapPhase>>,
     lightmap_pipeline: Res<LightmapCreationPipeline>,
     mut light_bind_groups: ResMut<LightBindGroups>,
     mut batches: ResMut<LightBatches>,
     round_occluders: Res<BufferManager<UniformRoundOccluder>>,
     poly_occluders: Res<BufferManager<UniformOccluder>>,
     light_buffer: Res<BufferManager<UniformPointLight>>,
-    vertices: Res<VertexBuffer>,
+    // grouped to stay under the system parameter limit
+    (vertices, receiver_buffer, mut stale_bins): (
+        Res<VertexBuffer>,
+        Res<ShadowReceiverBuffer>,
+        ResMut<StaleBins>,
+    ),
+    gradient_luts: Res<ColorGradientLuts>,
     pipeline_cache: Res<PipelineCache>,
+    (overrides, budget, test_clock): (
+        Res<FireflyGlobalOverrides>,
+        Option<Res<FireflyBinningBudget>>,
+        Option<Res<FireflyTestClock>>,
+    ),
 ) {
+    let start = Instant::now();
+
     batches.clear();
 
     let light_bind_groups = &mut *light_bind_groups;
+    // taken so the occluder changes of the next frame are collected from scratch
+    let stale = std::mem::take(&mut *stale_bins);
 
     let mut lights: Vec<_> = lights.iter_mut().collect();
 
+    // skipping lights depends on timing, which would make test renders nondeterministic
+    let budget = budget.filter(|_| test_clock.is_none());
+    if budget.is_some() {
+        // important and directional lights first, then the ones that waited the longest for new bins
+        lights.sort_by_key(|(_, light, .., skipped)| {
+            (!(light.important || light.directional), Reverse(skipped.0))
+        });
+    }
+
     lights
         .par_splat_map_mut(ComputeTaskPool::get(), None, |_, lights| {
             let mut bind_groups: Vec<(Entity, HashMap<RetainedViewEntity, BindGroup>)> = vec![];
 
-            for (entity, light, light_pointer, light_index, bins) in lights {
+            for (entity, light, light_pointer, light_index, bins, skipped) in lights {
                 let Some(index) = light_index.0 else {
                     continue;
                 };
@@ -377,19 +634,30 @@ pub(crate) fn prepare_data(
                             return None;
                         }
 
-                        let Projection::Orthographic(projection) = camera.3 else {
-                            return None;
-                        };
+                        let camera_rect = view_world_rect(
+                            camera.0.world_from_view.to_matrix()
+                                * camera.0.clip_from_view.inverse(),
+                        );
 
-                        let camera_rect = Rect {
-                            min: projection.area.min + camera.2.camera_pos,
-                            max: projection.area.max + camera.2.camera_pos,
+                        // offscreen occluders within the margin can still cast shadows into view
+                        let margin = match camera.5.shadow_culling {
+                            ShadowCullingMode::Conservative => light.culling_margin,
+                            ShadowCullingMode::Aggressive => 0.,
                         };
 
-                        let light_rect = camera_rect.union_point(light.pos).intersect(Rect {
-                            min: light.pos - light.radius,
-                            max: light.pos + light.radius,
-                        });
+                        // occluders between the light and the view can shadow it, except for directional
+                        // lights, whose shadows only reach as far as their margin
+                        let light_rect = match light.bin_sector.y < TAU {
+                            true => camera_rect.inflate(margin),
+                            false => camera_rect
+                                .inflate(margin)
+                                .union_point(light.pos)
+                                .union_point(light.pos + light.streak),
+                        }
+                        .intersect(
+                            Rect::from_corners(light.pos, light.pos + light.streak)
+                                .inflate(light.radius),
+                        );
 
                         if light_rect.is_empty() {
                             return None;
@@ -400,138 +668,118 @@ pub(crate) fn prepare_data(
                             max: light_rect.max,
                         };
 
-                        let bins = bins
-                            .0
-                            .entry(camera.0.retained_view_entity)
-                            .or_insert(default());
-                        bins.reset();
-
                         Some((camera, light_aabb))
                     })
                     .collect::<Vec<_>>();
 
-                for (occluder, round_index, poly_index) in &occluders {
-                    if !light.cast_shadows
-                        || !light.render_layers.intersects(&occluder.render_layers)
-                    {
-                        continue;
-                    }
-
-                    let mut any_soft_shadows = false;
-
-                    let mut retained_views: HashSet<_, FixedHasher> = HashSet::default();
-
-                    cameras.iter().for_each(|(camera, light_aabb)| {
-                        if !occluder.aabb.intersects(light_aabb)
-                            || !camera.1.intersects(&occluder.render_layers)
-                        {
-                            return;
-                        }
-
-                        any_soft_shadows |= camera.7.soft_shadows;
+                // the views each camera sees the light through, used when binning occluders
+                let views = cameras
+                    .iter()
+                    .map(|(camera, light_aabb)| {
+                        (
+                            camera.0.retained_view_entity,
+                            BinnedView {
+                                render_layers: camera.1.clone(),
+                                soft_shadows: camera.5.soft_shadows,
+                                core_radius: camera.5.softness.core_radius(light.core.radius)
+                                    * overrides.softness.max(0.),
+                                // skewed shadows are cast from a light shifted by up to `skew * radius`,
+                                // directional lights aren't skewed
+                                dilation: match light.bin_sector.y < TAU {
+                                    true => camera.5.occluder_dilation,
+                                    false => camera
+                                        .5
+                                        .occluder_dilation
+                                        .max(camera.5.shadow_skew.length() * light.radius),
+                                }
+                                // line lights cast shadows from anywhere along their segment
+                                .max(match light.line {
+                                    Some(_) => light.streak.length(),
+                                    None => 0.,
+                                }),
+                                light_aabb: *light_aabb,
+                            },
+                        )
+                    })
+                    .collect::<Vec<_>>();
 
-                        retained_views.insert(camera.0.retained_view_entity);
+                // bins are kept until the light, an occluder or the view they were built for changes,
+                // lights that skipped a rebuild stay outdated until they're rebuilt
+                let outdated = skipped.0 > 0
+                    || stale.any
+                    || light.changes.needs_binning()
+                    || views.iter().any(|(retained_view, view)| {
+                        bins.0
+                            .get(retained_view)
+                            .is_none_or(|bin| bin.view.as_ref() != Some(view))
                     });
 
-                    let bins = bins
-                        .0
-                        .iter_mut()
-                        .filter(|(retained_view, _bin)| retained_views.contains(*retained_view))
-                        .map(|(_, x)| x)
-                        .collect::<Vec<_>>();
-
-                    if let Occluder2dShape::RoundRectangle {
-                        half_width,
-                        half_height,
-                        radius,
-                    } = occluder.shape
-                    {
-                        let Some(occluder_index) = round_index.0 else {
-                            continue;
-                        };
+                // bins pointing to occluders that moved, changed shape or lost their slot are wrong rather
+                // than outdated, so they're rebuilt regardless of the budget
+                let invalid = stale.refragmented
+                    || bins.0.values().any(|bin| bin.contains_any(&stale.pointers));
+
+                // over budget, low-priority lights keep the bins of a previous frame
+                let rebin = invalid
+                    || outdated
+                        && budget.as_ref().is_none_or(|budget| {
+                            light.important
+                                || light.directional
+                                || skipped.0 >= budget.max_skipped_frames
+                                || start.elapsed() < budget.budget
+                                || cameras.iter().any(|(camera, _)| {
+                                    !bins.0.contains_key(&camera.0.retained_view_entity)
+                                })
+                        });
 
-                        let vertices = vec![
-                            vec2(-half_width - radius, -half_height - radius),
-                            vec2(-half_width - radius, half_height + radius),
-                            vec2(half_width + radius, half_height + radius),
-                            vec2(half_width + radius, -half_height - radius),
-                        ];
+                if rebin {
+                    skipped.0 = 0;
 
-                        let light_pos =
-                            Vec2::from_angle(-occluder.rot).rotate(light.pos - occluder.pos);
+                    for (retained_view, view) in &views {
+                        let bin = bins.0.entry(*retained_view).or_insert(default());
+                        bin.reset(light.bin_sector);
+                        bin.view = Some(view.clone());
+                    }
 
-                        let aabb = Aabb2d {
-                            min: vec2(-half_width - radius, -half_height - radius),
-                            max: vec2(half_width + radius, half_height + radius),
-                        };
+                    for (occluder, round_index, poly_index) in &occluders {
+                        bin_occluder(light, occluder, round_index, poly_index, &views, bins);
+                    }
 
-                        let isometry = Isometry2d {
-                            translation: occluder.pos,
-                            rotation: Rot2::radians(occluder.rot),
-                        };
+                    // same area as the views', so batched occluders along line and moving lights' streaks are binned
+                    let light_rect = Rect::from_corners(light.pos, light.pos + light.streak)
+                        .inflate(light.radius);
+                    let light_aabb = Aabb2d {
+                        min: light_rect.min,
+                        max: light_rect.max,
+                    };
 
-                        let vertices =
-                            translate_vertices(vertices, isometry.translation, isometry.rotation);
-
-                        let closest = aabb.closest_point(light_pos);
-                        let light_inside_occluder = closest == light_pos;
-
-                        push_vertices(
-                            bins,
-                            &vertices,
-                            light.pos,
-                            light.core.radius,
-                            0,
-                            occluder_index.index as u32,
-                            closest.distance(light_pos),
-                            // 0.0,
-                            light_inside_occluder,
-                            false,
-                            any_soft_shadows,
-                            true,
-                        );
-                    } else {
-                        let Some(occluder_index) = poly_index.occluder else {
+                    for (batch, indices) in &occluder_batches {
+                        if !light.cast_shadows
+                            || !light.render_layers.intersects(&batch.render_layers)
+                        {
                             continue;
-                        };
+                        }
 
-                        let Some(vertex_index) = poly_index.vertices else {
-                            continue;
-                        };
+                        batch.query(&light_aabb, |i| {
+                            let (Some(occluder), Some((round_index, poly_index, _))) =
+                                (batch.occluders.get(i), indices.0.get(i))
+                            else {
+                                return;
+                            };
 
-                        let vertices = occluder.vertices();
-
-                        let light_inside_occluder =
-                            matches!(occluder.shape, Occluder2dShape::Polygon { .. })
-                                && point_inside_poly(
-                                    light.pos,
-                                    &vertices,
-                                    occluder.aabb,
-                                    occluder.shape.is_concave(),
-                                );
-
-                        let closest = occluder.aabb.closest_point(light.pos);
-
-                        push_vertices(
-                            bins,
-                            &vertices,
-                            light.pos,
-                            light.core.radius,
-                            vertex_index.index as u32,
-                            occluder_index.index as u32,
-                            closest.distance(light.pos),
-                            light_inside_occluder,
-                            true,
-                            any_soft_shadows,
-                            occluder.shape.is_concave(),
-                        );
+                            bin_occluder(light, occluder, round_index, poly_index, &views, bins);
+                        });
                     }
+                } else if outdated {
+                    skipped.0 += 1;
                 }
 
                 let mut bind_group = HashMap::default();
                 for (camera, _) in cameras {
                     let bins = bins.0.get_mut(&camera.0.retained_view_entity).unwrap();
-                    bins.write(&render_device, &render_queue);
+                    if rebin {
+                        bins.write(&render_device, &render_queue);
+                    }
                     bind_group.insert(
                         camera.0.retained_view_entity,
                         render_device.create_bind_group(
@@ -546,9 +794,12 @@ pub(crate) fn prepare_data(
                                 vertices.binding(),
                                 bins.bin_binding(),
                                 bins.bin_indices_binding(),
-                                &camera.4.0.default_view,
-                                &camera.5.0.default_view,
-                                camera.6.0.binding().unwrap(),
+                                &camera.2.0.default_view,
+                                &camera.3.0.default_view,
+                                camera.4.0.binding().unwrap(),
+                                gradient_luts.texture_view(),
+                                &camera.6.0.default_view,
+                                receiver_buffer.binding(),
                             )),
                         ),
                     );
@@ -575,6 +826,140 @@ pub(crate) fn prepare_data(
         });
 }
 
+/// Pushes an occluder into the light's bins of every view that can see both of them.
+fn bin_occluder(
+    light: &ExtractedPointLight,
+    occluder: &ExtractedOccluder,
+    round_index: &RoundOccluderIndex,
+    poly_index: &PolyOccluderIndex,
+    views: &[(RetainedViewEntity, BinnedView)],
+    bins: &mut BinBuffers,
+) {
+    if !light.cast_shadows || !light.render_layers.intersects(&occluder.render_layers) {
+        return;
+    }
+
+    // one-way occluders let through the lights on the side their shadow is cast towards
+    if let Some(dir) = occluder.one_way
+        && (occluder.pos - light.pos).dot(dir) <= 0.
+    {
+        return;
+    }
+
+    let mut any_soft_shadows = false;
+    let mut core_radius = 0.0_f32;
+    let mut dilation = occluder.dilation;
+
+    let mut retained_views: HashSet<_, FixedHasher> = HashSet::default();
+
+    views.iter().for_each(|(retained_view, view)| {
+        if !occluder.aabb.intersects(&view.light_aabb)
+            || !view.render_layers.intersects(&occluder.render_layers)
+        {
+            return;
+        }
+
+        any_soft_shadows |= view.soft_shadows;
+        core_radius = core_radius.max(view.core_radius);
+        dilation = dilation.max(view.dilation);
+
+        retained_views.insert(*retained_view);
+    });
+
+    let bins = bins
+        .0
+        .iter_mut()
+        .filter(|(retained_view, _bin)| retained_views.contains(*retained_view))
+        .map(|(_, x)| x)
+        .collect::<Vec<_>>();
+
+    if let Occluder2dShape::RoundRectangle {
+        half_width,
+        half_height,
+        radius,
+    } = occluder.shape
+    {
+        let Some(occluder_index) = round_index.0 else {
+            return;
+        };
+
+        let vertices = vec![
+            vec2(-half_width - radius, -half_height - radius),
+            vec2(-half_width - radius, half_height + radius),
+            vec2(half_width + radius, half_height + radius),
+            vec2(half_width + radius, -half_height - radius),
+        ];
+
+        let light_pos = Vec2::from_angle(-occluder.rot).rotate(light.pos - occluder.pos);
+
+        let aabb = Aabb2d {
+            min: vec2(-half_width - radius, -half_height - radius),
+            max: vec2(half_width + radius, half_height + radius),
+        };
+
+        let isometry = Isometry2d {
+            translation: occluder.pos,
+            rotation: Rot2::radians(occluder.rot),
+        };
+
+        let vertices = translate_vertices(vertices, isometry.translation, isometry.rotation);
+
+        let closest = aabb.closest_point(light_pos);
+        let light_inside_occluder = closest == light_pos;
+
+        push_vertices(
+            bins,
+            &vertices,
+            light.pos,
+            core_radius,
+            0,
+            occluder_index.index as u32,
+            closest.distance(light_pos),
+            // 0.0,
+            light_inside_occluder,
+            false,
+            any_soft_shadows,
+            true,
+            dilation,
+        );
+    } else {
+        let Some(occluder_index) = poly_index.occluder else {
+            return;
+        };
+
+        let Some(vertex_index) = poly_index.vertices else {
+            return;
+        };
+
+        let vertices = occluder.vertices();
+
+        let light_inside_occluder = matches!(occluder.shape, Occluder2dShape::Polygon { .. })
+            && point_inside_poly(
+                light.pos,
+                &vertices,
+                occluder.aabb,
+                occluder.shape.is_concave(),
+            );
+
+        let closest = occluder.aabb.closest_point(light.pos);
+
+        push_vertices(
+            bins,
+            &vertices,
+            light.pos,
+            core_radius,
+            vertex_index.index as u32,
+            occluder_index.index as u32,
+            closest.distance(light.pos),
+            light_inside_occluder,
+            true,
+            any_soft_shadows,
+            occluder.shape.is_concave(),
+            dilation,
+        );
+    }
+}
+
 #[derive(Debug, Default)]
 struct OccluderSlice {
     pub start_index: usize,
@@ -606,6 +991,15 @@ struct Vertex {
     pub angle: f32,
 }
 
+/// Angle covered by a distance of `dilation` at the given vertex, as seen from the light.
+fn dilation_angle(light_pos: Vec2, vertex: Vec2, dilation: f32) -> f32 {
+    if dilation <= 0.0 {
+        return 0.0;
+    }
+
+    (dilation / light_pos.distance(vertex).max(dilation)).atan()
+}
+
 fn push_vertices(
     mut bins: Vec<&mut BinBuffer>,
     occluder_vertices: &[Vec2],
@@ -618,11 +1012,9 @@ fn push_vertices(
     poly: bool,
     soft_shadows: bool,
     concave: bool,
+    dilation: f32,
 ) {
-    let index = match poly {
-        true => (1 << 31) | index,
-        false => index,
-    };
+    let index = OccluderPointer::encode_index(index, poly);
 
     if !poly && rev {
         bins.iter_mut().for_each(|bins| {
@@ -695,10 +1087,13 @@ fn push_vertices(
             let min_v = (rev << 29) | (slice.start_vertex + start_vertex);
             let length = slice.length;
 
+            let left = occluder_vertices[vertices[slice.start_index].index as usize];
+            let right = occluder_vertices
+                [vertices[slice.start_index + slice.length as usize - 1].index as usize];
+
             let angle_left = if !soft_shadows || light_radius <= 0.0 {
                 0.0
             } else {
-                let left = occluder_vertices[vertices[slice.start_index].index as usize];
                 (light_pos - left)
                     .normalize()
                     .dot(
@@ -716,8 +1111,6 @@ fn push_vertices(
             let angle_right = if !soft_shadows || light_radius <= 0.0 {
                 0.0
             } else {
-                let right = occluder_vertices
-                    [vertices[slice.start_index + slice.length as usize - 1].index as usize];
                 (light_pos - right)
                     .normalize()
                     .dot(
@@ -732,6 +1125,10 @@ fn push_vertices(
                     .acos()
             };
 
+            // widens the slice by the angle the dilation covers at its ends, so seams between occluders stay dark
+            let angle_left = angle_left + dilation_angle(light_pos, left, dilation);
+            let angle_right = angle_right + dilation_angle(light_pos, right, dilation);
+
             match slice.split {
                 None => {
                     let data = OccluderData {
@@ -871,6 +1268,7 @@ fn prepare_light_luts(
     }
 }
 
+#[cfg(feature = "sprites")]
 fn prepare_sprite_view_bind_groups(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
@@ -905,6 +1303,7 @@ fn prepare_sprite_view_bind_groups(
     }
 }
 
+#[cfg(feature = "sprites")]
 fn prepare_sprite_image_bind_groups(
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
@@ -929,7 +1328,9 @@ fn prepare_sprite_image_bind_groups(
             // Images don't have dependencies
             AssetEvent::LoadedWithDependencies { .. } => {}
             AssetEvent::Unused { id } | AssetEvent::Modified { id } | AssetEvent::Removed { id } => {
-                image_bind_groups.values.retain(|k, _| k.0 != *id && k.1 != *id);
+                image_bind_groups
+                    .values
+                    .retain(|k, _| k.0 != *id && k.1 != *id && k.3 != Some(*id));
             }
         };
     }
@@ -949,6 +1350,8 @@ fn prepare_sprite_image_bind_groups(
         let mut batch_item_index = 0;
         let mut batch_image_size = Vec2::ZERO;
         let mut batch_image_handle = AssetId::invalid();
+        let mut batch_detail_handle = None;
+        let mut batch_decal = false;
         let mut batch_normal_handle;
         let mut is_dummy;
 
@@ -970,7 +1373,12 @@ fn prepare_sprite_image_bind_groups(
                 continue;
             };
 
-            if batch_image_handle != extracted_sprite.image_handle_id {
+            let detail_handle = extracted_sprite.detail.map(|x| x.image_handle_id);
+
+            if batch_image_handle != extracted_sprite.image_handle_id
+                || batch_detail_handle != detail_handle
+                || batch_decal != extracted_sprite.decal
+            {
                 let Some(gpu_image) = gpu_images.get(extracted_sprite.image_handle_id) else {
                     continue;
                 };
@@ -991,6 +1399,15 @@ fn prepare_sprite_image_bind_groups(
                     continue;
                 };
 
+                let Some(detail_image) = (match detail_handle {
+                    None => Some(gpu_image),
+                    Some(x) => gpu_images.get(x),
+                }) else {
+                    continue;
+                };
+                batch_detail_handle = detail_handle;
+                batch_decal = extracted_sprite.decal;
+
                 let mut dummy_buffer = UniformBuffer::<u32>::from(if is_dummy { 1 } else { 0 });
                 dummy_buffer.write_buffer(&render_device, &render_queue);
 
@@ -1000,7 +1417,12 @@ fn prepare_sprite_image_bind_groups(
 
                 image_bind_groups
                     .values
-                    .entry((batch_image_handle, batch_normal_handle, is_dummy))
+                    .entry((
+                        batch_image_handle,
+                        batch_normal_handle,
+                        is_dummy,
+                        batch_detail_handle,
+                    ))
                     .or_insert_with(|| {
                         render_device.create_bind_group(
                             "sprite_material_bind_group",
@@ -1010,6 +1432,8 @@ fn prepare_sprite_image_bind_groups(
                                 &normal_image.texture_view,
                                 &gpu_image.sampler,
                                 dummy_buffer_binding,
+                                &detail_image.texture_view,
+                                &sprite_pipeline.detail_sampler,
                             )),
                         )
                     });
@@ -1020,10 +1444,15 @@ fn prepare_sprite_image_bind_groups(
                         image_handle_id: batch_image_handle,
                         normal_handle_id: batch_normal_handle,
                         normal_dummy: is_dummy,
+                        detail_handle_id: batch_detail_handle,
                         range: index..index,
                     },
                 ));
             }
+            let (detail_strength, detail_tiling) = extracted_sprite
+                .detail
+                .map_or((0.0, Vec2::ONE), |x| (x.strength, x.tiling));
+
             match extracted_sprite.kind {
                 ExtractedFireflySpriteKind::Single {
                     anchor,
@@ -1088,15 +1517,21 @@ fn prepare_sprite_image_bind_groups(
                         );
 
                     // Store the vertex data and add the item to the render phase
-                    sprite_meta
-                        .sprite_instance_buffer
-                        .push(SpriteInstance::from(
+                    sprite_meta.sprite_instance_buffer.push(
+                        SpriteInstance::from(
                             &transform,
                             &uv_offset_scale,
                             extracted_sprite.transform.translation().z,
                             extracted_sprite.height,
                             extracted_sprite.transform.translation().y,
-                        ));
+                            detail_strength,
+                            detail_tiling,
+                        )
+                        .with_outline(extracted_sprite.outline)
+                        .with_id(extracted_sprite.stencil_id)
+                        .with_min_light(extracted_sprite.min_light)
+                        .with_emissive(extracted_sprite.emissive),
+                    );
 
                     if let Some(batch) = current_batch.as_mut() {
                         batch.get_mut().range.end += 1;
@@ -1138,15 +1573,21 @@ fn prepare_sprite_image_bind_groups(
                             );
 
                         // Store the vertex data and add the item to the render phase
-                        sprite_meta
-                            .sprite_instance_buffer
-                            .push(SpriteInstance::from(
+                        sprite_meta.sprite_instance_buffer.push(
+                            SpriteInstance::from(
                                 &transform,
                                 &uv_offset_scale,
                                 extracted_sprite.transform.translation().z,
                                 extracted_sprite.height,
                                 extracted_sprite.transform.translation().y,
-                            ));
+                                detail_strength,
+                                detail_tiling,
+                            )
+                            .with_outline(extracted_sprite.outline)
+                            .with_id(extracted_sprite.stencil_id)
+                            .with_min_light(extracted_sprite.min_light)
+                            .with_emissive(extracted_sprite.emissive),
+                        );
 
                         if let Some(batch) = current_batch.as_mut() {
                             batch.get_mut().range.end += 1;
@@ -0,0 +1,300 @@
+//! Module containing [`LightmapPreview`], a picture-in-picture view of a camera's raw lightmap.
+//!
+//! Requires the `preview` feature.
+
+use bevy::{
+    camera::{CameraUpdateSystems, RenderTarget, ScalingMode, visibility::RenderLayers},
+    prelude::*,
+    render::render_resource::TextureFormat,
+    ui::UiTargetCamera,
+};
+
+use crate::data::FireflyConfig;
+
+/// Camera component that shows the camera's raw lightmap in a corner of the screen, while the camera itself renders normally.
+///
+/// A secondary low-resolution camera is spawned with [`show_lightmap`](FireflyConfig::show_lightmap) enabled, and follows the
+/// position, projection and config of this camera. Its output is displayed through a UI [`ImageNode`] targeting this camera.
+///
+/// Removing the component despawns the preview.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Camera2d,
+///     FireflyConfig::default(),
+///     LightmapPreview::default(),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[require(FireflyConfig)]
+pub struct LightmapPreview {
+    /// Resolution of the preview's render target. The lightmap is rendered at this size.
+    ///
+    /// **Default:** 320x180.
+    pub resolution: UVec2,
+
+    /// Width of the preview on screen, in logical pixels. The height follows the camera's aspect ratio.
+    ///
+    /// **Default:** 320.
+    pub width: f32,
+
+    /// Corner of the screen the preview is shown in.
+    ///
+    /// **Default:** Top right.
+    pub corner: PreviewCorner,
+
+    /// Distance between the preview and the edges of the screen, in logical pixels.
+    ///
+    /// **Default:** 8.
+    pub margin: f32,
+
+    #[reflect(ignore)]
+    entities: Option<PreviewEntities>,
+}
+
+impl Default for LightmapPreview {
+    fn default() -> Self {
+        Self {
+            resolution: uvec2(320, 180),
+            width: 320.,
+            corner: PreviewCorner::TopRight,
+            margin: 8.,
+            entities: None,
+        }
+    }
+}
+
+impl LightmapPreview {
+    /// Construct a new preview with the given on-screen width and corner.
+    pub fn new(width: f32, corner: PreviewCorner) -> Self {
+        Self {
+            width,
+            corner,
+            ..default()
+        }
+    }
+
+    /// Construct a new preview with the given render target resolution.
+    pub fn with_resolution(self, resolution: UVec2) -> Self {
+        Self { resolution, ..self }
+    }
+
+    /// The preview camera, if it was spawned.
+    pub fn camera(&self) -> Option<Entity> {
+        self.entities.map(|entities| entities.camera)
+    }
+}
+
+/// Corner of the screen a [`LightmapPreview`] is shown in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum PreviewCorner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PreviewEntities {
+    camera: Entity,
+    node: Entity,
+}
+
+/// Marks the secondary camera spawned by a [`LightmapPreview`].
+#[derive(Component)]
+struct LightmapPreviewCamera;
+
+/// Plugin that spawns and updates [`LightmapPreview`]s. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin)
+/// when the `preview` feature is enabled.
+pub struct LightmapPreviewPlugin;
+
+impl Plugin for LightmapPreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LightmapPreview>();
+        app.add_observer(on_preview_removed);
+        app.add_systems(
+            PostUpdate,
+            (spawn_lightmap_previews, update_lightmap_previews)
+                .chain()
+                .after(TransformSystems::Propagate)
+                .before(CameraUpdateSystems),
+        );
+    }
+}
+
+fn spawn_lightmap_previews(
+    mut commands: Commands,
+    mut cameras: Query<(Entity, &Camera, &mut LightmapPreview)>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for (entity, camera, mut preview) in &mut cameras {
+        if preview.entities.is_some() {
+            continue;
+        }
+
+        let image = images.add(Image::new_target_texture(
+            preview.resolution.x.max(1),
+            preview.resolution.y.max(1),
+            TextureFormat::Rgba8UnormSrgb,
+            None,
+        ));
+
+        let preview_camera = commands
+            .spawn((
+                Name::new("Lightmap Preview Camera"),
+                LightmapPreviewCamera,
+                Camera2d,
+                Camera {
+                    // render before the main camera so the preview is up to date when the UI samples it
+                    order: camera.order - 1,
+                    ..default()
+                },
+                RenderTarget::Image(image.clone().into()),
+                FireflyConfig::default(),
+            ))
+            .id();
+
+        let node = commands
+            .spawn((
+                Name::new("Lightmap Preview"),
+                ImageNode::new(image),
+                Node {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                UiTargetCamera(entity),
+            ))
+            .id();
+
+        preview.entities = Some(PreviewEntities {
+            camera: preview_camera,
+            node,
+        });
+    }
+}
+
+fn update_lightmap_previews(
+    cameras: Query<
+        (
+            &LightmapPreview,
+            &GlobalTransform,
+            Ref<Projection>,
+            Ref<FireflyConfig>,
+            &RenderLayers,
+        ),
+        Without<LightmapPreviewCamera>,
+    >,
+    mut preview_cameras: Query<
+        (
+            &mut Transform,
+            &mut Projection,
+            &mut FireflyConfig,
+            &mut RenderLayers,
+        ),
+        With<LightmapPreviewCamera>,
+    >,
+    mut nodes: Query<&mut Node>,
+) {
+    for (preview, transform, projection, config, layers) in &cameras {
+        let Some(entities) = preview.entities else {
+            continue;
+        };
+
+        let Ok((
+            mut preview_transform,
+            mut preview_projection,
+            mut preview_config,
+            mut preview_layers,
+        )) = preview_cameras.get_mut(entities.camera)
+        else {
+            continue;
+        };
+
+        preview_transform.set_if_neq(transform.compute_transform());
+        preview_layers.set_if_neq(layers.clone());
+
+        if config.is_changed() || preview_config.is_added() {
+            *preview_config = FireflyConfig {
+                show_lightmap: true,
+                accessibility: None,
+                ..config.clone()
+            };
+        }
+
+        // the preview covers the same area as the camera, regardless of its own resolution
+        let area = match projection.as_ref() {
+            Projection::Orthographic(ortho) => Some(ortho.area),
+            _ => None,
+        }
+        .filter(|area| area.width() > 0. && area.height() > 0.);
+
+        if projection.is_changed() || preview_projection.is_added() {
+            *preview_projection = match (projection.as_ref(), area) {
+                (Projection::Orthographic(ortho), Some(area)) => {
+                    Projection::Orthographic(OrthographicProjection {
+                        scaling_mode: ScalingMode::Fixed {
+                            width: area.width(),
+                            height: area.height(),
+                        },
+                        scale: 1.,
+                        ..ortho.clone()
+                    })
+                }
+                (projection, _) => projection.clone(),
+            };
+        }
+
+        let aspect_ratio = area.map_or(16. / 9., |area| area.width() / area.height());
+
+        let Ok(mut node) = nodes.get_mut(entities.node) else {
+            continue;
+        };
+
+        let margin = Val::Px(preview.margin);
+        let (top, bottom) = match preview.corner {
+            PreviewCorner::TopLeft | PreviewCorner::TopRight => (margin, Val::Auto),
+            PreviewCorner::BottomLeft | PreviewCorner::BottomRight => (Val::Auto, margin),
+        };
+        let (left, right) = match preview.corner {
+            PreviewCorner::TopLeft | PreviewCorner::BottomLeft => (margin, Val::Auto),
+            PreviewCorner::TopRight | PreviewCorner::BottomRight => (Val::Auto, margin),
+        };
+
+        let width = Val::Px(preview.width);
+        let height = Val::Px(preview.width / aspect_ratio);
+
+        if node.width != width
+            || node.height != height
+            || node.top != top
+            || node.bottom != bottom
+            || node.left != left
+            || node.right != right
+        {
+            node.width = width;
+            node.height = height;
+            node.top = top;
+            node.bottom = bottom;
+            node.left = left;
+            node.right = right;
+        }
+    }
+}
+
+fn on_preview_removed(
+    trigger: On<Remove, LightmapPreview>,
+    previews: Query<&LightmapPreview>,
+    mut commands: Commands,
+) {
+    let Ok(preview) = previews.get(trigger.entity) else {
+        return;
+    };
+
+    if let Some(entities) = preview.entities {
+        commands.entity(entities.camera).try_despawn();
+        commands.entity(entities.node).try_despawn();
+    }
+}
@@ -0,0 +1,406 @@
+//! Module containing [`LightingProfile`]s, snapshots of a camera's lighting that can be saved, restored
+//! and blended between at runtime, and [`FireflyConfigTransition`]s for tweening a camera's config.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::data::{
+    AccessibilityAssist, AutoExposure, ContactShadows, FireflyConfig, FireflyGlobalOverrides,
+    FireflyTestClock, Softness, SpriteAmbient,
+};
+
+/// Snapshot of a camera's [`FireflyConfig`] and the [`FireflyGlobalOverrides`], meant for switching between
+/// lighting moods such as "dungeon", "boss" and "overworld".
+///
+/// Profiles can be applied instantly with [`apply_profile`](LightingProfileCommands::apply_profile), or crossfaded
+/// over a duration with [`blend_to_profile`](LightingProfileCommands::blend_to_profile). With the `serde` feature,
+/// they can be saved alongside other game data.
+///
+/// # Example
+///
+/// ```
+/// fn enter_boss_room(mut commands: Commands, camera: Single<Entity, With<FireflyConfig>>) {
+///     let boss = LightingProfile::new(FireflyConfig {
+///         ambient_color: Color::srgb(1.0, 0.2, 0.2),
+///         ambient_brightness: 0.1,
+///         ..default()
+///     });
+///
+///     commands.blend_to_profile(*camera, &boss, Duration::from_secs(2));
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightingProfile {
+    /// Config of the camera.
+    pub config: FireflyConfig,
+
+    /// Global overrides. These affect all cameras once the profile is applied.
+    pub overrides: FireflyGlobalOverrides,
+}
+
+impl LightingProfile {
+    /// Construct a new profile with the given config and default overrides.
+    pub fn new(config: FireflyConfig) -> Self {
+        Self {
+            config,
+            overrides: default(),
+        }
+    }
+
+    /// Construct a new profile from the current config and overrides.
+    pub fn capture(config: &FireflyConfig, overrides: &FireflyGlobalOverrides) -> Self {
+        Self {
+            config: config.clone(),
+            overrides: overrides.clone(),
+        }
+    }
+
+    /// Returns the profile with the given overrides.
+    pub fn with_overrides(self, overrides: FireflyGlobalOverrides) -> Self {
+        Self { overrides, ..self }
+    }
+
+    /// Interpolates between this profile and another one, `t` going from 0 to 1.
+    ///
+    /// Numeric fields and colors are interpolated. Fields that can't be interpolated, such as the
+    /// [normal mode](FireflyConfig::normal_mode) or [lightmap size](FireflyConfig::lightmap_size), switch to
+    /// the other profile's value halfway through.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            config: self.config.lerp(&other.config, t),
+            overrides: self.overrides.lerp(&other.overrides, t),
+        }
+    }
+}
+
+impl FireflyConfig {
+    /// Interpolates between this config and another one, `t` going from 0 to 1.
+    ///
+    /// See [`LightingProfile::lerp`].
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let t = t.clamp(0., 1.);
+        let halfway = match t < 0.5 {
+            true => self.clone(),
+            false => other.clone(),
+        };
+
+        Self {
+            ambient_color: self
+                .ambient_color
+                .to_linear()
+                .mix(&other.ambient_color.to_linear(), t)
+                .into(),
+            ambient_brightness: self.ambient_brightness.lerp(other.ambient_brightness, t),
+            sprite_ambient: lerp_option(self.sprite_ambient, other.sprite_ambient, t, |a, b, t| {
+                SpriteAmbient {
+                    color: a.color.to_linear().mix(&b.color.to_linear(), t).into(),
+                    brightness: a.brightness.lerp(b.brightness, t),
+                }
+            }),
+            light_bands: lerp_option(self.light_bands, other.light_bands, t, f32::lerp),
+            softness: match (self.softness, other.softness) {
+                (Softness::CoreScale(a), Softness::CoreScale(b)) => {
+                    Softness::CoreScale(a.lerp(b, t))
+                }
+                (Softness::WorldUnits(a), Softness::WorldUnits(b)) => {
+                    Softness::WorldUnits(a.lerp(b, t))
+                }
+                _ => halfway.softness,
+            },
+            shadow_strength: self.shadow_strength.lerp(other.shadow_strength, t),
+            shadow_skew: self.shadow_skew.lerp(other.shadow_skew, t),
+            z_sorting_error_margin: self
+                .z_sorting_error_margin
+                .lerp(other.z_sorting_error_margin, t),
+            normal_attenuation: self.normal_attenuation.lerp(other.normal_attenuation, t),
+            light_pitch: lerp_option(self.light_pitch, other.light_pitch, t, f32::lerp),
+            contact_shadows: lerp_option(
+                self.contact_shadows,
+                other.contact_shadows,
+                t,
+                |a, b, t| ContactShadows {
+                    length: a.length.lerp(b.length, t),
+                    steps: match t < 0.5 {
+                        true => a.steps,
+                        false => b.steps,
+                    },
+                    opacity: a.opacity.lerp(b.opacity, t),
+                },
+            ),
+            occluder_dilation: self.occluder_dilation.lerp(other.occluder_dilation, t),
+            accessibility: lerp_option(self.accessibility, other.accessibility, t, |a, b, t| {
+                AccessibilityAssist {
+                    min_luminance: a.min_luminance.lerp(b.min_luminance, t),
+                    outline_color: a
+                        .outline_color
+                        .to_linear()
+                        .mix(&b.outline_color.to_linear(), t)
+                        .into(),
+                    outline_width: a.outline_width.lerp(b.outline_width, t),
+                    outline_threshold: a.outline_threshold.lerp(b.outline_threshold, t),
+                }
+            }),
+            auto_exposure: lerp_option(self.auto_exposure, other.auto_exposure, t, |a, b, t| {
+                AutoExposure {
+                    target: a.target.lerp(b.target, t),
+                    min: a.min.lerp(b.min, t),
+                    max: a.max.lerp(b.max, t),
+                    brighten_speed: a.brighten_speed.lerp(b.brighten_speed, t),
+                    darken_speed: a.darken_speed.lerp(b.darken_speed, t),
+                    filter: a.filter.lerp(b.filter, t),
+                }
+            }),
+            ..halfway
+        }
+    }
+}
+
+impl FireflyGlobalOverrides {
+    /// Interpolates between these overrides and other ones, `t` going from 0 to 1.
+    ///
+    /// See [`LightingProfile::lerp`].
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let t = t.clamp(0., 1.);
+        let halfway = match t < 0.5 {
+            true => self.clone(),
+            false => other.clone(),
+        };
+
+        Self {
+            softness: self.softness.lerp(other.softness, t),
+            ambient: self.ambient.lerp(other.ambient, t),
+            ..halfway
+        }
+    }
+}
+
+/// Interpolates two optional values if both are set, otherwise switches halfway through.
+fn lerp_option<T: Copy>(
+    a: Option<T>,
+    b: Option<T>,
+    t: f32,
+    lerp: impl Fn(T, T, f32) -> T,
+) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(lerp(a, b, t)),
+        _ if t < 0.5 => a,
+        _ => b,
+    }
+}
+
+/// Camera component that crossfades the camera's [`FireflyConfig`] and the [`FireflyGlobalOverrides`] between two
+/// [`LightingProfile`]s. It's removed once the blend is done.
+///
+/// Usually inserted through [`blend_to_profile`](LightingProfileCommands::blend_to_profile).
+#[derive(Component, Clone, Debug, Reflect)]
+#[require(FireflyConfig)]
+pub struct LightingProfileBlend {
+    /// Profile the blend starts from.
+    pub from: LightingProfile,
+
+    /// Profile the blend ends at.
+    pub to: LightingProfile,
+
+    /// Duration of the blend.
+    pub duration: Duration,
+
+    /// Time elapsed since the start of the blend.
+    pub elapsed: Duration,
+}
+
+impl LightingProfileBlend {
+    /// Progress of the blend, from 0 to 1, with a smooth start and end.
+    pub fn progress(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.;
+        }
+
+        let t = (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).clamp(0., 1.);
+        t * t * (3. - 2. * t)
+    }
+}
+
+/// Extension trait for applying [`LightingProfile`]s through [`Commands`].
+pub trait LightingProfileCommands {
+    /// Instantly applies the profile to the camera and the global overrides.
+    fn apply_profile(&mut self, camera: Entity, profile: &LightingProfile);
+
+    /// Crossfades the camera and the global overrides from their current values to the profile, over the given duration.
+    fn blend_to_profile(&mut self, camera: Entity, profile: &LightingProfile, duration: Duration);
+}
+
+impl LightingProfileCommands for Commands<'_, '_> {
+    fn apply_profile(&mut self, camera: Entity, profile: &LightingProfile) {
+        self.blend_to_profile(camera, profile, Duration::ZERO);
+    }
+
+    fn blend_to_profile(&mut self, camera: Entity, profile: &LightingProfile, duration: Duration) {
+        let profile = profile.clone();
+
+        self.queue(move |world: &mut World| {
+            let overrides = world
+                .get_resource::<FireflyGlobalOverrides>()
+                .cloned()
+                .unwrap_or_default();
+
+            let Ok(mut entity) = world.get_entity_mut(camera) else {
+                return;
+            };
+
+            let from = LightingProfile::capture(
+                entity.get::<FireflyConfig>().unwrap_or(&profile.config),
+                &overrides,
+            );
+
+            if duration.is_zero() {
+                entity.remove::<LightingProfileBlend>();
+                entity.insert(profile.config);
+                world.insert_resource(profile.overrides);
+                return;
+            }
+
+            entity.insert(LightingProfileBlend {
+                from,
+                to: profile,
+                duration,
+                elapsed: Duration::ZERO,
+            });
+        });
+    }
+}
+
+/// Camera component that moves the camera's [`FireflyConfig`] towards a target config over a duration, with easing.
+/// It's removed once the target is reached.
+///
+/// The config the transition starts from is the camera's config when the transition is first updated.
+/// Fields are interpolated as described in [`FireflyConfig::lerp`].
+///
+/// # Example
+///
+/// ```
+/// fn dim_lights(mut commands: Commands, camera: Single<(Entity, &FireflyConfig)>) {
+///     let (entity, config) = *camera;
+///
+///     commands.entity(entity).insert(
+///         FireflyConfigTransition::new(
+///             FireflyConfig {
+///                 ambient_brightness: 0.05,
+///                 light_bands: Some(0.25),
+///                 ..config.clone()
+///             },
+///             Duration::from_secs_f32(1.5),
+///         )
+///         .with_easing(EaseFunction::CubicOut),
+///     );
+/// }
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[require(FireflyConfig)]
+pub struct FireflyConfigTransition {
+    /// Config the camera ends up with.
+    pub target: FireflyConfig,
+
+    /// Duration of the transition.
+    pub duration: Duration,
+
+    /// Easing applied to the progress of the transition.
+    ///
+    /// **Default:** [SmoothStep](EaseFunction::SmoothStep).
+    pub easing: EaseFunction,
+
+    /// Time elapsed since the start of the transition.
+    pub elapsed: Duration,
+
+    #[reflect(ignore)]
+    start: Option<FireflyConfig>,
+}
+
+impl FireflyConfigTransition {
+    /// Construct a new transition towards the given config.
+    pub fn new(target: FireflyConfig, duration: Duration) -> Self {
+        Self {
+            target,
+            duration,
+            easing: EaseFunction::SmoothStep,
+            elapsed: Duration::ZERO,
+            start: None,
+        }
+    }
+
+    /// Returns the transition with the given [easing](FireflyConfigTransition::easing).
+    pub fn with_easing(self, easing: EaseFunction) -> Self {
+        Self { easing, ..self }
+    }
+
+    /// Progress of the transition, from 0 to 1, with the easing applied.
+    pub fn progress(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.;
+        }
+
+        let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        self.easing.sample_clamped(t.clamp(0., 1.))
+    }
+}
+
+/// Plugin that updates [`LightingProfileBlend`]s and [`FireflyConfigTransition`]s. Added automatically by
+/// [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LightingProfileBlend>();
+        app.register_type::<FireflyConfigTransition>();
+        app.add_systems(PostUpdate, (blend_profiles, update_config_transitions));
+    }
+}
+
+fn blend_profiles(
+    mut commands: Commands,
+    mut cameras: Query<(Entity, &mut LightingProfileBlend, &mut FireflyConfig)>,
+    mut overrides: ResMut<FireflyGlobalOverrides>,
+    time: Res<Time>,
+    test_clock: Option<Res<FireflyTestClock>>,
+) {
+    let delta = test_clock.map_or(time.delta(), |clock| clock.delta);
+
+    for (entity, mut blend, mut config) in &mut cameras {
+        blend.elapsed = (blend.elapsed + delta).min(blend.duration);
+
+        let profile = blend.from.lerp(&blend.to, blend.progress());
+        *config = profile.config;
+        *overrides = profile.overrides;
+
+        if blend.elapsed >= blend.duration {
+            commands.entity(entity).remove::<LightingProfileBlend>();
+        }
+    }
+}
+
+fn update_config_transitions(
+    mut commands: Commands,
+    mut cameras: Query<(Entity, &mut FireflyConfigTransition, &mut FireflyConfig)>,
+    time: Res<Time>,
+    test_clock: Option<Res<FireflyTestClock>>,
+) {
+    let delta = test_clock.map_or(time.delta(), |clock| clock.delta);
+
+    for (entity, mut transition, mut config) in &mut cameras {
+        let start = transition
+            .start
+            .get_or_insert_with(|| config.clone())
+            .clone();
+
+        transition.elapsed = (transition.elapsed + delta).min(transition.duration);
+
+        if transition.elapsed >= transition.duration {
+            *config = transition.target.clone();
+            commands.entity(entity).remove::<FireflyConfigTransition>();
+            continue;
+        }
+
+        *config = start.lerp(&transition.target, transition.progress());
+    }
+}
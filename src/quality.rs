@@ -0,0 +1,208 @@
+//! Module containing [`FireflyQuality`] presets and the [`FireflyDynamicResolution`] controller.
+
+use bevy::{prelude::*, render::render_resource::TextureFormat};
+
+use crate::data::{FireflyConfig, FireflyTestClock, LightmapSize};
+
+/// Quality presets that bundle the [`FireflyConfig`] fields with the largest impact on performance.
+///
+/// | | Low | Medium | High |
+/// |---|---|---|---|
+/// | [Lightmap size](FireflyConfig::lightmap_size) | 0.5x | 0.75x | Window |
+/// | [Soft shadows](FireflyConfig::soft_shadows) | Off | On | On |
+/// | [Lightmap format](FireflyConfig::lightmap_format) | `Rgba8UnormSrgb` | Camera's | Camera's |
+/// | [32 bit stencils](FireflyConfig::enable_32bit_stencils) | Off | Off | On |
+/// | [Max lights](FireflyConfig::max_lights) | 16 | 64 | Unlimited |
+///
+/// Note that 32 bit stencils prevent the app from running on web, so the [High](FireflyQuality::High)
+/// preset shouldn't be used there.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Camera2d,
+///     FireflyConfig::default().with_quality(FireflyQuality::Low),
+/// ));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FireflyQuality {
+    /// Meant for mobile and other low-power targets.
+    Low,
+    /// Meant for web and integrated graphics.
+    Medium,
+    /// Full quality, matching the default config (except for 32 bit stencils).
+    #[default]
+    High,
+}
+
+impl FireflyQuality {
+    /// Applies the preset to the given config, leaving all other fields untouched.
+    pub fn apply(self, config: &mut FireflyConfig) {
+        match self {
+            Self::Low => {
+                config.lightmap_size = LightmapSize::Scaled(0.5);
+                config.soft_shadows = false;
+                config.lightmap_format = Some(TextureFormat::Rgba8UnormSrgb);
+                config.enable_32bit_stencils = false;
+                config.max_lights = Some(16);
+            }
+            Self::Medium => {
+                config.lightmap_size = LightmapSize::Scaled(0.75);
+                config.soft_shadows = true;
+                config.lightmap_format = None;
+                config.enable_32bit_stencils = false;
+                config.max_lights = Some(64);
+            }
+            Self::High => {
+                config.lightmap_size = LightmapSize::Window;
+                config.soft_shadows = true;
+                config.lightmap_format = None;
+                config.enable_32bit_stencils = true;
+                config.max_lights = None;
+            }
+        }
+    }
+}
+
+impl FireflyConfig {
+    /// Construct a new config with the given [quality preset](FireflyQuality) applied.
+    pub fn with_quality(mut self, quality: FireflyQuality) -> Self {
+        quality.apply(&mut self);
+        self
+    }
+}
+
+/// Camera component that scales the [lightmap size](FireflyConfig::lightmap_size) down when the frame time
+/// exceeds a target, and back up once there's headroom again.
+///
+/// The controller overrides the camera's lightmap size with [Scaled](LightmapSize::Scaled) every frame.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Camera2d,
+///     FireflyConfig::default(),
+///     // aim for 30 fps
+///     FireflyDynamicResolution::new(1.0 / 30.0),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[require(FireflyConfig)]
+pub struct FireflyDynamicResolution {
+    /// Target frame time, in seconds.
+    ///
+    /// **Default:** 1 / 60.
+    pub target_frame_time: f32,
+
+    /// Smallest lightmap scale the controller can go down to.
+    ///
+    /// **Default:** 0.25.
+    pub min_scale: f32,
+
+    /// Largest lightmap scale the controller can go up to.
+    ///
+    /// **Default:** 1.
+    pub max_scale: f32,
+
+    /// How much the scale changes per second while the frame time is off target.
+    ///
+    /// **Default:** 0.5.
+    pub speed: f32,
+
+    /// Current lightmap scale.
+    ///
+    /// **Default:** 1.
+    pub scale: f32,
+
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    smoothed_frame_time: Option<f32>,
+}
+
+impl Default for FireflyDynamicResolution {
+    fn default() -> Self {
+        Self {
+            target_frame_time: 1. / 60.,
+            min_scale: 0.25,
+            max_scale: 1.,
+            speed: 0.5,
+            scale: 1.,
+            smoothed_frame_time: None,
+        }
+    }
+}
+
+impl FireflyDynamicResolution {
+    /// Construct a new controller with the given target frame time, in seconds.
+    pub fn new(target_frame_time: f32) -> Self {
+        Self {
+            target_frame_time,
+            ..default()
+        }
+    }
+
+    /// Construct a new controller with the given scale range.
+    pub fn with_scale_range(self, min_scale: f32, max_scale: f32) -> Self {
+        Self {
+            min_scale,
+            max_scale,
+            ..self
+        }
+    }
+}
+
+/// Plugin that updates [`FireflyDynamicResolution`] controllers. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct QualityPlugin;
+
+impl Plugin for QualityPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<FireflyDynamicResolution>();
+        app.add_systems(PostUpdate, update_dynamic_resolution);
+    }
+}
+
+fn update_dynamic_resolution(
+    mut cameras: Query<(&mut FireflyDynamicResolution, &mut FireflyConfig)>,
+    time: Res<Time<Real>>,
+    test_clock: Option<Res<FireflyTestClock>>,
+) {
+    // the frame time isn't deterministic, so the lightmap size is kept as-is
+    if test_clock.is_some() {
+        return;
+    }
+
+    let delta = time.delta_secs();
+    if delta <= 0. {
+        return;
+    }
+
+    for (mut controller, mut config) in &mut cameras {
+        // smooth out the frame time so single spikes don't cause the resolution to jump around
+        let smoothed = controller
+            .smoothed_frame_time
+            .map_or(delta, |smoothed| smoothed.lerp(delta, 0.1));
+        controller.smoothed_frame_time = Some(smoothed);
+
+        let step = controller.speed * delta;
+        let scale = if smoothed > controller.target_frame_time * 1.05 {
+            controller.scale - step
+        } else if smoothed < controller.target_frame_time * 0.85 {
+            controller.scale + step
+        } else {
+            controller.scale
+        }
+        .clamp(controller.min_scale, controller.max_scale);
+
+        if scale != controller.scale {
+            controller.scale = scale;
+        }
+
+        if !matches!(config.lightmap_size, LightmapSize::Scaled(current) if current == scale) {
+            config.lightmap_size = LightmapSize::Scaled(scale);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Module containing [`OccluderRaycast`], for casting rays against occluders on the CPU.
+
+use bevy::{
+    ecs::system::SystemParam,
+    math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume},
+    prelude::*,
+};
+
+use crate::{
+    occluder_batch::{OccluderBatch, batch_isometry},
+    prelude::Occluder2d,
+    visibility::{OccluderAabb, segment_hit},
+};
+
+/// [`SystemParam`] for casting rays against [`Occluder2d`]s and [`OccluderBatch`]es in the Main World.
+///
+/// The rays are tested against the exact same shapes that cast shadows, so it can be used for line-of-sight
+/// checks that are consistent with what the lighting shows, without needing a physics engine.
+///
+/// # Example
+///
+/// ```
+/// fn can_see_player(raycast: OccluderRaycast, enemy: Single<&Transform, With<Enemy>>, player: Single<&Transform, With<Player>>) -> bool {
+///     raycast.has_line_of_sight(enemy.translation.xy(), player.translation.xy())
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct OccluderRaycast<'w, 's> {
+    occluders: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GlobalTransform,
+            &'static Occluder2d,
+            &'static OccluderAabb,
+        ),
+    >,
+    batches: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GlobalTransform,
+            &'static OccluderBatch,
+            &'static OccluderAabb,
+        ),
+    >,
+}
+
+/// The result of a successful [raycast](OccluderRaycast::cast_ray).
+#[derive(Debug, Clone, Copy)]
+pub struct OccluderHit {
+    /// The entity with the occluder that was hit.
+    pub entity: Entity,
+    /// If the entity is an [`OccluderBatch`], the index of the hit occluder inside of it.
+    pub batch_index: Option<usize>,
+    /// The point where the ray hit the occluder.
+    pub point: Vec2,
+    /// The distance from the ray's origin to the hit point.
+    pub distance: f32,
+}
+
+impl OccluderRaycast<'_, '_> {
+    /// Casts a ray and returns the closest occluder hit within the given distance.
+    ///
+    /// If the origin is inside an occluder, that occluder is hit at distance 0.
+    pub fn cast_ray(&self, origin: Vec2, dir: Dir2, max_dist: f32) -> Option<OccluderHit> {
+        self.cast_ray_filtered(origin, dir, max_dist, |_| true)
+    }
+
+    /// Same as [`cast_ray`](OccluderRaycast::cast_ray), but only tests occluders on entities for which `filter` returns true.
+    ///
+    /// Useful e.g. for ignoring the occluder of the entity casting the ray.
+    pub fn cast_ray_filtered(
+        &self,
+        origin: Vec2,
+        dir: Dir2,
+        max_dist: f32,
+        filter: impl Fn(Entity) -> bool,
+    ) -> Option<OccluderHit> {
+        let to = origin + dir * max_dist;
+        let segment = Aabb2d {
+            min: origin.min(to),
+            max: origin.max(to),
+        };
+
+        let mut closest: Option<(Entity, Option<usize>, f32)> = None;
+        let mut hit = |entity: Entity, batch_index: Option<usize>, t: f32| {
+            if closest.is_none_or(|(_, _, closest)| t < closest) {
+                closest = Some((entity, batch_index, t));
+            }
+        };
+
+        for (entity, transform, occluder, aabb) in &self.occluders {
+            if !aabb.0.intersects(&segment) || !filter(entity) {
+                continue;
+            }
+
+            let isometry = Isometry2d {
+                rotation: Rot2::radians(transform.rotation().to_euler(EulerRot::XYZ).2),
+                translation: transform.translation().truncate() + occluder.offset.truncate(),
+            };
+
+            if let Some(t) = segment_hit(occluder.shape(), isometry, origin, to) {
+                hit(entity, None, t);
+            }
+        }
+
+        for (entity, transform, batch, aabb) in &self.batches {
+            if !aabb.0.intersects(&segment) || !filter(entity) {
+                continue;
+            }
+
+            let batch_isometry = batch_isometry(transform);
+            let inverse = batch_isometry.inverse();
+            let local = segment.transformed_by(inverse.translation, inverse.rotation);
+
+            let occluders = batch.occluders();
+            batch.data().tree().query(&local, |i| {
+                let occluder = &occluders[i];
+                let isometry = Isometry2d::new(
+                    batch_isometry.transform_point(occluder.offset.truncate()),
+                    batch_isometry.rotation,
+                );
+
+                if let Some(t) = segment_hit(occluder.shape(), isometry, origin, to) {
+                    hit(entity, Some(i), t);
+                }
+            });
+        }
+
+        closest.map(|(entity, batch_index, t)| OccluderHit {
+            entity,
+            batch_index,
+            point: origin.lerp(to, t),
+            distance: t * max_dist,
+        })
+    }
+
+    /// Returns true if no occluder is between the two points.
+    pub fn has_line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        let Ok(dir) = Dir2::new(to - from) else {
+            return true;
+        };
+
+        self.cast_ray(from, dir, from.distance(to)).is_none()
+    }
+}
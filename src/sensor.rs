@@ -0,0 +1,155 @@
+//! Module containing [`LightSensor`] and the events it triggers when entities enter or leave lights.
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*, transform::TransformSystems};
+
+use crate::visibility::LitVisibility;
+
+/// Component that makes an entity trigger [`LightEnterEvent`]s and [`LightExitEvent`]s when lights start or stop reaching it.
+///
+/// Light is computed the same way as [`LitVisibility::entity_illumination`], once per frame for every sensor.
+///
+/// The two thresholds add hysteresis, so an entity standing right at the edge of a light doesn't
+/// rapidly enter and leave it: the entity enters a light once it's lit with at least [`enter_threshold`](LightSensor::enter_threshold)
+/// intensity, and only leaves it once the intensity drops below [`exit_threshold`](LightSensor::exit_threshold).
+///
+/// # Example
+///
+/// ```
+/// commands
+///     .spawn((Sprite::from_image(vampire), LightSensor::default()))
+///     .observe(|enter: On<LightEnterEvent>| {
+///         info!("{} stepped into the light of {}", enter.entity, enter.light);
+///     });
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightSensor {
+    /// Intensity the entity needs to be lit with in order to enter a light.
+    ///
+    /// **Default:** 0.05.
+    pub enter_threshold: f32,
+
+    /// Intensity below which the entity leaves a light. Should be less than or equal to the enter threshold.
+    ///
+    /// **Default:** 0.025.
+    pub exit_threshold: f32,
+
+    #[reflect(ignore)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    lit_by: EntityHashMap<f32>,
+}
+
+impl Default for LightSensor {
+    fn default() -> Self {
+        Self {
+            enter_threshold: 0.05,
+            exit_threshold: 0.025,
+            lit_by: default(),
+        }
+    }
+}
+
+impl LightSensor {
+    /// Construct a new sensor with the given enter and exit thresholds.
+    pub fn new(enter_threshold: f32, exit_threshold: f32) -> Self {
+        Self {
+            enter_threshold,
+            exit_threshold,
+            ..default()
+        }
+    }
+
+    /// Returns the lights the entity is currently inside of, along with the intensity it's lit with.
+    pub fn lit_by(&self) -> impl Iterator<Item = (Entity, f32)> {
+        self.lit_by
+            .iter()
+            .map(|(light, intensity)| (*light, *intensity))
+    }
+
+    /// Returns true if the entity is currently inside of any light.
+    pub fn is_lit(&self) -> bool {
+        !self.lit_by.is_empty()
+    }
+}
+
+/// Event triggered when an entity with a [`LightSensor`] enters a light.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct LightEnterEvent {
+    /// The entity with the [`LightSensor`].
+    pub entity: Entity,
+    /// The entity with the [`PointLight2d`](crate::prelude::PointLight2d).
+    pub light: Entity,
+    /// The intensity the entity is lit with.
+    pub intensity: f32,
+}
+
+/// Event triggered when an entity with a [`LightSensor`] leaves a light, or the light is removed.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct LightExitEvent {
+    /// The entity with the [`LightSensor`].
+    pub entity: Entity,
+    /// The entity with the [`PointLight2d`](crate::prelude::PointLight2d).
+    pub light: Entity,
+    /// The intensity the entity is lit with. This is 0 if the light no longer reaches it at all.
+    pub intensity: f32,
+}
+
+/// Plugin that updates [`LightSensor`]s. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct SensorPlugin;
+
+impl Plugin for SensorPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LightSensor>();
+        app.add_systems(
+            PostUpdate,
+            update_light_sensors.after(TransformSystems::Propagate),
+        );
+    }
+}
+
+fn update_light_sensors(
+    mut commands: Commands,
+    mut sensors: Query<(Entity, &mut LightSensor)>,
+    lit: LitVisibility,
+) {
+    for (entity, mut sensor) in &mut sensors {
+        let illumination = lit.entity_illumination(entity);
+        let sensor = sensor.bypass_change_detection();
+
+        sensor.lit_by.retain(|light, _| {
+            if illumination.iter().any(|(lit_by, _)| lit_by == light) {
+                return true;
+            }
+
+            commands.trigger(LightExitEvent {
+                entity,
+                light: *light,
+                intensity: 0.,
+            });
+            false
+        });
+
+        for (light, intensity) in illumination {
+            match sensor.lit_by.get_mut(&light) {
+                Some(_) if intensity < sensor.exit_threshold => {
+                    sensor.lit_by.remove(&light);
+                    commands.trigger(LightExitEvent {
+                        entity,
+                        light,
+                        intensity,
+                    });
+                }
+                Some(current) => *current = intensity,
+                None if intensity >= sensor.enter_threshold => {
+                    sensor.lit_by.insert(light, intensity);
+                    commands.trigger(LightEnterEvent {
+                        entity,
+                        light,
+                        intensity,
+                    });
+                }
+                None => {}
+            }
+        }
+    }
+}
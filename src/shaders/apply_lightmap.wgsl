@@ -1,7 +1,9 @@
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
-#import firefly::types::FireflyConfig
+#import firefly::types::{FireflyConfig, DarknessOutline}
 
-#import firefly::utils::blend
+#import firefly::utils::{blend, deband_dither, ndc_to_ground, decode_stencil_id}
+
+const LUMINANCE: vec3f = vec3f(0.2126, 0.7152, 0.0722);
 
 @group(0) @binding(0)
 var screen_texture: texture_2d<f32>;
@@ -18,18 +20,57 @@ var texture_sampler2: sampler;
 @group(0) @binding(4)
 var<uniform> config: FireflyConfig;
 
-#ifdef IS_COMBINED
 @group(0) @binding(5)
+var assist_mask: texture_2d<f32>;
+
+#ifdef IS_COMBINED
+@group(0) @binding(6)
 var light_map_textures: texture_2d_array<f32>;
 #endif
 
+@group(0) @binding(7)
+var weather_texture: texture_2d<f32>;
+
+@group(0) @binding(8)
+var weather_sampler: sampler;
+
+@group(0) @binding(9)
+var sprite_ids: texture_2d<f32>;
+
+// the first outline is empty
+@group(0) @binding(10)
+var<storage> darkness_outlines: array<DarknessOutline>;
+
+// in stops, 0 without auto exposure
+@group(0) @binding(11)
+var<storage> exposure: f32;
+
 @fragment
 fn fragment(vo: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-    var light_frag = blend(textureSample(light_map_texture, texture_sampler2, vo.uv), vec4f(config.ambient_color, 0), config.ambient_brightness);
+    var lights = textureSample(light_map_texture, texture_sampler2, vo.uv);
+
+    // the alpha only marks pixels reached by important lights, which are never banded
+    let important = lights.a > 0.0;
+    lights.a = 0.0;
+    var ambient_color = config.ambient_color;
+    var ambient_brightness = config.ambient_brightness;
+
+    if config.sprite_ambient_brightness >= 0 && is_sprite(vo.uv) {
+        ambient_color = config.sprite_ambient_color;
+        ambient_brightness = config.sprite_ambient_brightness;
+    }
+
+    if config.weather_ambient > 0 || config.weather_lights > 0 {
+        let coverage = weather_coverage(vo.uv);
+        lights = vec4f(lights.rgb * (1.0 - coverage * config.weather_lights), lights.a);
+        ambient_brightness *= 1.0 - coverage * config.weather_ambient;
+    }
+
+    var light_frag = blend(lights, vec4f(ambient_color, 0), ambient_brightness);
 
 #ifdef IS_COMBINED
     for (var i = 0u; i < config.n_combined_lightmaps; i += 1) {
-        let extra_light_frag = textureSample(light_map_textures, texture_sampler, vo.uv, i);
+        let extra_light_frag = vec4f(textureSample(light_map_textures, texture_sampler, vo.uv, i).rgb, 0);
         if config.combination_mode == 0u {
             light_frag *= extra_light_frag;
         }
@@ -45,11 +86,180 @@ fn fragment(vo: FullscreenVertexOutput) -> @location(0) vec4<f32> {
     }
 #endif    
 
-    if config.light_bands > 0 {
-        light_frag = floor(light_frag / vec4f(config.light_bands)) * config.light_bands;
+    light_frag = vec4f(light_frag.rgb * exp2(exposure), light_frag.a);
+
+    if (config.n_band_thresholds > 0 || config.light_bands > 0) && !important {
+        let band = light_band(light_frag.rgb);
+
+        if config.n_band_thresholds > 0 {
+            light_frag = vec4f(threshold_bands(light_frag.rgb), light_frag.a);
+        }
+        else {
+            light_frag = floor(light_frag / vec4f(config.light_bands)) * config.light_bands;
+        }
+
+        if config.n_band_tints > 0 {
+            let tint = config.band_tints[min(band, config.n_band_tints - 1u)];
+            light_frag = vec4f(light_frag.rgb * tint.rgb, light_frag.a);
+        }
+    }
+
+    if config.assist_min_luminance > 0 {
+        let luminance = dot(light_frag.rgb, LUMINANCE);
+        if luminance < config.assist_min_luminance {
+            light_frag = vec4f(light_frag.rgb + (config.assist_min_luminance - luminance), light_frag.a);
+        }
+    }
+
+    // minimum light of the sprite drawn over the pixel, 0 for the ground
+    let min_light = textureLoad(assist_mask, vec2<i32>(vo.uv * vec2f(textureDimensions(assist_mask))), 0).g;
+    if min_light > 0 {
+        let luminance = dot(light_frag.rgb, LUMINANCE);
+        if luminance < min_light {
+            light_frag = vec4f(light_frag.rgb + (min_light - luminance), light_frag.a);
+        }
+    }
+
+    if config.show_lightmap == 1u {
+        return vec4f(light_frag.rgb, 1.0);
     }
 
     let scene_frag = textureSample(screen_texture, texture_sampler, vo.uv);
     
-    return scene_frag * light_frag;
+    var res = scene_frag * light_frag;
+
+    if config.assist_outline_width > 0 && is_outlined(vo.uv) {
+        res = vec4f(mix(res.rgb, config.assist_outline_color.rgb, config.assist_outline_color.a), res.a);
+    }
+
+    if arrayLength(&darkness_outlines) > 1u {
+        let outline = darkness_outline(vo.uv);
+        res = vec4f(mix(res.rgb, outline.rgb, outline.a), res.a);
+    }
+
+#ifdef DEBAND_DITHER
+    res = vec4f(res.rgb + deband_dither(vo.position.xy), res.a);
+#endif
+
+    return res;
+}
+
+// index of the band the light's luminance falls in, counting from the darkest band
+fn light_band(light: vec3f) -> u32 {
+    let luminance = dot(light, LUMINANCE);
+
+    if config.n_band_thresholds > 0 {
+        var band = 0u;
+        for (var i = 0u; i < config.n_band_thresholds; i += 1u) {
+            if luminance >= config.band_thresholds[i / 4u][i % 4u] {
+                band = i + 1u;
+            }
+        }
+        return band;
+    }
+
+    return u32(max(floor(luminance / config.light_bands), 0.0));
+}
+
+// lowers each channel to the highest custom band threshold below it
+fn threshold_bands(light: vec3f) -> vec3f {
+    var res = vec3f(0.0);
+    for (var i = 0u; i < config.n_band_thresholds; i += 1u) {
+        let threshold = config.band_thresholds[i / 4u][i % 4u];
+        res = select(res, vec3f(threshold), light >= vec3f(threshold));
+    }
+    return res;
+}
+
+// density of the weather overlay at the world position under the pixel
+fn weather_coverage(uv: vec2f) -> f32 {
+    let pos = ndc_to_ground(config.world_from_clip, vec2f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0));
+
+    // texture rows go down, world y goes up
+    let weather_uv = vec2f(pos.x, -pos.y) / config.weather_tile_size - vec2f(config.weather_offset.x, -config.weather_offset.y);
+    return saturate(textureSample(weather_texture, weather_sampler, weather_uv).r);
+}
+
+// checks if a sprite was drawn over the pixel, as opposed to the ground
+fn is_sprite(uv: vec2f) -> bool {
+    let size = vec2<i32>(textureDimensions(sprite_ids));
+    return decode_stencil_id(textureLoad(sprite_ids, vec2<i32>(uv * vec2f(size)), 0)) != 0u;
+}
+
+// checks if the pixel is outside of, and within the outline width of, a dark outlined sprite 
+fn is_outlined(uv: vec2f) -> bool {
+    let size = vec2<i32>(textureDimensions(assist_mask));
+    let center = vec2<i32>(uv * vec2f(size));
+
+    if textureLoad(assist_mask, center, 0).r > 0.5 {
+        return false;
+    }
+
+    let width = i32(ceil(config.assist_outline_width));
+
+    for (var y = -width; y <= width; y += 1) {
+        for (var x = -width; x <= width; x += 1) {
+            if f32(x * x + y * y) > config.assist_outline_width * config.assist_outline_width {
+                continue;
+            }
+
+            let coords = center + vec2<i32>(x, y);
+            if any(coords < vec2<i32>(0)) || any(coords >= size) {
+                continue;
+            }
+
+            if textureLoad(assist_mask, coords, 0).r > 0.5 {
+                let light = textureSampleLevel(light_map_texture, texture_sampler2, (vec2f(coords) + 0.5) / vec2f(size), 0.0);
+                if dot(light.rgb, LUMINANCE) < config.assist_outline_threshold {
+                    return true;
+                }
+            }
+        }
+    }
+
+    return false;
+}
+
+// color of the darkness outline the pixel is part of, or transparent if it isn't part of any.
+// outlines are only drawn outside of their sprite, within their width.
+fn darkness_outline(uv: vec2f) -> vec4f {
+    let n_outlines = arrayLength(&darkness_outlines);
+    let size = vec2<i32>(textureDimensions(sprite_ids));
+    let center = vec2<i32>(uv * vec2f(size));
+    let center_id = decode_stencil_id(textureLoad(sprite_ids, center, 0));
+
+    var max_width = 0.0;
+    for (var i = 1u; i < n_outlines; i += 1) {
+        max_width = max(max_width, darkness_outlines[i].width);
+    }
+
+    let width = i32(ceil(max_width));
+
+    for (var y = -width; y <= width; y += 1) {
+        for (var x = -width; x <= width; x += 1) {
+            let dist = f32(x * x + y * y);
+            if dist > max_width * max_width {
+                continue;
+            }
+
+            let coords = center + vec2<i32>(x, y);
+            if any(coords < vec2<i32>(0)) || any(coords >= size) {
+                continue;
+            }
+
+            let id = decode_stencil_id(textureLoad(sprite_ids, coords, 0));
+            if id == 0u || id == center_id {
+                continue;
+            }
+
+            for (var i = 1u; i < n_outlines; i += 1) {
+                let outline = darkness_outlines[i];
+                if outline.id == id && dist <= outline.width * outline.width {
+                    return outline.color;
+                }
+            }
+        }
+    }
+
+    return vec4f(0.0);
 }
@@ -0,0 +1,94 @@
+const LUMINANCE: vec3f = vec3f(0.2126, 0.7152, 0.0722);
+
+// range of the histogram, in log2 luminance. The first bin holds everything darker
+const MIN_LOG_LUMINANCE: f32 = -8.0;
+const LOG_LUMINANCE_RANGE: f32 = 12.0;
+
+struct AutoExposure {
+    ambient: vec3<f32>,
+    // all in stops
+    target_luminance: f32,
+    min_exposure: f32,
+    max_exposure: f32,
+    // maximum change of the exposure this frame, in stops
+    brighten_step: f32,
+    darken_step: f32,
+    filter_range: vec2<f32>,
+}
+
+@group(0) @binding(0) var light_map_texture: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> settings: AutoExposure;
+@group(0) @binding(2) var<storage, read_write> histogram: array<atomic<u32>, 64>;
+@group(0) @binding(3) var<storage, read_write> exposure: f32;
+
+var<workgroup> shared_histogram: array<atomic<u32>, 64>;
+
+fn luminance_bin(light: vec3f) -> u32 {
+    // the ambient light is added the same way as when the lightmap is applied
+    let luminance = dot(max(light, settings.ambient), LUMINANCE);
+    if luminance < exp2(MIN_LOG_LUMINANCE) {
+        return 0u;
+    }
+
+    let t = saturate((log2(luminance) - MIN_LOG_LUMINANCE) / LOG_LUMINANCE_RANGE);
+    return u32(t * 62.0 + 1.0);
+}
+
+@compute @workgroup_size(16, 16, 1)
+fn compute_histogram(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    if local_index < 64u {
+        atomicStore(&shared_histogram[local_index], 0u);
+    }
+    workgroupBarrier();
+
+    let size = textureDimensions(light_map_texture);
+    if id.x < size.x && id.y < size.y {
+        let light = textureLoad(light_map_texture, vec2<i32>(id.xy), 0).rgb;
+        atomicAdd(&shared_histogram[luminance_bin(light)], 1u);
+    }
+    workgroupBarrier();
+
+    // the global histogram is cleared by compute_average
+    if local_index < 64u {
+        atomicAdd(&histogram[local_index], atomicLoad(&shared_histogram[local_index]));
+    }
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn compute_average() {
+    // cumulative histogram, so the filtered out pixels can be clamped away
+    var cumulative: array<u32, 64>;
+    var total = 0u;
+    for (var i = 0u; i < 64u; i += 1u) {
+        total += atomicLoad(&histogram[i]);
+        cumulative[i] = total;
+        atomicStore(&histogram[i], 0u);
+    }
+
+    let first = u32(f32(total) * saturate(settings.filter_range.x));
+    let last = u32(f32(total) * saturate(settings.filter_range.y));
+
+    var count = 0u;
+    var sum = 0.0;
+    for (var i = 1u; i < 64u; i += 1u) {
+        let bin_count = clamp(cumulative[i], first, last) - clamp(cumulative[i - 1u], first, last);
+        sum += f32(bin_count) * f32(i);
+        count += bin_count;
+    }
+
+    var average = MIN_LOG_LUMINANCE;
+    if count > 0u {
+        average = (sum / f32(count) - 1.0) / 62.0 * LOG_LUMINANCE_RANGE + MIN_LOG_LUMINANCE;
+    }
+
+    let target_exposure = clamp(settings.target_luminance - average, settings.min_exposure, settings.max_exposure);
+    let delta = target_exposure - exposure;
+    if delta > 0.0 {
+        exposure += min(delta, settings.brighten_step);
+    } else {
+        exposure += max(delta, -settings.darken_step);
+    }
+}
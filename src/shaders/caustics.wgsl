@@ -0,0 +1,55 @@
+#import firefly::types::view
+
+struct Caustics {
+    world_from_local: mat4x4<f32>,
+    size: vec2<f32>,
+    tile_size: vec2<f32>,
+    offset_a: vec2<f32>,
+    offset_b: vec2<f32>,
+    color: vec4<f32>,
+    feather: f32,
+}
+
+@group(1) @binding(0) var caustics_texture: texture_2d<f32>;
+@group(1) @binding(1) var caustics_sampler: sampler;
+@group(1) @binding(2) var<uniform> caustics: Caustics;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // position inside the region, relative to its center
+    @location(0) local: vec2<f32>,
+};
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    var quad = array<u32, 6>(2u, 0u, 1u, 1u, 3u, 2u);
+    let corner_index = quad[index];
+
+    let corner = vec2<f32>(
+        f32(corner_index & 0x1u),
+        f32((corner_index & 0x2u) >> 1u),
+    );
+
+    out.local = (corner - 0.5) * caustics.size;
+    out.clip_position = view.clip_from_world * caustics.world_from_local * vec4<f32>(out.local, 0.0, 1.0);
+
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.local / caustics.tile_size;
+
+    // two copies of the pattern scrolling in different directions, their minimum gives the moving web of caustics
+    let a = textureSample(caustics_texture, caustics_sampler, uv + caustics.offset_a).r;
+    let b = textureSample(caustics_texture, caustics_sampler, uv * 1.3 + caustics.offset_b).r;
+    let pattern = min(a, b);
+
+    // fade out towards the edges of the region
+    let edge = caustics.size * 0.5 - abs(in.local);
+    let fade = saturate(min(edge.x, edge.y) / max(caustics.feather, 0.0001));
+
+    return vec4<f32>(caustics.color.rgb * caustics.color.a * pattern * fade, 0.0);
+}
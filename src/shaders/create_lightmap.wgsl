@@ -13,10 +13,10 @@ enable f16;
 }
 
 #import firefly::utils::{
-    ndc_to_world, frag_coord_to_ndc, orientation, same_orientation, intersect, blend, 
+    ndc_to_world, world_to_ndc, ndc_to_uv, frag_coord_to_ndc, orientation, same_orientation, intersect, blend, 
     shadow_blend, intersects_arc, rotate, rotate_arctan, between_arctan, distance_point_to_line,
     intersection_point, rect_intersection, rect_line_intersection, intersects_axis_edge, intersects_corner_arc,
-    rotate_90, rotate_90_cc, intersects_half, falloff
+    rotate_90, rotate_90_cc, intersects_half, falloff, gradient_noise, decode_stencil_id
 }
 
 @group(1) @binding(0)
@@ -52,10 +52,28 @@ var normal_map: texture_2d<f32>;
 @group(1) @binding(10)
 var<uniform> config: FireflyConfig;
 
+@group(1) @binding(11)
+var color_ramps: texture_2d<f32>;
+
+@group(1) @binding(12)
+var sprite_ids: texture_2d<f32>;
+
+@group(1) @binding(13)
+var<storage> shadow_receivers: array<u32>;
+
 const PI2: f32 = 6.28318530717958647692528676655900577;
 const PI: f32 = 3.14159265358979323846264338327950288;
 const PIDIV2: f32 = 1.57079632679489661923132169163975144; 
 
+// position the occluder shadows are cast from, shifted by the shadow skew
+var<private> shadow_light_pos: vec2f;
+
+// point inside the light's core the shadows are sampled from with stochastic penumbras, relative to `shadow_light_pos`
+var<private> penumbra_offset: vec2f;
+
+// translation of the group of the poly occluder being checked, which its vertices are relative to
+var<private> vertex_offset: vec2f;
+
 @fragment
 fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
     // return vec4f(0.5);
@@ -66,8 +84,16 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
     let pos = ndc_to_world(frag_coord_to_ndc(in.position.xy * config.texture_scale));
     let normal = textureLoad(normal_map, vec2<i32>(in.uv * vec2<f32>(textureDimensions(normal_map))), 0);
     let stencil = textureSample(sprite_stencil, texture_sampler, in.uv);
+    let sprite_id = decode_stencil_id(textureLoad(sprite_ids, vec2<i32>(in.uv * vec2<f32>(textureDimensions(sprite_ids))), 0));
 
     let dist = distance(pos, light.pos);
+
+    // distance used for the light's falloff, measured from the capsule swept by moving lights or line lights
+    var light_dist = streak_distance(pos, light.pos, light.streak);
+    if light.line == 2u && dot(pos - light.pos, light.streak) > dot(light.streak, light.streak) {
+        // the end of the segment is capped by the next segment of the line
+        light_dist = light.radius;
+    }
     
     let a = pos - light.pos;
     let b = light.dir;
@@ -77,24 +103,39 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
 
     var light_color = light.color;
 
+    if light.color_ramp != 0u {
+        light_color *= sample_color_ramp(light.color_ramp - 1u, light_dist / light.radius);
+    }
+
 #ifdef TONEMAP_IN_SHADER
     light_color = tonemapping::tone_mapping(light_color, view.color_grading);
 #endif
 
     // light_color = pow(light_color, vec4<f32>(2.2));
 
-    if (dist < light.radius && angle <= light.outer_angle / 2.) {
+    let in_cone = light_dist < light.radius && angle <= (light.outer_angle + light.angle_feather) / 2.;
+    let in_glow = dist < light.glow_radius;
+    let in_range = in_cone || in_glow;
+
+    if in_range {
         
         var angle_multi = 1.0; 
 
-        if angle > light.inner_angle / 2. {
+        if light.angle_feather > 0.0 {
+            angle_multi = 1.0 - smoothstep((light.inner_angle - light.angle_feather) / 2., (light.outer_angle + light.angle_feather) / 2., angle);
+        }
+        else if angle > light.inner_angle / 2. {
             // return vec4<f32>(1.0, 0.0, 0.0, 1.0);
             angle_multi = 1.0 - (angle - light.inner_angle / 2.) / (light.outer_angle / 2. - light.inner_angle / 2.);
         }
 
+        if light.mask_period > 0.0 {
+            angle_multi *= angular_mask(a, b);
+        }
+
         var normal_multi = 1.0;
     
-        if config.normal_mode != 0 && normal.a > 0 && normal.b != 0.1 {
+        if config.normal_mode != 0 && light.affect_normals != 0 && normal.a > 0 && normal.b != 0.1 {
             let normal_dir = mix(normalize(normal.xyz * 2f - 1f), vec3f(0f), config.normal_attenuation);
 
             if normal.b == 0.0 {
@@ -108,11 +149,16 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
                 normal_multi = max(0f, dot(normal_dir, light_dir));
             }
             else if config.normal_mode == 2 {
-                let light_dir = normalize(vec3f(light.pos.x - pos.x, light.height - stencil.b, stencil.r - light.pos.y));
+                let light_dir = top_down_light_dir(vec2f(light.pos.x - pos.x, stencil.r - light.pos.y), light.height - stencil.b, light.pitch);
                 normal_multi = max(0f, dot(normal_dir, light_dir));
             }
             else if config.normal_mode == 3 {
-                let light_dir = normalize(vec3f(light.pos.x - pos.x, light.height - stencil.b, light.z - stencil.g));
+                let light_dir = top_down_light_dir(vec2f(light.pos.x - pos.x, light.z - stencil.g), light.height - stencil.b, light.pitch);
+                normal_multi = max(0f, dot(normal_dir, light_dir));
+            }
+            // the scene is a vertical plane facing the camera, with heights pointing toward the viewer
+            else if config.normal_mode == 4 {
+                let light_dir = normalize(vec3f(light.pos.x - pos.x, light.pos.y - pos.y, light.height - stencil.b));
                 normal_multi = max(0f, dot(normal_dir, light_dir));
             }
         }; 
@@ -121,16 +167,29 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
             normal_multi = 1.0;
         }
 
-        if dist <= light.core_radius {
-            res = vec4f(light_color.xyz, 0) * angle_multi * normal_multi * (light.intensity + light.core_boost * falloff(dist / light.core_radius, light.core_falloff, light.core_falloff_intensity));
+        if in_cone {
+            if light_dist <= light.core_radius {
+                res = vec4f(light_color.xyz, 0) * angle_multi * normal_multi * (light.intensity + light.core_boost * falloff(light_dist / light.core_radius, light.core_falloff, light.core_falloff_intensity));
+            }
+            else {
+                let x = (light_dist - light.core_radius) / (light.radius - light.core_radius);
+                res = vec4f(light_color.xyz, 0) * light.intensity * angle_multi * normal_multi * falloff(x, light.falloff, light.falloff_intensity);
+            }
         }
-        else {
-            let x = (dist - light.core_radius) / (light.radius - light.core_radius);
-            res = vec4f(light_color.xyz, 0) * light.intensity * angle_multi * normal_multi * falloff(x, light.falloff, light.falloff_intensity);
+
+        // the glow ignores the angle and normals, but is shadowed along with the rest of the light
+        if in_glow {
+            let glow = pow(1.0 - dist / light.glow_radius, light.glow_falloff) * light.glow_intensity;
+            res += vec4f(light_color.xyz, 0) * light.intensity * glow;
+        }
+
+        // sprites fade out between one and two z ranges away from the light
+        if light.z_range > 0.0 && stencil.a > 0.1 {
+            res *= 1.0 - smoothstep(light.z_range, light.z_range * 2.0, abs(stencil.g - light.z));
         }
 
         if dot(res, res) < 0.0001 {
-            return res;
+            return mark_important(res, light.important);
         }
 
         var round_index = 0u;
@@ -139,9 +198,30 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
 
         var shadow = vec3f(1); 
 
-        var bin = u32(floor(((atan2(pos.y - light.pos.y, pos.x - light.pos.x) + PI) / PI2) * f32(N_BINS)));
+        // directional lights, with their narrowed bin sectors, cast parallel shadows that aren't skewed
+        shadow_light_pos = light.pos;
+        if light.line != 0u {
+            // line lights cast shadows from the point of the segment closest to the pixel
+            shadow_light_pos = streak_point(pos, light.pos, light.streak);
+        }
+        if light.bin_span >= PI2 {
+            shadow_light_pos -= config.shadow_skew * dist;
+        }
+        penumbra_offset = vec2f(0.0);
+        if config.soft_shadows == 2u && shadow_core_radius(light.core_radius) > 0.0 {
+            penumbra_offset = penumbra_jitter(in.position.xy) * shadow_core_radius(light.core_radius);
+        }
+
+        let bin_angle = atan2(pos.y - light.pos.y, pos.x - light.pos.x) - light.bin_start;
+        var bin = u32(floor(((bin_angle - floor(bin_angle / PI2) * PI2) / light.bin_span) * f32(N_BINS)));
         bin = clamp(bin, 0, N_BINS-1);
 
+        // occluders are sorted by their distance to the light's position, which line lights don't cast shadows from
+        var max_distance = dist;
+        if light.line != 0u {
+            max_distance += length(light.streak);
+        }
+
         let left = bin_indices.indices[bin]; 
         let right = bin_indices.indices[bin + 1];
 
@@ -159,7 +239,7 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
         for (var pointer_index = left; pointer_index < right; pointer_index += 1) {
             let pointer = occluders[pointer_index];
             
-            if pointer.distance > dist { break; }
+            if pointer.distance > max_distance { break; }
             
             // return vec4<f32>(1.0, 0.0, 0.0, 1.0);
             let occluder_type = pointer.index & 2147483648u;
@@ -168,25 +248,36 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
             // round occluder
             if occluder_type == 0 {
                 if stencil.a > 0.1 {
-                    if config.z_sorting == 1 && round_occluders[occluder_index].z_sorting == 1 && stencil.g >= round_occluders[occluder_index].z - config.z_sorting_error_margin {
+                    if config.z_sorting != 0 && round_occluders[occluder_index].z_sorting == 1 && sprite_sort_key(stencil) >= occluder_sort_key(round_occluders[occluder_index].z, round_occluders[occluder_index].y + vertices[round_occluders[occluder_index].group].y) - config.z_sorting_error_margin {
                         continue;
                     }
                 }
 
-                let result = round_check(pos, occluder_index); 
+                if !receives_shadow(round_occluders[occluder_index].receiver_mode, round_occluders[occluder_index].receiver_start, round_occluders[occluder_index].receiver_count, sprite_id) {
+                    continue;
+                }
+
+                let result = round_check(penumbra_sample(pos, pointer.distance), occluder_index); 
 
                 if result > 0.0 {
-                    shadow = shadow_blend(shadow, round_occluders[occluder_index].color.rgb, round_occluders[occluder_index].opacity * result);
+                    let occluder = round_occluders[occluder_index];
+                    let along = shadow_falloff(dist, pointer.distance, light.radius, occluder.shadow_falloff);
+                    let near = distance_opacity(pointer.distance, occluder.near_distance, occluder.far_distance, occluder.near_opacity);
+                    shadow = shadow_blend(shadow, occluder.color.rgb, occluder.opacity * result * along * near);
                 }            
             }
             // poly occluder
             else {
                 if stencil.a > 0.1 {
-                    if config.z_sorting == 1 && poly_occluders[occluder_index].z_sorting == 1 && stencil.g >= poly_occluders[occluder_index].z - config.z_sorting_error_margin {
+                    if config.z_sorting != 0 && poly_occluders[occluder_index].z_sorting == 1 && sprite_sort_key(stencil) >= occluder_sort_key(poly_occluders[occluder_index].z, poly_occluders[occluder_index].y + vertices[poly_occluders[occluder_index].group].y) - config.z_sorting_error_margin {
                         continue;
                     }
                 }
 
+                if !receives_shadow(poly_occluders[occluder_index].receiver_mode, poly_occluders[occluder_index].receiver_start, poly_occluders[occluder_index].receiver_count, sprite_id) {
+                    continue;
+                }
+
                 if prev_index != occluder_index {
                     if prev_index != 0u && accumulated_occlusion > 0.0 {
                         shadow = shadow_blend(shadow, poly_occluders[prev_index].color.rgb, poly_occluders[prev_index].opacity * accumulated_occlusion);
@@ -203,8 +294,10 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
                 let split = pointer.split;
                 let length = pointer.length & 1073741823u;
 
-                let result = poly_check(pos, occluder_index, term, rev, min_v, split, length); 
-                accumulated_occlusion = max(accumulated_occlusion, result);
+                let result = poly_check(penumbra_sample(pos, pointer.distance), occluder_index, term, rev, min_v, split, length); 
+                let along = shadow_falloff(dist, pointer.distance, light.radius, poly_occluders[occluder_index].shadow_falloff);
+                let near = distance_opacity(pointer.distance, poly_occluders[occluder_index].near_distance, poly_occluders[occluder_index].far_distance, poly_occluders[occluder_index].near_opacity);
+                accumulated_occlusion = max(accumulated_occlusion, result * along * near);
             }
 
             if dot(shadow, shadow) < 0.001 {
@@ -216,18 +309,111 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4f {
             shadow = shadow_blend(shadow, poly_occluders[prev_index].color.rgb, poly_occluders[prev_index].opacity * accumulated_occlusion);
         }
 
-        res *= vec4f(shadow, 1);
+        let shadow_strength = light.shadow_strength * config.shadow_strength;
+
+        res *= vec4f(mix(vec3f(1), shadow, shadow_strength), 1);
+
+        if light.sprite_occlusion_steps > 0u && dot(res, res) >= 0.0001 {
+            res *= 1.0 - sprite_occlusion(in.uv, stencil) * shadow_strength;
+        }
+
+        if config.contact_shadow_steps > 0u && light.cast_shadows != 0u && dot(res, res) >= 0.0001 {
+            res *= 1.0 - contact_shadow(pos, dist, stencil) * shadow_strength;
+        }
+    }
+
+    if in_range {
+        res = mark_important(res, light.important);
     }
 
     // return pow(res, vec4<f32>(1.0/2.2));
     return res;
 }
 
+// the alpha of pixels reached by an ImportantLight is set to 1, so they aren't banded when the lightmap is applied
+fn mark_important(res: vec4f, important: u32) -> vec4f {
+    return vec4f(res.rgb, f32(important));
+}
+
+// Lit / unlit pattern repeating around the light. `a` is the offset from the light, `dir` the light's direction.
+fn angular_mask(a: vec2f, dir: vec2f) -> f32 {
+    let light = lights[light_index];
+
+    let angle = atan2(dir.x * a.y - dir.y * a.x, dot(dir, a)) - light.mask_phase;
+
+    // position inside the period, with the lit section centered around 0
+    let x = abs(fract(angle / light.mask_period + 0.5) - 0.5) * light.mask_period;
+    let half_lit = light.mask_duty * light.mask_period * 0.5;
+
+    if light.mask_softness <= 0.0 {
+        return select(0.0, 1.0, x <= half_lit);
+    }
+
+    return 1.0 - smoothstep(half_lit - light.mask_softness * 0.5, half_lit + light.mask_softness * 0.5, x);
+}
+
+// Opacity multiplier of an occluder at `caster_dist` from the light, ramping from `near_opacity` at `near` to 1 at `far`.
+fn distance_opacity(caster_dist: f32, near: f32, far: f32, near_opacity: f32) -> f32 {
+    if near_opacity >= 1.0 {
+        return 1.0;
+    }
+
+    let t = clamp((caster_dist - near) / max(far - near, 0.0001), 0.0, 1.0);
+    return mix(near_opacity, 1.0, t);
+}
+
+// Distance between `pos` and the segment from `light_pos` to `light_pos + streak`.
+fn streak_distance(pos: vec2f, light_pos: vec2f, streak: vec2f) -> f32 {
+    return distance(pos, streak_point(pos, light_pos, streak));
+}
+
+// Point of the segment from `light_pos` to `light_pos + streak` that is closest to `pos`.
+fn streak_point(pos: vec2f, light_pos: vec2f, streak: vec2f) -> vec2f {
+    let length_sq = dot(streak, streak);
+    if length_sq <= 0.0 {
+        return light_pos;
+    }
+
+    let t = clamp(dot(pos - light_pos, streak) / length_sq, 0.0, 1.0);
+    return light_pos + streak * t;
+}
+
+// Opacity multiplier of a shadow at `dist` from the light, fading from 1 at the caster to `1 - falloff` at the light's radius.
+fn shadow_falloff(dist: f32, caster_dist: f32, radius: f32, falloff: f32) -> f32 {
+    if falloff <= 0.0 {
+        return 1.0;
+    }
+
+    let t = clamp((dist - caster_dist) / max(radius - caster_dist, 0.0001), 0.0, 1.0);
+    return 1.0 - falloff * t;
+}
+
+// Direction towards the light in the top-down normal modes. `ground` is the (x, y) offset to the light on the ground plane,
+// `height` its height above the sprite. If a pitch is set (per light, or else in the config), it replaces the height.
+fn top_down_light_dir(ground: vec2f, height: f32, light_pitch: f32) -> vec3f {
+    var pitch = light_pitch;
+    if pitch < 0.0 {
+        pitch = config.light_pitch;
+    }
+
+    if pitch < 0.0 {
+        return normalize(vec3f(ground.x, height, ground.y));
+    }
+
+    var ground_dir = vec2f(0.0);
+    if dot(ground, ground) > 0.0 {
+        ground_dir = normalize(ground);
+    }
+
+    return vec3f(ground_dir.x * cos(pitch), sin(pitch), ground_dir.y * cos(pitch));
+}
+
 fn poly_check(pos: vec2f, index: u32, term: u32, rev: u32, min_v: u32, split: u32, length: u32) -> f32 {
     let light = lights[light_index];
     let occluder = poly_occluders[index];
+    vertex_offset = vertices[occluder.group];
 
-    let angle = atan2(pos.y - light.pos.y, pos.x - light.pos.x);
+    let angle = atan2(pos.y - shadow_light_pos.y, pos.x - shadow_light_pos.x);
 
     var maybe_prev = 0; 
 
@@ -264,31 +450,31 @@ fn poly_check(pos: vec2f, index: u32, term: u32, rev: u32, min_v: u32, split: u3
 
     if !out_of_bounds {
         if rev == 0 {
-            let v1 = vertices[start + u32(maybe_prev) - select(0, occluder.n_vertices, start + u32(maybe_prev) >= occluder.start_vertex + occluder.n_vertices)];
-            let v2 = vertices[start + u32(maybe_prev) + 1 - select(0, occluder.n_vertices, start + u32(maybe_prev) + 1 >= occluder.start_vertex + occluder.n_vertices)];
+            let v1 = vertex(start + u32(maybe_prev) - select(0, occluder.n_vertices, start + u32(maybe_prev) >= occluder.start_vertex + occluder.n_vertices));
+            let v2 = vertex(start + u32(maybe_prev) + 1 - select(0, occluder.n_vertices, start + u32(maybe_prev) + 1 >= occluder.start_vertex + occluder.n_vertices));
 
-            is_occluded = !same_orientation(v1, v2, pos, light.pos);
+            is_occluded = !same_orientation(v1, v2, pos, shadow_light_pos);
         }
         else {
-            let v1 = vertices[i32(start) - maybe_prev + select(0, i32(occluder.n_vertices), i32(start) - maybe_prev < i32(occluder.start_vertex))];
-            let v2 = vertices[i32(start) - maybe_prev - 1 + select(0, i32(occluder.n_vertices), i32(start) - maybe_prev - 1 < i32(occluder.start_vertex))];
+            let v1 = vertex(u32(i32(start) - maybe_prev + select(0, i32(occluder.n_vertices), i32(start) - maybe_prev < i32(occluder.start_vertex))));
+            let v2 = vertex(u32(i32(start) - maybe_prev - 1 + select(0, i32(occluder.n_vertices), i32(start) - maybe_prev - 1 < i32(occluder.start_vertex))));
 
-            is_occluded = !same_orientation(v1, v2, pos, light.pos);
+            is_occluded = !same_orientation(v1, v2, pos, shadow_light_pos);
         }
     }
 
-    if config.soft_shadows > 0 && light.core_radius > 0.0 && out_of_bounds {
+    if config.soft_shadows == 1u && shadow_core_radius(light.core_radius) > 0.0 && out_of_bounds {
         if rev == 0 {
             let loops = min_v + length - 1 >= occluder.start_vertex + occluder.n_vertices;
             let last = min_v + length - 1 - select(0, occluder.n_vertices, loops);
     
-            return get_softness_multi(light.core_radius, light.pos, pos, vertices[min_v], vertices[last]);
+            return get_softness_multi(shadow_core_radius(light.core_radius), shadow_light_pos, pos, vertex(min_v), vertex(last));
         }
         else {
             let loops = i32(min_v) - i32(length) + 1 < i32(occluder.start_vertex);
             let last = u32(i32(min_v) - i32(length) + 1 + select(0, i32(occluder.n_vertices), loops));
             
-            return get_softness_multi(light.core_radius, light.pos, pos, vertices[min_v], vertices[last]);
+            return get_softness_multi(shadow_core_radius(light.core_radius), shadow_light_pos, pos, vertex(min_v), vertex(last));
         }
     }
 
@@ -357,7 +543,7 @@ fn get_softness_multi(light_range: f32, light_pos: vec2<f32>, pos: vec2<f32>, ex
 
 fn angle_term(p: vec2f, i: u32, length: u32, term: u32) -> f32 {
     let light = lights[light_index];
-    var angle = atan2(p.y - light.pos.y, p.x - light.pos.x);
+    var angle = atan2(p.y - shadow_light_pos.y, p.x - shadow_light_pos.x);
     
     if i == length - 1 && term == 1 {
         angle += PI2;
@@ -369,18 +555,22 @@ fn angle_term(p: vec2f, i: u32, length: u32, term: u32) -> f32 {
     return angle;
 }
 
+fn vertex(index: u32) -> vec2f {
+    return vertices[index] + vertex_offset;
+}
+
 fn vertex_forward(start: u32, index: u32, start_vertex: u32, n_vertices: u32) -> vec2<f32> {
     if start + index >= start_vertex + n_vertices {
-        return vertices[start + index - n_vertices];
+        return vertex(start + index - n_vertices);
     }
-    return vertices[start + index];
+    return vertex(start + index);
 }
 
 fn vertex_reverse(start: u32, index: i32, start_vertex: u32, n_vertices: u32) -> vec2<f32> {
     if i32(start) - i32(index) < i32(start_vertex) {
-        return vertices[u32(i32(start) - i32(index) + i32(n_vertices))];
+        return vertex(u32(i32(start) - i32(index) + i32(n_vertices)));
     }
-    return vertices[u32(i32(start) - i32(index))];
+    return vertex(u32(i32(start) - i32(index)));
 } 
 
 fn bs_vertex_forward(angle: f32, start: u32, length: u32, term: u32, start_vertex: u32, n_vertices: u32) -> i32 {
@@ -447,6 +637,66 @@ fn bs_vertex_reverse(angle: f32, start: u32, length: u32, term: u32, start_verte
     return ans;
 }
 
+// radius of the light's core used for the penumbra, see `Softness`
+fn shadow_core_radius(core_radius: f32) -> f32 {
+    if config.shadow_core_radius >= 0.0 {
+        return config.shadow_core_radius;
+    }
+    return core_radius * config.softness;
+}
+
+// position sprites are z-sorted by: their z, or their -y when sorting by y
+fn sprite_sort_key(stencil: vec4f) -> f32 {
+    if config.z_sorting == 2 {
+        return -stencil.r;
+    }
+    return stencil.g;
+}
+
+// position occluders are z-sorted by, matching `sprite_sort_key`
+fn occluder_sort_key(z: f32, y: f32) -> f32 {
+    if config.z_sorting == 2 {
+        return -y;
+    }
+    return z;
+}
+
+// whether the occluder's shadow can darken the sprite with the given stencil ID, see `ShadowReceivers`
+fn receives_shadow(mode: u32, start: u32, count: u32, sprite_id: u32) -> bool {
+    if mode == 0u {
+        return true;
+    }
+
+    var listed = false;
+    if sprite_id != 0u {
+        for (var i = start; i < start + count; i += 1u) {
+            if shadow_receivers[i] == sprite_id {
+                listed = true;
+                break;
+            }
+        }
+    }
+
+    return listed == (mode == 1u);
+}
+
+// random point in the unit disk, using interleaved gradient noise that's offset every frame 
+fn penumbra_jitter(frag_coord: vec2f) -> vec2f {
+    let offset = 5.588238 * f32(config.frame % 64u);
+    let r = sqrt(gradient_noise(frag_coord + offset));
+    let theta = gradient_noise(frag_coord + vec2f(47.0, 17.0) + offset) * PI2;
+    return vec2f(cos(theta), sin(theta)) * r;
+}
+
+// position to check against an occluder at the given distance from the light, for the light to be sampled from
+// `penumbra_offset`. Moves the pixel instead of the light, by the offset that shifts the occluder's shadow by as much,
+// so the bins and silhouettes computed for the light's position stay valid.
+fn penumbra_sample(pos: vec2f, occluder_distance: f32) -> vec2f {
+    let pixel_distance = distance(pos, shadow_light_pos);
+    let occluder_dist = clamp(occluder_distance, 1.0, max(pixel_distance, 1.0));
+    return pos + penumbra_offset * (pixel_distance - occluder_dist) / occluder_dist;
+}
+
 // checks if pixel is blocked by round occluder
 fn round_check(pos: vec2f, occluder: u32) -> f32 {
     let light = lights[light_index];
@@ -456,8 +706,9 @@ fn round_check(pos: vec2f, occluder: u32) -> f32 {
     let half_h = occ.half_height;
     let radius = occ.radius;
 
-    let relative_pos = pos - occ.pos; 
-    let relative_light = light.pos - occ.pos; 
+    let occ_pos = occ.pos + vertices[occ.group];
+    let relative_pos = pos - occ_pos; 
+    let relative_light = shadow_light_pos - occ_pos; 
 
     let c = cos(occ.rot);
     let s = sin(occ.rot);
@@ -474,8 +725,8 @@ fn round_check(pos: vec2f, occluder: u32) -> f32 {
 
     if !rect_line_intersection(p_local, l_local, rect) {
 
-        if config.soft_shadows > 0 && light.core_radius > 0.0 {
-            return get_round_extreme_angle(half_w, half_h, p_local, l_local, light.core_radius, radius);
+        if config.soft_shadows == 1u && shadow_core_radius(light.core_radius) > 0.0 {
+            return get_round_extreme_angle(half_w, half_h, p_local, l_local, shadow_core_radius(light.core_radius), radius);
         }
 
         return 0.0;
@@ -543,8 +794,8 @@ fn round_check(pos: vec2f, occluder: u32) -> f32 {
         half_intersection |= arc4.half_intersection;
     }
 
-    if config.soft_shadows > 0 && light.core_radius > 0.0 && !half_intersection {
-        return get_round_extreme_angle(half_w, half_h, p_local, l_local, light.core_radius, radius);
+    if config.soft_shadows == 1u && shadow_core_radius(light.core_radius) > 0.0 && !half_intersection {
+        return get_round_extreme_angle(half_w, half_h, p_local, l_local, shadow_core_radius(light.core_radius), radius);
     }
 
     return 0.0;
@@ -663,3 +914,94 @@ struct ArcTangents {
     is_b: bool,
     b: vec2<f32>
 }
+
+// linearly interpolates between the two closest texels of a color gradient's lut row
+fn sample_color_ramp(row: u32, t: f32) -> vec4f {
+    let size = textureDimensions(color_ramps).x;
+    let x = clamp(t, 0.0, 1.0) * f32(size - 1u);
+    let i = u32(floor(x));
+    let j = min(i + 1u, size - 1u);
+
+    let a = textureLoad(color_ramps, vec2<u32>(i, row), 0);
+    let b = textureLoad(color_ramps, vec2<u32>(j, row), 0);
+    return mix(a, b, fract(x));
+}
+
+// ray-marches the sprite stencil from the pixel towards the light, returning how much light is blocked by sprites
+fn sprite_occlusion(uv: vec2f, stencil: vec4f) -> f32 {
+    let light = lights[light_index];
+    let light_uv = ndc_to_uv(world_to_ndc(light.pos));
+
+    // the sprite the light is on shouldn't block it
+    let carrier = textureSampleLevel(sprite_stencil, texture_sampler, light_uv, 0.0);
+
+    var occlusion = 0.0;
+    for (var i = 1u; i < light.sprite_occlusion_steps; i += 1u) {
+        let sample_uv = mix(uv, light_uv, f32(i) / f32(light.sprite_occlusion_steps));
+
+        if any(sample_uv < vec2f(0.0)) || any(sample_uv > vec2f(1.0)) {
+            break;
+        }
+
+        let blocker = textureSampleLevel(sprite_stencil, texture_sampler, sample_uv, 0.0);
+
+        if blocker.a <= 0.1 {
+            continue;
+        }
+        if carrier.a > 0.1 && abs(sprite_sort_key(blocker) - sprite_sort_key(carrier)) <= config.z_sorting_error_margin {
+            continue;
+        }
+        if light.sprite_occlusion_heights != 0u {
+            // walls only block the ray where they're taller than it, at this point between the pixel and the light
+            let ray_height = mix(select(0.0, stencil.b, stencil.a > 0.1), light.height, f32(i) / f32(light.sprite_occlusion_steps));
+            if blocker.b <= ray_height {
+                continue;
+            }
+        }
+        // same rule as z-sorting: sprites don't shadow pixels at or above their own z
+        else if stencil.a > 0.1 && sprite_sort_key(stencil) >= sprite_sort_key(blocker) - config.z_sorting_error_margin {
+            continue;
+        }
+
+        occlusion = max(occlusion, blocker.a * light.sprite_occlusion_opacity);
+        if occlusion >= 1.0 {
+            break;
+        }
+    }
+
+    return clamp(occlusion, 0.0, 1.0);
+}
+
+// ray-marches the sprite stencil a short distance from the pixel towards the light, returning how much light
+// is blocked by sprites standing right next to it
+fn contact_shadow(pos: vec2f, dist: f32, stencil: vec4f) -> f32 {
+    let light = lights[light_index];
+    let to_light = normalize(light.pos - pos);
+    let max_length = min(config.contact_shadow_length, dist);
+
+    var occlusion = 0.0;
+    for (var i = 1u; i <= config.contact_shadow_steps; i += 1u) {
+        let d = max_length * f32(i) / f32(config.contact_shadow_steps);
+        let sample_uv = ndc_to_uv(world_to_ndc(pos + to_light * d));
+
+        if any(sample_uv < vec2f(0.0)) || any(sample_uv > vec2f(1.0)) {
+            break;
+        }
+
+        let blocker = textureSampleLevel(sprite_stencil, texture_sampler, sample_uv, 0.0);
+
+        if blocker.a <= 0.1 {
+            continue;
+        }
+        // same rule as z-sorting: sprites don't shadow pixels at or above their own z
+        if stencil.a > 0.1 && sprite_sort_key(stencil) >= sprite_sort_key(blocker) - config.z_sorting_error_margin {
+            continue;
+        }
+
+        // raised sprites cast shorter contact shadows
+        let reach = 1.0 - (d + max(blocker.b - stencil.b, 0.0)) / config.contact_shadow_length;
+        occlusion = max(occlusion, blocker.a * config.contact_shadow_opacity * clamp(reach, 0.0, 1.0));
+    }
+
+    return clamp(occlusion, 0.0, 1.0);
+}
@@ -0,0 +1,61 @@
+#import bevy_sprite::sprite_view_bindings::view
+
+struct DropShadow {
+    world_from_local: mat4x4<f32>,
+    uv_offset_scale: vec4<f32>,
+    color: vec4<f32>,
+    blur: vec2<f32>,
+}
+
+@group(1) @binding(0) var sprite_texture: texture_2d<f32>;
+@group(1) @binding(1) var sprite_sampler: sampler;
+@group(1) @binding(2) var<uniform> shadow: DropShadow;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    // same quad layout as the sprite index buffer
+    var quad = array<u32, 6>(2u, 0u, 1u, 1u, 3u, 2u);
+    let corner_index = quad[index];
+
+    let corner = vec2<f32>(
+        f32(corner_index & 0x1u),
+        f32((corner_index & 0x2u) >> 1u),
+    );
+
+    // the silhouette stands on its bottom edge, which is placed at the sprite's feet
+    out.clip_position = view.clip_from_world * shadow.world_from_local * vec4<f32>(corner.x - 0.5, corner.y, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y) * shadow.uv_offset_scale.zw + shadow.uv_offset_scale.xy;
+
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // keep the taps inside the sprite's rect, so neighbouring atlas frames don't bleed in
+    let rect_a = shadow.uv_offset_scale.xy;
+    let rect_b = shadow.uv_offset_scale.xy + shadow.uv_offset_scale.zw;
+    let rect_min = min(rect_a, rect_b);
+    let rect_max = max(rect_a, rect_b);
+
+    // 5x5 gaussian over the sprite's alpha
+    var alpha = 0.0;
+    var total = 0.0;
+    for (var x = -2; x <= 2; x++) {
+        for (var y = -2; y <= 2; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * 0.5 * shadow.blur;
+            let weight = exp(-f32(x * x + y * y) / 4.0);
+            let uv = clamp(in.uv + offset, rect_min, rect_max);
+            alpha += textureSampleLevel(sprite_texture, sprite_sampler, uv, 0.0).a * weight;
+            total += weight;
+        }
+    }
+
+    return vec4<f32>(shadow.color.rgb, shadow.color.a * alpha / total);
+}
@@ -0,0 +1,66 @@
+struct LensFlare {
+    // in NDC
+    center: vec2<f32>,
+    pixel_size: vec2<f32>,
+    color: vec4<f32>,
+    // in pixels
+    halo_radius: f32,
+    streak_length: f32,
+    streak_width: f32,
+    // in radians
+    streak_rotation: f32,
+    streaks: u32,
+}
+
+@group(0) @binding(0) var<storage> flares: array<LensFlare>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // position relative to the center of the flare, in pixels
+    @location(0) local: vec2<f32>,
+    @location(1) @interpolate(flat) index: u32,
+};
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32, @builtin(instance_index) instance_index: u32) -> VertexOutput {
+    let flare = flares[instance_index];
+
+    var quad = array<vec2<f32>, 6>(
+        vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-1.0, 1.0),
+        vec2(-1.0, 1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    );
+
+    let local = quad[vertex_index] * max(flare.halo_radius, flare.streak_length);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(flare.center + local * flare.pixel_size, 0.0, 1.0);
+    out.local = local;
+    out.index = instance_index;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let flare = flares[in.index];
+    let dist = length(in.local);
+
+    var brightness = 0.0;
+
+    if flare.halo_radius > 0.0 {
+        let halo = saturate(1.0 - dist / flare.halo_radius);
+        brightness += halo * halo;
+    }
+
+    if flare.streaks > 0u && flare.streak_length > 0.0 && flare.streak_width > 0.0 {
+        // angle from the closest streak
+        let sector = 6.28318530718 / f32(flare.streaks);
+        let angle = atan2(in.local.y, in.local.x) - flare.streak_rotation;
+        let offset = abs(fract(angle / sector + 0.5) - 0.5) * sector;
+
+        let across = saturate(1.0 - dist * sin(offset) / flare.streak_width);
+        let along = saturate(1.0 - dist / flare.streak_length);
+        brightness += across * along * along;
+    }
+
+    return vec4<f32>(flare.color.rgb * brightness, 0.0);
+}
@@ -25,6 +25,13 @@ struct VertexInput {
     @location(4) z: f32,
     @location(5) height: f32,
     @location(6) y: f32,
+    @location(7) detail_strength: f32,
+    @location(8) detail_tiling: vec2<f32>,
+    @location(9) outline: f32,
+    @location(10) id: u32,
+    @location(11) min_light: f32,
+    // brightness and saturation thresholds, and strength. 0 strength if not emissive
+    @location(12) emissive: vec3<f32>,
 }
 
 struct VertexOutput {
@@ -33,6 +40,12 @@ struct VertexOutput {
     @location(1) z: f32,
     @location(2) height: f32,
     @location(3) y: f32,
+    @location(4) detail_uv: vec2<f32>,
+    @location(5) detail_strength: f32,
+    @location(6) outline: f32,
+    @location(7) @interpolate(flat) id: u32,
+    @location(8) min_light: f32,
+    @location(9) emissive: vec3<f32>,
 };
 
 @vertex
@@ -54,6 +67,12 @@ fn vertex(in: VertexInput) -> VertexOutput {
     out.z = in.z;
     out.height = in.height;
     out.y = in.y;
+    out.detail_uv = vertex_position.xy * in.detail_tiling;
+    out.detail_strength = in.detail_strength;
+    out.outline = in.outline;
+    out.id = in.id;
+    out.min_light = in.min_light;
+    out.emissive = in.emissive;
 
     return out;
 }
@@ -62,10 +81,16 @@ fn vertex(in: VertexInput) -> VertexOutput {
 @group(1) @binding(1) var normal_texture: texture_2d<f32>;
 @group(1) @binding(2) var sprite_sampler: sampler;
 @group(1) @binding(3) var<uniform> normal_dummy: u32;
+@group(1) @binding(4) var detail_texture: texture_2d<f32>;
+@group(1) @binding(5) var detail_sampler: sampler;
 
 struct FragmentOutput {
     @location(0) stencil: vec4<f32>, 
     @location(1) normal: vec4<f32>,
+    // r is 1 for sprites outlined by the accessibility assist, g is the sprite's minimum light
+    @location(2) outline: vec4<f32>,
+    // stencil ID of the sprite, split in bytes over rgb
+    @location(3) id: vec4<f32>,
 }
 
 @fragment
@@ -74,16 +99,42 @@ fn fragment(in: VertexOutput) -> FragmentOutput {
 
     var color = textureSample(sprite_texture, sprite_sampler, in.uv);
     var normal = textureSample(normal_texture, sprite_sampler, in.uv);
-    
+    let detail = textureSample(detail_texture, detail_sampler, in.detail_uv);
+
+#ifdef DECAL
+    // decals don't write to the stencil texture (masked by the pipeline), and their normals are
+    // blended only over the sprites that were already drawn underneath them.
+    res.stencil = vec4<f32>(0.0);
+    res.outline = vec4<f32>(0.0);
+    res.id = vec4<f32>(0.0);
+    if normal_dummy == 1 {
+        res.normal = vec4<f32>(0.0);
+    }
+    else {
+        res.normal = vec4<f32>(normal.rgb * color.a, color.a);
+    }
+#else
     if color.a >= 1.0 {
         res.stencil = vec4<f32>(in.y, in.z, in.height, 1.0);
+        res.outline = vec4<f32>(in.outline, max(in.min_light, emissive_light(color.rgb, in.emissive)), 0.0, 1.0);
+        res.id = encode_stencil_id(in.id);
     }
     else {
         res.stencil = vec4<f32>(0, 0, 0, 0);
+        res.outline = vec4<f32>(0.0);
+        res.id = vec4<f32>(0.0);
     }
 
     if color.a >= 1.0 {
-        if normal_dummy == 1 {
+        if in.detail_strength > 0.0 {
+            var base = vec3<f32>(0.0, 0.0, 1.0);
+            if normal_dummy == 0 {
+                base = normalize(normal.xyz * 2.0 - 1.0);
+            }
+            let blended = blend_detail_normal(base, detail.xyz * 2.0 - 1.0, in.detail_strength);
+            res.normal = vec4<f32>(blended * 0.5 + 0.5, 1.0);
+        }
+        else if normal_dummy == 1 {
             res.normal = vec4<f32>(0, 0, f32(f16(0.1)), 1.0);
         }
         else {
@@ -93,6 +144,34 @@ fn fragment(in: VertexOutput) -> FragmentOutput {
     else {
         res.normal = vec4<f32>(0.0);
     }
+#endif
 
     return res; 
 }
+
+// Minimum light of a pixel whose HSV value and saturation are above the emissive thresholds.
+fn emissive_light(color: vec3<f32>, emissive: vec3<f32>) -> f32 {
+    let value = max(color.r, max(color.g, color.b));
+    let saturation = select(0.0, (value - min(color.r, min(color.g, color.b))) / value, value > 0.0);
+
+    if emissive.z > 0.0 && value >= emissive.x && saturation >= emissive.y {
+        return emissive.z;
+    }
+    return 0.0;
+}
+
+// Splits a 24-bit stencil ID over the rgb channels of an 8-bit texture. Must match `decode_stencil_id`.
+fn encode_stencil_id(id: u32) -> vec4<f32> {
+    return vec4<f32>(
+        f32(id & 0xffu),
+        f32((id >> 8u) & 0xffu),
+        f32((id >> 16u) & 0xffu),
+        255.0,
+    ) / 255.0;
+}
+
+// Whiteout blending of a detail normal on top of a base normal, with the detail's slope scaled by its strength.
+fn blend_detail_normal(base: vec3<f32>, detail: vec3<f32>, strength: f32) -> vec3<f32> {
+    let d = vec3<f32>(detail.xy * strength, detail.z);
+    return normalize(vec3<f32>(base.xy + d.xy, base.z * d.z));
+}
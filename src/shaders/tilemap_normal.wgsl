@@ -0,0 +1,81 @@
+#import bevy_sprite::sprite_view_bindings::view
+
+struct TilemapNormalLayer {
+    world_from_local: mat4x4<f32>,
+    z: f32,
+    height: f32,
+    y: f32,
+}
+
+@group(1) @binding(0) var tileset: texture_2d_array<f32>;
+@group(1) @binding(1) var normal_tileset: texture_2d_array<f32>;
+@group(1) @binding(2) var tileset_sampler: sampler;
+@group(1) @binding(3) var tile_data: texture_2d<u32>;
+@group(1) @binding(4) var<uniform> layer: TilemapNormalLayer;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    // same quad layout as the sprite index buffer
+    var quad = array<u32, 6>(2u, 0u, 1u, 1u, 3u, 2u);
+    let corner_index = quad[index];
+
+    let corner = vec2<f32>(
+        f32(corner_index & 0x1u),
+        f32((corner_index & 0x2u) >> 1u),
+    );
+
+    out.clip_position = view.clip_from_world * layer.world_from_local * vec4<f32>(corner - 0.5, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) stencil: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) outline: vec4<f32>,
+    @location(3) id: vec4<f32>,
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    var res: FragmentOutput;
+
+    // tile lookup mirrors bevy's tilemap chunk material
+    let chunk_size = textureDimensions(tile_data, 0);
+    let tile_uv = in.uv * vec2<f32>(chunk_size);
+    var tile_coord = clamp(vec2<u32>(floor(tile_uv)), vec2<u32>(0u), chunk_size - 1u);
+    tile_coord.y = chunk_size.y - 1u - tile_coord.y;
+
+    let data = textureLoad(tile_data, tile_coord, 0);
+    let tileset_index = data.r;
+    let visible = data.a != 0u;
+
+    let local_uv = fract(tile_uv);
+    let color = textureSample(tileset, tileset_sampler, local_uv, tileset_index);
+    let normal = textureSample(normal_tileset, tileset_sampler, local_uv, tileset_index);
+
+    if tileset_index == 0xffffu || !visible || color.a < 1.0 {
+        res.stencil = vec4<f32>(0.0);
+        res.normal = vec4<f32>(0.0);
+        res.outline = vec4<f32>(0.0);
+        res.id = vec4<f32>(0.0);
+        return res;
+    }
+
+    res.stencil = vec4<f32>(layer.y, layer.z, layer.height, 1.0);
+    res.normal = normal;
+    // tiles are never outlined, but still cover the outlines of sprites below them
+    res.outline = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    // tiles don't have an entity, so they cover the sprites below them with the empty ID
+    res.id = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+
+    return res;
+}
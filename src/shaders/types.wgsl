@@ -9,6 +9,9 @@
 
 @group(0) @binding(2) var dt_lut_sampler: sampler;
 
+// Should correspond to LAYOUT_VERSION in layout.rs!
+const LAYOUT_VERSION: u32 = 9u;
+
 struct PointLight {
     pos: vec2<f32>,
     intensity: f32,
@@ -34,7 +37,58 @@ struct PointLight {
 
     z: f32,
     height: f32,
-    
+
+    // row of the color gradient lut + 1, 0 if the light doesn't have a color ramp
+    color_ramp: u32,
+
+    // 0 if sprite occlusion is disabled
+    sprite_occlusion_steps: u32,
+    sprite_occlusion_opacity: f32,
+
+    // 0 if the light ignores the normal map
+    affect_normals: u32,
+
+    // elevation angle in radians for the top-down normal modes, negative if the config's is used
+    pitch: f32,
+
+    // angular mask, in radians. a period of 0 means the light isn't masked
+    mask_period: f32,
+    mask_duty: f32,
+    mask_phase: f32,
+    mask_softness: f32,
+
+    // width of the smooth fade at the edge of the light's angle, in radians
+    angle_feather: f32,
+
+    // 0 if the light doesn't cast shadows
+    cast_shadows: u32,
+
+    // 0 - no shadows, 1 - fully dark shadows
+    shadow_strength: f32,
+
+    // offset from the light's position to the end of its motion streak, 0 if the light isn't streaked
+    streak: vec2<f32>,
+
+    // 1 if the light is an ImportantLight, which is never quantized by light bands
+    important: u32,
+
+    // 0 if the light doesn't glow
+    glow_radius: f32,
+    glow_intensity: f32,
+    glow_falloff: f32,
+
+    // start angle and span of the sector the occluders are binned over, narrower than 2 * PI for directional lights
+    bin_start: f32,
+    bin_span: f32,
+
+    // 0 if the light affects sprites at any z
+    z_range: f32,
+
+    // 0 - point light, 1 - line light segment, 2 - line light segment without an end cap
+    line: u32,
+
+    // 1 if sprite occlusion compares the heights of the sprites to the ray towards the light, instead of their z
+    sprite_occlusion_heights: u32,
 }
 
 struct PolyOccluder {
@@ -44,6 +98,19 @@ struct PolyOccluder {
     opacity: f32,
     color: vec4<f32>, 
     z_sorting: u32,
+    shadow_falloff: f32,
+    near_distance: f32,
+    far_distance: f32,
+    // 1 if the opacity doesn't depend on the light's distance
+    near_opacity: f32,
+    // 0 if the shadow darkens every sprite, 1 if it only darkens the receivers, 2 if it never darkens them
+    receiver_mode: u32,
+    receiver_start: u32,
+    receiver_count: u32,
+    // y relative to the group, used when sorting by -y
+    y: f32,
+    // index of the group's translation in the vertex buffer, 0 if the occluder has no group
+    group: u32,
 }
 
 struct OccluderPointer {
@@ -61,20 +128,34 @@ struct RoundOccluder {
     half_height: f32, 
     radius: f32,
     z: f32, 
-    @size(16)
-    color: vec3f,
     opacity: f32, 
+    color: vec4f,
     z_sorting: u32, 
+    shadow_falloff: f32,
+    near_distance: f32,
+    far_distance: f32,
+    // 1 if the opacity doesn't depend on the light's distance
+    near_opacity: f32,
+    // 0 if the shadow darkens every sprite, 1 if it only darkens the receivers, 2 if it never darkens them
+    receiver_mode: u32,
+    receiver_start: u32,
+    receiver_count: u32,
+    // y relative to the group, used when sorting by -y
+    y: f32,
+    // index of the group's translation in the vertex buffer, 0 if the occluder has no group
+    group: u32,
 }
 
 struct FireflyConfig {
     ambient_color: vec3<f32>,
     ambient_brightness: f32, 
     light_bands: f32,
+    // 0 - disabled, 1 - analytic penumbra, 2 - stochastic penumbra
     soft_shadows: u32,
+    // 0 - disabled, 1 - sorted by z, 2 - sorted by -y
     z_sorting: u32,
     z_sorting_error_margin: f32,
-    // 0 - none, 1 - simple, 2 - topdown
+    // 0 - none, 1 - simple, 2 - topdown y, 3 - topdown z, 4 - sidescroller
     normal_mode: u32, 
     normal_attenuation: f32,
     n_combined_lightmaps: u32,
@@ -82,7 +163,57 @@ struct FireflyConfig {
     // 0 - multiply, 1 - add, 2 - max, 3 - min, 4 - none
     combination_mode: u32,
 
-    texture_scale: vec2<f32>
+    texture_scale: vec2<f32>,
+
+    // elevation angle in radians for the top-down normal modes, negative if derived from the heights
+    light_pitch: f32,
+
+    // multiplier for the light core radius used by soft shadows
+    softness: f32,
+
+    // 0 steps if contact shadows are disabled
+    contact_shadow_length: f32,
+    contact_shadow_steps: u32,
+    contact_shadow_opacity: f32,
+
+    // frame count, used to animate noise
+    frame: u32,
+
+    // 0 - no shadows, 1 - fully dark shadows
+    shadow_strength: f32,
+
+    // accessibility assist, 0 luminance and width if disabled
+    assist_min_luminance: f32,
+    assist_outline_width: f32,
+    assist_outline_threshold: f32,
+    assist_outline_color: vec4<f32>,
+
+    // shear applied to occluder shadows, per world unit of distance from the light
+    shadow_skew: vec2<f32>,
+
+    // 1 if the lightmap is output instead of the lit scene
+    show_lightmap: u32,
+
+    world_from_clip: mat4x4<f32>,
+
+    // weather overlay, 0 darkening if disabled
+    weather_tile_size: vec2<f32>,
+    weather_offset: vec2<f32>,
+    weather_ambient: f32,
+    weather_lights: f32,
+    shadow_core_radius: f32,
+
+    // negative brightness if sprites use the same ambient light as the ground
+    sprite_ambient_color: vec3<f32>,
+    sprite_ambient_brightness: f32,
+
+    // sorted custom light band thresholds, packed 4 per vector. 0 thresholds if bands are evenly spaced
+    band_thresholds: array<vec4<f32>, 4>,
+    n_band_thresholds: u32,
+
+    // tints of the light bands, from the darkest band
+    band_tints: array<vec4<f32>, 16>,
+    n_band_tints: u32,
 }
 
 // Should correspond to the value in buffers.rs!
@@ -90,4 +221,11 @@ const N_BINS: u32 = 256;
 
 struct BinIndices {
     indices: array<u32, N_BINS + 1>,
-}
\ No newline at end of file
+}
+
+// sprite outlined by a `DarknessOutline`
+struct DarknessOutline {
+    color: vec4<f32>,
+    id: u32,
+    width: f32,
+}
@@ -3,11 +3,27 @@
 #import firefly::types::view
 
 fn world_to_ndc(world_position: vec2<f32>) -> vec2<f32> {
-    return (view.clip_from_world * vec4(world_position, 0.0, 1.0)).xy;
+    let clip = view.clip_from_world * vec4(world_position, 0.0, 1.0);
+    return clip.xy / clip.w;
 }
 
+// the point on the z = 0 plane seen at the ndc position, which also works for tilted perspective cameras
 fn ndc_to_world(ndc_position: vec2<f32>) -> vec2<f32> {
-    return (view.world_from_clip * vec4(ndc_position, 0.0, 1.0)).xy;
+    return ndc_to_ground(view.world_from_clip, ndc_position);
+}
+
+// rays that don't reach the plane (above the horizon) are cut off far away
+fn ndc_to_ground(world_from_clip: mat4x4<f32>, ndc_position: vec2<f32>) -> vec2<f32> {
+    let near_clip = world_from_clip * vec4(ndc_position, 1.0, 1.0);
+    let mid_clip = world_from_clip * vec4(ndc_position, 0.5, 1.0);
+    let near = near_clip.xyz / near_clip.w;
+    let dir = mid_clip.xyz / mid_clip.w - near;
+
+    if dir.z == 0.0 || -near.z / dir.z < 0.0 {
+        return near.xy + normalize(dir.xy + vec2(0.0, 1e-6)) * 10000.0;
+    }
+
+    return (near + dir * (-near.z / dir.z)).xy;
 }
 
 fn frag_coord_to_uv(frag_coord: vec2<f32>) -> vec2<f32> {
@@ -55,6 +71,19 @@ fn blend(bg: vec4f, fg: vec4f, intensity: f32) -> vec4f {
 }
 
 
+// interleaved gradient noise in [0, 1)
+fn gradient_noise(p: vec2f) -> f32 {
+    return fract(52.9829189 * fract(dot(p, vec2f(0.06711056, 0.00583715))));
+}
+
+// triangular-distributed noise in [-1, 1] for each channel, scaled to one 8-bit step 
+fn deband_dither(frag_coord: vec2f) -> vec3f {
+    let r = gradient_noise(frag_coord) + gradient_noise(frag_coord + vec2f(47.0, 17.0)) - 1.0;
+    let g = gradient_noise(frag_coord + vec2f(13.0, 71.0)) + gradient_noise(frag_coord + vec2f(59.0, 29.0)) - 1.0;
+    let b = gradient_noise(frag_coord + vec2f(37.0, 5.0)) + gradient_noise(frag_coord + vec2f(83.0, 43.0)) - 1.0;
+    return vec3f(r, g, b) / 255.0;
+}
+
 fn shadow_blend(bg: vec3f, fg: vec3f, opacity: f32) -> vec3f {
     return bg * min(vec3f(1), (vec3f(2) - (vec3f(1) - fg)) * (1 - opacity));
 }
@@ -304,4 +333,10 @@ fn falloff(x: f32, falloff: u32, falloff_intensity: f32) -> f32 {
     }
     
     return 1.0;
-}
\ No newline at end of file
+}
+
+// Reads the stencil ID written by `encode_stencil_id` in sprite.wgsl. 0 means there is no sprite.
+fn decode_stencil_id(encoded: vec4<f32>) -> u32 {
+    let bytes = vec3<u32>(round(encoded.rgb * 255.0));
+    return bytes.r | (bytes.g << 8u) | (bytes.b << 16u);
+}
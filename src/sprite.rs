@@ -19,6 +19,10 @@ pub fn add_sprite_visibility_classes(
 }
 
 /// Describes a sprite to be rendered to a 2D camera
+///
+/// Plain Bevy [`Sprite`]s are also written to the sprite stencil and support [`NormalMap`](crate::prelude::NormalMap),
+/// [`DetailNormalMap`](crate::prelude::DetailNormalMap), [`SpriteHeight`](crate::prelude::SpriteHeight) and
+/// [`Decal2d`](crate::prelude::Decal2d), so this component is optional.
 #[derive(Component, Debug, Default, Clone, Reflect)]
 #[require(Transform, Visibility, VisibilityClass, Anchor)]
 #[reflect(Component, Default, Debug, Clone)]
@@ -172,56 +176,121 @@ impl AsAssetId for FireflySprite {
 #[reflect(Debug, Default, Clone)]
 /// A sprite instance is rendered from a texture atlas
 pub struct SpriteInstance {
+    /// Index of the instance's texture in the sprite's [`TextureAtlas`] layout.
     pub index: usize,
+    /// Offset of the instance relative to the sprite.
     pub offset: Vec2,
+    /// Flip the instance along the `X` axis.
     pub flip_x: Option<bool>,
+    /// Flip the instance along the `Y` axis.
     pub flip_y: Option<bool>,
 }
 
-/// Controls how the image is altered when scaled.
-#[derive(Default, Debug, Clone, Reflect, PartialEq)]
-#[reflect(Debug, Default, Clone)]
-pub enum FireflySpriteImageMode {
-    /// The sprite will take on the size of the image by default, and will be stretched or shrunk if [`Sprite::custom_size`] is set.
-    #[default]
-    Auto,
-    /// The texture will be scaled to fit the rect bounds defined in [`Sprite::custom_size`].
-    /// Otherwise no scaling will be applied.
-    Scale(SpriteScalingMode),
-    /// The texture will be cut in 9 slices, keeping the texture in proportions on resize
-    Sliced(TextureSlicer),
-    /// The texture will be repeated if stretched beyond `stretched_value`
-    Tiled {
-        /// Should the image repeat horizontally
-        tile_x: bool,
-        /// Should the image repeat vertically
-        tile_y: bool,
-        /// The texture will repeat when the ratio between the *drawing dimensions* of texture and the
-        /// *original texture size* are above this value.
-        stretch_value: f32,
-    },
-    /// The texture will be rendered by the manually configured sprite instances in this vector.
-    Instances(Vec<SpriteInstance>),
+impl SpriteInstance {
+    /// Construct a new instance of the given atlas index, at the given offset.
+    pub fn new(index: usize, offset: Vec2) -> Self {
+        Self {
+            index,
+            offset,
+            ..default()
+        }
+    }
+
+    /// Flip the instance along the `X` axis.
+    pub fn with_flip_x(self, flip_x: bool) -> Self {
+        Self {
+            flip_x: Some(flip_x),
+            ..self
+        }
+    }
+
+    /// Flip the instance along the `Y` axis.
+    pub fn with_flip_y(self, flip_y: bool) -> Self {
+        Self {
+            flip_y: Some(flip_y),
+            ..self
+        }
+    }
 }
 
-impl FireflySpriteImageMode {
-    /// Returns true if this mode uses slices internally ([`SpriteImageMode::Sliced`] or [`SpriteImageMode::Tiled`])
-    #[inline]
-    pub fn uses_slices(&self) -> bool {
-        matches!(
-            self,
-            Self::Sliced(..) | Self::Tiled { .. } | Self::Instances(..)
-        )
+/// Component that renders a [`FireflySprite`] as a composite of multiple [instances](SpriteInstance) from its texture atlas.
+///
+/// Each instance picks its own atlas index, offset and flips, and all of them are extracted and drawn (along with their
+/// [`NormalMap`](crate::prelude::NormalMap)) as a single sprite. The sprite's [`image_mode`](FireflySprite::image_mode)
+/// is ignored while this component is present.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     FireflySprite::from_atlas_image(asset_server.load("composite_sheet.png"), TextureAtlas::from(layout)),
+///     CompositeSprite(vec![
+///         SpriteInstance::new(0, vec2(10., 0.)),
+///         SpriteInstance::new(1, vec2(-10., -10.)).with_flip_x(true),
+///     ]),
+/// ));
+/// ```
+#[derive(Component, Default, Debug, Clone, Reflect, PartialEq)]
+#[reflect(Component, Debug, Default, Clone)]
+#[require(FireflySprite)]
+pub struct CompositeSprite(pub Vec<SpriteInstance>);
+
+pub use image_mode::FireflySpriteImageMode;
+
+// the `Reflect` derive doesn't inherit lint attributes from the enum, so the deprecated variant is allowed for the module
+#[allow(deprecated)]
+mod image_mode {
+    use super::*;
+
+    /// Controls how the image is altered when scaled.
+    #[derive(Default, Debug, Clone, Reflect, PartialEq)]
+    #[reflect(Debug, Default, Clone)]
+    pub enum FireflySpriteImageMode {
+        /// The sprite will take on the size of the image by default, and will be stretched or shrunk if [`Sprite::custom_size`] is set.
+        #[default]
+        Auto,
+        /// The texture will be scaled to fit the rect bounds defined in [`Sprite::custom_size`].
+        /// Otherwise no scaling will be applied.
+        Scale(SpriteScalingMode),
+        /// The texture will be cut in 9 slices, keeping the texture in proportions on resize
+        Sliced(TextureSlicer),
+        /// The texture will be repeated if stretched beyond `stretched_value`
+        Tiled {
+            /// Should the image repeat horizontally
+            tile_x: bool,
+            /// Should the image repeat vertically
+            tile_y: bool,
+            /// The texture will repeat when the ratio between the *drawing dimensions* of texture and the
+            /// *original texture size* are above this value.
+            stretch_value: f32,
+        },
+        /// The texture will be rendered by the manually configured sprite instances in this vector.
+        ///
+        /// This mode is converted to a [`CompositeSprite`] internally.
+        #[deprecated(note = "use CompositeSprite instead")]
+        Instances(Vec<SpriteInstance>),
     }
 
-    /// Returns [`SpriteScalingMode`] if scale is presented or [`Option::None`] otherwise.
-    #[inline]
-    #[must_use]
-    pub const fn scale(&self) -> Option<SpriteScalingMode> {
-        if let Self::Scale(scale) = self {
-            Some(*scale)
-        } else {
-            None
+    impl FireflySpriteImageMode {
+        /// Returns true if this mode uses slices internally ([`SpriteImageMode::Sliced`] or [`SpriteImageMode::Tiled`])
+        #[inline]
+        #[allow(deprecated)]
+        pub fn uses_slices(&self) -> bool {
+            matches!(
+                self,
+                Self::Sliced(..) | Self::Tiled { .. } | Self::Instances(..)
+            )
+        }
+
+        /// Returns [`SpriteScalingMode`] if scale is presented or [`Option::None`] otherwise.
+        #[inline]
+        #[must_use]
+        pub const fn scale(&self) -> Option<SpriteScalingMode> {
+            if let Self::Scale(scale) = self {
+                Some(*scale)
+            } else {
+                None
+            }
         }
     }
 }
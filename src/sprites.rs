@@ -9,7 +9,7 @@ use std::ops::Range;
 use crate::data::FireflyConfig;
 use crate::phases::SpritePhase;
 use crate::pipelines::{SpritePipeline, SpritePipelineKey};
-use crate::sprite::FireflySprite;
+use crate::sprite::{CompositeSprite, FireflySprite};
 use crate::utils::{compute_slices_on_asset_event, compute_slices_on_sprite_change};
 
 use bevy::asset::{AssetEventSystems, AssetPath};
@@ -27,7 +27,7 @@ use bevy::{
         system::{SystemParamItem, lifetimeless::*},
     },
     math::{Affine3A, FloatOrd},
-    platform::collections::HashMap,
+    platform::collections::{HashMap, HashSet},
     prelude::*,
     render::{
         Render, RenderApp,
@@ -54,12 +54,27 @@ pub(crate) struct ExtractedFireflySprite {
     /// PERF: storing an `AssetId` instead of `Handle<Image>` enables some optimizations (`ExtractedSprite` becomes `Copy` and doesn't need to be dropped)
     pub image_handle_id: AssetId<Image>,
     pub normal_handle_id: Option<AssetId<Image>>,
+    pub detail: Option<ExtractedDetailNormalMap>,
+    pub decal: bool,
+    pub outline: bool,
+    /// [`StencilId`](crate::prelude::StencilId) of the sprite, or 0 if it doesn't have one.
+    pub stencil_id: u32,
+    /// [`MinimumLight`] of the sprite, or 0 if it doesn't have one.
+    pub min_light: f32,
+    pub emissive: Option<EmissiveThreshold>,
     pub flip_x: bool,
     pub flip_y: bool,
     pub kind: ExtractedFireflySpriteKind,
     pub height: f32,
 }
 
+#[derive(Clone, Copy)]
+pub(crate) struct ExtractedDetailNormalMap {
+    pub image_handle_id: AssetId<Image>,
+    pub strength: f32,
+    pub tiling: Vec2,
+}
+
 pub(crate) enum ExtractedFireflySpriteKind {
     /// A single sprite with custom sizing and scaling options
     Single {
@@ -93,12 +108,26 @@ pub(crate) struct SpriteInstance {
     pub z: f32,
     pub height: f32,
     pub y: f32,
-    pub _padding: f32,
+    pub detail_strength: f32,
+    pub detail_tiling: [f32; 2],
+    pub outline: f32,
+    pub id: u32,
+    pub min_light: f32,
+    /// Brightness and saturation thresholds, and strength of the sprite's [`EmissiveThreshold`]. 0 strength if it doesn't have one.
+    pub emissive: [f32; 3],
 }
 
 impl SpriteInstance {
     #[inline]
-    pub fn from(transform: &Affine3A, uv_offset_scale: &Vec4, z: f32, height: f32, y: f32) -> Self {
+    pub fn from(
+        transform: &Affine3A,
+        uv_offset_scale: &Vec4,
+        z: f32,
+        height: f32,
+        y: f32,
+        detail_strength: f32,
+        detail_tiling: Vec2,
+    ) -> Self {
         let transpose_model_3x3 = transform.matrix3.transpose();
         Self {
             i_model_transpose: [
@@ -110,7 +139,43 @@ impl SpriteInstance {
             i_uv_offset_scale: uv_offset_scale.to_array(),
             height,
             y,
-            _padding: 0.0,
+            detail_strength,
+            detail_tiling: detail_tiling.to_array(),
+            outline: 0.0,
+            id: 0,
+            min_light: 0.0,
+            emissive: [0.0; 3],
+        }
+    }
+
+    #[inline]
+    pub fn with_outline(self, outline: bool) -> Self {
+        Self {
+            outline: match outline {
+                true => 1.0,
+                false => 0.0,
+            },
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn with_id(self, id: u32) -> Self {
+        Self { id, ..self }
+    }
+
+    #[inline]
+    pub fn with_min_light(self, min_light: f32) -> Self {
+        Self { min_light, ..self }
+    }
+
+    #[inline]
+    pub fn with_emissive(self, emissive: Option<EmissiveThreshold>) -> Self {
+        Self {
+            emissive: emissive.map_or([0.0; 3], |emissive| {
+                [emissive.brightness, emissive.saturation, emissive.strength]
+            }),
+            ..self
         }
     }
 }
@@ -143,12 +208,13 @@ pub(crate) struct SpriteBatch {
     pub image_handle_id: AssetId<Image>,
     pub normal_handle_id: AssetId<Image>,
     pub normal_dummy: bool,
+    pub detail_handle_id: Option<AssetId<Image>>,
     pub range: Range<u32>,
 }
 
 #[derive(Resource, Default)]
 pub(crate) struct ImageBindGroups {
-    pub values: HashMap<(AssetId<Image>, AssetId<Image>, bool), BindGroup>,
+    pub values: HashMap<(AssetId<Image>, AssetId<Image>, bool, Option<AssetId<Image>>), BindGroup>,
 }
 
 /// Component you can add to an entity that also has a Sprite, containing the corresponding sprite's normal map.
@@ -178,16 +244,237 @@ pub(crate) struct ImageBindGroups {
 /// ```
 ///  
 /// See [Sprite] for more information on using sprites.
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct NormalMap {
     image: Handle<Image>,
+    srgb_handling: NormalMapSrgbHandling,
+}
+
+/// Describes what Firefly should do when a [NormalMap] image is flagged as sRGB.
+///
+/// Normal maps store directions, not colors, so they need to be sampled without gamma correction. If the image
+/// is loaded with `is_srgb = true` (Bevy's default), the GPU will decode the normals as if they were colors,
+/// silently producing skewed lighting.
+///
+/// **Default:** [Warn](NormalMapSrgbHandling::Warn).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NormalMapSrgbHandling {
+    /// Log a warning (once per image) when the normal map image is sRGB-flagged.
+    #[default]
+    Warn,
+    /// Reinterpret the image as linear by removing the sRGB suffix from its texture format.
+    ///
+    /// The image data is left untouched, since Bevy doesn't convert the pixels when loading an sRGB image.
+    Reinterpret,
+    /// Don't validate the image's color space.
+    Ignore,
+}
+
+/// Optional component you can add to sprites, containing a secondary normal map that is tiled across the sprite
+/// and blended with its base [NormalMap].
+///
+/// Useful for giving large sprites (e.g. terrain) fine surface detail without needing gigantic normal map textures.
+/// If the sprite doesn't have a [NormalMap], the detail normal map is blended with a flat surface.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Sprite::from_image(asset_server.load("ground.png")),
+///     NormalMap::from_file("ground_normal.png", &asset_server),
+///     DetailNormalMap::from_file("gravel_normal.png", &asset_server)
+///         .with_tiling(vec2(8.0, 8.0))
+///         .with_strength(0.5),
+/// ));
+/// ```
+#[derive(Component, Clone, Reflect)]
+pub struct DetailNormalMap {
+    image: Handle<Image>,
+    /// How strongly the detail normals perturb the base normals.
+    ///
+    /// **Default:** 1.
+    pub strength: f32,
+    /// How many times the detail normal map repeats across the sprite, on each axis.
+    ///
+    /// **Default:** (1, 1).
+    pub tiling: Vec2,
+}
+
+impl DetailNormalMap {
+    /// Construct a new [DetailNormalMap] from the [path](AssetPath) to the image and the [AssetServer].
+    ///
+    /// Like [`NormalMap::from_file`], the image is loaded without gamma correction.
+    pub fn from_file<'a>(path: impl Into<AssetPath<'a>>, asset_server: &AssetServer) -> Self {
+        let image: Handle<Image> =
+            asset_server.load_with_settings(path, |x: &mut ImageLoaderSettings| x.is_srgb = false);
+
+        Self::from_handle(image)
+    }
+
+    /// Construct a new [DetailNormalMap] from an image handle. The image should be loaded without gamma correction.
+    pub fn from_handle(handle: Handle<Image>) -> Self {
+        Self {
+            image: handle,
+            strength: 1.0,
+            tiling: Vec2::ONE,
+        }
+    }
+
+    /// Get the handle of the detail normal map image.
+    pub fn handle(&self) -> Handle<Image> {
+        self.image.clone()
+    }
+
+    /// Returns the detail normal map with the given strength.
+    pub fn with_strength(&self, strength: f32) -> Self {
+        Self {
+            strength,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the detail normal map with the given tiling factor.
+    pub fn with_tiling(&self, tiling: Vec2) -> Self {
+        Self {
+            tiling,
+            ..self.clone()
+        }
+    }
+}
+
+/// Marker component you can add to a [`Sprite`] or [`FireflySprite`], turning it into a decal (e.g. bullet holes, blood, moss patches).
+///
+/// Instead of writing its own data to the `Stencil Texture`, a decal keeps the data of the sprites below it, so it's lit
+/// exactly like the surface it's projected onto. If the decal also has a [`NormalMap`], its normals are blended
+/// over the normals of the sprites below it, weighted by the decal's alpha.
+///
+/// Decals are sorted by z like any other sprite, meaning they only project onto sprites with a lower z,
+/// and are covered by sprites with a higher z.
+///
+/// The decal's color is still rendered like a regular sprite and isn't clipped to the sprites below it.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Sprite::from_image(asset_server.load("bullet_hole.png")),
+///     NormalMap::from_file("bullet_hole_normal.png", &asset_server),
+///     Decal2d,
+///     Transform::from_xyz(10., 5., 1.),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Default, Reflect)]
+pub struct Decal2d;
+
+/// Marker component you can add to a [`Sprite`] or [`FireflySprite`] to have it outlined while it's in the dark,
+/// if the camera's [`FireflyConfig`] has an [accessibility assist](crate::prelude::AccessibilityAssist).
+///
+/// Meant for sprites the player needs to notice, such as interactables, pickups or enemies.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Sprite::from_image(asset_server.load("lever.png")),
+///     AssistOutline,
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Default, Reflect)]
+pub struct AssistOutline;
+
+/// Component you can add to a [`Sprite`] or [`FireflySprite`] to guarantee it's never lit below the given luminance,
+/// from 0 to 1, regardless of the scene's lighting.
+///
+/// Meant for key gameplay objects that should never render fully black. The light is brightened evenly across all channels,
+/// the same way as the [accessibility assist](crate::prelude::AccessibilityAssist)'s minimum luminance.
+///
+/// Applied through the sprite stencil, so it has no effect without the `sprites` feature.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Sprite::from_image(asset_server.load("exit_door.png")),
+///     MinimumLight(0.3),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Default, Debug, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MinimumLight(pub f32);
+
+/// Component you can add to a [`Sprite`] or [`FireflySprite`] to make its brightest pixels emissive, without needing an extra texture.
+///
+/// Pixels whose HSV value and saturation are above the thresholds are lit at least at the given [strength](EmissiveThreshold::strength),
+/// regardless of the scene's lighting, the same way as a [`MinimumLight`]. Meant for quickly prototyping glowing signs, lava or screens.
+///
+/// Applied through the sprite stencil, so it has no effect without the `sprites` feature.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Sprite::from_image(asset_server.load("lava.png")),
+///     EmissiveThreshold::new(0.8).with_saturation(0.5),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EmissiveThreshold {
+    /// Minimum HSV value (the brightest color channel) of an emissive pixel, from 0 to 1.
+    ///
+    /// **Default:** 0.9.
+    pub brightness: f32,
+
+    /// Minimum HSV saturation of an emissive pixel, from 0 to 1. Raising it keeps white highlights from glowing.
+    ///
+    /// **Default:** 0.
+    pub saturation: f32,
+
+    /// Minimum luminance of the light over emissive pixels, from 0 to 1.
+    ///
+    /// **Default:** 1.
+    pub strength: f32,
+}
+
+impl Default for EmissiveThreshold {
+    fn default() -> Self {
+        Self {
+            brightness: 0.9,
+            saturation: 0.,
+            strength: 1.,
+        }
+    }
+}
+
+impl EmissiveThreshold {
+    /// Construct a new [`EmissiveThreshold`] with the given [brightness](EmissiveThreshold::brightness) threshold.
+    pub fn new(brightness: f32) -> Self {
+        Self {
+            brightness,
+            ..default()
+        }
+    }
+
+    /// Returns the threshold with the given [saturation](EmissiveThreshold::saturation) threshold.
+    pub fn with_saturation(self, saturation: f32) -> Self {
+        Self { saturation, ..self }
+    }
+
+    /// Returns the threshold with the given [strength](EmissiveThreshold::strength).
+    pub fn with_strength(self, strength: f32) -> Self {
+        Self { strength, ..self }
+    }
 }
 
 /// Optional component you can add to sprites.
 ///
 /// Describes the sprite object's 2d height, useful for emulating 3d lighting in top-down 2d games.
 ///
-/// This is currently used along with the normal maps. It defaults to 0.   
+/// This is currently used along with the normal maps. It defaults to 0. With the
+/// [Sidescroller](crate::prelude::NormalMode::Sidescroller) normal mode, it's the sprite's distance toward the viewer instead.
 #[derive(Component, Default, Reflect)]
 pub struct SpriteHeight(pub f32);
 
@@ -208,7 +495,22 @@ impl NormalMap {
         let image: Handle<Image> =
             asset_server.load_with_settings(path, |x: &mut ImageLoaderSettings| x.is_srgb = false);
 
-        Self { image }
+        Self {
+            image,
+            srgb_handling: default(),
+        }
+    }
+
+    /// Construct a new [NormalMap] from an image handle.
+    ///
+    /// The image should be loaded without gamma correction. If it's flagged as sRGB, a warning will be logged once
+    /// the image is loaded. You can use [`with_srgb_handling`](NormalMap::with_srgb_handling) to have Firefly
+    /// reinterpret the image as linear instead.
+    pub fn from_handle(handle: Handle<Image>) -> Self {
+        Self {
+            image: handle,
+            srgb_handling: default(),
+        }
     }
 
     /// Construct a new [NormalMap] from an image handle. It's important that this image is loaded without gamma correction:
@@ -220,7 +522,84 @@ impl NormalMap {
     /// You can use the [`from_file`](NormalMap::from_file) constructor to handle this automatically for you, and later grab the handle
     /// via the [`.handle()`](NormalMap::handle) method.
     pub fn from_image(image: Handle<Image>) -> Self {
-        Self { image }
+        Self::from_handle(image)
+    }
+
+    /// Returns the normal map with the given [sRGB handling](NormalMapSrgbHandling).
+    pub fn with_srgb_handling(&self, srgb_handling: NormalMapSrgbHandling) -> Self {
+        Self {
+            srgb_handling,
+            ..self.clone()
+        }
+    }
+
+    /// Get the [sRGB handling](NormalMapSrgbHandling) of this normal map.
+    pub fn srgb_handling(&self) -> NormalMapSrgbHandling {
+        self.srgb_handling
+    }
+}
+
+/// Checks normal map images for an sRGB texture format once they're loaded, warning about
+/// or reinterpreting them depending on their [`NormalMapSrgbHandling`].
+fn validate_normal_maps(
+    mut events: MessageReader<AssetEvent<Image>>,
+    mut images: ResMut<Assets<Image>>,
+    normal_maps: Query<Ref<NormalMap>>,
+    mut warned: Local<HashSet<AssetId<Image>>>,
+) {
+    let loaded: HashSet<AssetId<Image>> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id }
+            | AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for normal_map in &normal_maps {
+        let id = normal_map.image.id();
+        if !normal_map.is_changed() && !loaded.contains(&id) {
+            continue;
+        }
+
+        let Some(image) = images.get(id) else {
+            continue;
+        };
+
+        if !image.texture_descriptor.format.is_srgb() {
+            continue;
+        }
+
+        match normal_map.srgb_handling {
+            NormalMapSrgbHandling::Ignore => {}
+            NormalMapSrgbHandling::Warn => {
+                if warned.insert(id) {
+                    warn!(
+                        "Normal map image {:?} has an sRGB texture format ({:?}), which will produce skewed lighting. \
+                        Load it with `is_srgb = false`, use `NormalMap::from_file`, or set `NormalMapSrgbHandling::Reinterpret`.",
+                        normal_map
+                            .image
+                            .path()
+                            .map(|p| p.to_string())
+                            .unwrap_or_else(|| id.to_string()),
+                        image.texture_descriptor.format
+                    );
+                }
+            }
+            NormalMapSrgbHandling::Reinterpret => {
+                let Some(image) = images.get_mut(id) else {
+                    continue;
+                };
+                let format = image.texture_descriptor.format.remove_srgb_suffix();
+                image.texture_descriptor.format = format;
+                if let Some(view_descriptor) = &mut image.texture_view_descriptor
+                    && let Some(view_format) = &mut view_descriptor.format
+                {
+                    *view_format = view_format.remove_srgb_suffix();
+                }
+            }
+        }
     }
 }
 
@@ -238,6 +617,15 @@ impl Plugin for SpritesPlugin {
                 .in_set(SpriteSystems::ComputeSlices),),
         );
 
+        app.register_type::<NormalMapSrgbHandling>()
+            .register_type::<DetailNormalMap>()
+            .register_type::<Decal2d>()
+            .register_type::<AssistOutline>()
+            .register_type::<MinimumLight>()
+            .register_type::<EmissiveThreshold>()
+            .register_type::<CompositeSprite>()
+            .add_systems(PostUpdate, validate_normal_maps.after(AssetEventSystems));
+
         if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app
                 .init_resource::<ImageBindGroups>()
@@ -322,12 +710,18 @@ fn queue_sprites(
             view_key |= SpritePipelineKey::ENABLED_32BIT_STENCIL;
         }
 
-        let pipeline = pipelines.specialize(&pipeline_cache, &pipeline, view_key);
+        let sprite_pipeline = pipelines.specialize(&pipeline_cache, &pipeline, view_key);
+        let decal_pipeline = pipelines.specialize(
+            &pipeline_cache,
+            &pipeline,
+            view_key | SpritePipelineKey::DECAL,
+        );
 
         view_entities.clear();
         view_entities.extend(
             visible_entities
                 .iter::<FireflySprite>()
+                .chain(visible_entities.iter::<Sprite>())
                 .map(|(_, e)| e.index_u32() as usize),
         );
 
@@ -343,6 +737,11 @@ fn queue_sprites(
             // These items will be sorted by depth with other phase items
             let sort_key = FloatOrd(extracted_sprite.transform.translation().z);
 
+            let pipeline = match extracted_sprite.decal {
+                false => sprite_pipeline,
+                true => decal_pipeline,
+            };
+
             // Add the item to the render phase
             phase.add(SpritePhase {
                 draw_function,
@@ -408,6 +807,7 @@ impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetSpriteTextureBindGrou
             batch.image_handle_id,
             batch.normal_handle_id,
             batch.normal_dummy,
+            batch.detail_handle_id,
         )) else {
             return RenderCommandResult::Skip;
         };
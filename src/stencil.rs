@@ -0,0 +1,149 @@
+//! Module containing [`StencilId`]s, which tell sprites apart in the sprite stencil.
+
+use bevy::{platform::collections::HashMap, prelude::*};
+
+#[cfg(feature = "sprites")]
+use crate::sprite::FireflySprite;
+
+/// Component with the ID a sprite writes to the [sprite ID texture](crate::SpriteIdTexture), so that
+/// shadows and custom render passes can apply effects to specific entities, such as highlighting the
+/// hovered entity or keeping a character out of the lighting.
+///
+/// IDs are allocated automatically by [`StencilIds`] for every [`Sprite`] and [`FireflySprite`], and released
+/// when the sprite is removed. In WGSL, the ID of a pixel can be read from the texture with `decode_stencil_id`
+/// from `firefly::utils`, and on the CPU it can be mapped back to its entity with [`StencilIds::resolve`].
+///
+/// # Example
+///
+/// ```
+/// fn highlight_hovered(
+///     hovered: Query<&StencilId, With<Hovered>>,
+///     mut highlight: ResMut<MyHighlightSettings>,
+/// ) {
+///     // passed to a custom render pass, which compares it to the ID texture
+///     highlight.id = hovered.single().map_or(0, |id| id.get());
+/// }
+/// ```
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Component, Debug, Clone)]
+pub struct StencilId(u32);
+
+impl StencilId {
+    /// The ID of pixels that aren't covered by any sprite.
+    pub const NONE: StencilId = StencilId(0);
+
+    /// Largest ID that can be stored in the sprite ID texture.
+    pub const MAX: u32 = 0xFF_FFFF;
+
+    /// Returns the ID as it's written to the sprite ID texture.
+    pub fn get(self) -> u32 {
+        self.0
+    }
+}
+
+/// Resource that allocates [`StencilId`]s and maps them back to their entities.
+///
+/// Sprites get their IDs automatically. IDs can also be allocated manually for entities that are drawn
+/// into the sprite ID texture by custom passes.
+#[derive(Resource, Default, Debug)]
+pub struct StencilIds {
+    ids: HashMap<Entity, StencilId>,
+    entities: HashMap<u32, Entity>,
+    free: Vec<u32>,
+    next: u32,
+}
+
+impl StencilIds {
+    /// Returns the ID of the entity, allocating a new one if it doesn't have any.
+    ///
+    /// Returns `None` if all [`StencilId::MAX`] IDs are in use.
+    pub fn allocate(&mut self, entity: Entity) -> Option<StencilId> {
+        if let Some(id) = self.ids.get(&entity) {
+            return Some(*id);
+        }
+
+        let id = match self.free.pop() {
+            Some(id) => id,
+            None if self.next < StencilId::MAX => {
+                self.next += 1;
+                self.next
+            }
+            None => return None,
+        };
+
+        self.ids.insert(entity, StencilId(id));
+        self.entities.insert(id, entity);
+        Some(StencilId(id))
+    }
+
+    /// Frees the ID of the entity, so it can be reused by another one. Returns the freed ID, if any.
+    pub fn release(&mut self, entity: Entity) -> Option<StencilId> {
+        let id = self.ids.remove(&entity)?;
+        self.entities.remove(&id.0);
+        self.free.push(id.0);
+        Some(id)
+    }
+
+    /// Returns the ID of the entity, if it has one.
+    pub fn get(&self, entity: Entity) -> Option<StencilId> {
+        self.ids.get(&entity).copied()
+    }
+
+    /// Returns the entity that the ID read from the sprite ID texture belongs to, if any.
+    pub fn resolve(&self, id: u32) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+
+    /// Returns the number of allocated IDs.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns true if no IDs are allocated.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+/// Plugin that allocates [`StencilId`]s for sprites. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct StencilPlugin;
+impl Plugin for StencilPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<StencilId>();
+        app.init_resource::<StencilIds>();
+
+        app.add_observer(on_sprite_added::<Sprite>);
+        app.add_observer(on_sprite_removed::<Sprite>);
+
+        #[cfg(feature = "sprites")]
+        {
+            app.add_observer(on_sprite_added::<FireflySprite>);
+            app.add_observer(on_sprite_removed::<FireflySprite>);
+        }
+    }
+}
+
+fn on_sprite_added<T: Component>(
+    trigger: On<Add, T>,
+    mut commands: Commands,
+    mut ids: ResMut<StencilIds>,
+) {
+    let Some(id) = ids.allocate(trigger.entity) else {
+        warn_once!(
+            "Ran out of stencil IDs, new sprites won't be told apart in the sprite stencil."
+        );
+        return;
+    };
+
+    commands.entity(trigger.entity).insert(id);
+}
+
+fn on_sprite_removed<T: Component>(
+    trigger: On<Remove, T>,
+    mut commands: Commands,
+    mut ids: ResMut<StencilIds>,
+) {
+    if ids.release(trigger.entity).is_some() {
+        commands.entity(trigger.entity).try_remove::<StencilId>();
+    }
+}
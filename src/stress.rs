@@ -0,0 +1,159 @@
+//! Module containing generators for stress-test scenes, used by the benchmarks to measure regressions across releases.
+//!
+//! The scenes are deterministic: the same [`StressScene`] always spawns the same entities at the same positions,
+//! so measurements of different versions of Firefly can be compared to each other.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::prelude::*;
+
+/// Marker component added to every entity spawned by a [`StressScene`].
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct StressSceneEntity;
+
+/// Description of a scene filled with randomly placed lights, occluders and sprites.
+///
+/// # Example
+///
+/// ```
+/// fn setup(mut commands: Commands) {
+///     commands.spawn((Camera2d, FireflyConfig::default()));
+///
+///     StressScene::new(200, 2000, 500)
+///         .with_size(vec2(4000., 4000.))
+///         .spawn(&mut commands);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct StressScene {
+    /// Number of [point lights](PointLight2d) to spawn.
+    pub lights: u32,
+    /// Number of [occluders](Occluder2d) to spawn, cycling through round, polygonal and polyline shapes.
+    pub occluders: u32,
+    /// Number of [sprites](crate::prelude::FireflySprite) to spawn. Ignored without the `sprites` feature.
+    pub sprites: u32,
+
+    /// Size of the area the entities are spawned in, centered on the origin.
+    ///
+    /// **Default:** 2000x2000.
+    pub size: Vec2,
+
+    /// Seed of the placement. Different seeds give different scenes with the same entity counts.
+    ///
+    /// **Default:** 0.
+    pub seed: u32,
+}
+
+impl StressScene {
+    /// Construct a new scene with the given number of lights, occluders and sprites.
+    pub const fn new(lights: u32, occluders: u32, sprites: u32) -> Self {
+        Self {
+            lights,
+            occluders,
+            sprites,
+            size: vec2(2000., 2000.),
+            seed: 0,
+        }
+    }
+
+    /// Construct a new scene with the specified [size](StressScene::size).
+    pub const fn with_size(self, size: Vec2) -> Self {
+        Self { size, ..self }
+    }
+
+    /// Construct a new scene with the specified [seed](StressScene::seed).
+    pub const fn with_seed(self, seed: u32) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Spawn all entities of the scene. They can be despawned through the [`StressSceneEntity`] marker.
+    pub fn spawn(&self, commands: &mut Commands) {
+        let mut rng = StressRng((self.seed.wrapping_mul(0x9E37_79B9) ^ 0x85EB_CA6B).max(1));
+
+        for _ in 0..self.lights {
+            let pos = rng.point(self.size);
+            commands.spawn((
+                StressSceneEntity,
+                Transform::from_translation(pos.extend(0.)),
+                PointLight2d {
+                    color: Color::hsl(rng.range(0., 360.), 0.8, 0.6),
+                    intensity: rng.range(0.5, 2.),
+                    radius: rng.range(50., 300.),
+                    ..default()
+                },
+            ));
+        }
+
+        for i in 0..self.occluders {
+            let pos = rng.point(self.size);
+            let occluder = match i % 3 {
+                0 => Occluder2d::round_rectangle(
+                    rng.range(5., 40.),
+                    rng.range(5., 40.),
+                    rng.range(0., 10.),
+                ),
+                1 => {
+                    let n = 3 + rng.next() % 6;
+                    let radius = rng.range(5., 30.);
+                    let vertices = (0..n)
+                        .map(|v| Vec2::from_angle(v as f32 / n as f32 * TAU) * radius)
+                        .collect::<Vec<_>>();
+                    Occluder2d::polygon(vertices).unwrap()
+                }
+                _ => {
+                    let vertices = (0..6)
+                        .map(|v| vec2(v as f32 * 10. - 25., rng.range(-8., 8.)))
+                        .collect::<Vec<_>>();
+                    Occluder2d::polyline(vertices).unwrap()
+                }
+            };
+
+            commands.spawn((
+                StressSceneEntity,
+                Transform::from_translation(pos.extend(0.))
+                    .with_rotation(Quat::from_rotation_z(rng.range(0., TAU))),
+                occluder,
+            ));
+        }
+
+        #[cfg(feature = "sprites")]
+        for _ in 0..self.sprites {
+            let pos = rng.point(self.size);
+            commands.spawn((
+                StressSceneEntity,
+                Transform::from_translation(pos.extend(0.)),
+                FireflySprite::sized(vec2(rng.range(8., 32.), rng.range(8., 32.))),
+            ));
+        }
+    }
+}
+
+/// Spawn a [`StressScene`] with the given number of lights, occluders and sprites, and the default size and seed.
+pub fn spawn_stress_scene(commands: &mut Commands, lights: u32, occluders: u32, sprites: u32) {
+    StressScene::new(lights, occluders, sprites).spawn(commands);
+}
+
+/// Small xorshift generator, so scenes don't depend on a random number crate.
+struct StressRng(u32);
+
+impl StressRng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (self.next() as f32 / u32::MAX as f32) * (max - min)
+    }
+
+    fn point(&mut self, size: Vec2) -> Vec2 {
+        vec2(
+            self.range(-size.x / 2., size.x / 2.),
+            self.range(-size.y / 2., size.y / 2.),
+        )
+    }
+}
@@ -0,0 +1,740 @@
+//! Module containing [`SvgOccluders`], an asset of occluders imported from the shapes of an SVG file.
+
+use std::f32::consts::{PI, TAU};
+
+use bevy::{
+    asset::{AssetEventSystems, AssetLoader, LoadContext, io::Reader},
+    math::Affine2,
+    platform::collections::HashSet,
+    prelude::*,
+};
+
+use crate::{occluder_batch::OccluderBatch, prelude::Occluder2d};
+
+/// Asset holding the occluders imported from the shapes of an SVG file, so vector-authored level geometry
+/// (e.g. drawn in Inkscape or Illustrator) can cast shadows directly.
+///
+/// With the `svg` feature, it's loaded from `.svg` files. Every `<path>`, `<rect>`, `<circle>`, `<ellipse>`,
+/// `<line>`, `<polyline>` and `<polygon>` becomes an occluder. Closed outlines become polygons and open ones
+/// polylines, while curves and arcs are flattened into line segments with the [tolerance](SvgOccludersSettings::tolerance)
+/// of the loader settings. Circles are imported as round occluders, unless they're skewed or stretched by a transform.
+///
+/// One SVG user unit is one world unit, scaled by the [scale](SvgOccludersSettings::scale) of the loader settings.
+/// Since SVG's y axis points down, the shapes are flipped vertically, so the origin of the SVG is the top-left corner.
+///
+/// # Example
+///
+/// ```
+/// // spawns an entity with an `OccluderBatch` holding the SVG's occluders once it's loaded
+/// commands.spawn(SvgOccluder2d(asset_server.load("level.svg")));
+///
+/// // or with a lower tolerance, for smoother curves
+/// let handle: Handle<SvgOccluders> = asset_server.load_with_settings("level.svg", |settings: &mut SvgOccludersSettings| {
+///     settings.tolerance = 0.1;
+/// });
+/// ```
+#[derive(Asset, TypePath, Clone, Default)]
+pub struct SvgOccluders {
+    /// The imported occluders, positioned relative to the origin of the SVG.
+    pub occluders: Vec<Occluder2d>,
+}
+
+impl SvgOccluders {
+    /// Imports the occluders from the source of an SVG file.
+    ///
+    /// Malformed path data is read up to the first error, like browsers do.
+    pub fn from_svg(
+        source: &str,
+        settings: &SvgOccludersSettings,
+    ) -> Result<Self, SvgOccludersLoaderError> {
+        let document = roxmltree::Document::parse(source)?;
+
+        let mut occluders = vec![];
+        read_node(
+            document.root_element(),
+            Affine2::IDENTITY,
+            settings,
+            &mut occluders,
+        );
+
+        Ok(Self { occluders })
+    }
+
+    /// Returns an [`OccluderBatch`] holding the occluders.
+    pub fn batch(&self) -> OccluderBatch {
+        OccluderBatch::from_occluders(self.occluders.iter().cloned())
+    }
+}
+
+/// Settings of the [`SvgOccludersLoader`].
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SvgOccludersSettings {
+    /// Maximum distance between a curve and the line segments it's flattened into, in SVG user units.
+    ///
+    /// **Performance Impact:** Lower tolerances create more vertices, which are more expensive to cast shadows from.
+    ///
+    /// **Default:** 0.5.
+    pub tolerance: f32,
+
+    /// Size of an SVG user unit, in world units.
+    ///
+    /// **Default:** 1.
+    pub scale: f32,
+}
+
+impl Default for SvgOccludersSettings {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.5,
+            scale: 1.,
+        }
+    }
+}
+
+/// Component that keeps the entity's [`OccluderBatch`] in sync with an [`SvgOccluders`] asset.
+///
+/// The batch is inserted once the asset is loaded, and replaced whenever it's modified, e.g. when
+/// the SVG file is hot reloaded.
+#[derive(Component, Clone, Default, Debug)]
+pub struct SvgOccluder2d(pub Handle<SvgOccluders>);
+
+/// [`AssetLoader`] for [`SvgOccluders`] stored as `.svg` files.
+#[derive(Default, TypePath)]
+pub struct SvgOccludersLoader;
+
+/// Error that can occur while loading [`SvgOccluders`].
+#[derive(Debug)]
+pub enum SvgOccludersLoaderError {
+    /// The file couldn't be read.
+    Io(std::io::Error),
+    /// The file isn't valid UTF-8.
+    Utf8(std::str::Utf8Error),
+    /// The file isn't a valid XML document.
+    Xml(roxmltree::Error),
+}
+
+impl std::fmt::Display for SvgOccludersLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "could not read svg: {err}"),
+            Self::Utf8(err) => write!(f, "could not decode svg: {err}"),
+            Self::Xml(err) => write!(f, "could not parse svg: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SvgOccludersLoaderError {}
+
+impl From<std::io::Error> for SvgOccludersLoaderError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<std::str::Utf8Error> for SvgOccludersLoaderError {
+    fn from(err: std::str::Utf8Error) -> Self {
+        Self::Utf8(err)
+    }
+}
+
+impl From<roxmltree::Error> for SvgOccludersLoaderError {
+    fn from(err: roxmltree::Error) -> Self {
+        Self::Xml(err)
+    }
+}
+
+impl AssetLoader for SvgOccludersLoader {
+    type Asset = SvgOccluders;
+    type Settings = SvgOccludersSettings;
+    type Error = SvgOccludersLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &SvgOccludersSettings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        SvgOccluders::from_svg(std::str::from_utf8(&bytes)?, settings)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["svg"]
+    }
+}
+
+/// Plugin that loads [`SvgOccluders`] and keeps [`SvgOccluder2d`] entities in sync with them.
+/// Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin) with the `svg` feature.
+pub struct SvgOccluderPlugin;
+
+impl Plugin for SvgOccluderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<SvgOccluders>();
+        app.init_asset_loader::<SvgOccludersLoader>();
+        app.add_systems(PostUpdate, update_svg_occluders.after(AssetEventSystems));
+    }
+}
+
+fn update_svg_occluders(
+    mut commands: Commands,
+    mut events: MessageReader<AssetEvent<SvgOccluders>>,
+    svgs: Res<Assets<SvgOccluders>>,
+    occluders: Query<(Entity, Ref<SvgOccluder2d>)>,
+) {
+    let changed: HashSet<_> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id }
+            | AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, svg) in &occluders {
+        if !svg.is_changed() && !changed.contains(&svg.0.id()) {
+            continue;
+        }
+
+        // not loaded yet, the batch is inserted once it is
+        let Some(svg) = svgs.get(&svg.0) else {
+            continue;
+        };
+
+        commands.entity(entity).insert(svg.batch());
+    }
+}
+
+fn read_node(
+    node: roxmltree::Node,
+    parent_transform: Affine2,
+    settings: &SvgOccludersSettings,
+    occluders: &mut Vec<Occluder2d>,
+) {
+    let transform = node
+        .attribute("transform")
+        .map_or(parent_transform, |transform| {
+            parent_transform * parse_transform(transform)
+        });
+
+    let number = |name: &str| node.attribute(name).map_or(0., parse_length);
+    let tolerance = settings.tolerance.max(0.001);
+    let mut path = PathBuilder::new(tolerance);
+
+    match node.tag_name().name() {
+        // never rendered directly
+        "defs" | "clipPath" | "mask" | "symbol" | "pattern" | "marker" => return,
+        "path" => {
+            if let Some(data) = node.attribute("d") {
+                parse_path(data, &mut path);
+            }
+        }
+        "rect" => {
+            let (x, y) = (number("x"), number("y"));
+            let (width, height) = (number("width"), number("height"));
+
+            // a missing radius takes the value of the other one
+            let (rx, ry) = match (node.attribute("rx"), node.attribute("ry")) {
+                (None, None) => (0., 0.),
+                (Some(rx), None) => (parse_length(rx), parse_length(rx)),
+                (None, Some(ry)) => (parse_length(ry), parse_length(ry)),
+                (Some(rx), Some(ry)) => (parse_length(rx), parse_length(ry)),
+            };
+            let radius = vec2(rx.clamp(0., width * 0.5), ry.clamp(0., height * 0.5));
+
+            if width > 0. && height > 0. {
+                path.move_to(vec2(x + radius.x, y));
+                path.line_to(vec2(x + width - radius.x, y));
+                path.arc_to(radius, 0., false, true, vec2(x + width, y + radius.y));
+                path.line_to(vec2(x + width, y + height - radius.y));
+                path.arc_to(
+                    radius,
+                    0.,
+                    false,
+                    true,
+                    vec2(x + width - radius.x, y + height),
+                );
+                path.line_to(vec2(x + radius.x, y + height));
+                path.arc_to(radius, 0., false, true, vec2(x, y + height - radius.y));
+                path.line_to(vec2(x, y + radius.y));
+                path.arc_to(radius, 0., false, true, vec2(x + radius.x, y));
+                path.close();
+            }
+        }
+        "circle" => {
+            let center = vec2(number("cx"), number("cy"));
+            let radius = number("r");
+
+            if radius > 0. {
+                // round occluders stay exact, as long as the transform keeps the circle round
+                let (x_axis, y_axis) = (transform.matrix2.x_axis, transform.matrix2.y_axis);
+                if x_axis.dot(y_axis).abs() < 0.001
+                    && (x_axis.length() - y_axis.length()).abs() < 0.001
+                {
+                    let center = to_world(transform.transform_point2(center), settings);
+                    occluders.push(
+                        Occluder2d::circle(radius * x_axis.length() * settings.scale)
+                            .with_offset(center.extend(0.)),
+                    );
+                } else {
+                    path.ellipse(center, Vec2::splat(radius));
+                }
+            }
+        }
+        "ellipse" => {
+            let radius = vec2(number("rx"), number("ry"));
+            if radius.x > 0. && radius.y > 0. {
+                path.ellipse(vec2(number("cx"), number("cy")), radius);
+            }
+        }
+        "line" => {
+            path.move_to(vec2(number("x1"), number("y1")));
+            path.line_to(vec2(number("x2"), number("y2")));
+        }
+        name @ ("polyline" | "polygon") => {
+            let numbers = parse_numbers(node.attribute("points").unwrap_or_default());
+            for (i, point) in numbers.chunks_exact(2).enumerate() {
+                match i {
+                    0 => path.move_to(vec2(point[0], point[1])),
+                    _ => path.line_to(vec2(point[0], point[1])),
+                }
+            }
+            if name == "polygon" {
+                path.close();
+            }
+        }
+        _ => {}
+    }
+
+    for subpath in path.finish() {
+        let vertices: Vec<Vec2> = subpath
+            .points
+            .iter()
+            .map(|point| to_world(transform.transform_point2(*point), settings))
+            .collect();
+
+        let occluder = match subpath.closed && vertices.len() >= 3 {
+            true => Occluder2d::polygon(vertices),
+            false => Occluder2d::polyline(vertices),
+        };
+        occluders.extend(occluder);
+    }
+
+    for child in node.children().filter(|child| child.is_element()) {
+        read_node(child, transform, settings, occluders);
+    }
+}
+
+fn to_world(point: Vec2, settings: &SvgOccludersSettings) -> Vec2 {
+    vec2(point.x, -point.y) * settings.scale
+}
+
+/// A flattened subpath of an SVG shape.
+struct Subpath {
+    points: Vec<Vec2>,
+    closed: bool,
+}
+
+/// Flattens the commands of an SVG shape into subpaths.
+struct PathBuilder {
+    subpaths: Vec<Subpath>,
+    current: Vec<Vec2>,
+    tolerance: f32,
+}
+
+impl PathBuilder {
+    fn new(tolerance: f32) -> Self {
+        Self {
+            subpaths: vec![],
+            current: vec![],
+            tolerance,
+        }
+    }
+
+    fn last(&self) -> Vec2 {
+        self.current.last().copied().unwrap_or_default()
+    }
+
+    fn start(&self) -> Vec2 {
+        self.current.first().copied().unwrap_or_default()
+    }
+
+    fn end_subpath(&mut self, closed: bool) {
+        let mut points = std::mem::take(&mut self.current);
+        points.dedup_by(|a, b| a.distance_squared(*b) < 1e-8);
+
+        if closed && points.len() > 1 && points[0].distance_squared(points[points.len() - 1]) < 1e-8
+        {
+            points.pop();
+        }
+
+        if points.len() >= 2 {
+            self.subpaths.push(Subpath { points, closed });
+        }
+    }
+
+    fn move_to(&mut self, point: Vec2) {
+        self.end_subpath(false);
+        self.current.push(point);
+    }
+
+    fn line_to(&mut self, point: Vec2) {
+        if self.current.is_empty() {
+            self.current.push(Vec2::ZERO);
+        }
+        self.current.push(point);
+    }
+
+    fn close(&mut self) {
+        let start = self.start();
+        self.end_subpath(true);
+        // drawing after a close continues from the start of the closed subpath
+        self.current.push(start);
+    }
+
+    fn quad_to(&mut self, control: Vec2, point: Vec2) {
+        let start = self.last();
+        // the flattening error is bounded by an eighth of the second derivative over the squared segment count
+        let deviation = 2. * (start - 2. * control + point).length();
+        let segments = self.segments(deviation);
+
+        for i in 1..=segments {
+            let t = i as f32 / segments as f32;
+            let a = start.lerp(control, t);
+            let b = control.lerp(point, t);
+            self.line_to(a.lerp(b, t));
+        }
+    }
+
+    fn cubic_to(&mut self, control1: Vec2, control2: Vec2, point: Vec2) {
+        let start = self.last();
+        let deviation = 6.
+            * (start - 2. * control1 + control2)
+                .length()
+                .max((control1 - 2. * control2 + point).length());
+        let segments = self.segments(deviation);
+
+        for i in 1..=segments {
+            let t = i as f32 / segments as f32;
+            let u = 1. - t;
+            self.line_to(
+                start * u * u * u
+                    + control1 * 3. * u * u * t
+                    + control2 * 3. * u * t * t
+                    + point * t * t * t,
+            );
+        }
+    }
+
+    fn segments(&self, deviation: f32) -> usize {
+        ((deviation / (8. * self.tolerance)).sqrt().ceil() as usize).clamp(1, 256)
+    }
+
+    /// Elliptical arc, following the endpoint parameterization of the SVG specification.
+    fn arc_to(&mut self, radius: Vec2, rotation: f32, large_arc: bool, sweep: bool, point: Vec2) {
+        let start = self.last();
+        let mut radius = radius.abs();
+
+        if radius.x == 0. || radius.y == 0. || start.distance_squared(point) < 1e-8 {
+            self.line_to(point);
+            return;
+        }
+
+        let rotation = Rot2::degrees(rotation);
+        let half = rotation.inverse() * ((start - point) * 0.5);
+
+        // radii too small to reach the end point are scaled up
+        let lambda = (half.x / radius.x).powi(2) + (half.y / radius.y).powi(2);
+        if lambda > 1. {
+            radius *= lambda.sqrt();
+        }
+
+        let numerator = radius.x.powi(2) * radius.y.powi(2)
+            - radius.x.powi(2) * half.y.powi(2)
+            - radius.y.powi(2) * half.x.powi(2);
+        let denominator = radius.x.powi(2) * half.y.powi(2) + radius.y.powi(2) * half.x.powi(2);
+        let mut factor = (numerator / denominator).max(0.).sqrt();
+        if large_arc == sweep {
+            factor = -factor;
+        }
+
+        let center_rotated =
+            factor * vec2(radius.x * half.y / radius.y, -radius.y * half.x / radius.x);
+        let center = rotation * center_rotated + (start + point) * 0.5;
+
+        let start_angle = ((half - center_rotated) / radius).to_angle();
+        let end_angle = ((-half - center_rotated) / radius).to_angle();
+        let mut delta = (end_angle - start_angle).rem_euclid(TAU);
+        if !sweep && delta > 0. {
+            delta -= TAU;
+        }
+
+        self.arc(center, radius, rotation, start_angle, delta);
+        self.line_to(point);
+    }
+
+    fn ellipse(&mut self, center: Vec2, radius: Vec2) {
+        self.move_to(center + vec2(radius.x, 0.));
+        self.arc(center, radius, Rot2::IDENTITY, 0., TAU);
+        self.close();
+    }
+
+    /// Flattens an arc of an ellipse, without its end point.
+    fn arc(&mut self, center: Vec2, radius: Vec2, rotation: Rot2, start_angle: f32, delta: f32) {
+        let max_radius = radius.max_element();
+        let step = 2. * (1. - self.tolerance / max_radius).clamp(-1., 1.).acos();
+        let segments = match step > 0. {
+            true => ((delta.abs() / step).ceil() as usize).clamp(1, 256),
+            false => 1,
+        };
+
+        for i in 1..segments {
+            let angle = start_angle + delta * i as f32 / segments as f32;
+            self.line_to(center + rotation * (Vec2::from_angle(angle) * radius));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Subpath> {
+        self.end_subpath(false);
+        self.subpaths
+    }
+}
+
+/// Cursor over the numbers and commands of SVG path data and attribute lists.
+struct Tokens<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            bytes: source.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_whitespace() || *c == b',')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn at_number(&mut self) -> bool {
+        self.skip_separators();
+        self.bytes
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, b'-' | b'+' | b'.'))
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let c = *self.bytes.get(self.pos)?;
+        if !c.is_ascii_alphabetic() {
+            return None;
+        }
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let start = self.pos;
+
+        let mut end = start;
+        if matches!(self.bytes.get(end), Some(b'-' | b'+')) {
+            end += 1;
+        }
+        let mut dot = false;
+        while let Some(c) = self.bytes.get(end) {
+            match c {
+                b'0'..=b'9' => {}
+                b'.' if !dot => dot = true,
+                _ => break,
+            }
+            end += 1;
+        }
+        if matches!(self.bytes.get(end), Some(b'e' | b'E')) {
+            let mut exponent_end = end + 1;
+            if matches!(self.bytes.get(exponent_end), Some(b'-' | b'+')) {
+                exponent_end += 1;
+            }
+            if self.bytes.get(exponent_end).is_some_and(u8::is_ascii_digit) {
+                end = exponent_end;
+                while self.bytes.get(end).is_some_and(u8::is_ascii_digit) {
+                    end += 1;
+                }
+            }
+        }
+
+        let number = std::str::from_utf8(&self.bytes[start..end])
+            .ok()?
+            .parse()
+            .ok()?;
+        self.pos = end;
+        Some(number)
+    }
+
+    /// Arc flags, which don't need separators.
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.bytes.get(self.pos)? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(flag)
+    }
+
+    fn point(&mut self) -> Option<Vec2> {
+        Some(vec2(self.number()?, self.number()?))
+    }
+}
+
+fn parse_numbers(source: &str) -> Vec<f32> {
+    let mut tokens = Tokens::new(source);
+    std::iter::from_fn(|| tokens.number()).collect()
+}
+
+/// Parses a length attribute, ignoring its unit.
+fn parse_length(source: &str) -> f32 {
+    Tokens::new(source).number().unwrap_or(0.)
+}
+
+fn parse_path(data: &str, path: &mut PathBuilder) {
+    let mut tokens = Tokens::new(data);
+    // reflected by the smooth curve commands
+    let mut last_control: Option<Vec2> = None;
+
+    let Some(mut command) = tokens.command() else {
+        return;
+    };
+
+    loop {
+        let relative = command.is_ascii_lowercase();
+        let origin = match relative {
+            true => path.last(),
+            false => Vec2::ZERO,
+        };
+        let reflected = 2. * path.last() - last_control.unwrap_or(path.last());
+
+        let parsed = (|| {
+            let mut control = None;
+            match command.to_ascii_uppercase() {
+                b'M' => {
+                    path.move_to(origin + tokens.point()?);
+                    // extra coordinates after a move are lines
+                    command = if relative { b'l' } else { b'L' };
+                }
+                b'L' => path.line_to(origin + tokens.point()?),
+                b'H' => path.line_to(vec2(origin.x + tokens.number()?, path.last().y)),
+                b'V' => path.line_to(vec2(path.last().x, origin.y + tokens.number()?)),
+                b'Q' => {
+                    let (c, point) = (origin + tokens.point()?, origin + tokens.point()?);
+                    path.quad_to(c, point);
+                    control = Some(c);
+                }
+                b'T' => {
+                    let point = origin + tokens.point()?;
+                    path.quad_to(reflected, point);
+                    control = Some(reflected);
+                }
+                b'C' => {
+                    let c1 = origin + tokens.point()?;
+                    let (c2, point) = (origin + tokens.point()?, origin + tokens.point()?);
+                    path.cubic_to(c1, c2, point);
+                    control = Some(c2);
+                }
+                b'S' => {
+                    let (c2, point) = (origin + tokens.point()?, origin + tokens.point()?);
+                    path.cubic_to(reflected, c2, point);
+                    control = Some(c2);
+                }
+                b'A' => {
+                    let radius = tokens.point()?;
+                    let rotation = tokens.number()?;
+                    let (large_arc, sweep) = (tokens.flag()?, tokens.flag()?);
+                    path.arc_to(radius, rotation, large_arc, sweep, origin + tokens.point()?);
+                }
+                b'Z' => path.close(),
+                _ => return None,
+            }
+            Some(control)
+        })();
+
+        let Some(control) = parsed else {
+            return;
+        };
+
+        // only consecutive curves of the same kind reflect their control points
+        let is_quad = matches!(command.to_ascii_uppercase(), b'Q' | b'T');
+        let is_cubic = matches!(command.to_ascii_uppercase(), b'C' | b'S');
+        last_control = control.filter(|_| is_quad || is_cubic);
+
+        if command.eq_ignore_ascii_case(&b'Z') || !tokens.at_number() {
+            match tokens.command() {
+                Some(next) => {
+                    if is_quad != matches!(next.to_ascii_uppercase(), b'Q' | b'T')
+                        || is_cubic != matches!(next.to_ascii_uppercase(), b'C' | b'S')
+                    {
+                        last_control = None;
+                    }
+                    command = next;
+                }
+                None => return,
+            }
+        }
+    }
+}
+
+fn parse_transform(source: &str) -> Affine2 {
+    let mut transform = Affine2::IDENTITY;
+
+    for function in source.split(')') {
+        let Some((name, args)) = function.split_once('(') else {
+            continue;
+        };
+        let name = name.trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let args = parse_numbers(args);
+        let arg = |i: usize| args.get(i).copied();
+
+        let function = match name {
+            "matrix" if args.len() == 6 => {
+                Affine2::from_cols_array(&[args[0], args[1], args[2], args[3], args[4], args[5]])
+            }
+            "translate" => {
+                Affine2::from_translation(vec2(arg(0).unwrap_or(0.), arg(1).unwrap_or(0.)))
+            }
+            "scale" => {
+                let x = arg(0).unwrap_or(1.);
+                Affine2::from_scale(vec2(x, arg(1).unwrap_or(x)))
+            }
+            "rotate" => {
+                let rotation = Affine2::from_angle(arg(0).unwrap_or(0.) * PI / 180.);
+                let pivot = vec2(arg(1).unwrap_or(0.), arg(2).unwrap_or(0.));
+                Affine2::from_translation(pivot) * rotation * Affine2::from_translation(-pivot)
+            }
+            "skewX" => Affine2::from_cols(
+                Vec2::X,
+                vec2((arg(0).unwrap_or(0.) * PI / 180.).tan(), 1.),
+                Vec2::ZERO,
+            ),
+            "skewY" => Affine2::from_cols(
+                vec2(1., (arg(0).unwrap_or(0.) * PI / 180.).tan()),
+                Vec2::Y,
+                Vec2::ZERO,
+            ),
+            _ => continue,
+        };
+
+        transform *= function;
+    }
+
+    transform
+}
@@ -0,0 +1,373 @@
+//! Module containing structs and functions relevant to normal-mapped tilemaps.
+//!
+//! Instead of spawning a [`FireflySprite`](crate::prelude::FireflySprite) for every tile, a [`TilemapNormalLayer`]
+//! can be added to a Bevy [`TilemapChunk`], rendering the normals of the whole chunk into the `Normal Map` in a single draw.
+
+use bevy::{
+    ecs::{
+        query::ROQueryItem,
+        system::{SystemParamItem, lifetimeless::*},
+    },
+    math::FloatOrd,
+    platform::collections::HashMap,
+    prelude::*,
+    render::{
+        Extract, ExtractSchedule, Render, RenderApp, RenderSystems,
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
+            RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+        },
+        render_resource::*,
+        renderer::{RenderDevice, RenderQueue},
+        sync_world::RenderEntity,
+        texture::GpuImage,
+        view::{ExtractedView, RenderVisibleEntities},
+    },
+    sprite_render::{TilemapChunk, TilemapChunkMaterial},
+};
+
+use fixedbitset::FixedBitSet;
+
+use crate::{
+    data::FireflyConfig,
+    phases::SpritePhase,
+    pipelines::{SpritePipelineKey, TilemapNormalPipeline},
+    sprites::SetSpriteViewBindGroup,
+};
+
+/// Component you can add to an entity with a Bevy [`TilemapChunk`], containing the normals of its tiles.
+///
+/// The whole chunk is rendered into the normal map in a single draw, which is a lot faster than spawning
+/// a [`FireflySprite`](crate::prelude::FireflySprite) with a [`NormalMap`](crate::prelude::NormalMap) for every tile.
+///
+/// The normal tileset **MUST** be an array texture with the same layers and layer size as the chunk's tileset,
+/// with each layer containing the normal map of the corresponding tile. Just like [`NormalMap`](crate::prelude::NormalMap),
+/// it needs to be loaded without gamma correction.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     TilemapChunk {
+///         chunk_size,
+///         tile_display_size,
+///         tileset: tileset.clone(),
+///         ..default()
+///     },
+///     TilemapChunkTileData(tile_data),
+///     TilemapNormalLayer::new(normal_tileset),
+/// ));
+/// ```
+#[derive(Component, Clone, Reflect)]
+pub struct TilemapNormalLayer {
+    /// Array texture containing the normal map of each tile in the tileset.
+    pub normal_tileset: Handle<Image>,
+    /// The 2d height of the chunk, same as [`SpriteHeight`](crate::prelude::SpriteHeight) for sprites.
+    ///
+    /// **Default:** 0.
+    pub height: f32,
+}
+
+impl TilemapNormalLayer {
+    /// Construct a new [`TilemapNormalLayer`] from the handle to the normal tileset.
+    pub fn new(normal_tileset: Handle<Image>) -> Self {
+        Self {
+            normal_tileset,
+            height: 0.,
+        }
+    }
+
+    /// Returns the layer with the given height.
+    pub fn with_height(&self, height: f32) -> Self {
+        Self {
+            height,
+            ..self.clone()
+        }
+    }
+}
+
+/// Data of a [`TilemapNormalLayer`] that is sent to the GPU.
+#[derive(ShaderType, Clone, Copy)]
+pub struct UniformTilemapNormalLayer {
+    pub world_from_local: Mat4,
+    pub z: f32,
+    pub height: f32,
+    pub y: f32,
+}
+
+pub(crate) struct ExtractedTilemapNormalLayer {
+    pub main_entity: Entity,
+    pub render_entity: Entity,
+    pub transform: GlobalTransform,
+    pub size: Vec2,
+    pub height: f32,
+    pub tileset: AssetId<Image>,
+    pub normal_tileset: AssetId<Image>,
+    pub tile_data: AssetId<Image>,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedTilemapNormalLayers {
+    pub layers: Vec<ExtractedTilemapNormalLayer>,
+}
+
+/// Tileset, normal tileset and tile data of a [`TilemapNormalLayer`].
+type TilemapNormalImages = [AssetId<Image>; 3];
+
+#[derive(Resource, Default)]
+pub(crate) struct TilemapNormalBindGroups {
+    /// Uniforms of all tilemap normal layers of the frame, each bound at its own offset.
+    pub uniforms: DynamicUniformBuffer<UniformTilemapNormalLayer>,
+    /// Bind group of each set of images used by a layer.
+    pub images: HashMap<TilemapNormalImages, BindGroup>,
+    /// Images and uniform offset of each layer.
+    pub values: HashMap<Entity, (TilemapNormalImages, u32)>,
+}
+
+/// Plugin that extracts and queues [`TilemapNormalLayer`]s into the sprite render phase. Added
+/// automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct TilemapPlugin;
+impl Plugin for TilemapPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TilemapNormalLayer>();
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<ExtractedTilemapNormalLayers>()
+            .init_resource::<TilemapNormalBindGroups>()
+            .add_render_command::<SpritePhase, DrawTilemapNormalLayer>()
+            .add_systems(ExtractSchedule, extract_tilemap_normal_layers)
+            .add_systems(
+                Render,
+                (
+                    queue_tilemap_normal_layers.in_set(RenderSystems::Queue),
+                    prepare_tilemap_normal_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                ),
+            );
+    }
+}
+
+fn extract_tilemap_normal_layers(
+    mut extracted: ResMut<ExtractedTilemapNormalLayers>,
+    materials: Extract<Res<Assets<TilemapChunkMaterial>>>,
+    chunks: Extract<
+        Query<(
+            Entity,
+            RenderEntity,
+            &ViewVisibility,
+            &TilemapChunk,
+            &TilemapNormalLayer,
+            &MeshMaterial2d<TilemapChunkMaterial>,
+            &GlobalTransform,
+        )>,
+    >,
+) {
+    extracted.layers.clear();
+
+    for (main_entity, render_entity, visibility, chunk, layer, material, transform) in &chunks {
+        if !visibility.get() {
+            continue;
+        }
+
+        // the tile data image is created by bevy when the chunk is inserted
+        let Some(material) = materials.get(material.id()) else {
+            continue;
+        };
+
+        extracted.layers.push(ExtractedTilemapNormalLayer {
+            main_entity,
+            render_entity,
+            transform: *transform,
+            size: (chunk.chunk_size * chunk.tile_display_size).as_vec2(),
+            height: layer.height,
+            tileset: chunk.tileset.id(),
+            normal_tileset: layer.normal_tileset.id(),
+            tile_data: material.tile_data.id(),
+        });
+    }
+}
+
+fn queue_tilemap_normal_layers(
+    mut view_entities: Local<FixedBitSet>,
+    draw_functions: Res<DrawFunctions<SpritePhase>>,
+    pipeline: Res<TilemapNormalPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<TilemapNormalPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    extracted: Res<ExtractedTilemapNormalLayers>,
+    mut phases: ResMut<ViewSortedRenderPhases<SpritePhase>>,
+    views: Query<(&FireflyConfig, &RenderVisibleEntities, &ExtractedView)>,
+) {
+    if extracted.layers.is_empty() {
+        return;
+    }
+
+    let draw_function = draw_functions.read().id::<DrawTilemapNormalLayer>();
+
+    for (config, visible_entities, view) in &views {
+        let Some(phase) = phases.get_mut(&view.retained_view_entity) else {
+            continue;
+        };
+
+        let mut key = SpritePipelineKey::NONE;
+        if config.enable_32bit_stencils {
+            key |= SpritePipelineKey::ENABLED_32BIT_STENCIL;
+        }
+
+        let pipeline = pipelines.specialize(&pipeline_cache, &pipeline, key);
+
+        view_entities.clear();
+        view_entities.extend(
+            visible_entities
+                .iter::<Mesh2d>()
+                .map(|(_, e)| e.index_u32() as usize),
+        );
+
+        for (index, layer) in extracted.layers.iter().enumerate() {
+            if !view_entities.contains(layer.main_entity.index_u32() as usize) {
+                continue;
+            }
+
+            phase.add(SpritePhase {
+                draw_function,
+                pipeline,
+                entity: (layer.render_entity, layer.main_entity.into()),
+                sort_key: FloatOrd(layer.transform.translation().z),
+                // each layer is drawn on its own, so the batch range is never extended
+                batch_range: 0..1,
+                extra_index: PhaseItemExtraIndex::None,
+                extracted_index: index,
+                indexed: false,
+            });
+        }
+    }
+}
+
+fn prepare_tilemap_normal_bind_groups(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    pipeline: Res<TilemapNormalPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    extracted: Res<ExtractedTilemapNormalLayers>,
+    mut bind_groups: ResMut<TilemapNormalBindGroups>,
+) {
+    let bind_groups = &mut *bind_groups;
+    bind_groups.uniforms.clear();
+    bind_groups.images.clear();
+    bind_groups.values.clear();
+
+    for layer in &extracted.layers {
+        let images = [layer.tileset, layer.normal_tileset, layer.tile_data];
+        if images.iter().any(|image| gpu_images.get(*image).is_none()) {
+            continue;
+        }
+
+        let translation = layer.transform.translation();
+        let offset = bind_groups.uniforms.push(&UniformTilemapNormalLayer {
+            world_from_local: layer.transform.to_matrix()
+                * Mat4::from_scale(layer.size.extend(1.0)),
+            z: translation.z,
+            height: layer.height,
+            y: translation.y,
+        });
+
+        bind_groups
+            .values
+            .insert(layer.render_entity, (images, offset));
+    }
+
+    if bind_groups.values.is_empty() {
+        return;
+    }
+
+    bind_groups
+        .uniforms
+        .write_buffer(&render_device, &render_queue);
+
+    let Some(uniform_binding) = bind_groups.uniforms.binding() else {
+        return;
+    };
+
+    // layers with the same images share a bind group, and only differ in their uniform offset
+    for (images, _) in bind_groups.values.values() {
+        if bind_groups.images.contains_key(images) {
+            continue;
+        }
+
+        let [Some(tileset), Some(normal_tileset), Some(tile_data)] =
+            images.map(|image| gpu_images.get(image))
+        else {
+            continue;
+        };
+
+        let bind_group = render_device.create_bind_group(
+            "tilemap_normal_bind_group",
+            &pipeline_cache.get_bind_group_layout(&pipeline.layout),
+            &BindGroupEntries::sequential((
+                &tileset.texture_view,
+                &normal_tileset.texture_view,
+                &tileset.sampler,
+                &tile_data.texture_view,
+                uniform_binding.clone(),
+            )),
+        );
+
+        bind_groups.images.insert(*images, bind_group);
+    }
+}
+
+pub(crate) type DrawTilemapNormalLayer = (
+    SetItemPipeline,
+    SetSpriteViewBindGroup<0>,
+    SetTilemapNormalBindGroup<1>,
+    DrawTilemapNormalQuad,
+);
+
+pub(crate) struct SetTilemapNormalBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetTilemapNormalBindGroup<I> {
+    type Param = SRes<TilemapNormalBindGroups>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &P,
+        _view: ROQueryItem<'w, '_, Self::ViewQuery>,
+        _entity: Option<()>,
+        bind_groups: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let bind_groups = bind_groups.into_inner();
+        let Some((bind_group, offset)) = bind_groups
+            .values
+            .get(&item.entity())
+            .and_then(|(images, offset)| Some((bind_groups.images.get(images)?, *offset)))
+        else {
+            return RenderCommandResult::Skip;
+        };
+
+        pass.set_bind_group(I, bind_group, &[offset]);
+        RenderCommandResult::Success
+    }
+}
+
+pub(crate) struct DrawTilemapNormalQuad;
+impl<P: PhaseItem> RenderCommand<P> for DrawTilemapNormalQuad {
+    type Param = ();
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        _view: ROQueryItem<'w, '_, Self::ViewQuery>,
+        _entity: Option<()>,
+        _param: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        pass.draw(0..6, 0..1);
+        RenderCommandResult::Success
+    }
+}
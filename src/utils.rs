@@ -2,7 +2,7 @@ use bevy::{
     platform::collections::HashSet, prelude::*, sprite::Anchor, sprite_render::ExtractedSlice,
 };
 
-use crate::sprite::{FireflySprite, FireflySpriteImageMode};
+use crate::sprite::{CompositeSprite, FireflySprite, FireflySpriteImageMode, SpriteInstance};
 
 // use crate::sprites::stencil::ExtractedSlice;
 /// Component storing texture slices for tiled or sliced sprite entities
@@ -42,22 +42,28 @@ impl ComputedTextureSlices {
     }
 }
 
-/// Generates sprite slices for a [`Sprite`] with [`SpriteImageMode::Sliced`] or [`SpriteImageMode::Sliced`]. The slices
-/// will be computed according to the `image_handle` dimensions or the sprite rect.
+/// Generates sprite slices for a [`Sprite`] with [`SpriteImageMode::Sliced`] or [`SpriteImageMode::Sliced`], or
+/// with a [`CompositeSprite`]. The slices will be computed according to the `image_handle` dimensions or the sprite rect.
 ///
 /// Returns `None` if the image asset is not loaded
 ///
 /// # Arguments
 ///
 /// * `sprite` - The sprite component with the image handle and image mode
+/// * `composite` - The sprite's (optional) composite instances, overriding the image mode
 /// * `images` - The image assets, use to retrieve the image dimensions
 /// * `atlas_layouts` - The atlas layout assets, used to retrieve the texture atlas section rect
 #[must_use]
 fn compute_sprite_slices(
     sprite: &FireflySprite,
+    composite: Option<&CompositeSprite>,
     images: &Assets<Image>,
     atlas_layouts: &Assets<TextureAtlasLayout>,
 ) -> Option<ComputedTextureSlices> {
+    if let Some(composite) = composite {
+        return compute_instance_slices(sprite, &composite.0, atlas_layouts);
+    }
+
     let (image_size, texture_rect) = match &sprite.texture_atlas {
         Some(a) => {
             let layout = atlas_layouts.get(&a.layout)?;
@@ -95,25 +101,9 @@ fn compute_sprite_slices(
             };
             slice.tiled(*stretch_value, (*tile_x, *tile_y))
         }
+        #[allow(deprecated)]
         FireflySpriteImageMode::Instances(instances) => {
-            let layout = atlas_layouts.get(&sprite.texture_atlas.as_ref()?.layout)?;
-
-            instances
-                .iter()
-                .map(|instance| {
-                    let mut slice_rect = layout.textures.get(instance.index)?.as_rect();
-
-                    if instance.flip_x.is_some_and(|v| v) {
-                        std::mem::swap(&mut slice_rect.max.x, &mut slice_rect.min.x);
-                    }
-
-                    Some(TextureSlice {
-                        offset: instance.offset,
-                        texture_rect: slice_rect,
-                        draw_size: slice_rect.size().abs(),
-                    })
-                })
-                .collect::<Option<Vec<TextureSlice>>>()?
+            return compute_instance_slices(sprite, instances, atlas_layouts);
         }
         FireflySpriteImageMode::Auto => {
             unreachable!("Slices should not be computed for SpriteImageMode::Stretch")
@@ -125,6 +115,38 @@ fn compute_sprite_slices(
     Some(ComputedTextureSlices(slices))
 }
 
+/// Generates one slice per [`SpriteInstance`], taken from the sprite's texture atlas.
+///
+/// Returns `None` if the sprite has no texture atlas, the layout is not loaded, or an index is out of bounds.
+fn compute_instance_slices(
+    sprite: &FireflySprite,
+    instances: &[SpriteInstance],
+    atlas_layouts: &Assets<TextureAtlasLayout>,
+) -> Option<ComputedTextureSlices> {
+    let layout = atlas_layouts.get(&sprite.texture_atlas.as_ref()?.layout)?;
+
+    instances
+        .iter()
+        .map(|instance| {
+            let mut slice_rect = layout.textures.get(instance.index)?.as_rect();
+
+            if instance.flip_x.is_some_and(|v| v) {
+                std::mem::swap(&mut slice_rect.max.x, &mut slice_rect.min.x);
+            }
+            if instance.flip_y.is_some_and(|v| v) {
+                std::mem::swap(&mut slice_rect.max.y, &mut slice_rect.min.y);
+            }
+
+            Some(TextureSlice {
+                offset: instance.offset,
+                texture_rect: slice_rect,
+                draw_size: slice_rect.size().abs(),
+            })
+        })
+        .collect::<Option<Vec<TextureSlice>>>()
+        .map(ComputedTextureSlices)
+}
+
 /// System reacting to added or modified [`Image`] handles, and recompute sprite slices
 /// on sprite entities with a matching  [`SpriteImageMode`]
 pub(crate) fn compute_slices_on_asset_event(
@@ -132,7 +154,7 @@ pub(crate) fn compute_slices_on_asset_event(
     mut events: MessageReader<AssetEvent<Image>>,
     images: Res<Assets<Image>>,
     atlas_layouts: Res<Assets<TextureAtlasLayout>>,
-    sprites: Query<(Entity, &FireflySprite)>,
+    sprites: Query<(Entity, &FireflySprite, Option<&CompositeSprite>)>,
 ) {
     // We store the asset ids of added/modified image assets
     let added_handles: HashSet<_> = events
@@ -146,34 +168,56 @@ pub(crate) fn compute_slices_on_asset_event(
         return;
     }
     // We recompute the sprite slices for sprite entities with a matching asset handle id
-    for (entity, sprite) in &sprites {
-        if !sprite.image_mode.uses_slices() {
+    for (entity, sprite, composite) in &sprites {
+        if composite.is_none() && !sprite.image_mode.uses_slices() {
             continue;
         }
         if !added_handles.contains(&sprite.image.id()) {
             continue;
         }
-        if let Some(slices) = compute_sprite_slices(sprite, &images, &atlas_layouts) {
+        if let Some(slices) = compute_sprite_slices(sprite, composite, &images, &atlas_layouts) {
             commands.entity(entity).insert(slices);
         }
     }
 }
 
-/// System reacting to changes on the [`Sprite`] component to compute the sprite slices
+/// System reacting to changes on the [`Sprite`] and [`CompositeSprite`] components to compute the sprite slices
 pub(crate) fn compute_slices_on_sprite_change(
     mut commands: Commands,
     images: Res<Assets<Image>>,
     atlas_layouts: Res<Assets<TextureAtlasLayout>>,
-    changed_sprites: Query<(Entity, &FireflySprite), Changed<FireflySprite>>,
+    changed_sprites: Query<
+        (Entity, &FireflySprite, Option<&CompositeSprite>),
+        Or<(Changed<FireflySprite>, Changed<CompositeSprite>)>,
+    >,
+    sprites: Query<&FireflySprite>,
+    mut removed_composites: RemovedComponents<CompositeSprite>,
 ) {
-    for (entity, sprite) in &changed_sprites {
-        if !sprite.image_mode.uses_slices() {
+    for (entity, sprite, composite) in &changed_sprites {
+        if composite.is_none() && !sprite.image_mode.uses_slices() {
             continue;
         }
-        if let Some(slices) = compute_sprite_slices(sprite, &images, &atlas_layouts) {
+        if let Some(slices) = compute_sprite_slices(sprite, composite, &images, &atlas_layouts) {
             commands.entity(entity).insert(slices);
         }
     }
+
+    // Fall back to the sprite's own image mode once its composite is removed
+    for entity in removed_composites.read() {
+        let Ok(sprite) = sprites.get(entity) else {
+            continue;
+        };
+        match sprite.image_mode.uses_slices() {
+            true => {
+                if let Some(slices) = compute_sprite_slices(sprite, None, &images, &atlas_layouts) {
+                    commands.entity(entity).insert(slices);
+                }
+            }
+            false => {
+                commands.entity(entity).remove::<ComputedTextureSlices>();
+            }
+        }
+    }
 }
 
 /// Scales a texture to fit within a given quad size with keeping the aspect ratio.
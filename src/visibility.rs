@@ -3,19 +3,32 @@
 //! for instance occluders can be off-screen and still visible because they can block light
 //! that would be otherwise visible on-screen.
 
-use std::any::TypeId;
+use std::{any::TypeId, f32::consts::TAU};
 
 use bevy::{
-    camera::visibility::{SetViewVisibility, VisibilitySystems, VisibleEntities, check_visibility},
+    camera::{
+        CameraUpdateSystems,
+        primitives::Aabb,
+        visibility::{SetViewVisibility, VisibilitySystems, VisibleEntities, check_visibility},
+    },
+    ecs::system::SystemParam,
     math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume},
     prelude::*,
+    transform::TransformSystems,
 };
 
 use crate::{
-    data::FireflyConfig,
-    lights::{LightHeight, PointLight2d},
-    occluders::Occluder2dShape,
+    data::{FireflyConfig, FireflyTestClock, ShadowCullingMode},
+    directional::DirectionalLight2d,
+    lights::{
+        ImportantLight, LightGroup, LightGroups, LightHeight, LightModulator, MovingLight,
+        PointLight2d,
+    },
+    line::LineLightSegment,
+    occluder_batch::{AabbTree, OccluderBatch, batch_isometry},
+    occluders::{Occluder2dShape, point_inside_poly},
     prelude::Occluder2d,
+    raycast::OccluderRaycast,
 };
 
 /// Timer that starts ticking down when an entity no longer affects
@@ -50,8 +63,10 @@ pub struct VisibilityPlugin;
 impl Plugin for VisibilityPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<LightRect>();
+        app.register_type::<ComputedLightVisibility>();
+        app.register_type::<ComputedLightBounds>();
 
-        app.add_systems(Update, occluder_aabb);
+        app.add_systems(Update, (occluder_aabb, occluder_batch_aabb));
 
         app.add_systems(
             PostUpdate,
@@ -60,6 +75,18 @@ impl Plugin for VisibilityPlugin {
                 .in_set(VisibilitySystems::CheckVisibility)
                 .after(check_visibility),
         );
+
+        app.add_systems(
+            PostUpdate,
+            compute_light_visibility.after(TransformSystems::Propagate),
+        );
+
+        app.add_systems(
+            PostUpdate,
+            compute_light_bounds
+                .after(TransformSystems::Propagate)
+                .after(CameraUpdateSystems),
+        );
     }
 }
 
@@ -72,71 +99,190 @@ fn mark_visible_lights(
         &GlobalTransform,
         &PointLight2d,
         &LightHeight,
+        Option<&MovingLight>,
+        Option<&LineLightSegment>,
+        Option<&LightModulator>,
+        Has<ImportantLight>,
+        Has<DirectionalLight2d>,
+        Option<&LightGroup>,
         &mut ViewVisibility,
         &mut VisibilityTimer,
     )>,
-    mut cameras: Query<(&GlobalTransform, &mut VisibleEntities, &Projection), With<FireflyConfig>>,
+    mut cameras: Query<(
+        &GlobalTransform,
+        &mut VisibleEntities,
+        &FireflyConfig,
+        &Camera,
+    )>,
     mut light_rect: ResMut<LightRect>,
+    groups: Res<LightGroups>,
     time: Res<Time>,
+    test_clock: Option<Res<FireflyTestClock>>,
 ) {
+    let delta = test_clock.map_or(time.delta(), |clock| clock.delta);
+
     let mut camera_rects = cameras
         .iter_mut()
         .filter_map(|camera| {
-            let Projection::Orthographic(projection) = camera.2 else {
+            // not computed until the camera's first update
+            let clip_from_view = camera.3.clip_from_view();
+            if clip_from_view.determinant() == 0. {
                 return None;
-            };
+            }
+
+            let camera_rect = view_world_rect(camera.0.to_matrix() * clip_from_view.inverse());
+
             Some((
                 Aabb2d {
-                    min: projection.area.min + camera.0.translation().truncate(),
-                    max: projection.area.max + camera.0.translation().truncate(),
-                },
-                Rect {
-                    min: projection.area.min + camera.0.translation().truncate(),
-                    max: projection.area.max + camera.0.translation().truncate(),
+                    min: camera_rect.min,
+                    max: camera_rect.max,
                 },
+                camera_rect,
                 camera.1,
+                camera.2.max_lights,
+                camera.2.shadow_culling,
+                Vec::<(Entity, f32)>::new(),
             ))
         })
         .collect::<Vec<_>>();
 
     light_rect.0 = Rect::EMPTY;
 
-    for (entity, transform, light, height, mut visibility, mut visibility_timer) in &mut lights {
-        let pos = transform.translation().truncate() - vec2(0.0, height.0) + light.offset.xy();
+    for (
+        entity,
+        transform,
+        light,
+        height,
+        moving,
+        segment,
+        modulator,
+        important,
+        directional,
+        group,
+        mut visibility,
+        mut visibility_timer,
+    ) in &mut lights
+    {
+        let z = transform.translation().z + light.offset.z;
+        let pos =
+            transform.translation().truncate() - vec2(0.0, height.height(z)) + light.offset.xy();
+
+        // moving lights also light up the capsule swept behind them, and line lights the one around their segment
+        let streak = segment.map_or(
+            moving.map_or(Vec2::ZERO, |moving| moving.streak()),
+            |segment| segment.vector,
+        );
+        let radius = modulator.map_or(light.radius, |modulator| {
+            modulator.modulate_range(light.radius)
+        });
+        let light_bounds = Rect {
+            min: pos.min(pos + streak) - radius,
+            max: pos.max(pos + streak) + radius,
+        };
 
         let light_aabb = Aabb2d {
-            min: pos - light.radius,
-            max: pos + light.radius,
+            min: light_bounds.min,
+            max: light_bounds.max,
         };
 
-        for (camera_aabb, camera_rect, visible_entities) in camera_rects.iter_mut() {
-            if light_aabb.intersects(camera_aabb) {
+        // lights of disabled groups are culled, but their visibility timer keeps running
+        let enabled = group.is_none_or(|group| groups.resolve(&group.0).enabled);
+
+        for (camera_aabb, camera_rect, visible_entities, max_lights, shadow_culling, candidates) in
+            camera_rects.iter_mut()
+        {
+            if enabled && light_aabb.intersects(camera_aabb) {
                 if !visibility.get() {
                     visibility.set_visible();
                     *visibility_timer = default();
                 }
 
-                let visible_lights = visible_entities.get_mut(TypeId::of::<PointLight2d>());
-                visible_lights.push(entity);
+                // important and directional lights don't count towards the budget
+                match max_lights {
+                    Some(_) if !important && !directional => {
+                        candidates.push((entity, camera_rect.center().distance_squared(pos)))
+                    }
+                    _ => visible_entities
+                        .get_mut(TypeId::of::<PointLight2d>())
+                        .push(entity),
+                }
+
+                // occluders within the culling margin of the view can cast shadows into it
+                let margin = match shadow_culling {
+                    ShadowCullingMode::Conservative => {
+                        light.culling_margin.unwrap_or(radius).max(0.)
+                    }
+                    ShadowCullingMode::Aggressive => 0.,
+                };
 
-                light_rect.0 = light_rect
-                    .0
-                    .union(camera_rect.union_point(pos).intersect(Rect {
-                        min: pos - light.radius,
-                        max: pos + light.radius,
-                    }));
+                // directional lights are far away, their shadows only reach as far as their margin
+                light_rect.0 = light_rect.0.union(
+                    match directional {
+                        true => camera_rect.inflate(margin),
+                        false => camera_rect.inflate(margin).union_point(pos),
+                    }
+                    .intersect(light_bounds),
+                );
             }
         }
 
-        visibility_timer.0.tick(time.delta());
+        visibility_timer.0.tick(delta);
     }
+
+    // only keep the lights closest to the camera
+    for (_, _, visible_entities, max_lights, _, candidates) in camera_rects.iter_mut() {
+        let Some(max_lights) = *max_lights else {
+            continue;
+        };
+
+        candidates.sort_unstable_by(|(_, a), (_, b)| a.total_cmp(b));
+        candidates.truncate(max_lights as usize);
+
+        visible_entities
+            .get_mut(TypeId::of::<PointLight2d>())
+            .extend(candidates.iter().map(|(entity, _)| *entity));
+    }
+}
+
+/// How far the view of a tilted perspective camera extends towards the horizon, in world units.
+const MAX_VIEW_DISTANCE: f32 = 10000.;
+
+/// World-space bounds of the area seen through a view on the z = 0 plane, given its `world_from_clip` matrix.
+///
+/// This covers every [scaling mode](bevy::camera::ScalingMode) and viewport origin of orthographic projections,
+/// rotated and scaled cameras, as well as perspective and custom projections looking down at the plane at an angle.
+pub(crate) fn view_world_rect(world_from_clip: Mat4) -> Rect {
+    [vec2(-1., -1.), vec2(1., -1.), vec2(-1., 1.), vec2(1., 1.)]
+        .into_iter()
+        .map(|corner| ndc_to_ground(world_from_clip, corner))
+        .fold(Rect::EMPTY, |rect, corner| rect.union_point(corner))
+}
+
+/// The point on the z = 0 plane seen at the given ndc position.
+///
+/// Rays that don't reach the plane, such as the ones above the horizon of a tilted perspective camera,
+/// are cut off at [`MAX_VIEW_DISTANCE`].
+pub(crate) fn ndc_to_ground(world_from_clip: Mat4, ndc: Vec2) -> Vec2 {
+    let near = world_from_clip.project_point3(ndc.extend(1.));
+    let dir = world_from_clip.project_point3(ndc.extend(0.5)) - near;
+
+    let t = -near.z / dir.z;
+    if t.is_finite() && t >= 0. {
+        let hit = (near + dir * t).truncate();
+        return near.truncate() + (hit - near.truncate()).clamp_length_max(MAX_VIEW_DISTANCE);
+    }
+
+    near.truncate() + dir.truncate().normalize_or_zero() * MAX_VIEW_DISTANCE
 }
 
 fn mark_visible_occluders(
     mut occluders: Query<(&OccluderAabb, &mut ViewVisibility, &mut VisibilityTimer)>,
     light_rect: Res<LightRect>,
     time: Res<Time>,
+    test_clock: Option<Res<FireflyTestClock>>,
 ) {
+    let delta = test_clock.map_or(time.delta(), |clock| clock.delta);
+
     let light_rect_aabb = Aabb2d {
         min: light_rect.0.min,
         max: light_rect.0.max,
@@ -152,7 +298,7 @@ fn mark_visible_occluders(
             *visibility_timer = default();
         }
 
-        visibility_timer.0.tick(time.delta());
+        visibility_timer.0.tick(delta);
     }
 }
 
@@ -168,21 +314,877 @@ fn occluder_aabb(
             translation: transform.translation().truncate() + occluder.offset.truncate(),
         };
 
-        rect.0 = match occluder.shape() {
-            Occluder2dShape::RoundRectangle {
-                half_width,
-                half_height,
-                radius,
-            } => Aabb2d {
-                min: vec2(-half_width, -half_height) - radius,
-                max: vec2(*half_width, *half_height) + radius,
+        rect.0 = shape_aabb(occluder.shape(), isometry);
+    }
+}
+
+fn occluder_batch_aabb(
+    mut batches: Query<
+        (&OccluderBatch, &GlobalTransform, &mut OccluderAabb),
+        Or<(Changed<GlobalTransform>, Changed<OccluderBatch>)>,
+    >,
+) {
+    for (batch, transform, mut rect) in &mut batches {
+        let isometry = batch_isometry(transform);
+        rect.0 = batch
+            .aabb()
+            .transformed_by(isometry.translation, isometry.rotation);
+    }
+}
+
+/// Computes the bounding box of an occluder shape placed with the given isometry.
+pub(crate) fn shape_aabb(shape: &Occluder2dShape, isometry: Isometry2d) -> Aabb2d {
+    match shape {
+        Occluder2dShape::RoundRectangle {
+            half_width,
+            half_height,
+            radius,
+        } => Aabb2d {
+            min: vec2(-half_width, -half_height) - radius,
+            max: vec2(*half_width, *half_height) + radius,
+        }
+        .transformed_by(isometry.translation, isometry.rotation),
+
+        Occluder2dShape::Polygon { vertices, .. } => Aabb2d::from_point_cloud(isometry, vertices),
+        Occluder2dShape::Polyline { vertices } => Aabb2d::from_point_cloud(isometry, vertices),
+    }
+}
+
+/// [`SystemParam`] for checking, in the Main World, whether something is currently lit by any [`PointLight2d`].
+///
+/// A point is lit if it's inside a light's [radius](PointLight2d::radius) and [angle](PointLight2d::angle), and
+/// the line between the two isn't fully blocked by occluders. Semi-transparent occluders in the way reduce the
+/// light that reaches the point, but don't block it completely.
+///
+/// This is computed on the CPU from the lights' and occluders' shapes, so it doesn't take into account
+/// things that only exist on the GPU, such as [sprite occlusion](PointLight2d::sprite_occlusion) or soft shadows.
+///
+/// # Example
+///
+/// ```
+/// fn burn_vampires(vampires: Query<Entity, With<Vampire>>, lit: LitVisibility) {
+///     for vampire in &vampires {
+///         if lit.is_entity_lit(vampire) {
+///             // ..
+///         }
+///     }
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct LitVisibility<'w, 's> {
+    lights: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GlobalTransform,
+            &'static PointLight2d,
+            Option<&'static LightModulator>,
+            Option<&'static LightGroup>,
+        ),
+    >,
+    groups: Res<'w, LightGroups>,
+    occluders: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GlobalTransform,
+            &'static Occluder2d,
+            &'static OccluderAabb,
+        ),
+    >,
+    batches: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GlobalTransform,
+            &'static OccluderBatch,
+            &'static OccluderAabb,
+        ),
+    >,
+    bounds: Query<'w, 's, (&'static GlobalTransform, Option<&'static Aabb>)>,
+}
+
+/// A light as it's rendered, with its [modulator](LightModulator) and [group](LightGroup) applied.
+struct LitLight<'a> {
+    entity: Entity,
+    pos: Vec2,
+    dir: Vec2,
+    light: &'a PointLight2d,
+    radius: f32,
+    intensity: f32,
+}
+
+impl LitLight<'_> {
+    /// Intensity of the light at the point, before occluders.
+    fn intensity_at(&self, point: Vec2) -> f32 {
+        self.intensity
+            * self
+                .light
+                .falloff
+                .evaluate(point.distance(self.pos) / self.radius)
+    }
+}
+
+impl LitVisibility<'_, '_> {
+    /// Returns how much light reaches the point from the brightest light, between 0 and 1.
+    ///
+    /// This is 1 if a light reaches the point unobstructed, 0 if no light reaches it, and in-between
+    /// if the only lights that reach it pass through semi-transparent occluders.
+    pub fn light_at(&self, point: Vec2) -> f32 {
+        self.light_at_ignoring(point, None)
+    }
+
+    /// Returns the total light reaching the point, summed over all lights.
+    ///
+    /// Each light contributes its [intensity](PointLight2d::intensity), attenuated the same way as in
+    /// [`entity_illumination`](LitVisibility::entity_illumination).
+    pub fn illumination_at(&self, point: Vec2) -> f32 {
+        self.lights()
+            .filter_map(|light| {
+                let transmission = self.reach(&light, point, None)?;
+                Some(light.intensity_at(point) * transmission)
+            })
+            .sum()
+    }
+
+    /// Returns true if any light reaches the point.
+    pub fn is_point_lit(&self, point: Vec2) -> bool {
+        self.light_at(point) > 0.
+    }
+
+    /// Returns how much light reaches each of the points, in the same order, computed the same way as [`light_at`](LitVisibility::light_at).
+    ///
+    /// Meant for systems that query many points every frame, e.g. to muffle sounds coming from dark areas. The lights
+    /// are indexed once for the whole batch, so each point is only tested against the lights whose range contains it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// fn muffle_sounds(mut emitters: Query<(&GlobalTransform, &mut AudioSink)>, lit: LitVisibility) {
+    ///     let points = emitters.iter().map(|(transform, _)| transform.translation().xy()).collect::<Vec<_>>();
+    ///     let light = lit.are_points_lit(&points);
+    ///
+    ///     for ((_, mut sink), light) in emitters.iter_mut().zip(light) {
+    ///         sink.set_volume(Volume::Linear(0.3 + 0.7 * light));
+    ///     }
+    /// }
+    /// ```
+    pub fn are_points_lit(&self, points: &[Vec2]) -> Vec<f32> {
+        let lights = self
+            .lights()
+            .filter(|light| light.intensity > 0.)
+            .collect::<Vec<_>>();
+
+        let aabbs = lights
+            .iter()
+            .map(|light| Aabb2d::new(light.pos, Vec2::splat(light.radius.max(0.))))
+            .collect::<Vec<_>>();
+        let tree = AabbTree::new(&aabbs);
+
+        points
+            .iter()
+            .map(|point| {
+                let mut res = 0f32;
+                tree.query(&Aabb2d::new(*point, Vec2::ZERO), |i| {
+                    if res >= 1. {
+                        return;
+                    }
+
+                    if let Some(transmission) = self.reach(&lights[i], *point, None) {
+                        res = res.max(transmission);
+                    }
+                });
+                res
+            })
+            .collect()
+    }
+
+    /// Returns true if any light reaches any part of the bounding box.
+    ///
+    /// The box is sampled at its center and corners.
+    pub fn is_lit(&self, aabb: Aabb2d) -> bool {
+        self.is_lit_ignoring(aabb, None)
+    }
+
+    /// Returns true if any light reaches any part of the entity.
+    ///
+    /// The entity's bounds are taken from its [`Aabb`] (computed automatically by Bevy for sprites and meshes),
+    /// or from its position if it has none. If the entity is an occluder itself, its own shapes are ignored.
+    ///
+    /// Returns false if the entity doesn't exist or has no [`GlobalTransform`].
+    pub fn is_entity_lit(&self, entity: Entity) -> bool {
+        let Some(aabb) = self.entity_aabb(entity) else {
+            return false;
+        };
+
+        self.is_lit_ignoring(aabb, Some(entity))
+    }
+
+    /// Returns every light that reaches the entity, along with the intensity it's lit with.
+    ///
+    /// The intensity is the light's [intensity](PointLight2d::intensity), attenuated by its [falloff](PointLight2d::falloff)
+    /// and by any semi-transparent occluders in the way, at the brightest of the points sampled on the entity's bounds
+    /// (see [`is_entity_lit`](LitVisibility::is_entity_lit)).
+    pub fn entity_illumination(&self, entity: Entity) -> Vec<(Entity, f32)> {
+        let Some(aabb) = self.entity_aabb(entity) else {
+            return default();
+        };
+
+        self.lights()
+            .filter_map(|light| {
+                let intensity = sample_points(aabb)
+                    .iter()
+                    .filter_map(|point| {
+                        let transmission = self.reach(&light, *point, Some(entity))?;
+                        Some(light.intensity_at(*point) * transmission)
+                    })
+                    .reduce(f32::max)?;
+
+                (intensity > 0.).then_some((light.entity, intensity))
+            })
+            .collect()
+    }
+
+    /// Returns the total light reaching the entity, summed over all lights and averaged over the points
+    /// sampled on its bounds (see [`is_entity_lit`](LitVisibility::is_entity_lit)).
+    ///
+    /// Each light contributes its [intensity](PointLight2d::intensity), attenuated the same way as in
+    /// [`entity_illumination`](LitVisibility::entity_illumination).
+    pub fn entity_average_illumination(&self, entity: Entity) -> f32 {
+        let Some(aabb) = self.entity_aabb(entity) else {
+            return 0.;
+        };
+
+        let points = sample_points(aabb);
+
+        let total: f32 = self
+            .lights()
+            .map(|light| {
+                points
+                    .iter()
+                    .filter_map(|point| {
+                        let transmission = self.reach(&light, *point, Some(entity))?;
+                        Some(light.intensity_at(*point) * transmission)
+                    })
+                    .sum::<f32>()
+            })
+            .sum();
+
+        total / points.len() as f32
+    }
+
+    fn entity_aabb(&self, entity: Entity) -> Option<Aabb2d> {
+        let (transform, aabb) = self.bounds.get(entity).ok()?;
+
+        Some(match aabb {
+            Some(aabb) => {
+                let center = transform.transform_point(aabb.center.into()).truncate();
+                let half_size =
+                    (transform.affine().matrix3.abs() * Vec3::from(aabb.half_extents)).truncate();
+                Aabb2d::new(center, half_size)
+            }
+            None => Aabb2d::new(transform.translation().truncate(), Vec2::ZERO),
+        })
+    }
+
+    fn is_lit_ignoring(&self, aabb: Aabb2d, ignored: Option<Entity>) -> bool {
+        sample_points(aabb)
+            .iter()
+            .any(|point| self.light_at_ignoring(*point, ignored) > 0.)
+    }
+
+    fn light_at_ignoring(&self, point: Vec2, ignored: Option<Entity>) -> f32 {
+        let mut res = 0f32;
+
+        for light in self.lights() {
+            if let Some(transmission) = self.reach(&light, point, ignored) {
+                res = res.max(transmission);
+            }
+
+            if res >= 1. {
+                break;
+            }
+        }
+
+        res
+    }
+
+    /// The lights as they're rendered, with their [modulators](LightModulator) and [groups](LightGroup) applied.
+    /// Lights of disabled groups are skipped.
+    fn lights(&self) -> impl Iterator<Item = LitLight<'_>> {
+        self.lights
+            .iter()
+            .filter_map(|(entity, transform, light, modulator, group)| {
+                let group_intensity = match group {
+                    Some(group) => {
+                        let settings = self.groups.resolve(&group.0);
+                        if !settings.enabled {
+                            return None;
+                        }
+                        settings.intensity
+                    }
+                    None => 1.,
+                };
+
+                Some(LitLight {
+                    entity,
+                    pos: transform.translation().truncate() + light.offset.xy(),
+                    dir: (transform.rotation() * Vec3::Y).xy(),
+                    light,
+                    radius: modulator.map_or(light.radius, |modulator| {
+                        modulator.modulate_range(light.radius)
+                    }),
+                    intensity: modulator.map_or(light.intensity, |modulator| {
+                        modulator.modulate_intensity(light.intensity)
+                    }) * group_intensity,
+                })
+            })
+    }
+
+    /// Fraction of the light that reaches the point, or None if the point is outside of the light's range.
+    fn reach(&self, light: &LitLight, point: Vec2, ignored: Option<Entity>) -> Option<f32> {
+        if light.intensity <= 0. {
+            return None;
+        }
+
+        let offset = point - light.pos;
+
+        if offset.length_squared() > light.radius * light.radius {
+            return None;
+        }
+
+        let angle = light.light.angle;
+        if angle.outer < 360. && offset != Vec2::ZERO {
+            let degrees = light.dir.angle_to(offset).abs().to_degrees();
+            if degrees > (angle.outer + angle.feather) / 2. {
+                return None;
+            }
+        }
+
+        if !light.light.cast_shadows {
+            return Some(1.);
+        }
+
+        Some(self.transmission(light.pos, point, ignored))
+    }
+
+    /// Fraction of light that passes through the occluders between the two points.
+    fn transmission(&self, from: Vec2, to: Vec2, ignored: Option<Entity>) -> f32 {
+        let segment = Aabb2d {
+            min: from.min(to),
+            max: from.max(to),
+        };
+
+        let mut res = 1.;
+
+        for (entity, transform, occluder, aabb) in &self.occluders {
+            if Some(entity) == ignored || !aabb.0.intersects(&segment) {
+                continue;
+            }
+
+            let isometry = Isometry2d {
+                rotation: Rot2::radians(transform.rotation().to_euler(EulerRot::XYZ).2),
+                translation: transform.translation().truncate() + occluder.offset.truncate(),
+            };
+
+            if segment_hit(occluder.shape(), isometry, from, to).is_some() {
+                res *= 1. - occluder.opacity.clamp(0., 1.);
+                if res <= 0. {
+                    return 0.;
+                }
+            }
+        }
+
+        for (entity, transform, batch, aabb) in &self.batches {
+            if Some(entity) == ignored || !aabb.0.intersects(&segment) {
+                continue;
+            }
+
+            let batch_isometry = batch_isometry(transform);
+            let inverse = batch_isometry.inverse();
+            let local = segment.transformed_by(inverse.translation, inverse.rotation);
+
+            let occluders = batch.occluders();
+            batch.data().tree().query(&local, |i| {
+                let occluder = &occluders[i];
+                let isometry = Isometry2d::new(
+                    batch_isometry.transform_point(occluder.offset.truncate()),
+                    batch_isometry.rotation,
+                );
+
+                if segment_hit(occluder.shape(), isometry, from, to).is_some() {
+                    res *= 1. - occluder.opacity.clamp(0., 1.);
+                }
+            });
+
+            if res <= 0. {
+                return 0.;
             }
-            .transformed_by(isometry.translation, isometry.rotation),
+        }
+
+        res
+    }
+}
+
+/// Points of a bounding box that are checked for light: its center and corners.
+fn sample_points(aabb: Aabb2d) -> [Vec2; 5] {
+    [
+        aabb.center(),
+        aabb.min,
+        aabb.max,
+        vec2(aabb.min.x, aabb.max.y),
+        vec2(aabb.max.x, aabb.min.y),
+    ]
+}
+
+/// Returns the fraction (between 0 and 1) along the segment at which it first touches the shape, if it does.
+///
+/// If the segment starts inside the shape, this returns 0.
+pub(crate) fn segment_hit(
+    shape: &Occluder2dShape,
+    isometry: Isometry2d,
+    from: Vec2,
+    to: Vec2,
+) -> Option<f32> {
+    match shape {
+        Occluder2dShape::RoundRectangle {
+            half_width,
+            half_height,
+            radius,
+        } => {
+            // a round rectangle is the union of two rectangles and four circles
+            let inverse = isometry.inverse();
+            let from = inverse.transform_point(from);
+            let to = inverse.transform_point(to);
+            let half_size = vec2(*half_width, *half_height);
 
-            Occluder2dShape::Polygon { vertices, .. } => {
-                Aabb2d::from_point_cloud(isometry, vertices)
+            let mut hits = vec![
+                segment_box_hit(from, to, half_size + vec2(*radius, 0.)),
+                segment_box_hit(from, to, half_size + vec2(0., *radius)),
+            ];
+
+            if *radius > 0. {
+                for corner in [
+                    half_size,
+                    -half_size,
+                    vec2(half_size.x, -half_size.y),
+                    vec2(-half_size.x, half_size.y),
+                ] {
+                    hits.push(segment_circle_hit(from, to, corner, *radius));
+                }
+            }
+
+            hits.into_iter().flatten().reduce(f32::min)
+        }
+        Occluder2dShape::Polygon { concave, .. } => {
+            let vertices = shape.vertices(isometry.translation, isometry.rotation);
+            let aabb = Aabb2d::from_point_cloud(Isometry2d::IDENTITY, &vertices);
+
+            if point_inside_poly(from, &vertices, aabb, *concave) {
+                return Some(0.);
             }
-            Occluder2dShape::Polyline { vertices } => Aabb2d::from_point_cloud(isometry, vertices),
+
+            let n = vertices.len();
+            (0..n)
+                .filter_map(|i| segment_segment_hit(from, to, vertices[i], vertices[(i + 1) % n]))
+                .reduce(f32::min)
+        }
+        Occluder2dShape::Polyline { .. } => {
+            let vertices = shape.vertices(isometry.translation, isometry.rotation);
+
+            vertices
+                .windows(2)
+                .filter_map(|line| segment_segment_hit(from, to, line[0], line[1]))
+                .reduce(f32::min)
         }
     }
 }
+
+fn segment_segment_hit(from: Vec2, to: Vec2, a: Vec2, b: Vec2) -> Option<f32> {
+    let d = to - from;
+    let e = b - a;
+    let denom = d.perp_dot(e);
+
+    if denom == 0. {
+        return None;
+    }
+
+    let t = (a - from).perp_dot(e) / denom;
+    let u = (a - from).perp_dot(d) / denom;
+
+    ((0. ..=1.).contains(&t) && (0. ..=1.).contains(&u)).then_some(t)
+}
+
+fn segment_box_hit(from: Vec2, to: Vec2, half_size: Vec2) -> Option<f32> {
+    let d = to - from;
+    let mut t_min = 0f32;
+    let mut t_max = 1f32;
+
+    for axis in 0..2 {
+        if d[axis] == 0. {
+            if from[axis].abs() > half_size[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let t1 = (-half_size[axis] - from[axis]) / d[axis];
+        let t2 = (half_size[axis] - from[axis]) / d[axis];
+
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+
+        if t_min > t_max {
+            return None;
+        }
+    }
+
+    Some(t_min)
+}
+
+fn segment_circle_hit(from: Vec2, to: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let d = to - from;
+    let f = from - center;
+
+    let c = f.length_squared() - radius * radius;
+    if c <= 0. {
+        return Some(0.);
+    }
+
+    let a = d.length_squared();
+    if a == 0. {
+        return None;
+    }
+
+    let b = f.dot(d);
+    let discriminant = b * b - a * c;
+    if discriminant < 0. {
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / a;
+    (0. ..=1.).contains(&t).then_some(t)
+}
+
+/// Component that stores the visibility polygon of a [`PointLight2d`]: the area the light reaches before being
+/// blocked by occluders.
+///
+/// Add it to a light to have the polygon computed once per frame, so gameplay queries (e.g. "is the player inside the
+/// guard's flashlight?"), [gizmos](crate::prelude::FireflyGizmosPlugin) and your own effects can all share it instead of
+/// each sweeping the occluders again.
+///
+/// The polygon is built by casting [rays](ComputedLightVisibility::rays) against the occluders with [`OccluderRaycast`],
+/// so any occluder blocks the light regardless of its [opacity](Occluder2d::opacity). Just like [`LitVisibility`], it
+/// doesn't take into account things that only exist on the GPU, such as soft shadows.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((PointLight2d::default(), ComputedLightVisibility::new(256)));
+///
+/// fn spotted(flashlight: Single<&ComputedLightVisibility>, player: Single<&Transform, With<Player>>) -> bool {
+///     flashlight.contains(player.translation.xy())
+/// }
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+pub struct ComputedLightVisibility {
+    /// Number of rays cast over a full circle. Lights with a narrower [angle](PointLight2d::angle) cast
+    /// proportionally fewer rays.
+    ///
+    /// **Performance Impact:** High, each ray is tested against every nearby occluder.
+    ///
+    /// **Default:** 128.
+    pub rays: u32,
+
+    #[reflect(ignore)]
+    origin: Vec2,
+    #[reflect(ignore)]
+    start_angle: f32,
+    #[reflect(ignore)]
+    step: f32,
+    #[reflect(ignore)]
+    full_circle: bool,
+    #[reflect(ignore)]
+    distances: Vec<f32>,
+}
+
+impl Default for ComputedLightVisibility {
+    fn default() -> Self {
+        Self {
+            rays: 128,
+            origin: Vec2::ZERO,
+            start_angle: 0.,
+            step: 0.,
+            full_circle: true,
+            distances: vec![],
+        }
+    }
+}
+
+impl ComputedLightVisibility {
+    /// Construct a new visibility polygon with the given number of [rays](ComputedLightVisibility::rays).
+    pub fn new(rays: u32) -> Self {
+        Self { rays, ..default() }
+    }
+
+    /// Position of the light the polygon was computed from.
+    pub fn origin(&self) -> Vec2 {
+        self.origin
+    }
+
+    /// Returns the vertices of the polygon in world space.
+    ///
+    /// For lights with an [angle](PointLight2d::angle) under 360 degrees, the polygon starts and ends at the light's position.
+    pub fn polygon(&self) -> Vec<Vec2> {
+        let points = self.distances.iter().enumerate().map(|(i, distance)| {
+            self.origin + Vec2::from_angle(self.start_angle + i as f32 * self.step) * *distance
+        });
+
+        match self.full_circle {
+            true => points.collect(),
+            false => [self.origin]
+                .into_iter()
+                .chain(points)
+                .chain([self.origin])
+                .collect(),
+        }
+    }
+
+    /// Returns true if the point is inside the polygon, meaning the light reaches it.
+    pub fn contains(&self, point: Vec2) -> bool {
+        if self.distances.is_empty() || self.step <= 0. {
+            return false;
+        }
+
+        let offset = point - self.origin;
+        let angle = (offset.to_angle() - self.start_angle).rem_euclid(TAU);
+
+        let n = self.distances.len();
+        let last = match self.full_circle {
+            true => n,
+            false => n - 1,
+        };
+
+        // outside of the light's cone
+        if !self.full_circle && angle > last as f32 * self.step {
+            return false;
+        }
+
+        let x = angle / self.step;
+        let i = (x.floor() as usize).min(last.saturating_sub(1));
+        let a = self.distances[i];
+        let b = self.distances[(i + 1) % n];
+
+        offset.length() <= a.lerp(b, (x - i as f32).clamp(0., 1.))
+    }
+}
+
+fn compute_light_visibility(
+    mut lights: Query<(
+        &GlobalTransform,
+        &PointLight2d,
+        Option<&LightModulator>,
+        Option<&LightGroup>,
+        &mut ComputedLightVisibility,
+    )>,
+    groups: Res<LightGroups>,
+    raycast: OccluderRaycast,
+) {
+    for (transform, light, modulator, group, mut visibility) in &mut lights {
+        let visibility = visibility.bypass_change_detection();
+
+        // lights of disabled groups don't reach anything
+        let enabled = group.is_none_or(|group| groups.resolve(&group.0).enabled);
+        let radius = match enabled {
+            true => modulator.map_or(light.radius, |modulator| {
+                modulator.modulate_range(light.radius)
+            }),
+            false => 0.,
+        };
+
+        let origin = transform.translation().truncate() + light.offset.xy();
+        let outer = (light.angle.outer + light.angle.feather).clamp(0., 360.);
+
+        let (start_angle, step, n, full_circle) = if outer >= 360. {
+            let n = visibility.rays.max(3);
+            (0., TAU / n as f32, n, true)
+        } else {
+            // same direction as the one extracted for the shader
+            let dir = (transform.rotation() * Vec3::Y).xy().normalize_or(Vec2::Y);
+            let span = outer.to_radians();
+            let n = ((visibility.rays as f32 * outer / 360.).ceil() as u32).max(2);
+            (dir.to_angle() - span / 2., span / n as f32, n + 1, false)
+        };
+
+        visibility.origin = origin;
+        visibility.start_angle = start_angle;
+        visibility.step = step;
+        visibility.full_circle = full_circle;
+        visibility.distances.clear();
+
+        for i in 0..n {
+            let dir = Dir2::new_unchecked(Vec2::from_angle(start_angle + i as f32 * step));
+
+            let distance = match light.cast_shadows {
+                true => raycast
+                    .cast_ray(origin, dir, radius)
+                    .map_or(radius, |hit| hit.distance),
+                false => radius,
+            };
+
+            visibility.distances.push(distance);
+        }
+    }
+}
+
+/// Component that stores the screen-space bounds of a [`PointLight2d`]'s influence, after culling by the camera's view.
+///
+/// Add it to a light to have its bounds computed once per frame, so UI can anchor indicators (e.g. "you are seen!" or
+/// an interaction prompt) to the visible extent of the light. The bounds cover the light's [radius](PointLight2d::radius),
+/// narrowed to its [angle](PointLight2d::angle), and don't take occluders into account.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((PointLight2d::default(), ComputedLightBounds::default()));
+///
+/// fn anchor_prompt(lamp: Single<&ComputedLightBounds>, mut prompt: Single<&mut Node, With<Prompt>>) {
+///     if let Some(rect) = lamp.viewport_rect() {
+///         prompt.left = Val::Px(rect.center().x);
+///         prompt.top = Val::Px(rect.min.y);
+///     }
+/// }
+/// ```
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct ComputedLightBounds {
+    /// The camera the bounds are computed for. If None, the active camera with a [`FireflyConfig`] and the highest
+    /// [order](Camera::order) is used.
+    ///
+    /// **Default:** None.
+    pub camera: Option<Entity>,
+
+    #[reflect(ignore)]
+    world: Option<Rect>,
+    #[reflect(ignore)]
+    viewport: Option<Rect>,
+}
+
+impl ComputedLightBounds {
+    /// Construct new bounds computed for the given camera.
+    pub fn for_camera(camera: Entity) -> Self {
+        Self {
+            camera: Some(camera),
+            ..default()
+        }
+    }
+
+    /// World-space rectangle of the light's influence that is within the camera's view, or None if it's offscreen.
+    pub fn world_rect(&self) -> Option<Rect> {
+        self.world
+    }
+
+    /// Rectangle of the light's influence in the camera's viewport, in logical pixels with the origin at the top left,
+    /// or None if it's offscreen.
+    pub fn viewport_rect(&self) -> Option<Rect> {
+        self.viewport
+    }
+
+    /// Whether any part of the light's influence is within the camera's view.
+    pub fn is_visible(&self) -> bool {
+        self.viewport.is_some()
+    }
+}
+
+fn compute_light_bounds(
+    mut lights: Query<(
+        &GlobalTransform,
+        &PointLight2d,
+        Option<&LightModulator>,
+        &mut ComputedLightBounds,
+    )>,
+    cameras: Query<(Entity, &Camera, &GlobalTransform), With<FireflyConfig>>,
+) {
+    let default_camera = cameras
+        .iter()
+        .filter(|(_, camera, _)| camera.is_active)
+        .max_by_key(|(_, camera, _)| camera.order)
+        .map(|(entity, ..)| entity);
+
+    for (transform, light, modulator, mut bounds) in &mut lights {
+        let bounds = bounds.bypass_change_detection();
+        bounds.world = None;
+        bounds.viewport = None;
+
+        let Some((_, camera, camera_transform)) = bounds
+            .camera
+            .or(default_camera)
+            .and_then(|camera| cameras.get(camera).ok())
+        else {
+            continue;
+        };
+
+        let clip_from_view = camera.clip_from_view();
+        if clip_from_view.determinant() == 0. {
+            continue;
+        }
+
+        let view_rect = view_world_rect(camera_transform.to_matrix() * clip_from_view.inverse());
+
+        let pos = transform.translation().truncate() + light.offset.xy();
+        let dir = (transform.rotation() * Vec3::Y).xy().normalize_or(Vec2::Y);
+        let radius = modulator.map_or(light.radius, |modulator| {
+            modulator.modulate_range(light.radius)
+        });
+        let outer = (light.angle.outer + light.angle.feather).clamp(0., 360.);
+
+        let world = sector_rect(pos, dir, radius, outer.to_radians()).intersect(view_rect);
+        if world.is_empty() {
+            continue;
+        }
+
+        let viewport = [
+            world.min,
+            vec2(world.min.x, world.max.y),
+            vec2(world.max.x, world.min.y),
+            world.max,
+        ]
+        .into_iter()
+        .filter_map(|corner| {
+            camera
+                .world_to_viewport(camera_transform, corner.extend(0.))
+                .ok()
+        })
+        .fold(Rect::EMPTY, |rect, corner| rect.union_point(corner));
+
+        bounds.world = Some(world);
+        bounds.viewport = (!viewport.is_empty()).then_some(viewport);
+    }
+}
+
+/// Bounding rectangle of a circular sector, given its center, direction, radius and angle in radians.
+fn sector_rect(center: Vec2, dir: Vec2, radius: f32, angle: f32) -> Rect {
+    if angle >= TAU {
+        return Rect::from_center_half_size(center, Vec2::splat(radius));
+    }
+
+    let half = angle / 2.;
+    let start = dir.to_angle() - half;
+
+    let mut rect = Rect::from_corners(center, center)
+        .union_point(center + Vec2::from_angle(start) * radius)
+        .union_point(center + Vec2::from_angle(start + angle) * radius);
+
+    // the sector also reaches the extremes of the circle along each axis that lie within it
+    for axis in [Vec2::X, Vec2::Y, Vec2::NEG_X, Vec2::NEG_Y] {
+        if axis.angle_to(dir).abs() <= half {
+            rect = rect.union_point(center + axis * radius);
+        }
+    }
+
+    rect
+}
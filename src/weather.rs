@@ -0,0 +1,170 @@
+//! Module containing [`WeatherOverlay`], for cloud shadows and other weather that darkens the lighting.
+
+use bevy::{
+    math::DVec2,
+    prelude::*,
+    render::{Extract, ExtractSchedule, RenderApp, sync_world::RenderEntity},
+};
+
+use crate::data::{FireflyConfig, FireflyTestClock};
+
+/// Camera component that darkens the lighting with a density texture scrolling in world space, such as
+/// drifting cloud shadows or a passing rain front.
+///
+/// The overlay is applied before the lightmap is combined with the scene, so it darkens both the
+/// [ambient light](FireflyConfig::ambient_brightness) and the lights, and stays consistent with the rest of the
+/// lighting (bands, combined lightmaps, accessibility assist). Since it's sampled in world space, the
+/// pattern stays in place as the camera moves, and only moves with its [scroll](WeatherOverlay::scroll).
+///
+/// Only the red channel of the [image](WeatherOverlay::image) is used, 0 being clear and 1 being fully covered.
+/// The texture should tile seamlessly.
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     Camera2d,
+///     FireflyConfig::default(),
+///     WeatherOverlay::new(asset_server.load("clouds.png")).with_density(0.7),
+/// ));
+/// ```
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[require(FireflyConfig)]
+pub struct WeatherOverlay {
+    /// Tiling density texture of the weather.
+    ///
+    /// **Default:** None.
+    pub image: Handle<Image>,
+
+    /// Size of one tile of the texture, in world units.
+    ///
+    /// **Default:** 1024x1024.
+    pub tile_size: Vec2,
+
+    /// Speed at which the weather drifts across the world, in world units per second.
+    ///
+    /// **Default:** 24x8.
+    pub scroll: Vec2,
+
+    /// Overall strength of the weather. 0 is clear skies, 1 applies the texture at full strength.
+    ///
+    /// **Default:** 0.5.
+    pub density: f32,
+
+    /// How much fully covered areas darken the ambient light.
+    ///
+    /// **Default:** 1.
+    pub ambient_darkening: f32,
+
+    /// How much fully covered areas darken the lights. Lower values keep street lamps and torches bright
+    /// under the clouds.
+    ///
+    /// **Default:** 0.5.
+    pub light_darkening: f32,
+}
+
+impl Default for WeatherOverlay {
+    fn default() -> Self {
+        Self {
+            image: default(),
+            tile_size: vec2(1024., 1024.),
+            scroll: vec2(24., 8.),
+            density: 0.5,
+            ambient_darkening: 1.,
+            light_darkening: 0.5,
+        }
+    }
+}
+
+impl WeatherOverlay {
+    /// Construct a new overlay with the given density texture.
+    pub fn new(image: Handle<Image>) -> Self {
+        Self { image, ..default() }
+    }
+
+    /// Returns the overlay with the given [tile size](WeatherOverlay::tile_size).
+    pub fn with_tile_size(self, tile_size: Vec2) -> Self {
+        Self { tile_size, ..self }
+    }
+
+    /// Returns the overlay with the given [scrolling speed](WeatherOverlay::scroll).
+    pub fn with_scroll(self, scroll: Vec2) -> Self {
+        Self { scroll, ..self }
+    }
+
+    /// Returns the overlay with the given [density](WeatherOverlay::density).
+    pub fn with_density(self, density: f32) -> Self {
+        Self { density, ..self }
+    }
+
+    /// Returns the overlay with the given [ambient](WeatherOverlay::ambient_darkening) and
+    /// [light](WeatherOverlay::light_darkening) darkening.
+    pub fn with_darkening(self, ambient_darkening: f32, light_darkening: f32) -> Self {
+        Self {
+            ambient_darkening,
+            light_darkening,
+            ..self
+        }
+    }
+}
+
+/// Weather overlay of a camera, extracted to the render world.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ExtractedWeatherOverlay {
+    pub image: AssetId<Image>,
+    pub tile_size: Vec2,
+    /// Offset of the texture, in tiles.
+    pub offset: Vec2,
+    pub ambient_darkening: f32,
+    pub light_darkening: f32,
+}
+
+/// Plugin that extracts [`WeatherOverlay`]s. Added automatically by [`FireflyPlugin`](crate::prelude::FireflyPlugin).
+pub struct WeatherPlugin;
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WeatherOverlay>();
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.add_systems(ExtractSchedule, extract_weather_overlays);
+    }
+}
+
+fn extract_weather_overlays(
+    mut commands: Commands,
+    time: Extract<Res<Time>>,
+    test_clock: Extract<Option<Res<FireflyTestClock>>>,
+    cameras: Extract<Query<(RenderEntity, Option<&WeatherOverlay>), With<FireflyConfig>>>,
+) {
+    let elapsed = test_clock
+        .as_ref()
+        .map_or(time.elapsed_secs_f64(), |clock| {
+            clock.frame as f64 * clock.delta.as_secs_f64()
+        });
+
+    for (entity, weather) in &cameras {
+        let Some(weather) = weather else {
+            commands.entity(entity).remove::<ExtractedWeatherOverlay>();
+            continue;
+        };
+
+        let tile_size = weather.tile_size.max(Vec2::splat(0.001));
+        let density = weather.density.clamp(0., 1.);
+
+        // computed in f64 and kept in the [0, 1) range, so the scrolling doesn't lose precision over time
+        let offset =
+            (weather.scroll.as_dvec2() * elapsed / tile_size.as_dvec2()).rem_euclid(DVec2::ONE);
+
+        commands.entity(entity).insert(ExtractedWeatherOverlay {
+            image: weather.image.id(),
+            tile_size,
+            offset: offset.as_vec2(),
+            ambient_darkening: weather.ambient_darkening.clamp(0., 1.) * density,
+            light_darkening: weather.light_darkening.clamp(0., 1.) * density,
+        });
+    }
+}
@@ -0,0 +1,92 @@
+//! Module containing the [`YSort`] component, which keeps an entity's z in sync with its y.
+
+use bevy::{prelude::*, transform::TransformSystems};
+
+/// Component that sets the entity's z position from its y position every frame, so that entities lower on the screen
+/// are drawn on top and aren't darkened by the shadows of entities above them.
+///
+/// The z is computed as `offset - y * scale`, from the entity's local [`Transform`]. Entities whose [`Transform`] and
+/// [`YSort`] didn't change since the last frame are skipped, so static entities have no per-frame cost.
+///
+/// Requires [`YSortPlugin`] to be added to the app.
+///
+/// As an alternative that doesn't move the entities, [z-sorting](crate::prelude::FireflyConfig::z_sorting) can be
+/// based on the y position directly with [`ZSortKey::NegativeY`](crate::prelude::ZSortKey::NegativeY).
+///
+/// # Example
+///
+/// ```
+/// commands.spawn((
+///     FireflySprite::from_image(asset_server.load("crate.png")),
+///     Transform::from_translation(vec3(20., 10., 0.)),
+///     // z = -y / 100
+///     YSort::default().with_scale(0.01),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YSort {
+    /// How much the z changes per unit of y.
+    ///
+    /// **Default:** 1.
+    pub scale: f32,
+
+    /// Added to the z after scaling. Useful for keeping e.g. floor decals below the sorted entities.
+    ///
+    /// **Default:** 0.
+    pub offset: f32,
+}
+
+impl Default for YSort {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl YSort {
+    /// Construct a new y-sort with the given [scale](YSort::scale) and [offset](YSort::offset).
+    pub fn new(scale: f32, offset: f32) -> Self {
+        Self { scale, offset }
+    }
+
+    /// Construct a new y-sort with the specified [scale](YSort::scale).
+    pub fn with_scale(self, scale: f32) -> Self {
+        Self { scale, ..self }
+    }
+
+    /// Construct a new y-sort with the specified [offset](YSort::offset).
+    pub fn with_offset(self, offset: f32) -> Self {
+        Self { offset, ..self }
+    }
+
+    /// The z position for the given y position.
+    pub fn z(&self, y: f32) -> f32 {
+        self.offset - y * self.scale
+    }
+}
+
+/// Plugin that updates the z position of all entities with [`YSort`].
+///
+/// Not added by [`FireflyPlugin`](crate::prelude::FireflyPlugin), since many games already sort their entities.
+pub struct YSortPlugin;
+
+impl Plugin for YSortPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<YSort>();
+        app.add_systems(PostUpdate, y_sort.before(TransformSystems::Propagate));
+    }
+}
+
+fn y_sort(mut entities: Query<(&mut Transform, &YSort), Or<(Changed<Transform>, Changed<YSort>)>>) {
+    for (mut transform, y_sort) in &mut entities {
+        let z = y_sort.z(transform.translation.y);
+
+        // only writing changed values, so the entity isn't picked up again next frame
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}